@@ -0,0 +1,208 @@
+use std::io;
+
+use super::{Deserialize, Serialize, Error};
+use super::primitives::{Uint8, VarUint32};
+use super::ops::{read_memarg, write_memarg};
+use super::types::ValueType;
+
+/// Sub-opcode of `atomic.fence`, the only instruction without a memarg.
+const ATOMIC_FENCE: u32 = 0x03;
+
+// 带 memarg 的原子指令：子操作码、自然对齐（log2）和值类型
+macro_rules! atomic_instructions {
+    ($($variant: ident = ($opcode: expr, $natural: expr, $t: ident),)*) => {
+        /// Instruction of the threads proposal, encoded after the 0xFE prefix.
+        ///
+        /// Except `atomic.fence`, all of them access memory and carry the
+        /// alignment exponent and the offset like the core loads and stores.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[allow(missing_docs)]
+        pub enum AtomicInstruction {
+            AtomicFence,
+            $($variant(u32, u64, u32),)*
+        }
+
+        impl AtomicInstruction {
+            /// Sub-opcode following the 0xFE prefix.
+            pub fn opcode(&self) -> u32 {
+                match *self {
+                    AtomicInstruction::AtomicFence => ATOMIC_FENCE,
+                    $(AtomicInstruction::$variant(..) => $opcode,)*
+                }
+            }
+
+            /// Alignment exponent, offset and memory index, `None` for `atomic.fence`.
+            pub fn memarg(&self) -> Option<(u32, u64, u32)> {
+                match *self {
+                    AtomicInstruction::AtomicFence => None,
+                    $(AtomicInstruction::$variant(align, offset, memory) => Some((align, offset, memory)),)*
+                }
+            }
+
+            /// Log2 of the size of the accessed value, which is also the only valid alignment.
+            pub fn natural_alignment(&self) -> Option<u32> {
+                match *self {
+                    AtomicInstruction::AtomicFence => None,
+                    $(AtomicInstruction::$variant(..) => Some($natural),)*
+                }
+            }
+
+            /// Type of the value loaded, stored or compared, before narrowing.
+            pub fn value_type(&self) -> Option<ValueType> {
+                match *self {
+                    AtomicInstruction::AtomicFence => None,
+                    $(AtomicInstruction::$variant(..) => Some(ValueType::$t),)*
+                }
+            }
+        }
+
+        /// Constructor of the instruction with a memarg encoded as `opcode`.
+        fn memory_atomic_instruction(opcode: u32) -> Option<fn(u32, u64, u32) -> AtomicInstruction> {
+            match opcode {
+                $($opcode => Some(AtomicInstruction::$variant as fn(u32, u64, u32) -> AtomicInstruction),)*
+                _ => None,
+            }
+        }
+    }
+}
+
+atomic_instructions! {
+    MemoryAtomicNotify = (0x00, 2, I32),
+    MemoryAtomicWait32 = (0x01, 2, I32),
+    MemoryAtomicWait64 = (0x02, 3, I64),
+
+    I32AtomicLoad = (0x10, 2, I32),
+    I64AtomicLoad = (0x11, 3, I64),
+    I32AtomicLoad8U = (0x12, 0, I32),
+    I32AtomicLoad16U = (0x13, 1, I32),
+    I64AtomicLoad8U = (0x14, 0, I64),
+    I64AtomicLoad16U = (0x15, 1, I64),
+    I64AtomicLoad32U = (0x16, 2, I64),
+
+    I32AtomicStore = (0x17, 2, I32),
+    I64AtomicStore = (0x18, 3, I64),
+    I32AtomicStore8 = (0x19, 0, I32),
+    I32AtomicStore16 = (0x1a, 1, I32),
+    I64AtomicStore8 = (0x1b, 0, I64),
+    I64AtomicStore16 = (0x1c, 1, I64),
+    I64AtomicStore32 = (0x1d, 2, I64),
+
+    I32AtomicRmwAdd = (0x1e, 2, I32),
+    I64AtomicRmwAdd = (0x1f, 3, I64),
+    I32AtomicRmw8AddU = (0x20, 0, I32),
+    I32AtomicRmw16AddU = (0x21, 1, I32),
+    I64AtomicRmw8AddU = (0x22, 0, I64),
+    I64AtomicRmw16AddU = (0x23, 1, I64),
+    I64AtomicRmw32AddU = (0x24, 2, I64),
+
+    I32AtomicRmwSub = (0x25, 2, I32),
+    I64AtomicRmwSub = (0x26, 3, I64),
+    I32AtomicRmw8SubU = (0x27, 0, I32),
+    I32AtomicRmw16SubU = (0x28, 1, I32),
+    I64AtomicRmw8SubU = (0x29, 0, I64),
+    I64AtomicRmw16SubU = (0x2a, 1, I64),
+    I64AtomicRmw32SubU = (0x2b, 2, I64),
+
+    I32AtomicRmwAnd = (0x2c, 2, I32),
+    I64AtomicRmwAnd = (0x2d, 3, I64),
+    I32AtomicRmw8AndU = (0x2e, 0, I32),
+    I32AtomicRmw16AndU = (0x2f, 1, I32),
+    I64AtomicRmw8AndU = (0x30, 0, I64),
+    I64AtomicRmw16AndU = (0x31, 1, I64),
+    I64AtomicRmw32AndU = (0x32, 2, I64),
+
+    I32AtomicRmwOr = (0x33, 2, I32),
+    I64AtomicRmwOr = (0x34, 3, I64),
+    I32AtomicRmw8OrU = (0x35, 0, I32),
+    I32AtomicRmw16OrU = (0x36, 1, I32),
+    I64AtomicRmw8OrU = (0x37, 0, I64),
+    I64AtomicRmw16OrU = (0x38, 1, I64),
+    I64AtomicRmw32OrU = (0x39, 2, I64),
+
+    I32AtomicRmwXor = (0x3a, 2, I32),
+    I64AtomicRmwXor = (0x3b, 3, I64),
+    I32AtomicRmw8XorU = (0x3c, 0, I32),
+    I32AtomicRmw16XorU = (0x3d, 1, I32),
+    I64AtomicRmw8XorU = (0x3e, 0, I64),
+    I64AtomicRmw16XorU = (0x3f, 1, I64),
+    I64AtomicRmw32XorU = (0x40, 2, I64),
+
+    I32AtomicRmwXchg = (0x41, 2, I32),
+    I64AtomicRmwXchg = (0x42, 3, I64),
+    I32AtomicRmw8XchgU = (0x43, 0, I32),
+    I32AtomicRmw16XchgU = (0x44, 1, I32),
+    I64AtomicRmw8XchgU = (0x45, 0, I64),
+    I64AtomicRmw16XchgU = (0x46, 1, I64),
+    I64AtomicRmw32XchgU = (0x47, 2, I64),
+
+    I32AtomicRmwCmpxchg = (0x48, 2, I32),
+    I64AtomicRmwCmpxchg = (0x49, 3, I64),
+    I32AtomicRmw8CmpxchgU = (0x4a, 0, I32),
+    I32AtomicRmw16CmpxchgU = (0x4b, 1, I32),
+    I64AtomicRmw8CmpxchgU = (0x4c, 0, I64),
+    I64AtomicRmw16CmpxchgU = (0x4d, 1, I64),
+    I64AtomicRmw32CmpxchgU = (0x4e, 2, I64),
+}
+
+impl Deserialize for AtomicInstruction {
+    type Error = Error;
+
+    /// Reads the sub-opcode and immediates, the 0xFE prefix is already consumed.
+    fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+        let val: u32 = VarUint32::deserialize(reader)?.into();
+        if val == ATOMIC_FENCE {
+            let reserved: u8 = Uint8::deserialize(reader)?.into();
+            if reserved != 0 {
+                return Err(Error::Other("atomic.fence reserved byte must be zero"));
+            }
+            return Ok(AtomicInstruction::AtomicFence);
+        }
+        match memory_atomic_instruction(val) {
+            Some(constructor) => read_memarg(reader, constructor),
+            None => Err(Error::UnknownAtomicOpcode(val)),
+        }
+    }
+}
+
+impl Serialize for AtomicInstruction {
+    type Error = Error;
+
+    /// Writes the sub-opcode and immediates, without the 0xFE prefix.
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        VarUint32(self.opcode()).serialize(writer)?;
+        match self.memarg() {
+            Some((align, offset, memory)) => write_memarg(writer, align, offset, memory)?,
+            None => Uint8(0).serialize(writer)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elements::ops::Instruction;
+    use crate::elements::{deserialize_buffer, serialize};
+
+    #[test]
+    fn test_atomic_encoding() {
+        let cases: Vec<(Vec<u8>, AtomicInstruction)> = vec![
+            (vec![0xfe, 0x00, 0x02, 0x00], AtomicInstruction::MemoryAtomicNotify(2, 0, 0)),
+            (vec![0xfe, 0x03, 0x00], AtomicInstruction::AtomicFence),
+            (vec![0xfe, 0x10, 0x02, 0x08], AtomicInstruction::I32AtomicLoad(2, 8, 0)),
+            (vec![0xfe, 0x1e, 0x02, 0x00], AtomicInstruction::I32AtomicRmwAdd(2, 0, 0)),
+            (vec![0xfe, 0x4e, 0x02, 0x80, 0x01], AtomicInstruction::I64AtomicRmw32CmpxchgU(2, 128, 0)),
+        ];
+        for (bytes, instruction) in cases {
+            let instruction = Instruction::Atomic(instruction);
+            assert_eq!(deserialize_buffer::<Instruction>(&bytes).unwrap(), instruction);
+            assert_eq!(serialize(&instruction).unwrap(), bytes);
+        }
+
+        match deserialize_buffer::<Instruction>(&[0xfe, 0x4f, 0x02, 0x00]) {
+            Err(Error::UnknownAtomicOpcode(0x4f)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(deserialize_buffer::<Instruction>(&[0xfe, 0x03, 0x01]).is_err());
+    }
+}
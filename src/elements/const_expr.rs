@@ -0,0 +1,244 @@
+//! Evaluation of constant expressions.
+//!
+//! Besides the single instructions of the MVP, the extended-const proposal
+//! allows `add`, `sub` and `mul` of `i32` and `i64` operands, and the GC
+//! proposal allows allocations and `ref.i31`, which are left to the environment.
+
+use std::fmt;
+
+use super::ops::{Instruction, InitExpr};
+use super::types::{TableElementType, ValueType};
+#[cfg(feature = "simd")]
+use super::simd::SimdInstruction;
+#[cfg(feature = "gc")]
+use super::gc::GcInstruction;
+
+/// Value of a constant expression, references are represented by the environment.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstValue<R> {
+    I32(i32),
+    I64(i64),
+    /// Bit pattern of an `f32`.
+    F32(u32),
+    /// Bit pattern of an `f64`.
+    F64(u64),
+    /// Lanes are stored little endian.
+    #[cfg(feature = "simd")]
+    V128(u128),
+    Ref(R),
+}
+
+/// Globals and functions a constant expression can refer to.
+pub trait ConstEnv {
+    /// Representation of references, e.g. runtime references or just their type.
+    type Ref;
+
+    /// Value of the global `idx`, which must be visible and immutable.
+    fn global(&self, idx: u32) -> Result<ConstValue<Self::Ref>, ConstExprError>;
+
+    /// Null reference of `elem_type`.
+    fn null(&self, elem_type: TableElementType) -> Self::Ref;
+
+    /// Reference to the function `idx`.
+    fn func(&self, idx: u32) -> Result<Self::Ref, ConstExprError>;
+
+    /// Execute a constant GC instruction, taking its operands from `stack`.
+    #[cfg(feature = "gc")]
+    fn gc(&self, instruction: &GcInstruction, stack: &mut Vec<ConstValue<Self::Ref>>) -> Result<ConstValue<Self::Ref>, ConstExprError> {
+        let _ = stack;
+        Err(ConstExprError::NonConstant(Instruction::Gc(instruction.clone())))
+    }
+}
+
+/// Error evaluating a constant expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstExprError {
+    /// Instruction which is not allowed in constant expressions.
+    NonConstant(Instruction),
+    /// Global index is out of bounds or not visible to the expression.
+    UnknownGlobal(u32),
+    /// `global.get` of a mutable global.
+    MutableGlobal(u32),
+    /// Function index is out of bounds.
+    UnknownFunction(u32),
+    /// Type index is out of bounds or not of the kind the instruction allocates.
+    #[cfg(feature = "gc")]
+    UnknownType(u32),
+    /// Operand of an arithmetic instruction has the wrong type.
+    TypeMismatch(ValueType),
+    /// Arithmetic instruction without enough operands.
+    StackUnderflow,
+    /// Expression leaves the given number of values instead of exactly one.
+    UnbalancedStack(usize),
+    /// Expression is not terminated by `end`.
+    MissingEnd,
+}
+
+impl fmt::Display for ConstExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConstExprError::NonConstant(ref instruction) => {
+                write!(f, "Instruction {:?} is not allowed in a constant expression", instruction)
+            },
+            ConstExprError::UnknownGlobal(idx) => write!(f, "Unknown global {} in a constant expression", idx),
+            ConstExprError::MutableGlobal(idx) => write!(f, "Constant expression reads mutable global {}", idx),
+            ConstExprError::UnknownFunction(idx) => write!(f, "Unknown function {} in a constant expression", idx),
+            #[cfg(feature = "gc")]
+            ConstExprError::UnknownType(idx) => write!(f, "Invalid type {} in a constant expression", idx),
+            ConstExprError::TypeMismatch(expected) => write!(f, "Constant expression expects an operand of type {:?}", expected),
+            ConstExprError::StackUnderflow => write!(f, "Constant expression stack underflow"),
+            ConstExprError::UnbalancedStack(n) => write!(f, "Constant expression produces {} values instead of one", n),
+            ConstExprError::MissingEnd => write!(f, "Constant expression is not terminated by end"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for ConstExprError {}
+
+fn pop_i32<R>(stack: &mut Vec<ConstValue<R>>) -> Result<i32, ConstExprError> {
+    match stack.pop() {
+        Some(ConstValue::I32(v)) => Ok(v),
+        Some(_) => Err(ConstExprError::TypeMismatch(ValueType::I32)),
+        None => Err(ConstExprError::StackUnderflow),
+    }
+}
+
+fn pop_i64<R>(stack: &mut Vec<ConstValue<R>>) -> Result<i64, ConstExprError> {
+    match stack.pop() {
+        Some(ConstValue::I64(v)) => Ok(v),
+        Some(_) => Err(ConstExprError::TypeMismatch(ValueType::I64)),
+        None => Err(ConstExprError::StackUnderflow),
+    }
+}
+
+impl InitExpr {
+    /// Evaluate the expression with globals and functions of `env`.
+    pub fn evaluate<E: ConstEnv>(&self, env: &E) -> Result<ConstValue<E::Ref>, ConstExprError> {
+        use self::Instruction::*;
+
+        let code = match self.0.split_last() {
+            Some((&End, code)) => code,
+            _ => return Err(ConstExprError::MissingEnd),
+        };
+        let mut stack: Vec<ConstValue<E::Ref>> = Vec::new();
+        for instruction in code.iter() {
+            let value = match *instruction {
+                I32Const(v) => ConstValue::I32(v),
+                I64Const(v) => ConstValue::I64(v),
+                F32Const(bits) => ConstValue::F32(bits),
+                F64Const(bits) => ConstValue::F64(bits),
+                #[cfg(feature = "simd")]
+                Simd(SimdInstruction::V128Const(ref bytes)) => ConstValue::V128(u128::from_le_bytes(**bytes)),
+                GetGlobal(idx) => env.global(idx)?,
+                RefNull(elem_type) => ConstValue::Ref(env.null(elem_type)),
+                RefFunc(idx) => ConstValue::Ref(env.func(idx)?),
+                #[cfg(feature = "gc")]
+                Gc(ref gc) if gc.is_constant() => env.gc(gc, &mut stack)?,
+                I32Add | I32Sub | I32Mul => {
+                    let b = pop_i32(&mut stack)?;
+                    let a = pop_i32(&mut stack)?;
+                    ConstValue::I32(match *instruction {
+                        I32Add => a.wrapping_add(b),
+                        I32Sub => a.wrapping_sub(b),
+                        _ => a.wrapping_mul(b),
+                    })
+                },
+                I64Add | I64Sub | I64Mul => {
+                    let b = pop_i64(&mut stack)?;
+                    let a = pop_i64(&mut stack)?;
+                    ConstValue::I64(match *instruction {
+                        I64Add => a.wrapping_add(b),
+                        I64Sub => a.wrapping_sub(b),
+                        _ => a.wrapping_mul(b),
+                    })
+                },
+                ref other => return Err(ConstExprError::NonConstant(other.clone())),
+            };
+            stack.push(value);
+        }
+
+        if stack.len() != 1 {
+            return Err(ConstExprError::UnbalancedStack(stack.len()));
+        }
+        Ok(stack.pop().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Globals 0 and 1 are immutable `i32` and `i64`, global 2 is mutable, functions are 0..2.
+    struct Env;
+
+    impl ConstEnv for Env {
+        type Ref = Option<u32>;
+
+        fn global(&self, idx: u32) -> Result<ConstValue<Option<u32>>, ConstExprError> {
+            match idx {
+                0 => Ok(ConstValue::I32(10)),
+                1 => Ok(ConstValue::I64(-1)),
+                2 => Err(ConstExprError::MutableGlobal(idx)),
+                _ => Err(ConstExprError::UnknownGlobal(idx)),
+            }
+        }
+
+        fn null(&self, _: TableElementType) -> Option<u32> {
+            None
+        }
+
+        fn func(&self, idx: u32) -> Result<Option<u32>, ConstExprError> {
+            if idx < 2 { Ok(Some(idx)) } else { Err(ConstExprError::UnknownFunction(idx)) }
+        }
+    }
+
+    fn eval(code: Vec<Instruction>) -> Result<ConstValue<Option<u32>>, ConstExprError> {
+        InitExpr(code).evaluate(&Env)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        use crate::elements::ops::Instruction::*;
+
+        assert_eq!(eval(vec![I32Const(7), End]), Ok(ConstValue::I32(7)));
+        assert_eq!(eval(vec![F64Const(1.5f64.to_bits()), End]), Ok(ConstValue::F64(1.5f64.to_bits())));
+        assert_eq!(eval(vec![RefNull(TableElementType::AnyFunc), End]), Ok(ConstValue::Ref(None)));
+        assert_eq!(eval(vec![RefFunc(1), End]), Ok(ConstValue::Ref(Some(1))));
+
+        // (global 0 + 2) * 3 - 1
+        let extended = vec![GetGlobal(0), I32Const(2), I32Add, I32Const(3), I32Mul, I32Const(1), I32Sub, End];
+        assert_eq!(eval(extended), Ok(ConstValue::I32(35)));
+        assert_eq!(eval(vec![I64Const(i64::MIN), GetGlobal(1), I64Add, End]), Ok(ConstValue::I64(i64::MAX)));
+
+        assert_eq!(eval(vec![I32Const(1), I32DivS, End]), Err(ConstExprError::NonConstant(I32DivS)));
+        assert_eq!(eval(vec![GetGlobal(2), End]), Err(ConstExprError::MutableGlobal(2)));
+        assert_eq!(eval(vec![RefFunc(2), End]), Err(ConstExprError::UnknownFunction(2)));
+        assert_eq!(eval(vec![I32Const(1), GetGlobal(1), I32Add, End]), Err(ConstExprError::TypeMismatch(ValueType::I32)));
+        assert_eq!(eval(vec![I64Const(1), I64Mul, End]), Err(ConstExprError::StackUnderflow));
+        assert_eq!(eval(vec![I32Const(1), I32Const(2), End]), Err(ConstExprError::UnbalancedStack(2)));
+        assert_eq!(eval(vec![End]), Err(ConstExprError::UnbalancedStack(0)));
+        assert_eq!(eval(vec![I32Const(1)]), Err(ConstExprError::MissingEnd));
+    }
+
+    #[test]
+    fn test_decoded_offsets() {
+        use crate::elements::deserialize_buffer;
+        use crate::elements::global_entry::GlobalEntry;
+        use crate::elements::ops::Instruction::*;
+        use crate::elements::segment::{DataSegment, ElementSegment};
+
+        // 解码后的常量表达式必须保留全部指令
+        let global: GlobalEntry = deserialize_buffer(&[0x7f, 0x00, 0x41, 0x05, 0x41, 0x02, 0x6a, 0x0b]).unwrap();
+        assert_eq!(global.init_expr.0, vec![I32Const(5), I32Const(2), I32Add, End]);
+        assert_eq!(global.init_expr.evaluate(&Env), Ok(ConstValue::I32(7)));
+
+        let data: DataSegment = deserialize_buffer(&[0x00, 0x23, 0x00, 0x0b, 0x01, 0xaa]).unwrap();
+        let offset = data.offset.unwrap();
+        assert_eq!(offset.0, vec![GetGlobal(0), End]);
+        assert_eq!(offset.evaluate(&Env), Ok(ConstValue::I32(10)));
+
+        let elem: ElementSegment = deserialize_buffer(&[0x00, 0x41, 0x03, 0x0b, 0x01, 0x00]).unwrap();
+        assert_eq!(elem.offset.unwrap().evaluate(&Env), Ok(ConstValue::I32(3)));
+    }
+}
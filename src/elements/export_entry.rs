@@ -1,4 +1,4 @@
-use super::{Deserialize, Error};
+use super::{Deserialize, Serialize, Error};
 use super::primitives::{VarUint7, VarUint32};
 use std::io;
 
@@ -24,6 +24,15 @@ impl Deserialize for ExportEntry {
     }
 }
 
+impl Serialize for ExportEntry {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        self.field_str.serialize(writer)?;
+        self.internal.serialize(writer)
+    }
+}
+
 /// Internal reference of the exported entry.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Internal {
@@ -35,6 +44,9 @@ pub enum Internal {
     Memory(u32),
     /// Global reference.
     Global(u32),
+    /// Tag reference.
+    #[cfg(feature = "exceptions")]
+    Tag(u32),
 }
 
 impl Deserialize for Internal {
@@ -47,7 +59,26 @@ impl Deserialize for Internal {
             0x01 => Ok(Internal::Table(VarUint32::deserialize(reader)?.into())),
             0x02 => Ok(Internal::Memory(VarUint32::deserialize(reader)?.into())),
             0x03 => Ok(Internal::Global(VarUint32::deserialize(reader)?.into())),
+            #[cfg(feature = "exceptions")]
+            0x04 => Ok(Internal::Tag(VarUint32::deserialize(reader)?.into())),
             _ => Err(Error::UnknownInternalKind(kind.into())),
         }
     }
-}
\ No newline at end of file
+}
+
+impl Serialize for Internal {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        let (kind, index) = match *self {
+            Internal::Function(index) => (0x00, index),
+            Internal::Table(index) => (0x01, index),
+            Internal::Memory(index) => (0x02, index),
+            Internal::Global(index) => (0x03, index),
+            #[cfg(feature = "exceptions")]
+            Internal::Tag(index) => (0x04, index),
+        };
+        VarUint7(kind).serialize(writer)?;
+        VarUint32(index).serialize(writer)
+    }
+}
@@ -0,0 +1,548 @@
+//! Proposals a decoded module is allowed to use.
+//!
+//! Encodings of proposals which are not compiled in are always rejected, the
+//! ones which are can still be turned off at runtime, e.g. to only accept MVP
+//! modules in a deterministic runtime.
+
+use super::Error;
+use super::export_entry::ExportEntry;
+use super::func::{Func, FuncBody, Local};
+use super::global_entry::GlobalEntry;
+use super::import_entry::{External, ImportEntry, ResizableLimits, TableType};
+#[cfg(feature = "exceptions")]
+use super::import_entry::TagType;
+use super::module::Module;
+use super::sections::Section;
+use super::ops::{InitExpr, Instruction};
+use super::segment::{DataSegment, ElementSegment};
+use super::types::{BlockType, CompositeType, RecGroup, TableElementType, ValueType};
+
+/// WebAssembly proposals accepted by `Module::deserialize_with`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmFeatures {
+    /// `i32.extend8_s` and the other sign extension instructions.
+    pub sign_extension: bool,
+    /// Non-trapping float-to-int conversions.
+    pub saturating_float_to_int: bool,
+    /// Passive segments, the data count section and the bulk memory instructions.
+    pub bulk_memory: bool,
+    /// Reference values, several tables and the table instructions.
+    pub reference_types: bool,
+    /// Functions with several results and blocks with a type index.
+    pub multi_value: bool,
+    /// `add`, `sub` and `mul` in constant expressions.
+    pub extended_const: bool,
+    pub simd: bool,
+    pub threads: bool,
+    pub memory64: bool,
+    pub multi_memory: bool,
+    pub tail_call: bool,
+    pub exceptions: bool,
+    /// GC types and instructions, and typed function references.
+    pub gc: bool,
+}
+
+impl WasmFeatures {
+    /// Only the 1.0 specification.
+    pub fn mvp() -> WasmFeatures {
+        WasmFeatures {
+            sign_extension: false,
+            saturating_float_to_int: false,
+            bulk_memory: false,
+            reference_types: false,
+            multi_value: false,
+            extended_const: false,
+            simd: false,
+            threads: false,
+            memory64: false,
+            multi_memory: false,
+            tail_call: false,
+            exceptions: false,
+            gc: false,
+        }
+    }
+
+    /// Every proposal the crate is compiled with.
+    pub fn all() -> WasmFeatures {
+        WasmFeatures {
+            sign_extension: true,
+            saturating_float_to_int: true,
+            bulk_memory: true,
+            reference_types: true,
+            multi_value: cfg!(feature = "multi_value"),
+            extended_const: true,
+            simd: cfg!(feature = "simd"),
+            threads: cfg!(feature = "threads"),
+            memory64: cfg!(feature = "memory64"),
+            multi_memory: cfg!(feature = "multi_memory"),
+            tail_call: cfg!(feature = "tail_call"),
+            exceptions: cfg!(feature = "exceptions"),
+            gc: cfg!(feature = "gc"),
+        }
+    }
+
+    /// Check that `module` only uses the enabled proposals.
+    pub fn check(&self, module: &Module) -> Result<(), Error> {
+        let mut check = FeatureCheck::new(Some(self));
+        for section in module.sections.iter() {
+            check.entry(section)?;
+        }
+        Ok(())
+    }
+
+    fn value_type(&self, value_type: ValueType) -> Result<(), Error> {
+        match value_type {
+            ValueType::I32 | ValueType::I64 | ValueType::F32 | ValueType::F64 => Ok(()),
+            ValueType::FuncRef | ValueType::ExternRef => require(self.reference_types, "reference_types", "Reference value"),
+            #[cfg(feature = "exceptions")]
+            ValueType::ExnRef => require(self.exceptions, "exceptions", "exnref value"),
+            #[cfg(feature = "simd")]
+            ValueType::V128 => require(self.simd, "simd", "v128 value"),
+            #[cfg(feature = "gc")]
+            ValueType::Ref(_) => require(self.gc, "gc", "Typed reference"),
+        }
+    }
+
+    fn table_element_type(&self, elem_type: TableElementType) -> Result<(), Error> {
+        match elem_type {
+            TableElementType::AnyFunc => Ok(()),
+            other => self.value_type(other.value_type()),
+        }
+    }
+
+    fn limits(&self, limits: &ResizableLimits) -> Result<(), Error> {
+        if limits.shared {
+            require(self.threads, "threads", "Shared memory")?;
+        }
+        if limits.memory64 {
+            require(self.memory64, "memory64", "64-bit memory")?;
+        }
+        Ok(())
+    }
+
+    fn rec_group(&self, group: &RecGroup) -> Result<(), Error> {
+        if group.0.len() != 1 {
+            require(self.gc, "gc", "Recursion group")?;
+        }
+        for sub in group.0.iter() {
+            if !sub.is_final || !sub.supertypes.is_empty() {
+                require(self.gc, "gc", "Subtype declaration")?;
+            }
+            match sub.composite {
+                CompositeType::Func(ref func) => {
+                    if func.results.len() > 1 {
+                        require(self.multi_value, "multi_value", "Multiple function results")?;
+                    }
+                    for &value_type in func.params.iter().chain(func.results.iter()) {
+                        self.value_type(value_type)?;
+                    }
+                },
+                #[cfg(feature = "gc")]
+                _ => require(self.gc, "gc", "Struct or array type")?,
+            }
+        }
+        Ok(())
+    }
+
+    fn element_segment(&self, segment: &ElementSegment) -> Result<(), Error> {
+        if segment.declarative {
+            require(self.reference_types, "reference_types", "Declarative element segment")?;
+        }
+        if segment.is_passive() {
+            require(self.bulk_memory, "bulk_memory", "Passive element segment")?;
+        }
+        if segment.index != 0 {
+            require(self.reference_types, "reference_types", "Element segment of a table other than 0")?;
+        }
+        self.table_element_type(segment.elem_type)?;
+        if let Some(ref offset) = segment.offset {
+            self.init_expr(offset)?;
+        }
+        if let Some(ref exprs) = segment.exprs {
+            require(self.reference_types, "reference_types", "Element expressions")?;
+            for expr in exprs.iter() {
+                self.init_expr(expr)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn data_segment(&self, segment: &DataSegment) -> Result<(), Error> {
+        if segment.index != 0 {
+            require(self.multi_memory, "multi_memory", "Data segment of a memory other than 0")?;
+        }
+        match segment.offset {
+            Some(ref offset) => self.init_expr(offset),
+            None => require(self.bulk_memory, "bulk_memory", "Passive data segment"),
+        }
+    }
+
+    fn init_expr(&self, expr: &InitExpr) -> Result<(), Error> {
+        use self::Instruction::*;
+
+        for instruction in expr.0.iter() {
+            match *instruction {
+                I32Add | I32Sub | I32Mul | I64Add | I64Sub | I64Mul => {
+                    require(self.extended_const, "extended_const", "Arithmetic in a constant expression")?
+                },
+                _ => self.instruction(instruction)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn block_type(&self, block_type: BlockType) -> Result<(), Error> {
+        match block_type {
+            BlockType::Value(value_type) => self.value_type(value_type),
+            BlockType::NoResult => Ok(()),
+            #[cfg(feature = "multi_value")]
+            BlockType::TypeIndex(_) => require(self.multi_value, "multi_value", "Block type index"),
+        }
+    }
+
+    fn instruction(&self, instruction: &Instruction) -> Result<(), Error> {
+        use self::Instruction::*;
+
+        match *instruction {
+            Block(block_type) | Loop(block_type) | If(block_type) => self.block_type(block_type),
+            #[cfg(feature = "exceptions")]
+            TryTable(ref data) => {
+                require(self.exceptions, "exceptions", "Exception handling instruction")?;
+                self.block_type(data.block_type)
+            },
+            #[cfg(feature = "exceptions")]
+            Throw(_) | ThrowRef => require(self.exceptions, "exceptions", "Exception handling instruction"),
+            #[cfg(feature = "tail_call")]
+            ReturnCall(_) | ReturnCallIndirect(..) => require(self.tail_call, "tail_call", "Tail call"),
+            #[cfg(all(feature = "gc", feature = "tail_call"))]
+            ReturnCallRef(_) => {
+                require(self.tail_call, "tail_call", "Tail call through a reference")?;
+                require(self.gc, "gc", "Tail call through a reference")
+            },
+            #[cfg(feature = "gc")]
+            CallRef(_) | RefEq | RefAsNonNull | BrOnNull(_) | BrOnNonNull(_) | Gc(_) => {
+                require(self.gc, "gc", "GC instruction")
+            },
+            CallIndirect(_, table) if table != 0 => {
+                require(self.reference_types, "reference_types", "Indirect call through a table other than 0")
+            },
+            TypedSelect(value_type) => {
+                require(self.reference_types, "reference_types", "Typed select")?;
+                self.value_type(value_type)
+            },
+            I32Extend8S | I32Extend16S | I64Extend8S | I64Extend16S | I64Extend32S => {
+                require(self.sign_extension, "sign_extension", "Sign extension instruction")
+            },
+            I32TruncSatSF32 | I32TruncSatUF32 | I32TruncSatSF64 | I32TruncSatUF64 |
+            I64TruncSatSF32 | I64TruncSatUF32 | I64TruncSatSF64 | I64TruncSatUF64 => {
+                require(self.saturating_float_to_int, "saturating_float_to_int", "Saturating conversion")
+            },
+            RefNull(elem_type) => {
+                require(self.reference_types, "reference_types", "Reference instruction")?;
+                self.table_element_type(elem_type)
+            },
+            RefIsNull | RefFunc(_) => require(self.reference_types, "reference_types", "Reference instruction"),
+            TableGet(_) | TableSet(_) | TableGrow(_) | TableSize(_) | TableFill(_) => {
+                require(self.reference_types, "reference_types", "Table instruction")
+            },
+            MemoryInit(..) | DataDrop(_) | MemoryCopy(..) | MemoryFill(_) | TableInit(..) | ElemDrop(_) | TableCopy(..) => {
+                require(self.bulk_memory, "bulk_memory", "Bulk memory instruction")
+            },
+            #[cfg(feature = "simd")]
+            Simd(_) => require(self.simd, "simd", "SIMD instruction"),
+            #[cfg(feature = "threads")]
+            Atomic(_) => require(self.threads, "threads", "Atomic instruction"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Every compiled-in proposal, the behaviour of `Module::deserialize`.
+impl Default for WasmFeatures {
+    fn default() -> WasmFeatures {
+        WasmFeatures::all()
+    }
+}
+
+/// Tables and memories declared by the entries checked so far.
+#[derive(Debug, Default)]
+pub(crate) struct Declared {
+    tables: u32,
+    memories: u32,
+}
+
+/// Proposal checks run on every entry while it is decoded, nothing is checked without `features`.
+pub(crate) struct FeatureCheck<'a> {
+    features: Option<&'a WasmFeatures>,
+    declared: Declared,
+}
+
+impl<'a> FeatureCheck<'a> {
+    pub fn new(features: Option<&'a WasmFeatures>) -> FeatureCheck<'a> {
+        FeatureCheck { features, declared: Declared::default() }
+    }
+
+    pub fn entry<T: CheckFeatures>(&mut self, entry: &T) -> Result<(), Error> {
+        match self.features {
+            Some(features) => entry.check_features(features, &mut self.declared),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Section entry, or instruction of a body, which is checked on its own.
+pub(crate) trait CheckFeatures {
+    fn check_features(&self, features: &WasmFeatures, declared: &mut Declared) -> Result<(), Error>;
+}
+
+fn check_all<T: CheckFeatures>(entries: &[T], features: &WasmFeatures, declared: &mut Declared) -> Result<(), Error> {
+    for entry in entries.iter() {
+        entry.check_features(features, declared)?;
+    }
+    Ok(())
+}
+
+impl CheckFeatures for Section {
+    fn check_features(&self, features: &WasmFeatures, declared: &mut Declared) -> Result<(), Error> {
+        match *self {
+            Section::Type(ref types) => check_all(&types.0, features, declared),
+            Section::Import(ref imports) => check_all(&imports.0, features, declared),
+            Section::Table(ref tables) => check_all(&tables.0, features, declared),
+            Section::Memory(ref memories) => check_all(&memories.0, features, declared),
+            #[cfg(feature = "exceptions")]
+            Section::Tag(ref tags) => check_all(&tags.0, features, declared),
+            Section::Global(ref globals) => check_all(&globals.0, features, declared),
+            Section::Element(ref segments) => check_all(&segments.0, features, declared),
+            Section::DataCount(_) => require(features.bulk_memory, "bulk_memory", "Data count section"),
+            Section::Code(ref code) => check_all(&code.0, features, declared),
+            Section::Data(ref segments) => check_all(&segments.0, features, declared),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl CheckFeatures for RecGroup {
+    fn check_features(&self, features: &WasmFeatures, _: &mut Declared) -> Result<(), Error> {
+        features.rec_group(self)
+    }
+}
+
+impl CheckFeatures for ImportEntry {
+    fn check_features(&self, features: &WasmFeatures, declared: &mut Declared) -> Result<(), Error> {
+        match self.external {
+            External::Function(_) => Ok(()),
+            External::Table(ref table) => table.check_features(features, declared),
+            External::Memory(ref limits) => limits.check_features(features, declared),
+            External::Global(ref global) => features.value_type(global.content_type),
+            #[cfg(feature = "exceptions")]
+            External::Tag(ref tag) => tag.check_features(features, declared),
+        }
+    }
+}
+
+impl CheckFeatures for Func {
+    fn check_features(&self, _: &WasmFeatures, _: &mut Declared) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl CheckFeatures for TableType {
+    fn check_features(&self, features: &WasmFeatures, declared: &mut Declared) -> Result<(), Error> {
+        declared.tables += 1;
+        if declared.tables > 1 {
+            require(features.reference_types, "reference_types", "Multiple tables")?;
+        }
+        features.table_element_type(self.elem_type)
+    }
+}
+
+impl CheckFeatures for ResizableLimits {
+    fn check_features(&self, features: &WasmFeatures, declared: &mut Declared) -> Result<(), Error> {
+        declared.memories += 1;
+        if declared.memories > 1 {
+            require(features.multi_memory, "multi_memory", "Multiple memories")?;
+        }
+        features.limits(self)
+    }
+}
+
+#[cfg(feature = "exceptions")]
+impl CheckFeatures for TagType {
+    fn check_features(&self, features: &WasmFeatures, _: &mut Declared) -> Result<(), Error> {
+        require(features.exceptions, "exceptions", "Tag")
+    }
+}
+
+impl CheckFeatures for GlobalEntry {
+    fn check_features(&self, features: &WasmFeatures, _: &mut Declared) -> Result<(), Error> {
+        features.value_type(self.global_type.content_type)?;
+        features.init_expr(&self.init_expr)
+    }
+}
+
+impl CheckFeatures for ExportEntry {
+    fn check_features(&self, _: &WasmFeatures, _: &mut Declared) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl CheckFeatures for ElementSegment {
+    fn check_features(&self, features: &WasmFeatures, _: &mut Declared) -> Result<(), Error> {
+        features.element_segment(self)
+    }
+}
+
+impl CheckFeatures for DataSegment {
+    fn check_features(&self, features: &WasmFeatures, _: &mut Declared) -> Result<(), Error> {
+        features.data_segment(self)
+    }
+}
+
+impl CheckFeatures for FuncBody {
+    fn check_features(&self, features: &WasmFeatures, declared: &mut Declared) -> Result<(), Error> {
+        check_all(&self.locals, features, declared)?;
+        check_all(self.instructions.elements(), features, declared)
+    }
+}
+
+impl CheckFeatures for Local {
+    fn check_features(&self, features: &WasmFeatures, _: &mut Declared) -> Result<(), Error> {
+        features.value_type(self.value_type)
+    }
+}
+
+impl CheckFeatures for Instruction {
+    fn check_features(&self, features: &WasmFeatures, _: &mut Declared) -> Result<(), Error> {
+        features.instruction(self)
+    }
+}
+
+fn require(enabled: bool, feature: &'static str, used: &'static str) -> Result<(), Error> {
+    if enabled {
+        Ok(())
+    } else {
+        Err(Error::FeatureDisabled { feature, used })
+    }
+}
+
+/// Options of `Module::deserialize_with`.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub(crate) features: WasmFeatures,
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config::default()
+    }
+
+    /// Reject modules using proposals which are not enabled in `features`.
+    pub fn features(mut self, features: WasmFeatures) -> Config {
+        self.features = features;
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io;
+    use crate::elements::{serialize, DecodeError};
+
+    fn decode(bytes: &[u8], features: WasmFeatures) -> Result<Module, DecodeError> {
+        let mut cursor = io::Cursor::new(bytes);
+        Module::deserialize_with(&mut cursor, &Config::new().features(features))
+    }
+
+    fn decode_text(text: &str, features: WasmFeatures) -> Result<Module, DecodeError> {
+        decode(&serialize(&crate::wat::parse(text).unwrap()).unwrap(), features)
+    }
+
+    fn disabled(result: Result<Module, DecodeError>) -> Option<&'static str> {
+        match result.map_err(|e| e.kind) {
+            Err(Error::FeatureDisabled { feature, .. }) => Some(feature),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_features() {
+        let mvp = "(module (memory 1) (func (param i32) (result i32) local.get 0 i32.const 1 i32.add))";
+        assert!(decode_text(mvp, WasmFeatures::mvp()).is_ok());
+
+        let extend = "(module (func (param i32) (result i32) local.get 0 i32.extend8_s))";
+        assert!(decode_text(extend, WasmFeatures::all()).is_ok());
+        assert_eq!(disabled(decode_text(extend, WasmFeatures::mvp())), Some("sign_extension"));
+
+        let passive = r#"(module (memory 1) (data "abc") (func data.drop 0))"#;
+        assert_eq!(disabled(decode_text(passive, WasmFeatures::mvp())), Some("bulk_memory"));
+        let features = WasmFeatures { bulk_memory: true, ..WasmFeatures::mvp() };
+        assert!(decode_text(passive, features).is_ok());
+
+        let externref = "(module (table 1 externref))";
+        assert_eq!(disabled(decode_text(externref, WasmFeatures::mvp())), Some("reference_types"));
+
+        let extended = "(module (global i32 (i32.add (i32.const 1) (i32.const 2))))";
+        let features = WasmFeatures { extended_const: false, ..WasmFeatures::all() };
+        assert_eq!(disabled(decode_text(extended, features)), Some("extended_const"));
+    }
+
+    #[test]
+    fn test_disabled_offset() {
+        // 报告的位置是用到 proposal 的指令或条目，而不是 section 的开头
+        let bytes = serialize(&crate::wat::parse("(module (func (param i32) (result i32) local.get 0 i32.extend8_s))").unwrap()).unwrap();
+        let extend = bytes.iter().rposition(|&b| b == 0xc0).unwrap();
+        let err = decode(&bytes, WasmFeatures::mvp()).unwrap_err();
+        assert_eq!((err.offset, err.section_id, err.function_index), (extend, Some(10), Some(0)));
+
+        // 第二个 table 从 offset 14 开始: 04 07 02 | 70 00 01 | 70 00 01
+        let bytes = serialize(&crate::wat::parse("(module (table 1 funcref) (table 1 funcref))").unwrap()).unwrap();
+        assert_eq!(&bytes[8..17], &[0x04, 0x07, 0x02, 0x70, 0x00, 0x01, 0x70, 0x00, 0x01]);
+        let err = decode(&bytes, WasmFeatures::mvp()).unwrap_err();
+        assert_eq!(err.to_string(), "Multiple tables used but reference_types feature disabled at offset 0xe in table section");
+    }
+
+    #[test]
+    #[cfg(all(feature = "gc", feature = "tail_call"))]
+    fn test_return_call_ref_disabled() {
+        let text = "(module (type $f (func)) (elem declare func 0) (func (type $f) ref.func 0 return_call_ref $f))";
+        let features = WasmFeatures { tail_call: false, ..WasmFeatures::all() };
+        assert_eq!(disabled(decode_text(text, features)), Some("tail_call"));
+        let features = WasmFeatures { gc: false, ..WasmFeatures::all() };
+        assert_eq!(disabled(decode_text(text, features)), Some("gc"));
+        assert!(decode_text(text, WasmFeatures::all()).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "simd")]
+    fn test_simd_disabled() {
+        let text = "(module (func (result i32) v128.const i64x2 1 2 i32x4.extract_lane 0))";
+        let err = decode_text(text, WasmFeatures::mvp()).unwrap_err();
+        assert_eq!(err.kind.to_string(), "SIMD instruction used but simd feature disabled");
+        assert_eq!((err.section_id, err.function_index), (Some(10), Some(0)));
+        assert!(decode_text(text, WasmFeatures { simd: true, ..WasmFeatures::mvp() }).is_ok());
+    }
+
+    #[test]
+    #[cfg(not(feature = "simd"))]
+    fn test_simd_compiled_out() {
+        // (func v128.const ...) 的函数体以 0xfd 前缀开始
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00,
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0xfd, 0x0c, 0x0b,
+        ];
+        let err = decode(&bytes, WasmFeatures::all()).unwrap_err();
+        assert_eq!(err.to_string(), "SIMD instruction used but simd feature disabled at offset 0x17 in code section, function #0");
+    }
+
+    #[test]
+    #[cfg(feature = "threads")]
+    fn test_threads_disabled() {
+        let text = "(module (memory 1 1 shared) (func (result i32) i32.const 0 i32.atomic.load))";
+        assert_eq!(disabled(decode_text(text, WasmFeatures::mvp())), Some("threads"));
+        let features = WasmFeatures { threads: true, ..WasmFeatures::mvp() };
+        assert!(decode_text(text, features).is_ok());
+    }
+}
@@ -1,9 +1,11 @@
 use std::io;
-use super::{Deserialize, Error};
-use super::primitives::{VarUint32, CountedList};
+use super::{Deserialize, Serialize, Error, PayloadError};
+use super::primitives::{VarUint32, CountedList, CountedListWriter};
 use super::types::{ValueType};
-use super::sections::SectionReader;
+use super::sections::{read_value, read_whole, SectionReader};
 use super::ops::{Instructions};
+use super::features::FeatureCheck;
+use crate::io::PositionReader;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Func(pub u32);
@@ -17,6 +19,14 @@ impl Deserialize for Func {
     }
 }
 
+impl Serialize for Func {
+    type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        VarUint32(self.0).serialize(writer)
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Local {
     pub count: u32,
@@ -35,6 +45,15 @@ impl Deserialize for Local {
     }
 }
 
+impl Serialize for Local {
+    type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        VarUint32(self.count).serialize(writer)?;
+        self.value_type.serialize(writer)
+    }
+}
+
 /// Function body definition.
 #[derive(Debug, Clone, PartialEq)]
 pub struct FuncBody {
@@ -42,23 +61,56 @@ pub struct FuncBody {
     pub instructions: Instructions,
 }
 
+impl FuncBody {
+    /// Decode a length-prefixed body, checking its locals and instructions as they are decoded.
+    /// Errors are located at the failing instruction.
+    pub(crate) fn read<R: io::Read>(reader: &mut PositionReader<R>, check: &mut FeatureCheck) -> Result<FuncBody, PayloadError> {
+        let start = reader.position();
+        let body = SectionReader::new(reader).map_err(|kind| PayloadError::at(start, kind))?.payload();
+        let base = reader.position() - body.len();
+
+        read_whole(&body, |reader| {
+            let locals: Vec<Local> = read_value::<CountedList<Local>, _>(reader)?.into_inner();
+            let located = |kind| PayloadError::at(0, kind);
+
+            // The specification obliges us to count the total number of local variables while
+            // decoding the binary format.
+            locals
+                .iter()
+                .try_fold(0u32, |acc, &Local { count, .. }| acc.checked_add(count))
+                .ok_or(Error::TooManyLocals)
+                .map_err(located)?;
+            for local in locals.iter() {
+                check.entry(local).map_err(located)?;
+            }
+
+            let instructions = Instructions::read(reader, check)?;
+            Ok(FuncBody { locals: locals, instructions: instructions })
+        }).map_err(|e| PayloadError { offset: base + e.offset, ..e })
+    }
+}
+
 impl Deserialize for FuncBody {
     type Error = Error;
 
     fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
-        let mut body_reader = SectionReader::new(reader)?;
-        let locals: Vec<Local> = CountedList::<Local>::deserialize(&mut body_reader)?.into_inner();
-
-        // The specification obliges us to count the total number of local variables while
-        // decoding the binary format.
-        locals
-            .iter()
-            .try_fold(0u32, |acc, &Local { count, .. }| acc.checked_add(count))
-            .ok_or_else(|| Error::TooManyLocals)?;
-
-        let instructions = Instructions::deserialize(&mut body_reader)?;
-        body_reader.close()?;
-        Ok(FuncBody { locals: locals, instructions: instructions })
+        let mut reader = PositionReader::new(reader);
+        FuncBody::read(&mut reader, &mut FeatureCheck::new(None)).map_err(|e| e.kind)
+    }
+}
+
+impl Serialize for FuncBody {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        // 函数体前面有长度前缀，所以先写到 buffer 里
+        let mut body: Vec<u8> = Vec::new();
+        CountedListWriter(&self.locals).serialize(&mut body)?;
+        self.instructions.serialize(&mut body)?;
+
+        VarUint32(body.len() as u32).serialize(writer)?;
+        writer.write_all(&body)?;
+        Ok(())
     }
 }
 
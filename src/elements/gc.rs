@@ -0,0 +1,269 @@
+use std::io;
+
+use super::{Deserialize, Serialize, Error};
+use super::primitives::{Uint8, VarUint32};
+use super::types::{HeapType, RefType};
+
+/// Label and types of `br_on_cast` and `br_on_cast_fail`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BrOnCastData {
+    pub label: u32,
+    /// Type of the operand.
+    pub from: RefType,
+    /// Type the operand is cast to.
+    pub to: RefType,
+}
+
+/// Instruction of the GC proposal, encoded after the 0xFB prefix.
+///
+/// Type immediates are indices of struct or array types, field
+/// immediates follow the type index.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[allow(missing_docs)]
+pub enum GcInstruction {
+    StructNew(u32),
+    StructNewDefault(u32),
+    StructGet(u32, u32),
+    StructGetS(u32, u32),
+    StructGetU(u32, u32),
+    StructSet(u32, u32),
+    ArrayNew(u32),
+    ArrayNewDefault(u32),
+    /// Type index and number of elements taken from the stack.
+    ArrayNewFixed(u32, u32),
+    /// Type index and data segment.
+    ArrayNewData(u32, u32),
+    /// Type index and element segment.
+    ArrayNewElem(u32, u32),
+    ArrayGet(u32),
+    ArrayGetS(u32),
+    ArrayGetU(u32),
+    ArraySet(u32),
+    ArrayLen,
+    ArrayFill(u32),
+    /// Destination and source type index.
+    ArrayCopy(u32, u32),
+    ArrayInitData(u32, u32),
+    ArrayInitElem(u32, u32),
+    RefTest(RefType),
+    RefCast(RefType),
+    BrOnCast(Box<BrOnCastData>),
+    BrOnCastFail(Box<BrOnCastData>),
+    AnyConvertExtern,
+    ExternConvertAny,
+    RefI31,
+    I31GetS,
+    I31GetU,
+}
+
+impl GcInstruction {
+    /// Sub-opcode following the 0xFB prefix.
+    pub fn opcode(&self) -> u32 {
+        use self::GcInstruction::*;
+
+        match *self {
+            StructNew(_) => 0,
+            StructNewDefault(_) => 1,
+            StructGet(..) => 2,
+            StructGetS(..) => 3,
+            StructGetU(..) => 4,
+            StructSet(..) => 5,
+            ArrayNew(_) => 6,
+            ArrayNewDefault(_) => 7,
+            ArrayNewFixed(..) => 8,
+            ArrayNewData(..) => 9,
+            ArrayNewElem(..) => 10,
+            ArrayGet(_) => 11,
+            ArrayGetS(_) => 12,
+            ArrayGetU(_) => 13,
+            ArraySet(_) => 14,
+            ArrayLen => 15,
+            ArrayFill(_) => 16,
+            ArrayCopy(..) => 17,
+            ArrayInitData(..) => 18,
+            ArrayInitElem(..) => 19,
+            RefTest(ref_type) => if ref_type.nullable { 21 } else { 20 },
+            RefCast(ref_type) => if ref_type.nullable { 23 } else { 22 },
+            BrOnCast(_) => 24,
+            BrOnCastFail(_) => 25,
+            AnyConvertExtern => 26,
+            ExternConvertAny => 27,
+            RefI31 => 28,
+            I31GetS => 29,
+            I31GetU => 30,
+        }
+    }
+
+    /// Whether the instruction is allowed in constant expressions.
+    pub fn is_constant(&self) -> bool {
+        use self::GcInstruction::*;
+
+        matches!(
+            *self,
+            StructNew(_) | StructNewDefault(_) | ArrayNew(_) | ArrayNewDefault(_) | ArrayNewFixed(..)
+                | AnyConvertExtern | ExternConvertAny | RefI31
+        )
+    }
+}
+
+fn read_index<R: io::Read>(reader: &mut R) -> Result<u32, Error> {
+    Ok(VarUint32::deserialize(reader)?.into())
+}
+
+fn read_pair<R: io::Read>(reader: &mut R) -> Result<(u32, u32), Error> {
+    Ok((read_index(reader)?, read_index(reader)?))
+}
+
+/// Reads `br_on_cast` immediates, bit 0 and 1 of the flags make the source and target types nullable.
+fn read_br_on_cast<R: io::Read>(reader: &mut R) -> Result<Box<BrOnCastData>, Error> {
+    let flags: u8 = Uint8::deserialize(reader)?.into();
+    if flags > 3 {
+        return Err(Error::Other("Invalid br_on_cast flags"));
+    }
+    let label = read_index(reader)?;
+    let from = RefType { nullable: flags & 1 != 0, heap: HeapType::deserialize(reader)? };
+    let to = RefType { nullable: flags & 2 != 0, heap: HeapType::deserialize(reader)? };
+    Ok(Box::new(BrOnCastData { label, from, to }))
+}
+
+impl Deserialize for GcInstruction {
+    type Error = Error;
+
+    /// Reads the sub-opcode and immediates, the 0xFB prefix is already consumed.
+    fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+        use self::GcInstruction::*;
+
+        let val: u32 = VarUint32::deserialize(reader)?.into();
+        Ok(match val {
+            0 => StructNew(read_index(reader)?),
+            1 => StructNewDefault(read_index(reader)?),
+            2..=5 => {
+                let (ty, field) = read_pair(reader)?;
+                match val {
+                    2 => StructGet(ty, field),
+                    3 => StructGetS(ty, field),
+                    4 => StructGetU(ty, field),
+                    _ => StructSet(ty, field),
+                }
+            },
+            6 => ArrayNew(read_index(reader)?),
+            7 => ArrayNewDefault(read_index(reader)?),
+            8 => {
+                let (ty, len) = read_pair(reader)?;
+                ArrayNewFixed(ty, len)
+            },
+            9 => {
+                let (ty, segment) = read_pair(reader)?;
+                ArrayNewData(ty, segment)
+            },
+            10 => {
+                let (ty, segment) = read_pair(reader)?;
+                ArrayNewElem(ty, segment)
+            },
+            11 => ArrayGet(read_index(reader)?),
+            12 => ArrayGetS(read_index(reader)?),
+            13 => ArrayGetU(read_index(reader)?),
+            14 => ArraySet(read_index(reader)?),
+            15 => ArrayLen,
+            16 => ArrayFill(read_index(reader)?),
+            17 => {
+                let (dst, src) = read_pair(reader)?;
+                ArrayCopy(dst, src)
+            },
+            18 => {
+                let (ty, segment) = read_pair(reader)?;
+                ArrayInitData(ty, segment)
+            },
+            19 => {
+                let (ty, segment) = read_pair(reader)?;
+                ArrayInitElem(ty, segment)
+            },
+            20 | 21 => RefTest(RefType { nullable: val == 21, heap: HeapType::deserialize(reader)? }),
+            22 | 23 => RefCast(RefType { nullable: val == 23, heap: HeapType::deserialize(reader)? }),
+            24 => BrOnCast(read_br_on_cast(reader)?),
+            25 => BrOnCastFail(read_br_on_cast(reader)?),
+            26 => AnyConvertExtern,
+            27 => ExternConvertAny,
+            28 => RefI31,
+            29 => I31GetS,
+            30 => I31GetU,
+            _ => return Err(Error::UnknownGcOpcode(val)),
+        })
+    }
+}
+
+impl Serialize for GcInstruction {
+    type Error = Error;
+
+    /// Writes the sub-opcode and immediates, without the 0xFB prefix.
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        use self::GcInstruction::*;
+
+        VarUint32(self.opcode()).serialize(writer)?;
+        match *self {
+            StructNew(idx) | StructNewDefault(idx) | ArrayNew(idx) | ArrayNewDefault(idx) | ArrayGet(idx)
+                | ArrayGetS(idx) | ArrayGetU(idx) | ArraySet(idx) | ArrayFill(idx) => {
+                VarUint32(idx).serialize(writer)?;
+            },
+            StructGet(a, b) | StructGetS(a, b) | StructGetU(a, b) | StructSet(a, b) | ArrayNewFixed(a, b)
+                | ArrayNewData(a, b) | ArrayNewElem(a, b) | ArrayCopy(a, b) | ArrayInitData(a, b)
+                | ArrayInitElem(a, b) => {
+                VarUint32(a).serialize(writer)?;
+                VarUint32(b).serialize(writer)?;
+            },
+            RefTest(ref_type) | RefCast(ref_type) => ref_type.heap.serialize(writer)?,
+            BrOnCast(ref data) | BrOnCastFail(ref data) => {
+                let flags = data.from.nullable as u8 | (data.to.nullable as u8) << 1;
+                Uint8(flags).serialize(writer)?;
+                VarUint32(data.label).serialize(writer)?;
+                data.from.heap.serialize(writer)?;
+                data.to.heap.serialize(writer)?;
+            },
+            ArrayLen | AnyConvertExtern | ExternConvertAny | RefI31 | I31GetS | I31GetU => {},
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elements::ops::Instruction;
+    use crate::elements::{deserialize_buffer, serialize};
+
+    #[test]
+    fn test_gc_encoding() {
+        let cases: Vec<(Vec<u8>, Instruction)> = vec![
+            (vec![0xfb, 0x00, 0x02], Instruction::Gc(GcInstruction::StructNew(2))),
+            (vec![0xfb, 0x03, 0x01, 0x04], Instruction::Gc(GcInstruction::StructGetS(1, 4))),
+            (vec![0xfb, 0x08, 0x00, 0x03], Instruction::Gc(GcInstruction::ArrayNewFixed(0, 3))),
+            (vec![0xfb, 0x0f], Instruction::Gc(GcInstruction::ArrayLen)),
+            (vec![0xfb, 0x14, 0x6c], Instruction::Gc(GcInstruction::RefTest(RefType::non_nullable(HeapType::I31)))),
+            (vec![0xfb, 0x17, 0x05], Instruction::Gc(GcInstruction::RefCast(RefType::nullable(HeapType::Concrete(5))))),
+            (
+                vec![0xfb, 0x18, 0x01, 0x02, 0x6e, 0x03],
+                Instruction::Gc(GcInstruction::BrOnCast(Box::new(BrOnCastData {
+                    label: 2,
+                    from: RefType::nullable(HeapType::Any),
+                    to: RefType::non_nullable(HeapType::Concrete(3)),
+                }))),
+            ),
+            (vec![0xfb, 0x1c], Instruction::Gc(GcInstruction::RefI31)),
+            (vec![0x14, 0x01], Instruction::CallRef(1)),
+            (vec![0xd3], Instruction::RefEq),
+            (vec![0xd5, 0x00], Instruction::BrOnNull(0)),
+            (vec![0xd0, 0x6b], Instruction::RefNull(crate::elements::types::TableElementType::null_of(HeapType::Struct))),
+            (vec![0xd0, 0x70], Instruction::RefNull(crate::elements::types::TableElementType::AnyFunc)),
+        ];
+        for (bytes, instruction) in cases {
+            assert_eq!(deserialize_buffer::<Instruction>(&bytes).unwrap(), instruction);
+            assert_eq!(serialize(&instruction).unwrap(), bytes);
+        }
+
+        match deserialize_buffer::<Instruction>(&[0xfb, 0x1f]) {
+            Err(Error::UnknownGcOpcode(0x1f)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(deserialize_buffer::<Instruction>(&[0xfb, 0x18, 0x04, 0x00, 0x6e, 0x6e]).is_err());
+    }
+}
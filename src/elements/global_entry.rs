@@ -1,6 +1,6 @@
 use super::types::{ValueType};
 use super::ops::{Instruction, InitExpr};
-use super::{Deserialize, Error};
+use super::{Deserialize, Serialize, Error};
 use super::import_entry::{GlobalType};
 use std::io;
 
@@ -23,4 +23,11 @@ impl Deserialize for GlobalEntry {
     }
 }
 
+impl Serialize for GlobalEntry {
+    type Error = Error;
 
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.global_type.serialize(writer)?;
+        self.init_expr.serialize(writer)
+    }
+}
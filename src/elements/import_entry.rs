@@ -1,14 +1,20 @@
 use super::types::{TableElementType, ValueType};
-use super::{Deserialize, Error};
-use super::primitives::{Uint8, VarUint32, VarUint1, VarInt7};
+use super::{Deserialize, Serialize, Error};
+use super::primitives::{Uint8, VarUint32, VarUint64, VarUint1, VarInt7, VarUint7};
 use std::io;
 
 const FLAG_HAS_MAX: u8 = 0x01;
+const FLAG_SHARED: u8 = 0x02;
+const FLAG_MEMORY64: u8 = 0x04;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct ResizableLimits {
-	pub initial: u32,
-	pub maximum: Option<u32>
+	pub initial: u64,
+	pub maximum: Option<u64>,
+	/// Shared between threads, only decoded with the `threads` feature.
+	pub shared: bool,
+	/// Addressed with i64, only decoded with the `memory64` feature.
+	pub memory64: bool,
 }
 
 impl Deserialize for ResizableLimits {
@@ -16,26 +22,65 @@ impl Deserialize for ResizableLimits {
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Error> {
         let flags: u8 = Uint8::deserialize(reader)?.into();
-        match flags {
-            0x00 | 0x01 => {},
-            _ => return Err(Error::InvalidLimitsFlags(flags)),
+        #[allow(unused_mut)]
+        let mut allowed = FLAG_HAS_MAX;
+        #[cfg(feature = "threads")]
+        {
+            allowed |= FLAG_SHARED;
+        }
+        #[cfg(feature = "memory64")]
+        {
+            allowed |= FLAG_MEMORY64;
+        }
+        if flags & !allowed != 0 {
+            return Err(Error::InvalidLimitsFlags(flags));
         }
 
-        let initial: u32 = VarUint32::deserialize(reader)?.into();
+        let memory64 = flags & FLAG_MEMORY64 != 0;
+        // 64 位内存的大小是 u64，否则是 u32
+        let size = |reader: &mut R| -> Result<u64, Error> {
+            if memory64 {
+                Ok(VarUint64::deserialize(reader)?.into())
+            } else {
+                Ok(u32::from(VarUint32::deserialize(reader)?) as u64)
+            }
+        };
+        let initial = size(reader)?;
         let maximum = if flags & FLAG_HAS_MAX != 0 {
-            Some(VarUint32::deserialize(reader)?.into())
+            Some(size(reader)?)
         } else {
             None
         };
 
         Ok(
             ResizableLimits {
-                initial, maximum
+                initial, maximum,
+                shared: flags & FLAG_SHARED != 0,
+                memory64,
             }
         )
     }
 }
 
+impl Serialize for ResizableLimits {
+    type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let mut flags: u8 = if self.maximum.is_some() { FLAG_HAS_MAX } else { 0x00 };
+        if self.shared {
+            flags |= FLAG_SHARED;
+        }
+        if self.memory64 {
+            flags |= FLAG_MEMORY64;
+        }
+        Uint8(flags).serialize(writer)?;
+        VarUint64(self.initial).serialize(writer)?;
+        if let Some(maximum) = self.maximum {
+            VarUint64(maximum).serialize(writer)?;
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct TableType {
@@ -59,12 +104,23 @@ impl Deserialize for TableType {
 
 }   
 
+impl Serialize for TableType {
+    type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.elem_type.serialize(writer)?;
+        self.limits.serialize(writer)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum External {
     Function(u32),
     Table(TableType),
     Memory(ResizableLimits),
-    Global(GlobalType)
+    Global(GlobalType),
+    #[cfg(feature = "exceptions")]
+    Tag(TagType),
 }
 
 impl Deserialize for External {
@@ -78,11 +134,43 @@ impl Deserialize for External {
             0x01 => Ok(External::Table(TableType::deserialize(reader)?)),
             0x02 => Ok(External::Memory(ResizableLimits::deserialize(reader)?)),
             0x03 => Ok(External::Global(GlobalType::deserialize(reader)?)),
+            #[cfg(feature = "exceptions")]
+            0x04 => Ok(External::Tag(TagType::deserialize(reader)?)),
             _ => Err(Error::UnknownExternalKind(kind)),
         }
     }
 }
 
+impl Serialize for External {
+    type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        match *self {
+            External::Function(index) => {
+                VarUint7(0x00).serialize(writer)?;
+                VarUint32(index).serialize(writer)
+            },
+            External::Table(ref table_type) => {
+                VarUint7(0x01).serialize(writer)?;
+                table_type.serialize(writer)
+            },
+            External::Memory(ref limits) => {
+                VarUint7(0x02).serialize(writer)?;
+                limits.serialize(writer)
+            },
+            External::Global(ref global_type) => {
+                VarUint7(0x03).serialize(writer)?;
+                global_type.serialize(writer)
+            },
+            #[cfg(feature = "exceptions")]
+            External::Tag(ref tag_type) => {
+                VarUint7(0x04).serialize(writer)?;
+                tag_type.serialize(writer)
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ImportEntry {
     pub module_str: String,
@@ -104,6 +192,16 @@ impl Deserialize for ImportEntry {
     }
 }
 
+impl Serialize for ImportEntry {
+    type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.module_str.serialize(writer)?;
+        self.field_str.serialize(writer)?;
+        self.external.serialize(writer)
+    }
+}
+
 /// Global definition struct
 #[derive(Debug, Clone, PartialEq)]
 pub struct GlobalType {
@@ -126,4 +224,43 @@ impl Deserialize for GlobalType {
     }
 }
 
+impl Serialize for GlobalType {
+    type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.content_type.serialize(writer)?;
+        VarUint1(self.is_mutable).serialize(writer)
+    }
+}
+
+/// Tag of an exception, its parameters are the payload.
+#[cfg(feature = "exceptions")]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TagType {
+    /// Type index of a function type without results.
+    pub type_index: u32,
+}
+
+#[cfg(feature = "exceptions")]
+impl Deserialize for TagType {
+    type Error = Error;
+
+    fn deserialize<R: io::Read>(reader: &mut R) -> Result<TagType, Error> {
+        // 目前只定义了异常这一种 attribute
+        let attribute: u8 = Uint8::deserialize(reader)?.into();
+        if attribute != 0 {
+            return Err(Error::InvalidTagAttribute(attribute));
+        }
+        Ok(TagType { type_index: VarUint32::deserialize(reader)?.into() })
+    }
+}
+
+#[cfg(feature = "exceptions")]
+impl Serialize for TagType {
+    type Error = Error;
 
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        Uint8(0).serialize(writer)?;
+        VarUint32(self.type_index).serialize(writer)
+    }
+}
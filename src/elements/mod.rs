@@ -12,7 +12,7 @@ macro_rules! buffered_read {
             let mut buf = [0u8; $buffer_size];
             while total_read < $length {
                 let next_to_read = if $length - total_read > $buffer_size  { $buffer_size } else { $length - total_read };
-                $reader.read(&mut buf[0..next_to_read])?;
+                $reader.read_exact(&mut buf[0..next_to_read])?;
                 v.extend_from_slice(&buf[0..next_to_read]);
                 total_read += next_to_read;
             }
@@ -28,9 +28,18 @@ pub mod types;
 pub mod import_entry;
 pub mod func;
 pub mod ops;
+pub mod const_expr;
+pub mod features;
 pub mod global_entry;
 pub mod segment;
 pub mod export_entry;
+pub mod name_section;
+#[cfg(feature = "simd")]
+pub mod simd;
+#[cfg(feature = "threads")]
+pub mod atomics;
+#[cfg(feature = "gc")]
+pub mod gc;
 
 pub fn print_stream<R: io::Read>(r: &mut R, max_len: usize) -> io::Result<()> {
     const BUF_SIZE: usize = 256;
@@ -38,8 +47,8 @@ pub fn print_stream<R: io::Read>(r: &mut R, max_len: usize) -> io::Result<()> {
     let mut already_read: usize = 0;
     while already_read < max_len {
         let max = if max_len - already_read > BUF_SIZE  { BUF_SIZE } else { max_len - already_read };
-        let slice = &mut buf[0..max_len];
-        r.read(slice)?;
+        let slice = &mut buf[0..max];
+        r.read_exact(slice)?;
 
         for i in slice.iter() {
             print!("{:02x}", i);
@@ -59,11 +68,33 @@ pub trait Deserialize : Sized {
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error>;
 }
 
+/// Serialization to serial i/o.
+pub trait Serialize {
+	/// Serialization error produced by serialization routine.
+	type Error: From<io::Error>;
+	/// Serialize type to serial i/o
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error>;
+}
+
+/// Deserialize a value from a byte slice.
+pub fn deserialize_buffer<T: Deserialize>(contents: &[u8]) -> Result<T, T::Error> {
+    T::deserialize(&mut io::Cursor::new(contents))
+}
+
+/// Serialize a value into a new byte vector.
+pub fn serialize<T: Serialize>(val: &T) -> Result<Vec<u8>, T::Error> {
+    let mut buf = Vec::new();
+    val.serialize(&mut buf)?;
+    Ok(buf)
+}
+
 /// Deserialization/serialization error
 #[derive(Debug, Clone)]
 pub enum Error {
 	/// Unexpected end of input.
 	UnexpectedEof,
+	/// I/O error of the underlying reader or writer, other than end of input.
+	Io(io::ErrorKind),
 	/// Invalid magic.
 	InvalidMagic,
 	/// Unsupported version.
@@ -91,9 +122,24 @@ pub enum Error {
 	UnknownInternalKind(u8),
 	/// Unknown opcode encountered.
 	UnknownOpcode(u8),
+	/// Construct of a proposal which is compiled out or disabled by `WasmFeatures`.
+	FeatureDisabled {
+		/// Name of the proposal, as in `WasmFeatures`.
+		feature: &'static str,
+		/// What the module uses, e.g. "SIMD instruction".
+		used: &'static str,
+	},
+	/// Unknown sub-opcode after the 0xFC prefix.
+	UnknownFcOpcode(u32),
 	#[cfg(feature="simd")]
 	/// Unknown SIMD opcode encountered.
 	UnknownSimdOpcode(u32),
+	#[cfg(feature="threads")]
+	/// Unknown sub-opcode after the 0xFE prefix.
+	UnknownAtomicOpcode(u32),
+	#[cfg(feature="gc")]
+	/// Unknown sub-opcode after the 0xFB prefix.
+	UnknownGcOpcode(u32),
 	/// Invalid VarUint1 value.
 	InvalidVarUint1(u8),
 	/// Invalid VarInt32 value.
@@ -132,12 +178,19 @@ pub enum Error {
 	DuplicatedNameSubsections(u8),
 	/// Unknown name subsection type.
 	UnknownNameSubsectionType(u8),
+	#[cfg(feature="exceptions")]
+	/// Tag attribute other than 0 (exception).
+	InvalidTagAttribute(u8),
+	#[cfg(feature="exceptions")]
+	/// Unknown kind of a `try_table` catch clause.
+	UnknownCatchKind(u8),
 }
 
 impl fmt::Display for Error {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match *self {
 			Error::UnexpectedEof => write!(f, "Unexpected end of input"),
+			Error::Io(kind) => write!(f, "I/O error: {}", kind),
 			Error::InvalidMagic => write!(f, "Invalid magic number at start of file"),
 			Error::UnsupportedVersion(v) => write!(f, "Unsupported wasm version {}", v),
 			Error::InconsistentLength { expected, actual } => {
@@ -150,9 +203,15 @@ impl fmt::Display for Error {
 			Error::NonUtf8String => write!(f, "Non-UTF-8 string"),
 			Error::UnknownExternalKind(kind) => write!(f, "Unknown external kind {}", kind),
 			Error::UnknownInternalKind(kind) => write!(f, "Unknown internal kind {}", kind),
-			Error::UnknownOpcode(opcode) => write!(f, "Unknown opcode {}", opcode),
+			Error::UnknownOpcode(opcode) => write!(f, "Unknown opcode {:#04x}", opcode),
+			Error::FeatureDisabled { feature, used } => write!(f, "{} used but {} feature disabled", used, feature),
+			Error::UnknownFcOpcode(opcode) => write!(f, "Unknown 0xFC-prefixed opcode {}", opcode),
 			#[cfg(feature="simd")]
 			Error::UnknownSimdOpcode(opcode) => write!(f, "Unknown SIMD opcode {}", opcode),
+			#[cfg(feature="threads")]
+			Error::UnknownAtomicOpcode(opcode) => write!(f, "Unknown atomic opcode {}", opcode),
+			#[cfg(feature="gc")]
+			Error::UnknownGcOpcode(opcode) => write!(f, "Unknown GC opcode {}", opcode),
 			Error::InvalidVarUint1(val) => write!(f, "Not an unsigned 1-bit integer: {}", val),
 			Error::InvalidVarInt7(val) => write!(f, "Not a signed 7-bit integer: {}", val),
 			Error::InvalidVarInt32 => write!(f, "Not a signed 32-bit integer"),
@@ -172,6 +231,10 @@ impl fmt::Display for Error {
 			Error::TooManyLocals => write!(f, "Too many locals"),
 			Error::DuplicatedNameSubsections(n) =>  write!(f, "Duplicated name subsections: {}", n),
 			Error::UnknownNameSubsectionType(n) => write!(f, "Unknown subsection type: {}", n),
+			#[cfg(feature="exceptions")]
+			Error::InvalidTagAttribute(n) => write!(f, "Invalid tag attribute: {}", n),
+			#[cfg(feature="exceptions")]
+			Error::UnknownCatchKind(n) => write!(f, "Unknown catch kind: {}", n),
 		}
 	}
 }
@@ -181,6 +244,7 @@ impl ::std::error::Error for Error {
 	fn description(&self) -> &str {
 		match *self {
 			Error::UnexpectedEof => "Unexpected end of input",
+			Error::Io(_) => "I/O error",
 			Error::InvalidMagic => "Invalid magic number at start of file",
 			Error::UnsupportedVersion(_) => "Unsupported wasm version",
 			Error::InconsistentLength { .. } => "Inconsistent length",
@@ -192,8 +256,14 @@ impl ::std::error::Error for Error {
 			Error::UnknownExternalKind(_) => "Unknown external kind",
 			Error::UnknownInternalKind(_) => "Unknown internal kind",
 			Error::UnknownOpcode(_) => "Unknown opcode",
+			Error::FeatureDisabled { .. } => "Proposal feature disabled",
+			Error::UnknownFcOpcode(_) => "Unknown 0xFC-prefixed opcode",
 			#[cfg(feature="simd")]
 			Error::UnknownSimdOpcode(_) => "Unknown SIMD opcode",
+			#[cfg(feature="threads")]
+			Error::UnknownAtomicOpcode(_) => "Unknown atomic opcode",
+			#[cfg(feature="gc")]
+			Error::UnknownGcOpcode(_) => "Unknown GC opcode",
 			Error::InvalidVarUint1(_) => "Not an unsigned 1-bit integer",
 			Error::InvalidVarInt32 => "Not a signed 32-bit integer",
 			Error::InvalidVarInt7(_) => "Not a signed 7-bit integer",
@@ -213,12 +283,72 @@ impl ::std::error::Error for Error {
 			Error::TooManyLocals => "Too many locals",
 			Error::DuplicatedNameSubsections(_) =>  "Duplicated name subsections",
 			Error::UnknownNameSubsectionType(_) => "Unknown name subsections type",
+			#[cfg(feature="exceptions")]
+			Error::InvalidTagAttribute(_) => "Invalid tag attribute",
+			#[cfg(feature="exceptions")]
+			Error::UnknownCatchKind(_) => "Unknown catch kind",
+		}
+	}
+}
+
+/// Error decoding a module, with where in the binary it happened.
+#[derive(Debug, Clone)]
+pub struct DecodeError {
+	/// Offset in the module of the instruction, entry or section which failed to decode.
+	pub offset: usize,
+	/// Id of the section, `None` for the module header.
+	pub section_id: Option<u8>,
+	/// Function whose body failed to decode, imported functions included.
+	pub function_index: Option<u32>,
+	pub kind: Error,
+}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} at offset {:#x}", self.kind, self.offset)?;
+		if let Some(id) = self.section_id {
+			write!(f, " in {} section", sections::section_name(id))?;
+		}
+		if let Some(idx) = self.function_index {
+			write!(f, ", function #{}", idx)?;
 		}
+		Ok(())
+	}
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for DecodeError {
+	fn source(&self) -> Option<&(dyn ::std::error::Error + 'static)> {
+		Some(&self.kind)
+	}
+}
+
+impl From<io::Error> for DecodeError {
+	fn from(other: io::Error) -> DecodeError {
+		DecodeError { offset: 0, section_id: None, function_index: None, kind: other.into() }
+	}
+}
+
+/// Error in a section payload, `offset` is relative to the start of the payload.
+#[derive(Debug)]
+pub(crate) struct PayloadError {
+	pub offset: usize,
+	/// Index of the failing body in the code section.
+	pub body: Option<u32>,
+	pub kind: Error,
+}
+
+impl PayloadError {
+	pub fn at(offset: usize, kind: Error) -> PayloadError {
+		PayloadError { offset, body: None, kind }
 	}
 }
 
 impl From<io::Error> for Error {
     fn from(other: io::Error) -> Error {
-        Error::HeapOther(format!("I/O Error: {:?}", other))
+        match other.kind() {
+            io::ErrorKind::UnexpectedEof => Error::UnexpectedEof,
+            kind => Error::Io(kind),
+        }
     }
 }
\ No newline at end of file
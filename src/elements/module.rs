@@ -1,14 +1,26 @@
 const WASM_MAGIC_NUMBER: [u8; 4] = [0x00, 0x61, 0x73, 0x6d];
-use super::{Deserialize, Error};
-use super::primitives::Uint32;
-use super::sections::Section;
+use super::{Deserialize, Serialize, Error, DecodeError, serialize};
+use super::primitives::{Uint32, VarUint7, VarUint32};
+use super::sections::{
+    Section, CustomSection, TypeSection, ImportSection, FunctionSection, TableSection, MemorySection,
+    GlobalSection, ExportSection, ElementSection, CodeSection, DataSection,
+};
+#[cfg(feature = "exceptions")]
+use super::sections::TagSection;
+use super::name_section::NameSection;
+use super::features::{Config, FeatureCheck, WasmFeatures};
+use super::import_entry::External;
+use crate::io::{PositionReader, RecordingReader};
 use std::io;
+use std::ops::Range;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Module {
     pub magic: u32,
     pub version: u32,
     pub sections: Vec<Section>,
+    /// Original bytes of the module, only kept by `Module::deserialize_preserving_encoding`.
+    pub encoding: Option<ModuleEncoding>,
 }
 
 impl Default for Module {
@@ -16,64 +28,345 @@ impl Default for Module {
         Module {
             magic: u32::from_le_bytes(WASM_MAGIC_NUMBER),
             version:1,
-            sections: Vec::new()
+            sections: Vec::new(),
+            encoding: None,
         }
     }
 }
 
-impl Deserialize for Module {
-    	/// Serialization error produced by deserialization routine.
-	type Error = Error;
-	/// Deserialize type from serial i/o
-	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Module, Error> {
-        let mut buf = [0u8; 4];
+// 是否保留了原始编码不影响模块的内容，所以比较时忽略 encoding
+impl PartialEq for Module {
+    fn eq(&self, other: &Module) -> bool {
+        self.magic == other.magic && self.version == other.version && self.sections == other.sections
+    }
+}
 
-        // 因为 Error 实现了 From<std::io::Error>，所以可以直接使用 ? 语法糖
-        reader.read(&mut buf)?;
+/// Original encoding of a parsed module.
+///
+/// LEB128 integers may be padded (e.g. `0x80 0x00` for zero), so re-encoding a module
+/// canonically does not always reproduce its input. Every section remembers the exact
+/// byte range it was decoded from, and a section whose bytes are not canonical is
+/// written back verbatim as long as it is unmodified.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleEncoding {
+    /// The whole input, header included.
+    pub bytes: Vec<u8>,
+    /// Byte range of every section (id, size and payload), in the order of `Module::sections`.
+    pub sections: Vec<Range<usize>>,
+    /// Section as decoded when its original bytes differ from its canonical encoding,
+    /// compared with the current section to tell whether it was modified.
+    pub decoded: Vec<Option<Section>>,
+}
 
-        if buf != WASM_MAGIC_NUMBER {
-            return Err(
-                Error::InvalidMagic
-            );
-        }
+impl ModuleEncoding {
+    /// Original bytes of the section at `index`.
+    pub fn section_bytes(&self, index: usize) -> Option<&[u8]> {
+        self.sections.get(index).map(|r| &self.bytes[r.clone()])
+    }
+}
 
-        let version: u32 = Uint32::deserialize(reader)?.into();
-        if version != 1 {
-            return Err(Error::UnsupportedVersion(version));
-        }
+impl Module {
+    /// Deserialize a module and remember its original encoding,
+    /// so that serializing it again without modification yields identical bytes.
+    pub fn deserialize_preserving_encoding<R: io::Read>(reader: &mut R) -> Result<Module, DecodeError> {
+        Module::read(reader, true, None)
+    }
 
-        let mut sections: Vec<Section> = Vec::new();
+    /// Deserialize a module which may only use the proposals enabled in `config`.
+    pub fn deserialize_with<R: io::Read>(reader: &mut R, config: &Config) -> Result<Module, DecodeError> {
+        Module::read(reader, false, Some(&config.features))
+    }
+
+    /// Decode a module section by section, so that errors can be located.
+    fn read<R: io::Read>(reader: &mut R, preserve: bool, features: Option<&WasmFeatures>) -> Result<Module, DecodeError> {
+        let mut reader = PositionReader::new(reader);
+        let version = read_header(&mut reader).map_err(|kind| {
+            let offset = if let Error::UnsupportedVersion(_) = kind { 4 } else { 0 };
+            DecodeError { offset, section_id: None, function_index: None, kind }
+        })?;
 
+        let mut bytes: Vec<u8> = Vec::new();
+        if preserve {
+            bytes.extend_from_slice(&WASM_MAGIC_NUMBER);
+            bytes.extend_from_slice(&version.to_le_bytes());
+        }
+        let mut sections: Vec<Section> = Vec::new();
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut decoded: Vec<Option<Section>> = Vec::new();
+        let mut imported_functions = 0;
+        let mut check = FeatureCheck::new(features);
         loop {
-            match Section::deserialize(reader) {
-                Err(Error::UnexpectedEof) => break,
-                Err(e) => {
-                    return Err(e);
-                },
-                Ok(s) => sections.push(s),
+            let offset = reader.position();
+            let (section_bytes, header_len) = match read_section_bytes(&mut reader)? {
+                Some(section) => section,
+                None => break,
+            };
+            let id = section_bytes[0];
+            let section = Section::read_payload(id, &section_bytes[header_len..], &mut check).map_err(|e| DecodeError {
+                offset: offset + header_len + e.offset,
+                section_id: Some(id),
+                function_index: e.body.map(|i| imported_functions + i),
+                kind: e.kind,
+            })?;
+
+            if let Section::Import(ref imports) = section {
+                imported_functions = imports.0.iter().filter(|entry| matches!(entry.external, External::Function(_))).count() as u32;
             }
+            if preserve {
+                // 只在解码时比较一次，规范编码的 section 不需要保留
+                let canonical = serialize(&section).is_ok_and(|canonical| canonical == section_bytes);
+                decoded.push(if canonical { None } else { Some(section.clone()) });
+                ranges.push(offset..offset + section_bytes.len());
+                bytes.extend_from_slice(&section_bytes);
+            }
+            sections.push(section);
+        }
+
+        Ok(Module {
+            version,
+            sections,
+            encoding: if preserve { Some(ModuleEncoding { bytes, sections: ranges, decoded }) } else { None },
+            ..Module::default()
+        })
+    }
+
+    /// Custom sections of the module.
+    pub fn custom_sections(&self) -> impl Iterator<Item = &CustomSection> {
+        self.sections.iter().filter_map(|s| match *s {
+            Section::Custom(ref c) => Some(c),
+            _ => None,
+        })
+    }
+
+    /// Parsed contents of the `name` custom section, if the module has one.
+    pub fn name_section(&self) -> Result<Option<NameSection>, Error> {
+        match self.custom_sections().find(|s| s.name == NameSection::NAME) {
+            Some(section) => NameSection::from_payload(&section.payload).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn type_section(&self) -> Option<&TypeSection> {
+        self.sections.iter().find_map(|s| match *s { Section::Type(ref t) => Some(t), _ => None })
+    }
+
+    pub fn import_section(&self) -> Option<&ImportSection> {
+        self.sections.iter().find_map(|s| match *s { Section::Import(ref t) => Some(t), _ => None })
+    }
+
+    pub fn function_section(&self) -> Option<&FunctionSection> {
+        self.sections.iter().find_map(|s| match *s { Section::Function(ref t) => Some(t), _ => None })
+    }
+
+    pub fn table_section(&self) -> Option<&TableSection> {
+        self.sections.iter().find_map(|s| match *s { Section::Table(ref t) => Some(t), _ => None })
+    }
+
+    pub fn memory_section(&self) -> Option<&MemorySection> {
+        self.sections.iter().find_map(|s| match *s { Section::Memory(ref t) => Some(t), _ => None })
+    }
+
+    #[cfg(feature = "exceptions")]
+    pub fn tag_section(&self) -> Option<&TagSection> {
+        self.sections.iter().find_map(|s| match *s { Section::Tag(ref t) => Some(t), _ => None })
+    }
+
+    pub fn global_section(&self) -> Option<&GlobalSection> {
+        self.sections.iter().find_map(|s| match *s { Section::Global(ref t) => Some(t), _ => None })
+    }
+
+    pub fn export_section(&self) -> Option<&ExportSection> {
+        self.sections.iter().find_map(|s| match *s { Section::Export(ref t) => Some(t), _ => None })
+    }
+
+    pub fn start_section(&self) -> Option<u32> {
+        self.sections.iter().find_map(|s| match *s { Section::Start(idx) => Some(idx), _ => None })
+    }
+
+    pub fn elements_section(&self) -> Option<&ElementSection> {
+        self.sections.iter().find_map(|s| match *s { Section::Element(ref t) => Some(t), _ => None })
+    }
+
+    pub fn data_count_section(&self) -> Option<u32> {
+        self.sections.iter().find_map(|s| match *s { Section::DataCount(count) => Some(count), _ => None })
+    }
+
+    pub fn code_section(&self) -> Option<&CodeSection> {
+        self.sections.iter().find_map(|s| match *s { Section::Code(ref t) => Some(t), _ => None })
+    }
+
+    pub fn data_section(&self) -> Option<&DataSection> {
+        self.sections.iter().find_map(|s| match *s { Section::Data(ref t) => Some(t), _ => None })
+    }
+
+    /// Original bytes of the section at `index`, if they are not canonical and the section is unmodified.
+    fn preserved_section_bytes(&self, index: usize) -> Option<&[u8]> {
+        let encoding = self.encoding.as_ref()?;
+        match *encoding.decoded.get(index)? {
+            Some(ref original) if *original == self.sections[index] => encoding.section_bytes(index),
+            _ => None,
         }
+    }
+}
+
+fn read_header<R: io::Read>(reader: &mut R) -> Result<u32, Error> {
+    let mut buf = [0u8; 4];
+
+    // 因为 Error 实现了 From<std::io::Error>，所以可以直接使用 ? 语法糖
+    reader.read_exact(&mut buf)?;
 
-        let mut m = Module::default();
-        m.sections = sections;
-        Ok(m)
+    if buf != WASM_MAGIC_NUMBER {
+        return Err(
+            Error::InvalidMagic
+        );
     }
+
+    let version: u32 = Uint32::deserialize(reader)?.into();
+    if version != 1 {
+        return Err(Error::UnsupportedVersion(version));
+    }
+
+    Ok(version)
 }
 
+/// Bytes of the next section, id and size included, and the length of that header.
+/// `None` at the end of the module.
+fn read_section_bytes<R: io::Read>(reader: &mut PositionReader<R>) -> Result<Option<(Vec<u8>, usize)>, DecodeError> {
+    let offset = reader.position();
+    let id: u8 = match VarUint7::deserialize(reader) {
+        Ok(id) => id.into(),
+        // 只有在 section 的边界上读到末尾才是模块的结束
+        Err(Error::UnexpectedEof) => return Ok(None),
+        Err(kind) => return Err(DecodeError { offset, section_id: None, function_index: None, kind }),
+    };
+    let located = |kind| DecodeError { offset, section_id: Some(id), function_index: None, kind };
+
+    // 保留 size 的原始编码，可能不是最短的 LEB128
+    let mut header = RecordingReader::new(reader);
+    let size: u32 = VarUint32::deserialize(&mut header).map_err(located)?.into();
+    let mut bytes = vec![id];
+    bytes.extend_from_slice(&header.into_inner());
+
+    let header_len = bytes.len();
+    io::Read::read_to_end(&mut io::Read::take(&mut *reader, size as u64), &mut bytes)
+        .map_err(|e| located(e.into()))?;
+    if bytes.len() - header_len != size as usize {
+        return Err(located(Error::UnexpectedEof));
+    }
+    Ok(Some((bytes, header_len)))
+}
+
+impl Deserialize for Module {
+    	/// Serialization error produced by deserialization routine.
+	type Error = DecodeError;
+	/// Deserialize type from serial i/o
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Module, DecodeError> {
+        Module::read(reader, false, None)
+    }
+}
+
+impl Serialize for Module {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(&self.magic.to_le_bytes())?;
+        Uint32(self.version).serialize(writer)?;
+        for (i, s) in self.sections.iter().enumerate() {
+            match self.preserved_section_bytes(i) {
+                Some(bytes) => writer.write_all(bytes)?,
+                None => s.serialize(writer)?,
+            }
+        }
+        Ok(())
+    }
+}
 
 #[cfg(test)]
 mod test {
     use super::*;
     use std::io;
     use std::fs;
-    use super::super::print_stream;
+    use super::super::{deserialize_buffer, serialize};
     use crate::io::BufReader;
+    use crate::tests::SAMPLE_MODULE;
 
     #[test]
     pub fn test() {
         let _m = Module::default();
     }
 
+    #[test]
+    pub fn test_round_trip() {
+        let m: Module = deserialize_buffer(SAMPLE_MODULE).unwrap();
+        assert_eq!(m.sections.len(), 12);
+        let buf = serialize(&m).unwrap();
+        assert_eq!(&buf[..], SAMPLE_MODULE);
+
+        let m2: Module = deserialize_buffer(&buf).unwrap();
+        assert_eq!(m, m2);
+    }
+
+    #[test]
+    pub fn test_preserve_encoding() {
+        // start section 的索引 2 用了 3 字节的非规范编码: 0x82 0x80 0x00
+        let mut bytes = SAMPLE_MODULE.to_vec();
+        let start = bytes.windows(3).position(|w| w == [0x08, 0x01, 0x02]).unwrap();
+        bytes.splice(start..start + 3, [0x08, 0x03, 0x82, 0x80, 0x00].iter().cloned());
+
+        let mut cursor = io::Cursor::new(&bytes[..]);
+        let mut m = Module::deserialize_preserving_encoding(&mut cursor).unwrap();
+        assert_eq!(m, deserialize_buffer::<Module>(&bytes).unwrap());
+        assert_eq!(serialize(&m).unwrap(), bytes);
+        // 只有非规范编码的 start section 保留了解码结果
+        let decoded = &m.encoding.as_ref().unwrap().decoded;
+        assert_eq!(decoded.iter().filter(|s| s.is_some()).count(), 1);
+
+        // 修改过的 section 会被规范地重新编码
+        let index = m.sections.iter().position(|s| s.id() == 8).unwrap();
+        m.sections[index] = Section::Start(1);
+        let mut expected = SAMPLE_MODULE.to_vec();
+        expected[start + 2] = 0x01;
+        assert_eq!(serialize(&m).unwrap(), expected);
+    }
+
+    #[test]
+    pub fn test_decode_error() {
+        let m = crate::wat::parse(r#"(module (import "m" "f" (func)) (func) (func i32.const 1 drop))"#).unwrap();
+        let mut bytes = serialize(&m).unwrap();
+        let drop = bytes.windows(3).rposition(|w| w == [0x41, 0x01, 0x1a]).unwrap() + 2;
+        bytes[drop] = 0xff;
+
+        let err = deserialize_buffer::<Module>(&bytes).unwrap_err();
+        assert_eq!((err.offset, err.section_id, err.function_index), (drop, Some(10), Some(2)));
+        assert_eq!(err.to_string(), format!("Unknown opcode 0xff at offset {:#x} in code section, function #2", drop));
+
+        let err = deserialize_buffer::<Module>(&[0x00, 0x61, 0x73, 0x6d, 0x02, 0x00, 0x00, 0x00]).unwrap_err();
+        assert_eq!((err.offset, err.section_id), (4, None));
+
+        // 第二个 import 的 external kind 无效
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            0x02, 0x0c, 0x02, 0x01, b'm', 0x01, b'f', 0x00, 0x00, 0x01, b'm', 0x01, b'g', 0x07,
+        ];
+        let err = deserialize_buffer::<Module>(&bytes[..]).unwrap_err();
+        assert_eq!((err.offset, err.section_id, err.function_index), (17, Some(2), None));
+    }
+
+    #[test]
+    pub fn test_truncated() {
+        // 在 section 边界截断是合法的模块，在 section 中间截断则报告 UnexpectedEof
+        let end_of_types = 8 + 12;
+        let m: Module = deserialize_buffer(&SAMPLE_MODULE[..end_of_types]).unwrap();
+        assert_eq!(m.sections.len(), 1);
+
+        let err = deserialize_buffer::<Module>(&SAMPLE_MODULE[..end_of_types - 3]).unwrap_err();
+        assert!(matches!(err.kind, Error::UnexpectedEof));
+        assert_eq!((err.offset, err.section_id), (8, Some(1)));
+
+        let err = deserialize_buffer::<Module>(&SAMPLE_MODULE[..6]).unwrap_err();
+        assert!(matches!(err.kind, Error::UnexpectedEof));
+    }
+
     #[test]
     pub fn test_parse() {
         let mut f = fs::File::open("/Users/sal/Documents/Github/maze-protocol/layer2/main.wasm").unwrap();
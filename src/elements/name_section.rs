@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use super::{Deserialize, Serialize, Error};
+use super::primitives::{Uint8, VarUint32};
+use super::sections::{write_section_payload, SectionReader};
+
+/// Names of the entities of one index space, by index.
+pub type NameMap = BTreeMap<u32, String>;
+
+/// Names of entities nested in another one, e.g. locals by function index.
+pub type IndirectNameMap = BTreeMap<u32, NameMap>;
+
+// name section 子段的 id
+const MODULE_SUBSECTION: u8 = 0;
+const FUNCTION_SUBSECTION: u8 = 1;
+const LOCAL_SUBSECTION: u8 = 2;
+const LABEL_SUBSECTION: u8 = 3;
+const TYPE_SUBSECTION: u8 = 4;
+const TABLE_SUBSECTION: u8 = 5;
+const MEMORY_SUBSECTION: u8 = 6;
+const GLOBAL_SUBSECTION: u8 = 7;
+const ELEM_SUBSECTION: u8 = 8;
+const DATA_SUBSECTION: u8 = 9;
+
+/// Contents of the `name` custom section.
+///
+/// Besides the module, function and local names of the core spec this includes
+/// the subsections of the extended name section proposal. Labels are indexed by
+/// function, then by the position of the block in the function body.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NameSection {
+    pub module: Option<String>,
+    pub functions: NameMap,
+    pub locals: IndirectNameMap,
+    pub labels: IndirectNameMap,
+    pub types: NameMap,
+    pub tables: NameMap,
+    pub memories: NameMap,
+    pub globals: NameMap,
+    pub elements: NameMap,
+    pub data: NameMap,
+}
+
+impl NameSection {
+    /// Name of the custom section holding names.
+    pub const NAME: &'static str = "name";
+
+    /// Parse the payload of a `name` custom section.
+    pub fn from_payload(payload: &[u8]) -> Result<NameSection, Error> {
+        let mut names = NameSection::default();
+        let mut cursor = io::Cursor::new(payload);
+        let mut last_id: Option<u8> = None;
+        while (cursor.position() as usize) < payload.len() {
+            let id: u8 = Uint8::deserialize(&mut cursor)?.into();
+            if id > DATA_SUBSECTION {
+                return Err(Error::UnknownNameSubsectionType(id));
+            }
+            match last_id {
+                Some(last) if last == id => return Err(Error::DuplicatedNameSubsections(id)),
+                Some(last) if last > id => return Err(Error::Other("name subsections are out of order")),
+                _ => {},
+            }
+            last_id = Some(id);
+
+            let mut reader = SectionReader::new(&mut cursor)?;
+            match id {
+                MODULE_SUBSECTION => names.module = Some(String::deserialize(&mut reader)?),
+                FUNCTION_SUBSECTION => names.functions = read_name_map(&mut reader)?,
+                LOCAL_SUBSECTION => names.locals = read_indirect_name_map(&mut reader)?,
+                LABEL_SUBSECTION => names.labels = read_indirect_name_map(&mut reader)?,
+                TYPE_SUBSECTION => names.types = read_name_map(&mut reader)?,
+                TABLE_SUBSECTION => names.tables = read_name_map(&mut reader)?,
+                MEMORY_SUBSECTION => names.memories = read_name_map(&mut reader)?,
+                GLOBAL_SUBSECTION => names.globals = read_name_map(&mut reader)?,
+                ELEM_SUBSECTION => names.elements = read_name_map(&mut reader)?,
+                _ => names.data = read_name_map(&mut reader)?,
+            }
+            reader.close()?;
+        }
+        Ok(names)
+    }
+
+    /// Whether there are no names at all.
+    pub fn is_empty(&self) -> bool {
+        *self == NameSection::default()
+    }
+}
+
+fn read_name_map<R: io::Read>(reader: &mut R) -> Result<NameMap, Error> {
+    let count: u32 = VarUint32::deserialize(reader)?.into();
+    let mut map = NameMap::new();
+    let mut last: Option<u32> = None;
+    for _ in 0..count {
+        let idx: u32 = VarUint32::deserialize(reader)?.into();
+        if last.is_some_and(|last| idx <= last) {
+            return Err(Error::Other("name map indices are not in increasing order"));
+        }
+        last = Some(idx);
+        map.insert(idx, String::deserialize(reader)?);
+    }
+    Ok(map)
+}
+
+fn read_indirect_name_map<R: io::Read>(reader: &mut R) -> Result<IndirectNameMap, Error> {
+    let count: u32 = VarUint32::deserialize(reader)?.into();
+    let mut map = IndirectNameMap::new();
+    let mut last: Option<u32> = None;
+    for _ in 0..count {
+        let idx: u32 = VarUint32::deserialize(reader)?.into();
+        if last.is_some_and(|last| idx <= last) {
+            return Err(Error::Other("name map indices are not in increasing order"));
+        }
+        last = Some(idx);
+        map.insert(idx, read_name_map(reader)?);
+    }
+    Ok(map)
+}
+
+fn write_name_map(writer: &mut Vec<u8>, map: &NameMap) -> Result<(), Error> {
+    VarUint32(map.len() as u32).serialize(writer)?;
+    for (&idx, name) in map.iter() {
+        VarUint32(idx).serialize(writer)?;
+        name.serialize(writer)?;
+    }
+    Ok(())
+}
+
+fn write_indirect_name_map(writer: &mut Vec<u8>, map: &IndirectNameMap) -> Result<(), Error> {
+    VarUint32(map.len() as u32).serialize(writer)?;
+    for (&idx, names) in map.iter() {
+        VarUint32(idx).serialize(writer)?;
+        write_name_map(writer, names)?;
+    }
+    Ok(())
+}
+
+/// Writes the payload of the `name` custom section, skipping empty subsections.
+impl Serialize for NameSection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        if let Some(ref module) = self.module {
+            Uint8(MODULE_SUBSECTION).serialize(writer)?;
+            write_section_payload(writer, |w| module.serialize(w))?;
+        }
+        let indirect = [(LOCAL_SUBSECTION, &self.locals), (LABEL_SUBSECTION, &self.labels)];
+        let direct = [
+            (TYPE_SUBSECTION, &self.types),
+            (TABLE_SUBSECTION, &self.tables),
+            (MEMORY_SUBSECTION, &self.memories),
+            (GLOBAL_SUBSECTION, &self.globals),
+            (ELEM_SUBSECTION, &self.elements),
+            (DATA_SUBSECTION, &self.data),
+        ];
+        if !self.functions.is_empty() {
+            Uint8(FUNCTION_SUBSECTION).serialize(writer)?;
+            write_section_payload(writer, |w| write_name_map(w, &self.functions))?;
+        }
+        for &(id, map) in indirect.iter() {
+            if !map.is_empty() {
+                Uint8(id).serialize(writer)?;
+                write_section_payload(writer, |w| write_indirect_name_map(w, map))?;
+            }
+        }
+        for &(id, map) in direct.iter() {
+            if !map.is_empty() {
+                Uint8(id).serialize(writer)?;
+                write_section_payload(writer, |w| write_name_map(w, map))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elements::serialize;
+
+    #[test]
+    fn test_name_section() {
+        let payload = [
+            0x00, 0x02, 0x01, b'm',
+            0x01, 0x07, 0x02, 0x00, 0x01, b'f', 0x02, 0x01, b'g',
+            0x02, 0x06, 0x01, 0x00, 0x01, 0x00, 0x01, b'x',
+            0x03, 0x06, 0x01, 0x02, 0x01, 0x01, 0x01, b'l',
+            0x07, 0x04, 0x01, 0x00, 0x01, b'g',
+            0x09, 0x04, 0x01, 0x00, 0x01, b'd',
+        ];
+        let names = NameSection::from_payload(&payload).unwrap();
+        assert_eq!(names.module.as_deref(), Some("m"));
+        assert_eq!(names.functions.get(&2).map(|s| &s[..]), Some("g"));
+        assert_eq!(names.locals[&0][&0], "x");
+        assert_eq!(names.labels[&2][&1], "l");
+        assert_eq!(names.globals[&0], "g");
+        assert_eq!(names.data[&0], "d");
+        assert!(names.types.is_empty());
+        assert_eq!(serialize(&names).unwrap(), payload.to_vec());
+
+        assert!(NameSection::from_payload(&[]).unwrap().is_empty());
+        match NameSection::from_payload(&[0x01, 0x01, 0x00, 0x01, 0x01, 0x00]) {
+            Err(Error::DuplicatedNameSubsections(1)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        match NameSection::from_payload(&[0x0c, 0x00]) {
+            Err(Error::UnknownNameSubsectionType(12)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        // 子段长度和内容不符
+        assert!(NameSection::from_payload(&[0x01, 0x02, 0x00, 0x00]).is_err());
+        assert!(NameSection::from_payload(&[0x01, 0x05, 0x02, 0x01, 0x00, 0x00, 0x00]).is_err());
+    }
+}
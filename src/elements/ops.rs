@@ -1,6 +1,15 @@
-use super::types::BlockType;
-use super::{Deserialize, Error};
-use super::primitives::{VarUint32, CountedList, Uint8, VarInt32, VarInt64, Uint32, Uint64};
+use super::types::{BlockType, TableElementType, ValueType};
+use super::{Deserialize, Serialize, Error, PayloadError};
+use super::features::FeatureCheck;
+use super::sections::read_value;
+use super::primitives::{VarUint32, VarUint64, CountedList, CountedListWriter, Uint8, VarInt32, VarInt64, Uint32, Uint64};
+#[cfg(feature = "simd")]
+use super::simd::SimdInstruction;
+#[cfg(feature = "threads")]
+use super::atomics::AtomicInstruction;
+#[cfg(feature = "gc")]
+use super::gc::GcInstruction;
+use crate::io::PositionReader;
 use std::io;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -10,23 +19,128 @@ pub struct BrTableData {
 	pub default: u32,
 }
 
+/// Handler of a `try_table`, branching to `label` when an exception is caught.
+#[cfg(feature = "exceptions")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Catch {
+	/// Catch exceptions of `tag`, the branch carries the payload.
+	One { tag: u32, label: u32 },
+	/// Catch exceptions of `tag`, the branch carries the payload and the `exnref`.
+	OneRef { tag: u32, label: u32 },
+	/// Catch every exception, the branch carries nothing.
+	All { label: u32 },
+	/// Catch every exception, the branch carries the `exnref`.
+	AllRef { label: u32 },
+}
+
+#[cfg(feature = "exceptions")]
+impl Catch {
+	/// Label the handler branches to, relative to the outside of the `try_table`.
+	pub fn label(&self) -> u32 {
+		match *self {
+			Catch::One { label, .. } | Catch::OneRef { label, .. } | Catch::All { label } | Catch::AllRef { label } => label,
+		}
+	}
+
+	/// Tag of the exceptions caught, `None` for the catch-all handlers.
+	pub fn tag(&self) -> Option<u32> {
+		match *self {
+			Catch::One { tag, .. } | Catch::OneRef { tag, .. } => Some(tag),
+			Catch::All { .. } | Catch::AllRef { .. } => None,
+		}
+	}
+
+	/// Whether the branch also carries the caught `exnref`.
+	pub fn is_ref(&self) -> bool {
+		matches!(*self, Catch::OneRef { .. } | Catch::AllRef { .. })
+	}
+}
+
+#[cfg(feature = "exceptions")]
+impl Deserialize for Catch {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		let kind: u8 = Uint8::deserialize(reader)?.into();
+		let mut index = || -> Result<u32, Error> { Ok(VarUint32::deserialize(reader)?.into()) };
+		Ok(match kind {
+			0x00 => Catch::One { tag: index()?, label: index()? },
+			0x01 => Catch::OneRef { tag: index()?, label: index()? },
+			0x02 => Catch::All { label: index()? },
+			0x03 => Catch::AllRef { label: index()? },
+			_ => return Err(Error::UnknownCatchKind(kind)),
+		})
+	}
+}
+
+#[cfg(feature = "exceptions")]
+impl Serialize for Catch {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+		let kind: u8 = match *self {
+			Catch::One { .. } => 0x00,
+			Catch::OneRef { .. } => 0x01,
+			Catch::All { .. } => 0x02,
+			Catch::AllRef { .. } => 0x03,
+		};
+		Uint8(kind).serialize(writer)?;
+		if let Some(tag) = self.tag() {
+			VarUint32(tag).serialize(writer)?;
+		}
+		VarUint32(self.label()).serialize(writer)?;
+		Ok(())
+	}
+}
+
+/// Block type and handlers of a `try_table`.
+#[cfg(feature = "exceptions")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TryTableData {
+	pub block_type: BlockType,
+	pub catches: Box<[Catch]>,
+}
+
 /// List of instructions (usually inside a block section).
 #[derive(Debug, Clone, PartialEq)]
-pub struct Instructions(Vec<Instruction>);
+pub struct Instructions(pub Vec<Instruction>);
 
 impl Deserialize for Instructions {
 	type Error = Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+		let mut reader = PositionReader::new(reader);
+		Instructions::read(&mut reader, &mut FeatureCheck::new(None)).map_err(|e| e.kind)
+	}
+}
+
+impl Serialize for Instructions {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+		for instruction in self.0.iter() {
+			instruction.serialize(writer)?;
+		}
+		Ok(())
+	}
+}
+
+impl Instructions {
+	/// Decode instructions up to the `end` of the body, checking each of them as it is decoded.
+	/// Errors are located at the failing instruction.
+	pub(crate) fn read<R: io::Read>(reader: &mut PositionReader<R>, check: &mut FeatureCheck) -> Result<Instructions, PayloadError> {
 		let mut instructions = Vec::new();
 		let mut block_count = 1usize;
 
 		loop {
-			let instruction = Instruction::deserialize(reader)?;
+			let start = reader.position();
+			let located = |kind| PayloadError::at(start, kind);
+			let instruction: Instruction = read_value(reader)?;
+			check.entry(&instruction).map_err(located)?;
 			if instruction.is_terminal() {
 				block_count -= 1;
 			} else if instruction.is_block() {
-				block_count = block_count.checked_add(1).ok_or(Error::Other("too many instructions"))?;
+				block_count = block_count.checked_add(1).ok_or(Error::Other("too many instructions")).map_err(located)?;
 			}
 
 			instructions.push(instruction);
@@ -37,6 +151,11 @@ impl Deserialize for Instructions {
 
 		Ok(Instructions(instructions))
 	}
+
+	/// Instructions of the body, including the final `End`.
+	pub fn elements(&self) -> &[Instruction] {
+		&self.0
+	}
 }
 
 /// Initialization expression.
@@ -53,6 +172,7 @@ impl Deserialize for InitExpr {
         loop {
             let i = Instruction::deserialize(reader)?;
             let is_terminal = i.is_terminal();
+            ins.push(i);
 
             if is_terminal {
                 break;
@@ -63,11 +183,24 @@ impl Deserialize for InitExpr {
     }
 }
 
+impl Serialize for InitExpr {
+    type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        for i in self.0.iter() {
+            i.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
 impl Instruction {
 	/// Is this instruction starts the new block (which should end with terminal instruction).
 	pub fn is_block(&self) -> bool {
 		match self {
 			&Instruction::Block(_) | &Instruction::Loop(_) | &Instruction::If(_) => true,
+			#[cfg(feature = "exceptions")]
+			&Instruction::TryTable(_) => true,
 			_ => false,
 		}
 	}
@@ -81,6 +214,259 @@ impl Instruction {
 			_ => false,
 		}
 	}
+
+	/// Opcode byte this instruction is encoded with.
+	pub fn opcode(&self) -> u8 {
+		use self::Instruction::*;
+		use self::opcodes::*;
+
+		match *self {
+			Unreachable => UNREACHABLE,
+			Nop => NOP,
+			Else => ELSE,
+			End => END,
+			#[cfg(feature = "exceptions")]
+			Throw(_) => THROW,
+			#[cfg(feature = "exceptions")]
+			ThrowRef => THROWREF,
+			#[cfg(feature = "exceptions")]
+			TryTable(_) => TRYTABLE,
+			Return => RETURN,
+			Drop => DROP,
+			Select => SELECT,
+			TypedSelect(_) => SELECTTYPED,
+			TableGet(_) => TABLEGET,
+			TableSet(_) => TABLESET,
+			RefIsNull => REFISNULL,
+			I32Eqz => I32EQZ,
+			I32Eq => I32EQ,
+			I32Ne => I32NE,
+			I32LtS => I32LTS,
+			I32LtU => I32LTU,
+			I32GtS => I32GTS,
+			I32GtU => I32GTU,
+			I32LeS => I32LES,
+			I32LeU => I32LEU,
+			I32GeS => I32GES,
+			I32GeU => I32GEU,
+			I64Eqz => I64EQZ,
+			I64Eq => I64EQ,
+			I64Ne => I64NE,
+			I64LtS => I64LTS,
+			I64LtU => I64LTU,
+			I64GtS => I64GTS,
+			I64GtU => I64GTU,
+			I64LeS => I64LES,
+			I64LeU => I64LEU,
+			I64GeS => I64GES,
+			I64GeU => I64GEU,
+			F32Eq => F32EQ,
+			F32Ne => F32NE,
+			F32Lt => F32LT,
+			F32Gt => F32GT,
+			F32Le => F32LE,
+			F32Ge => F32GE,
+			F64Eq => F64EQ,
+			F64Ne => F64NE,
+			F64Lt => F64LT,
+			F64Gt => F64GT,
+			F64Le => F64LE,
+			F64Ge => F64GE,
+			I32Clz => I32CLZ,
+			I32Ctz => I32CTZ,
+			I32Popcnt => I32POPCNT,
+			I32Add => I32ADD,
+			I32Sub => I32SUB,
+			I32Mul => I32MUL,
+			I32DivS => I32DIVS,
+			I32DivU => I32DIVU,
+			I32RemS => I32REMS,
+			I32RemU => I32REMU,
+			I32And => I32AND,
+			I32Or => I32OR,
+			I32Xor => I32XOR,
+			I32Shl => I32SHL,
+			I32ShrS => I32SHRS,
+			I32ShrU => I32SHRU,
+			I32Rotl => I32ROTL,
+			I32Rotr => I32ROTR,
+			I64Clz => I64CLZ,
+			I64Ctz => I64CTZ,
+			I64Popcnt => I64POPCNT,
+			I64Add => I64ADD,
+			I64Sub => I64SUB,
+			I64Mul => I64MUL,
+			I64DivS => I64DIVS,
+			I64DivU => I64DIVU,
+			I64RemS => I64REMS,
+			I64RemU => I64REMU,
+			I64And => I64AND,
+			I64Or => I64OR,
+			I64Xor => I64XOR,
+			I64Shl => I64SHL,
+			I64ShrS => I64SHRS,
+			I64ShrU => I64SHRU,
+			I64Rotl => I64ROTL,
+			I64Rotr => I64ROTR,
+			F32Abs => F32ABS,
+			F32Neg => F32NEG,
+			F32Ceil => F32CEIL,
+			F32Floor => F32FLOOR,
+			F32Trunc => F32TRUNC,
+			F32Nearest => F32NEAREST,
+			F32Sqrt => F32SQRT,
+			F32Add => F32ADD,
+			F32Sub => F32SUB,
+			F32Mul => F32MUL,
+			F32Div => F32DIV,
+			F32Min => F32MIN,
+			F32Max => F32MAX,
+			F32Copysign => F32COPYSIGN,
+			F64Abs => F64ABS,
+			F64Neg => F64NEG,
+			F64Ceil => F64CEIL,
+			F64Floor => F64FLOOR,
+			F64Trunc => F64TRUNC,
+			F64Nearest => F64NEAREST,
+			F64Sqrt => F64SQRT,
+			F64Add => F64ADD,
+			F64Sub => F64SUB,
+			F64Mul => F64MUL,
+			F64Div => F64DIV,
+			F64Min => F64MIN,
+			F64Max => F64MAX,
+			F64Copysign => F64COPYSIGN,
+			I32WrapI64 => I32WRAPI64,
+			I32TruncSF32 => I32TRUNCSF32,
+			I32TruncUF32 => I32TRUNCUF32,
+			I32TruncSF64 => I32TRUNCSF64,
+			I32TruncUF64 => I32TRUNCUF64,
+			I64ExtendSI32 => I64EXTENDSI32,
+			I64ExtendUI32 => I64EXTENDUI32,
+			I64TruncSF32 => I64TRUNCSF32,
+			I64TruncUF32 => I64TRUNCUF32,
+			I64TruncSF64 => I64TRUNCSF64,
+			I64TruncUF64 => I64TRUNCUF64,
+			F32ConvertSI32 => F32CONVERTSI32,
+			F32ConvertUI32 => F32CONVERTUI32,
+			F32ConvertSI64 => F32CONVERTSI64,
+			F32ConvertUI64 => F32CONVERTUI64,
+			F32DemoteF64 => F32DEMOTEF64,
+			F64ConvertSI32 => F64CONVERTSI32,
+			F64ConvertUI32 => F64CONVERTUI32,
+			F64ConvertSI64 => F64CONVERTSI64,
+			F64ConvertUI64 => F64CONVERTUI64,
+			F64PromoteF32 => F64PROMOTEF32,
+			I32ReinterpretF32 => I32REINTERPRETF32,
+			I64ReinterpretF64 => I64REINTERPRETF64,
+			F32ReinterpretI32 => F32REINTERPRETI32,
+			F64ReinterpretI64 => F64REINTERPRETI64,
+			I32Extend8S => I32EXTEND8S,
+			I32Extend16S => I32EXTEND16S,
+			I64Extend8S => I64EXTEND8S,
+			I64Extend16S => I64EXTEND16S,
+			I64Extend32S => I64EXTEND32S,
+			I32Load(..) => I32LOAD,
+			I64Load(..) => I64LOAD,
+			F32Load(..) => F32LOAD,
+			F64Load(..) => F64LOAD,
+			I32Load8S(..) => I32LOAD8S,
+			I32Load8U(..) => I32LOAD8U,
+			I32Load16S(..) => I32LOAD16S,
+			I32Load16U(..) => I32LOAD16U,
+			I64Load8S(..) => I64LOAD8S,
+			I64Load8U(..) => I64LOAD8U,
+			I64Load16S(..) => I64LOAD16S,
+			I64Load16U(..) => I64LOAD16U,
+			I64Load32S(..) => I64LOAD32S,
+			I64Load32U(..) => I64LOAD32U,
+			I32Store(..) => I32STORE,
+			I64Store(..) => I64STORE,
+			F32Store(..) => F32STORE,
+			F64Store(..) => F64STORE,
+			I32Store8(..) => I32STORE8,
+			I32Store16(..) => I32STORE16,
+			I64Store8(..) => I64STORE8,
+			I64Store16(..) => I64STORE16,
+			I64Store32(..) => I64STORE32,
+			Block(_) => BLOCK,
+			Loop(_) => LOOP,
+			If(_) => IF,
+			Br(_) => BR,
+			BrIf(_) => BRIF,
+			BrTable(_) => BRTABLE,
+			Call(_) => CALL,
+			CallIndirect(..) => CALLINDIRECT,
+			#[cfg(feature = "tail_call")]
+			ReturnCall(_) => RETURNCALL,
+			#[cfg(feature = "tail_call")]
+			ReturnCallIndirect(..) => RETURNCALLINDIRECT,
+			#[cfg(feature = "gc")]
+			CallRef(_) => CALLREF,
+			#[cfg(all(feature = "gc", feature = "tail_call"))]
+			ReturnCallRef(_) => RETURNCALLREF,
+			#[cfg(feature = "gc")]
+			RefEq => REFEQ,
+			#[cfg(feature = "gc")]
+			RefAsNonNull => REFASNONNULL,
+			#[cfg(feature = "gc")]
+			BrOnNull(_) => BRONNULL,
+			#[cfg(feature = "gc")]
+			BrOnNonNull(_) => BRONNONNULL,
+			GetLocal(_) => GETLOCAL,
+			SetLocal(_) => SETLOCAL,
+			TeeLocal(_) => TEELOCAL,
+			GetGlobal(_) => GETGLOBAL,
+			SetGlobal(_) => SETGLOBAL,
+			CurrentMemory(_) => CURRENTMEMORY,
+			GrowMemory(_) => GROWMEMORY,
+			I32Const(_) => I32CONST,
+			I64Const(_) => I64CONST,
+			F32Const(_) => F32CONST,
+			F64Const(_) => F64CONST,
+			RefNull(_) => REFNULL,
+			RefFunc(_) => REFFUNC,
+			I32TruncSatSF32 | I32TruncSatUF32 | I32TruncSatSF64 | I32TruncSatUF64
+			| I64TruncSatSF32 | I64TruncSatUF32 | I64TruncSatSF64 | I64TruncSatUF64
+			| MemoryInit(..) | DataDrop(_) | MemoryCopy(..) | MemoryFill(_)
+			| TableInit(..) | ElemDrop(_) | TableCopy(..)
+			| TableGrow(_) | TableSize(_) | TableFill(_) => FC_PREFIX,
+			#[cfg(feature = "simd")]
+			Simd(_) => SIMD_PREFIX,
+			#[cfg(feature = "threads")]
+			Atomic(_) => ATOMIC_PREFIX,
+			#[cfg(feature = "gc")]
+			Gc(_) => GC_PREFIX,
+		}
+	}
+
+	/// Sub-opcode following the 0xFC prefix, for instructions encoded with one.
+	pub fn fc_opcode(&self) -> Option<u32> {
+		use self::Instruction::*;
+		use self::opcodes::fc::*;
+
+		Some(match *self {
+			I32TruncSatSF32 => I32TRUNCSATSF32,
+			I32TruncSatUF32 => I32TRUNCSATUF32,
+			I32TruncSatSF64 => I32TRUNCSATSF64,
+			I32TruncSatUF64 => I32TRUNCSATUF64,
+			I64TruncSatSF32 => I64TRUNCSATSF32,
+			I64TruncSatUF32 => I64TRUNCSATUF32,
+			I64TruncSatSF64 => I64TRUNCSATSF64,
+			I64TruncSatUF64 => I64TRUNCSATUF64,
+			MemoryInit(..) => MEMORYINIT,
+			DataDrop(_) => DATADROP,
+			MemoryCopy(..) => MEMORYCOPY,
+			MemoryFill(_) => MEMORYFILL,
+			TableInit(..) => TABLEINIT,
+			ElemDrop(_) => ELEMDROP,
+			TableCopy(..) => TABLECOPY,
+			TableGrow(_) => TABLEGROW,
+			TableSize(_) => TABLESIZE,
+			TableFill(_) => TABLEFILL,
+			_ => return None,
+		})
+	}
 }
 
 /// Instruction.
@@ -98,12 +484,34 @@ pub enum Instruction {
 	BrIf(u32),
 	BrTable(Box<BrTableData>),
 	Return,
+	/// Throw an exception of the tag, the payload is taken from the stack.
+	#[cfg(feature = "exceptions")]
+	Throw(u32),
+	/// Rethrow the exception referenced by an `exnref`.
+	#[cfg(feature = "exceptions")]
+	ThrowRef,
+	/// Block whose handlers catch the exceptions thrown inside it.
+	#[cfg(feature = "exceptions")]
+	TryTable(Box<TryTableData>),
 
 	Call(u32),
-	CallIndirect(u32, u8),
+	/// Type index and table index.
+	CallIndirect(u32, u32),
+	/// Tail call, the frame of the caller is replaced by the callee.
+	#[cfg(feature = "tail_call")]
+	ReturnCall(u32),
+	/// Tail call through a table, type index and table index.
+	#[cfg(feature = "tail_call")]
+	ReturnCallIndirect(u32, u32),
+	/// Call the function reference on top of the stack, whose type index is given.
+	#[cfg(feature = "gc")]
+	CallRef(u32),
+	#[cfg(all(feature = "gc", feature = "tail_call"))]
+	ReturnCallRef(u32),
 
 	Drop,
 	Select,
+	TypedSelect(ValueType),
 
 	GetLocal(u32),
 	SetLocal(u32),
@@ -112,33 +520,33 @@ pub enum Instruction {
 	SetGlobal(u32),
 
 	// All store/load instructions operate with 'memory immediates'
-	// which represented here as (flag, offset) tuple
-	I32Load(u32, u32),
-	I64Load(u32, u32),
-	F32Load(u32, u32),
-	F64Load(u32, u32),
-	I32Load8S(u32, u32),
-	I32Load8U(u32, u32),
-	I32Load16S(u32, u32),
-	I32Load16U(u32, u32),
-	I64Load8S(u32, u32),
-	I64Load8U(u32, u32),
-	I64Load16S(u32, u32),
-	I64Load16U(u32, u32),
-	I64Load32S(u32, u32),
-	I64Load32U(u32, u32),
-	I32Store(u32, u32),
-	I64Store(u32, u32),
-	F32Store(u32, u32),
-	F64Store(u32, u32),
-	I32Store8(u32, u32),
-	I32Store16(u32, u32),
-	I64Store8(u32, u32),
-	I64Store16(u32, u32),
-	I64Store32(u32, u32),
-
-	CurrentMemory(u8),
-	GrowMemory(u8),
+	// which represented here as (flag, offset) tuple, the offset is 64-bit for memory64
+	I32Load(u32, u64, u32),
+	I64Load(u32, u64, u32),
+	F32Load(u32, u64, u32),
+	F64Load(u32, u64, u32),
+	I32Load8S(u32, u64, u32),
+	I32Load8U(u32, u64, u32),
+	I32Load16S(u32, u64, u32),
+	I32Load16U(u32, u64, u32),
+	I64Load8S(u32, u64, u32),
+	I64Load8U(u32, u64, u32),
+	I64Load16S(u32, u64, u32),
+	I64Load16U(u32, u64, u32),
+	I64Load32S(u32, u64, u32),
+	I64Load32U(u32, u64, u32),
+	I32Store(u32, u64, u32),
+	I64Store(u32, u64, u32),
+	F32Store(u32, u64, u32),
+	F64Store(u32, u64, u32),
+	I32Store8(u32, u64, u32),
+	I32Store16(u32, u64, u32),
+	I64Store8(u32, u64, u32),
+	I64Store16(u32, u64, u32),
+	I64Store32(u32, u64, u32),
+
+	CurrentMemory(u32),
+	GrowMemory(u32),
 
 	I32Const(i32),
 	I64Const(i64),
@@ -275,6 +683,58 @@ pub enum Instruction {
 	I64ReinterpretF64,
 	F32ReinterpretI32,
 	F64ReinterpretI64,
+
+	I32Extend8S,
+	I32Extend16S,
+	I64Extend8S,
+	I64Extend16S,
+	I64Extend32S,
+
+	// 非陷入的浮点转整数，0xFC 前缀
+	I32TruncSatSF32,
+	I32TruncSatUF32,
+	I32TruncSatSF64,
+	I32TruncSatUF64,
+	I64TruncSatSF32,
+	I64TruncSatUF32,
+	I64TruncSatSF64,
+	I64TruncSatUF64,
+
+	RefNull(TableElementType),
+	RefIsNull,
+	RefFunc(u32),
+	#[cfg(feature = "gc")]
+	RefEq,
+	#[cfg(feature = "gc")]
+	RefAsNonNull,
+	/// Branch if the reference is null, otherwise keep it as non-nullable.
+	#[cfg(feature = "gc")]
+	BrOnNull(u32),
+	/// Branch with the reference if it is not null, otherwise drop it.
+	#[cfg(feature = "gc")]
+	BrOnNonNull(u32),
+
+	TableGet(u32),
+	TableSet(u32),
+	TableGrow(u32),
+	TableSize(u32),
+	TableFill(u32),
+
+	// bulk memory：段索引在前，内存/表索引在后，与编码顺序一致
+	MemoryInit(u32, u32),
+	DataDrop(u32),
+	MemoryCopy(u32, u32),
+	MemoryFill(u32),
+	TableInit(u32, u32),
+	ElemDrop(u32),
+	TableCopy(u32, u32),
+
+	#[cfg(feature = "simd")]
+	Simd(SimdInstruction),
+	#[cfg(feature = "threads")]
+	Atomic(AtomicInstruction),
+	#[cfg(feature = "gc")]
+	Gc(GcInstruction),
 }
 
 pub mod opcodes {
@@ -284,6 +744,8 @@ pub mod opcodes {
 	pub const LOOP: u8 = 0x03;
 	pub const IF: u8 = 0x04;
 	pub const ELSE: u8 = 0x05;
+	pub const THROW: u8 = 0x08;
+	pub const THROWREF: u8 = 0x0a;
 	pub const END: u8 = 0x0b;
 	pub const BR: u8 = 0x0c;
 	pub const BRIF: u8 = 0x0d;
@@ -291,13 +753,21 @@ pub mod opcodes {
 	pub const RETURN: u8 = 0x0f;
 	pub const CALL: u8 = 0x10;
 	pub const CALLINDIRECT: u8 = 0x11;
+	pub const RETURNCALL: u8 = 0x12;
+	pub const RETURNCALLINDIRECT: u8 = 0x13;
+	pub const CALLREF: u8 = 0x14;
+	pub const RETURNCALLREF: u8 = 0x15;
 	pub const DROP: u8 = 0x1a;
 	pub const SELECT: u8 = 0x1b;
+	pub const SELECTTYPED: u8 = 0x1c;
+	pub const TRYTABLE: u8 = 0x1f;
 	pub const GETLOCAL: u8 = 0x20;
 	pub const SETLOCAL: u8 = 0x21;
 	pub const TEELOCAL: u8 = 0x22;
 	pub const GETGLOBAL: u8 = 0x23;
 	pub const SETGLOBAL: u8 = 0x24;
+	pub const TABLEGET: u8 = 0x25;
+	pub const TABLESET: u8 = 0x26;
 	pub const I32LOAD: u8 = 0x28;
 	pub const I64LOAD: u8 = 0x29;
 	pub const F32LOAD: u8 = 0x2a;
@@ -456,6 +926,47 @@ pub mod opcodes {
 	pub const I64REINTERPRETF64: u8 = 0xbd;
 	pub const F32REINTERPRETI32: u8 = 0xbe;
 	pub const F64REINTERPRETI64: u8 = 0xbf;
+
+	pub const I32EXTEND8S: u8 = 0xc0;
+	pub const I32EXTEND16S: u8 = 0xc1;
+	pub const I64EXTEND8S: u8 = 0xc2;
+	pub const I64EXTEND16S: u8 = 0xc3;
+	pub const I64EXTEND32S: u8 = 0xc4;
+
+	pub const REFNULL: u8 = 0xd0;
+	pub const REFISNULL: u8 = 0xd1;
+	pub const REFFUNC: u8 = 0xd2;
+	pub const REFEQ: u8 = 0xd3;
+	pub const REFASNONNULL: u8 = 0xd4;
+	pub const BRONNULL: u8 = 0xd5;
+	pub const BRONNONNULL: u8 = 0xd6;
+
+	pub const GC_PREFIX: u8 = 0xfb;
+	pub const FC_PREFIX: u8 = 0xfc;
+	pub const SIMD_PREFIX: u8 = 0xfd;
+	pub const ATOMIC_PREFIX: u8 = 0xfe;
+
+	/// Sub-opcodes of the 0xFC prefix.
+	pub mod fc {
+		pub const I32TRUNCSATSF32: u32 = 0x00;
+		pub const I32TRUNCSATUF32: u32 = 0x01;
+		pub const I32TRUNCSATSF64: u32 = 0x02;
+		pub const I32TRUNCSATUF64: u32 = 0x03;
+		pub const I64TRUNCSATSF32: u32 = 0x04;
+		pub const I64TRUNCSATUF32: u32 = 0x05;
+		pub const I64TRUNCSATSF64: u32 = 0x06;
+		pub const I64TRUNCSATUF64: u32 = 0x07;
+		pub const MEMORYINIT: u32 = 0x08;
+		pub const DATADROP: u32 = 0x09;
+		pub const MEMORYCOPY: u32 = 0x0a;
+		pub const MEMORYFILL: u32 = 0x0b;
+		pub const TABLEINIT: u32 = 0x0c;
+		pub const ELEMDROP: u32 = 0x0d;
+		pub const TABLECOPY: u32 = 0x0e;
+		pub const TABLEGROW: u32 = 0x0f;
+		pub const TABLESIZE: u32 = 0x10;
+		pub const TABLEFILL: u32 = 0x11;
+	}
 }
 
 
@@ -477,6 +988,16 @@ impl Deserialize for Instruction {
 				IF => If(BlockType::deserialize(reader)?),
 				ELSE => Else,
 				END => End,
+				#[cfg(feature = "exceptions")]
+				THROW => Throw(VarUint32::deserialize(reader)?.into()),
+				#[cfg(feature = "exceptions")]
+				THROWREF => ThrowRef,
+				#[cfg(feature = "exceptions")]
+				TRYTABLE => {
+					let block_type = BlockType::deserialize(reader)?;
+					let catches: Vec<Catch> = CountedList::deserialize(reader)?.into_inner();
+					TryTable(Box::new(TryTableData { block_type, catches: catches.into_boxed_slice() }))
+				},
 
 				BR => Br(VarUint32::deserialize(reader)?.into()),
 				BRIF => BrIf(VarUint32::deserialize(reader)?.into()),
@@ -496,126 +1017,81 @@ impl Deserialize for Instruction {
 				CALL => Call(VarUint32::deserialize(reader)?.into()),
 				CALLINDIRECT => {
 					let signature: u32 = VarUint32::deserialize(reader)?.into();
-					let table_ref: u8 = Uint8::deserialize(reader)?.into();
-					if table_ref != 0 { return Err(Error::InvalidTableReference(table_ref)); }
+					let table_ref: u32 = VarUint32::deserialize(reader)?.into();
 
 					CallIndirect(
 						signature,
 						table_ref,
 					)
 				},
+				#[cfg(feature = "tail_call")]
+				RETURNCALL => ReturnCall(VarUint32::deserialize(reader)?.into()),
+				#[cfg(feature = "tail_call")]
+				RETURNCALLINDIRECT => {
+					let signature: u32 = VarUint32::deserialize(reader)?.into();
+					let table_ref: u32 = VarUint32::deserialize(reader)?.into();
+					ReturnCallIndirect(signature, table_ref)
+				},
+				#[cfg(feature = "gc")]
+				CALLREF => CallRef(VarUint32::deserialize(reader)?.into()),
+				#[cfg(all(feature = "gc", feature = "tail_call"))]
+				RETURNCALLREF => ReturnCallRef(VarUint32::deserialize(reader)?.into()),
 				DROP => Drop,
 				SELECT => Select,
+				SELECTTYPED => {
+					let types = CountedList::<ValueType>::deserialize(reader)?.into_inner();
+					if types.len() != 1 {
+						return Err(Error::Other("typed select must have exactly one result type"));
+					}
+					TypedSelect(types[0])
+				},
 
 				GETLOCAL => GetLocal(VarUint32::deserialize(reader)?.into()),
 				SETLOCAL => SetLocal(VarUint32::deserialize(reader)?.into()),
 				TEELOCAL => TeeLocal(VarUint32::deserialize(reader)?.into()),
 				GETGLOBAL => GetGlobal(VarUint32::deserialize(reader)?.into()),
 				SETGLOBAL => SetGlobal(VarUint32::deserialize(reader)?.into()),
+				TABLEGET => TableGet(VarUint32::deserialize(reader)?.into()),
+				TABLESET => TableSet(VarUint32::deserialize(reader)?.into()),
 
-				I32LOAD => I32Load(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
-
-				I64LOAD => I64Load(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
-
-				F32LOAD => F32Load(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
-
-				F64LOAD => F64Load(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
-
-				I32LOAD8S => I32Load8S(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
-
-				I32LOAD8U => I32Load8U(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
-
-				I32LOAD16S => I32Load16S(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
-
-				I32LOAD16U => I32Load16U(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				I32LOAD => read_memarg(reader, I32Load)?,
+				I64LOAD => read_memarg(reader, I64Load)?,
 
-				I64LOAD8S => I64Load8S(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				F32LOAD => read_memarg(reader, F32Load)?,
+				F64LOAD => read_memarg(reader, F64Load)?,
 
-				I64LOAD8U => I64Load8U(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				I32LOAD8S => read_memarg(reader, I32Load8S)?,
+				I32LOAD8U => read_memarg(reader, I32Load8U)?,
 
-				I64LOAD16S => I64Load16S(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				I32LOAD16S => read_memarg(reader, I32Load16S)?,
+				I32LOAD16U => read_memarg(reader, I32Load16U)?,
 
-				I64LOAD16U => I64Load16U(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				I64LOAD8S => read_memarg(reader, I64Load8S)?,
+				I64LOAD8U => read_memarg(reader, I64Load8U)?,
 
-				I64LOAD32S => I64Load32S(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				I64LOAD16S => read_memarg(reader, I64Load16S)?,
+				I64LOAD16U => read_memarg(reader, I64Load16U)?,
 
-				I64LOAD32U => I64Load32U(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				I64LOAD32S => read_memarg(reader, I64Load32S)?,
+				I64LOAD32U => read_memarg(reader, I64Load32U)?,
 
-				I32STORE => I32Store(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				I32STORE => read_memarg(reader, I32Store)?,
+				I64STORE => read_memarg(reader, I64Store)?,
 
-				I64STORE => I64Store(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				F32STORE => read_memarg(reader, F32Store)?,
+				F64STORE => read_memarg(reader, F64Store)?,
 
-				F32STORE => F32Store(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				I32STORE8 => read_memarg(reader, I32Store8)?,
+				I32STORE16 => read_memarg(reader, I32Store16)?,
 
-				F64STORE => F64Store(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				I64STORE8 => read_memarg(reader, I64Store8)?,
+				I64STORE16 => read_memarg(reader, I64Store16)?,
 
-				I32STORE8 => I32Store8(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
+				I64STORE32 => read_memarg(reader, I64Store32)?,
 
-				I32STORE16 => I32Store16(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
 
-				I64STORE8 => I64Store8(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
-
-				I64STORE16 => I64Store16(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
-
-				I64STORE32 => I64Store32(
-					VarUint32::deserialize(reader)?.into(),
-					VarUint32::deserialize(reader)?.into()),
-
-
-				CURRENTMEMORY => {
-					let mem_ref: u8 = Uint8::deserialize(reader)?.into();
-					if mem_ref != 0 { return Err(Error::InvalidMemoryReference(mem_ref)); }
-					CurrentMemory(mem_ref)
-				},
-				GROWMEMORY => {
-					let mem_ref: u8 = Uint8::deserialize(reader)?.into();
-					if mem_ref != 0 { return Err(Error::InvalidMemoryReference(mem_ref)); }
-					GrowMemory(mem_ref)
-				}
+				CURRENTMEMORY => CurrentMemory(read_memory_index(reader)?),
+				GROWMEMORY => GrowMemory(read_memory_index(reader)?),
 
 				I32CONST => I32Const(VarInt32::deserialize(reader)?.into()),
 				I64CONST => I64Const(VarInt64::deserialize(reader)?.into()),
@@ -752,7 +1228,38 @@ impl Deserialize for Instruction {
 				F32REINTERPRETI32 => F32ReinterpretI32,
 				F64REINTERPRETI64 => F64ReinterpretI64,
 
-		
+				I32EXTEND8S => I32Extend8S,
+				I32EXTEND16S => I32Extend16S,
+				I64EXTEND8S => I64Extend8S,
+				I64EXTEND16S => I64Extend16S,
+				I64EXTEND32S => I64Extend32S,
+
+				REFNULL => RefNull(TableElementType::read_heap_type(reader)?),
+				REFISNULL => RefIsNull,
+				REFFUNC => RefFunc(VarUint32::deserialize(reader)?.into()),
+				#[cfg(feature = "gc")]
+				REFEQ => RefEq,
+				#[cfg(feature = "gc")]
+				REFASNONNULL => RefAsNonNull,
+				#[cfg(feature = "gc")]
+				BRONNULL => BrOnNull(VarUint32::deserialize(reader)?.into()),
+				#[cfg(feature = "gc")]
+				BRONNONNULL => BrOnNonNull(VarUint32::deserialize(reader)?.into()),
+
+				FC_PREFIX => deserialize_fc(reader)?,
+				#[cfg(feature = "simd")]
+				SIMD_PREFIX => Simd(SimdInstruction::deserialize(reader)?),
+				#[cfg(feature = "threads")]
+				ATOMIC_PREFIX => Atomic(AtomicInstruction::deserialize(reader)?),
+				#[cfg(feature = "gc")]
+				GC_PREFIX => Gc(GcInstruction::deserialize(reader)?),
+				// 未编译进来的提案给出明确的错误，而不是 UnknownOpcode
+				#[cfg(not(feature = "simd"))]
+				SIMD_PREFIX => return Err(Error::FeatureDisabled { feature: "simd", used: "SIMD instruction" }),
+				#[cfg(not(feature = "threads"))]
+				ATOMIC_PREFIX => return Err(Error::FeatureDisabled { feature: "threads", used: "Atomic instruction" }),
+				#[cfg(not(feature = "gc"))]
+				GC_PREFIX => return Err(Error::FeatureDisabled { feature: "gc", used: "GC instruction" }),
 
 				_ => { return Err(Error::UnknownOpcode(val)); }
 			}
@@ -760,3 +1267,420 @@ impl Deserialize for Instruction {
 	}
 }
 
+/// Bit of the alignment field telling that a memory index follows it.
+const MEMARG_HAS_MEMORY: u32 = 0x40;
+
+/// Offset of a memory immediate, a u64 with the `memory64` feature and a u32 otherwise.
+fn read_offset<R: io::Read>(reader: &mut R) -> Result<u64, Error> {
+	#[cfg(feature = "memory64")]
+	let offset: u64 = VarUint64::deserialize(reader)?.into();
+	#[cfg(not(feature = "memory64"))]
+	let offset = u32::from(VarUint32::deserialize(reader)?) as u64;
+	Ok(offset)
+}
+
+/// Read the alignment exponent, offset and memory index of a memory access
+/// and pass them to `constructor`.
+pub(crate) fn read_memarg<R: io::Read, T, F>(reader: &mut R, constructor: F) -> Result<T, Error>
+	where F: FnOnce(u32, u64, u32) -> T
+{
+	#[allow(unused_mut)]
+	let mut align: u32 = VarUint32::deserialize(reader)?.into();
+	#[allow(unused_mut)]
+	let mut memory = 0;
+	// 多内存时对齐字段的第 6 位表示后面跟着内存索引
+	#[cfg(feature = "multi_memory")]
+	if align & MEMARG_HAS_MEMORY != 0 {
+		align &= !MEMARG_HAS_MEMORY;
+		memory = VarUint32::deserialize(reader)?.into();
+	}
+	let offset = read_offset(reader)?;
+	Ok(constructor(align, offset, memory))
+}
+
+/// Write a memarg, the memory index is only encoded when it is not 0.
+pub(crate) fn write_memarg<W: io::Write>(writer: &mut W, align: u32, offset: u64, memory: u32) -> Result<(), Error> {
+	if memory == 0 {
+		VarUint32(align).serialize(writer)?;
+	} else {
+		VarUint32(align | MEMARG_HAS_MEMORY).serialize(writer)?;
+		VarUint32(memory).serialize(writer)?;
+	}
+	VarUint64(offset).serialize(writer)?;
+	Ok(())
+}
+
+/// Memory index of `memory.size`, `memory.grow` and the bulk memory instructions,
+/// a reserved zero byte without the `multi_memory` feature.
+fn read_memory_index<R: io::Read>(reader: &mut R) -> Result<u32, Error> {
+	#[cfg(feature = "multi_memory")]
+	let mem_ref: u32 = VarUint32::deserialize(reader)?.into();
+	#[cfg(not(feature = "multi_memory"))]
+	let mem_ref = {
+		let mem_ref: u8 = Uint8::deserialize(reader)?.into();
+		if mem_ref != 0 { return Err(Error::InvalidMemoryReference(mem_ref)); }
+		mem_ref as u32
+	};
+	Ok(mem_ref)
+}
+
+fn deserialize_fc<R: io::Read>(reader: &mut R) -> Result<Instruction, Error> {
+	use self::Instruction::*;
+	use self::opcodes::fc::*;
+
+	let val: u32 = VarUint32::deserialize(reader)?.into();
+	Ok(match val {
+		I32TRUNCSATSF32 => I32TruncSatSF32,
+		I32TRUNCSATUF32 => I32TruncSatUF32,
+		I32TRUNCSATSF64 => I32TruncSatSF64,
+		I32TRUNCSATUF64 => I32TruncSatUF64,
+		I64TRUNCSATSF32 => I64TruncSatSF32,
+		I64TRUNCSATUF32 => I64TruncSatUF32,
+		I64TRUNCSATSF64 => I64TruncSatSF64,
+		I64TRUNCSATUF64 => I64TruncSatUF64,
+		MEMORYINIT => {
+			let segment: u32 = VarUint32::deserialize(reader)?.into();
+			MemoryInit(segment, read_memory_index(reader)?)
+		},
+		DATADROP => DataDrop(VarUint32::deserialize(reader)?.into()),
+		MEMORYCOPY => {
+			let dst = read_memory_index(reader)?;
+			MemoryCopy(dst, read_memory_index(reader)?)
+		},
+		MEMORYFILL => MemoryFill(read_memory_index(reader)?),
+		TABLEINIT => {
+			let segment: u32 = VarUint32::deserialize(reader)?.into();
+			TableInit(segment, VarUint32::deserialize(reader)?.into())
+		},
+		ELEMDROP => ElemDrop(VarUint32::deserialize(reader)?.into()),
+		TABLECOPY => {
+			let dst: u32 = VarUint32::deserialize(reader)?.into();
+			TableCopy(dst, VarUint32::deserialize(reader)?.into())
+		},
+		TABLEGROW => TableGrow(VarUint32::deserialize(reader)?.into()),
+		TABLESIZE => TableSize(VarUint32::deserialize(reader)?.into()),
+		TABLEFILL => TableFill(VarUint32::deserialize(reader)?.into()),
+		_ => return Err(Error::UnknownFcOpcode(val)),
+	})
+}
+
+impl Serialize for Instruction {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+		use self::Instruction::*;
+		use self::opcodes::*;
+
+		match *self {
+			Block(ref block_type) => {
+				Uint8(BLOCK).serialize(writer)?;
+				block_type.serialize(writer)?;
+			},
+			Loop(ref block_type) => {
+				Uint8(LOOP).serialize(writer)?;
+				block_type.serialize(writer)?;
+			},
+			If(ref block_type) => {
+				Uint8(IF).serialize(writer)?;
+				block_type.serialize(writer)?;
+			},
+			#[cfg(feature = "exceptions")]
+			Throw(tag) => {
+				Uint8(THROW).serialize(writer)?;
+				VarUint32(tag).serialize(writer)?;
+			},
+			#[cfg(feature = "exceptions")]
+			TryTable(ref data) => {
+				Uint8(TRYTABLE).serialize(writer)?;
+				data.block_type.serialize(writer)?;
+				CountedListWriter(&data.catches).serialize(writer)?;
+			},
+			Br(depth) => {
+				Uint8(BR).serialize(writer)?;
+				VarUint32(depth).serialize(writer)?;
+			},
+			BrIf(depth) => {
+				Uint8(BRIF).serialize(writer)?;
+				VarUint32(depth).serialize(writer)?;
+			},
+			BrTable(ref data) => {
+				Uint8(BRTABLE).serialize(writer)?;
+				let table: Vec<VarUint32> = data.table.iter().map(|&d| VarUint32(d)).collect();
+				CountedListWriter(&table).serialize(writer)?;
+				VarUint32(data.default).serialize(writer)?;
+			},
+			Call(index) => {
+				Uint8(CALL).serialize(writer)?;
+				VarUint32(index).serialize(writer)?;
+			},
+			CallIndirect(signature, table_ref) => {
+				Uint8(CALLINDIRECT).serialize(writer)?;
+				VarUint32(signature).serialize(writer)?;
+				VarUint32(table_ref).serialize(writer)?;
+			},
+			#[cfg(feature = "tail_call")]
+			ReturnCall(index) => {
+				Uint8(RETURNCALL).serialize(writer)?;
+				VarUint32(index).serialize(writer)?;
+			},
+			#[cfg(feature = "tail_call")]
+			ReturnCallIndirect(signature, table_ref) => {
+				Uint8(RETURNCALLINDIRECT).serialize(writer)?;
+				VarUint32(signature).serialize(writer)?;
+				VarUint32(table_ref).serialize(writer)?;
+			},
+			#[cfg(feature = "gc")]
+			CallRef(type_idx) | BrOnNull(type_idx) | BrOnNonNull(type_idx) => {
+				Uint8(self.opcode()).serialize(writer)?;
+				VarUint32(type_idx).serialize(writer)?;
+			},
+			#[cfg(all(feature = "gc", feature = "tail_call"))]
+			ReturnCallRef(type_idx) => {
+				Uint8(RETURNCALLREF).serialize(writer)?;
+				VarUint32(type_idx).serialize(writer)?;
+			},
+			TypedSelect(value_type) => {
+				Uint8(SELECTTYPED).serialize(writer)?;
+				CountedListWriter(&[value_type]).serialize(writer)?;
+			},
+			TableGet(table) => {
+				Uint8(TABLEGET).serialize(writer)?;
+				VarUint32(table).serialize(writer)?;
+			},
+			TableSet(table) => {
+				Uint8(TABLESET).serialize(writer)?;
+				VarUint32(table).serialize(writer)?;
+			},
+			#[cfg(feature = "simd")]
+			Simd(ref simd) => {
+				Uint8(SIMD_PREFIX).serialize(writer)?;
+				simd.serialize(writer)?;
+			},
+			#[cfg(feature = "threads")]
+			Atomic(ref atomic) => {
+				Uint8(ATOMIC_PREFIX).serialize(writer)?;
+				atomic.serialize(writer)?;
+			},
+			#[cfg(feature = "gc")]
+			Gc(ref gc) => {
+				Uint8(GC_PREFIX).serialize(writer)?;
+				gc.serialize(writer)?;
+			},
+			TableGrow(table) | TableSize(table) | TableFill(table) => {
+				Uint8(FC_PREFIX).serialize(writer)?;
+				VarUint32(self.fc_opcode().expect("table instructions have a 0xFC sub-opcode")).serialize(writer)?;
+				VarUint32(table).serialize(writer)?;
+			},
+			GetLocal(index) => {
+				Uint8(GETLOCAL).serialize(writer)?;
+				VarUint32(index).serialize(writer)?;
+			},
+			SetLocal(index) => {
+				Uint8(SETLOCAL).serialize(writer)?;
+				VarUint32(index).serialize(writer)?;
+			},
+			TeeLocal(index) => {
+				Uint8(TEELOCAL).serialize(writer)?;
+				VarUint32(index).serialize(writer)?;
+			},
+			GetGlobal(index) => {
+				Uint8(GETGLOBAL).serialize(writer)?;
+				VarUint32(index).serialize(writer)?;
+			},
+			SetGlobal(index) => {
+				Uint8(SETGLOBAL).serialize(writer)?;
+				VarUint32(index).serialize(writer)?;
+			},
+			I32Load(flags, offset, memory) |
+			I64Load(flags, offset, memory) |
+			F32Load(flags, offset, memory) |
+			F64Load(flags, offset, memory) |
+			I32Load8S(flags, offset, memory) |
+			I32Load8U(flags, offset, memory) |
+			I32Load16S(flags, offset, memory) |
+			I32Load16U(flags, offset, memory) |
+			I64Load8S(flags, offset, memory) |
+			I64Load8U(flags, offset, memory) |
+			I64Load16S(flags, offset, memory) |
+			I64Load16U(flags, offset, memory) |
+			I64Load32S(flags, offset, memory) |
+			I64Load32U(flags, offset, memory) |
+			I32Store(flags, offset, memory) |
+			I64Store(flags, offset, memory) |
+			F32Store(flags, offset, memory) |
+			F64Store(flags, offset, memory) |
+			I32Store8(flags, offset, memory) |
+			I32Store16(flags, offset, memory) |
+			I64Store8(flags, offset, memory) |
+			I64Store16(flags, offset, memory) |
+			I64Store32(flags, offset, memory) => {
+				Uint8(self.opcode()).serialize(writer)?;
+				write_memarg(writer, flags, offset, memory)?;
+			},
+			CurrentMemory(mem_ref) => {
+				Uint8(CURRENTMEMORY).serialize(writer)?;
+				VarUint32(mem_ref).serialize(writer)?;
+			},
+			GrowMemory(mem_ref) => {
+				Uint8(GROWMEMORY).serialize(writer)?;
+				VarUint32(mem_ref).serialize(writer)?;
+			},
+			I32Const(value) => {
+				Uint8(I32CONST).serialize(writer)?;
+				VarInt32::from(value).serialize(writer)?;
+			},
+			I64Const(value) => {
+				Uint8(I64CONST).serialize(writer)?;
+				VarInt64::from(value).serialize(writer)?;
+			},
+			F32Const(value) => {
+				Uint8(F32CONST).serialize(writer)?;
+				Uint32(value).serialize(writer)?;
+			},
+			F64Const(value) => {
+				Uint8(F64CONST).serialize(writer)?;
+				Uint64::from(value).serialize(writer)?;
+			},
+			RefNull(elem_type) => {
+				Uint8(REFNULL).serialize(writer)?;
+				elem_type.write_heap_type(writer)?;
+			},
+			RefFunc(index) => {
+				Uint8(REFFUNC).serialize(writer)?;
+				VarUint32(index).serialize(writer)?;
+			},
+			MemoryInit(segment, mem_ref) => {
+				Uint8(FC_PREFIX).serialize(writer)?;
+				VarUint32(fc::MEMORYINIT).serialize(writer)?;
+				VarUint32(segment).serialize(writer)?;
+				VarUint32(mem_ref).serialize(writer)?;
+			},
+			DataDrop(segment) => {
+				Uint8(FC_PREFIX).serialize(writer)?;
+				VarUint32(fc::DATADROP).serialize(writer)?;
+				VarUint32(segment).serialize(writer)?;
+			},
+			MemoryCopy(dst, src) => {
+				Uint8(FC_PREFIX).serialize(writer)?;
+				VarUint32(fc::MEMORYCOPY).serialize(writer)?;
+				VarUint32(dst).serialize(writer)?;
+				VarUint32(src).serialize(writer)?;
+			},
+			MemoryFill(mem_ref) => {
+				Uint8(FC_PREFIX).serialize(writer)?;
+				VarUint32(fc::MEMORYFILL).serialize(writer)?;
+				VarUint32(mem_ref).serialize(writer)?;
+			},
+			TableInit(segment, table) => {
+				Uint8(FC_PREFIX).serialize(writer)?;
+				VarUint32(fc::TABLEINIT).serialize(writer)?;
+				VarUint32(segment).serialize(writer)?;
+				VarUint32(table).serialize(writer)?;
+			},
+			ElemDrop(segment) => {
+				Uint8(FC_PREFIX).serialize(writer)?;
+				VarUint32(fc::ELEMDROP).serialize(writer)?;
+				VarUint32(segment).serialize(writer)?;
+			},
+			TableCopy(dst, src) => {
+				Uint8(FC_PREFIX).serialize(writer)?;
+				VarUint32(fc::TABLECOPY).serialize(writer)?;
+				VarUint32(dst).serialize(writer)?;
+				VarUint32(src).serialize(writer)?;
+			},
+			_ => {
+				Uint8(self.opcode()).serialize(writer)?;
+				if let Some(sub) = self.fc_opcode() {
+					VarUint32(sub).serialize(writer)?;
+				}
+			},
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::elements::{deserialize_buffer, serialize};
+
+	/// `instruction` is encoded as `bytes`, which decode back to it.
+	fn assert_encoding(bytes: &[u8], instruction: Instruction) {
+		assert_eq!(serialize(&instruction).unwrap(), bytes);
+		assert_eq!(deserialize_buffer::<Instruction>(bytes).unwrap(), instruction);
+	}
+
+	#[test]
+	fn test_fc_encoding() {
+		// 0xFC 前缀后面是 LEB128 编码的子操作码
+		assert_encoding(&[0xfc, 0x07], Instruction::I64TruncSatUF64);
+		assert_eq!(deserialize_buffer::<Instruction>(&[0xfc, 0x80, 0x00]).unwrap(), Instruction::I32TruncSatSF32);
+		match deserialize_buffer::<Instruction>(&[0xfc, 0x7f]) {
+			Err(Error::UnknownFcOpcode(0x7f)) => {},
+			other => panic!("unexpected {:?}", other),
+		}
+	}
+
+	#[test]
+	fn test_sign_extension_encoding() {
+		assert_encoding(&[0xc0], Instruction::I32Extend8S);
+		assert_encoding(&[0xc4], Instruction::I64Extend32S);
+		assert_encoding(&[0xc2], Instruction::I64Extend8S);
+	}
+
+	#[test]
+	fn test_reference_encoding() {
+		use super::TableElementType;
+
+		assert_encoding(&[0xd0, 0x6f], Instruction::RefNull(TableElementType::ExternRef));
+		assert_encoding(&[0xd2, 0x03], Instruction::RefFunc(3));
+		assert_encoding(&[0x25, 0x01], Instruction::TableGet(1));
+		assert_encoding(&[0x1c, 0x01, 0x6f], Instruction::TypedSelect(ValueType::ExternRef));
+		assert_encoding(&[0xfc, 0x0f, 0x01], Instruction::TableGrow(1));
+		assert_encoding(&[0xfc, 0x11, 0x00], Instruction::TableFill(0));
+		// 带类型的 select 只能有一个结果类型
+		assert!(deserialize_buffer::<Instruction>(&[0x1c, 0x02, 0x7f, 0x7f]).is_err());
+	}
+
+	#[cfg(feature = "memory64")]
+	#[test]
+	fn test_memory64_offset() {
+		// 偏移量超过 u32 时按 u64 LEB128 编码
+		assert_encoding(&[0x37, 0x03, 0x80, 0x80, 0x80, 0x80, 0x10], Instruction::I64Store(3, 1 << 32, 0));
+	}
+
+	#[cfg(feature = "multi_memory")]
+	#[test]
+	fn test_memory_index_encoding() {
+		// 对齐字段的第 6 位表示后面跟着内存索引
+		assert_encoding(&[0x28, 0x42, 0x01, 0x08], Instruction::I32Load(2, 8, 1));
+		assert_eq!(serialize(&Instruction::I32Load(2, 8, 0)).unwrap(), vec![0x28, 0x02, 0x08]);
+		assert_eq!(deserialize_buffer::<Instruction>(&[0x3f, 0x01]).unwrap(), Instruction::CurrentMemory(1));
+		assert_eq!(serialize(&Instruction::MemoryCopy(1, 0)).unwrap(), vec![0xfc, 0x0a, 0x01, 0x00]);
+	}
+
+	#[cfg(feature = "tail_call")]
+	#[test]
+	fn test_tail_call_encoding() {
+		assert_encoding(&[0x12, 0x02], Instruction::ReturnCall(2));
+		// 类型索引在前，表索引在后
+		assert_encoding(&[0x13, 0x01, 0x00], Instruction::ReturnCallIndirect(1, 0));
+	}
+
+	#[cfg(feature = "exceptions")]
+	#[test]
+	fn test_exception_encoding() {
+		let try_table = Instruction::TryTable(Box::new(TryTableData {
+			block_type: BlockType::NoResult,
+			catches: vec![Catch::One { tag: 0, label: 1 }, Catch::AllRef { label: 0 }].into_boxed_slice(),
+		}));
+		assert_encoding(&[0x1f, 0x40, 0x02, 0x00, 0x00, 0x01, 0x03, 0x00], try_table);
+		assert_encoding(&[0x08, 0x01], Instruction::Throw(1));
+		assert_encoding(&[0x0a], Instruction::ThrowRef);
+		match deserialize_buffer::<Instruction>(&[0x1f, 0x40, 0x01, 0x04, 0x00]) {
+			Err(Error::UnknownCatchKind(4)) => {},
+			other => panic!("unexpected {:?}", other),
+		}
+	}
+}
@@ -1,4 +1,4 @@
-use super::{Deserialize, Error};
+use super::{Deserialize, Serialize, Error};
 use std::io;
 
 
@@ -30,11 +30,20 @@ impl Deserialize for Uint32 {
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Uint32, Error> {
         let mut buf = [0u8; 4];
-        reader.read(&mut buf)?;
+        reader.read_exact(&mut buf)?;
         Ok(u32::from_le_bytes(buf).into())
     }
 }
 
+impl Serialize for Uint32 {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(&self.0.to_le_bytes())?;
+        Ok(())
+    }
+}
+
 /// Unsigned variable-length integer, limited to 32 bits,
 /// represented by at most 5 bytes that may contain padding 0x80 bytes.
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -62,7 +71,7 @@ impl Deserialize for VarUint32 {
 		loop {
 			if shift > 31 { return Err(Error::InvalidVarUint32); }
 
-			reader.read(&mut u8buf)?;
+			reader.read_exact(&mut u8buf)?;
 			let b = u8buf[0] as u32;
 			res |= (b & 0x7f).checked_shl(shift).ok_or(Error::InvalidVarUint32)?;
 			shift += 7;
@@ -77,6 +86,89 @@ impl Deserialize for VarUint32 {
     }
 }
 
+impl Serialize for VarUint32 {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let mut v = self.0;
+        loop {
+            let mut b = (v & 0x7f) as u8;
+            v >>= 7;
+            if v != 0 {
+                b |= 0x80;
+            }
+            writer.write_all(&[b])?;
+            if v == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Unsigned variable-length integer, limited to 64 bits,
+/// represented by at most 10 bytes that may contain padding 0x80 bytes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VarUint64(pub u64);
+
+impl From<u64> for VarUint64 {
+    fn from(x: u64) -> VarUint64 {
+        VarUint64(x)
+    }
+}
+
+impl From<VarUint64> for u64 {
+    fn from(x: VarUint64) -> u64 {
+        x.0
+    }
+}
+
+impl Deserialize for VarUint64 {
+    type Error = Error;
+
+    fn deserialize<R: io::Read>(reader: &mut R) -> Result<VarUint64, Error> {
+        let mut res = 0u64;
+        let mut shift = 0;
+        let mut u8buf = [0u8; 1];
+        loop {
+            if shift > 63 { return Err(Error::InvalidVarUint64); }
+
+            reader.read_exact(&mut u8buf)?;
+            let b = u8buf[0] as u64;
+            res |= (b & 0x7f).checked_shl(shift).ok_or(Error::InvalidVarUint64)?;
+            shift += 7;
+            if (b >> 7) == 0 {
+                // 第 10 个字节只能用最低一位
+                if shift >= 64 && b > 1 {
+                    return Err(Error::InvalidVarUint64);
+                }
+                break;
+            }
+        }
+        Ok(VarUint64(res))
+    }
+}
+
+impl Serialize for VarUint64 {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let mut v = self.0;
+        loop {
+            let mut b = (v & 0x7f) as u8;
+            v >>= 7;
+            if v != 0 {
+                b |= 0x80;
+            }
+            writer.write_all(&[b])?;
+            if v == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Deserialize for String {
     type Error = Error;
 
@@ -93,6 +185,16 @@ impl Deserialize for String {
     }
 }
 
+impl Serialize for String {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        VarUint32(self.len() as u32).serialize(writer)?;
+        writer.write_all(self.as_bytes())?;
+        Ok(())
+    }
+}
+
 /// 7-bit signed integer, encoded in LEB128 (always 1 byte length)
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct VarInt7(pub i8);
@@ -122,7 +224,7 @@ impl Deserialize for VarInt7 {
 	/// Deserialize type from serial i/o
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<VarInt7, Error> {
 		let mut u8buf = [0u8; 1];
-		reader.read(&mut u8buf)?;
+		reader.read_exact(&mut u8buf)?;
 
 		// check if number is not continued!
 		if u8buf[0] & 0b1000_0000 != 0 {
@@ -136,6 +238,16 @@ impl Deserialize for VarInt7 {
     }
 }
 
+impl Serialize for VarInt7 {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		// 只保留低 7 位，符号位由第 6 位表示
+		writer.write_all(&[(self.0 as u8) & 0b0111_1111])?;
+		Ok(())
+	}
+}
+
 #[derive(Debug, Clone)]
 pub struct CountedList<T: Deserialize>(pub Vec<T>);
 
@@ -161,6 +273,29 @@ impl<T: Deserialize> Deserialize for CountedList<T> where T::Error : From<Error>
     }     
 }
 
+impl<T: Deserialize + Serialize> Serialize for CountedList<T> where <T as Serialize>::Error: From<Error> {
+    type Error = <T as Serialize>::Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        CountedListWriter(&self.0).serialize(writer)
+    }
+}
+
+/// Borrowing counterpart of `CountedList`, writes the length prefix followed by every entry.
+pub struct CountedListWriter<'a, T: Serialize>(pub &'a [T]);
+
+impl<T: Serialize> Serialize for CountedListWriter<'_, T> where T::Error: From<Error> {
+    type Error = T::Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        VarUint32(self.0.len() as u32).serialize(writer)?;
+        for t in self.0.iter() {
+            t.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Uint8(pub u8);
 
@@ -175,11 +310,20 @@ impl Deserialize for Uint8 {
 
     fn deserialize<R: io::Read>(reader: &mut R) -> Result<Uint8, Error> {
         let mut buf = [0u8; 1];
-        reader.read(&mut buf)?;
+        reader.read_exact(&mut buf)?;
         Ok(Uint8(buf[0]))
     }
 }
 
+impl Serialize for Uint8 {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(&[self.0])?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct VarUint1(pub bool);
 
@@ -194,7 +338,7 @@ impl Deserialize for VarUint1 {
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<VarUint1, Error> {
         let mut buf = [0u8; 1];
-        reader.read(&mut buf)?;
+        reader.read_exact(&mut buf)?;
         match buf[0] {
             0 => Ok(VarUint1(false)),
             1 => Ok(VarUint1(true)),
@@ -205,6 +349,15 @@ impl Deserialize for VarUint1 {
     }
 }
 
+impl Serialize for VarUint1 {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(&[self.0 as u8])?;
+        Ok(())
+    }
+}
+
 /// 7-bit unsigned integer, encoded in LEB128 (always 1 byte length).
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct VarUint7(pub u8);
@@ -226,11 +379,20 @@ impl Deserialize for VarUint7 {
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
 		let mut u8buf = [0u8; 1];
-		reader.read(&mut u8buf)?;
+		reader.read_exact(&mut u8buf)?;
 		Ok(VarUint7(u8buf[0]))
 	}
 }
 
+impl Serialize for VarUint7 {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		writer.write_all(&[self.0])?;
+		Ok(())
+	}
+}
+
 /// 64-bit signed integer, encoded in LEB128 (can be 1-9 bytes length).
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct VarInt64(i64);
@@ -257,7 +419,7 @@ impl Deserialize for VarInt64 {
 
 		loop {
 			if shift > 63 { return Err(Error::InvalidVarInt64); }
-			reader.read(&mut u8buf)?;
+			reader.read_exact(&mut u8buf)?;
 			let b = u8buf[0];
 
 			res |= ((b & 0x7f) as i64).checked_shl(shift).ok_or(Error::InvalidVarInt64)?;
@@ -280,6 +442,24 @@ impl Deserialize for VarInt64 {
 	}
 }
 
+impl Serialize for VarInt64 {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		let mut v = self.0;
+		loop {
+			let b = (v & 0x7f) as u8;
+			v >>= 7;
+			let done = (v == 0 && b & 0b0100_0000 == 0) || (v == -1 && b & 0b0100_0000 != 0);
+			writer.write_all(&[if done { b } else { b | 0b1000_0000 }])?;
+			if done {
+				break;
+			}
+		}
+		Ok(())
+	}
+}
+
 /// 32-bit signed integer, encoded in LEB128 (can be 1-5 bytes length).
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct VarInt32(i32);
@@ -305,7 +485,7 @@ impl Deserialize for VarInt32 {
 		let mut u8buf = [0u8; 1];
 		loop {
 			if shift > 31 { return Err(Error::InvalidVarInt32); }
-			reader.read(&mut u8buf)?;
+			reader.read_exact(&mut u8buf)?;
 			let b = u8buf[0];
 
 			res |= ((b & 0x7f) as i32).checked_shl(shift).ok_or(Error::InvalidVarInt32)?;
@@ -330,11 +510,19 @@ impl Deserialize for VarInt32 {
 	}
 }
 
+impl Serialize for VarInt32 {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		VarInt64(self.0 as i64).serialize(writer)
+	}
+}
+
 #[cfg(test)]
 mod test{
     use crate::tests::ByteStream;
     use super::Uint32;
-    use super::{Deserialize, Error};
+    use super::Deserialize;
 
     #[test]
     fn test() {
@@ -344,6 +532,72 @@ mod test{
         let u = Uint32::deserialize(&mut stream);
         println!("{:?}", u);
     }
+
+    #[test]
+    fn test_leb_round_trip() {
+        use super::{VarInt32, VarInt64, VarUint32, VarUint64};
+        use crate::elements::{deserialize_buffer, serialize};
+
+        for &v in [0u32, 1, 127, 128, 624485, u32::MAX].iter() {
+            let buf = serialize(&VarUint32(v)).unwrap();
+            assert_eq!(deserialize_buffer::<VarUint32>(&buf).unwrap(), VarUint32(v));
+        }
+        for &v in [0u64, 127, 128, 1 << 32, u64::MAX].iter() {
+            let buf = serialize(&VarUint64(v)).unwrap();
+            assert_eq!(deserialize_buffer::<VarUint64>(&buf).unwrap(), VarUint64(v));
+        }
+        assert!(deserialize_buffer::<VarUint64>(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02]).is_err());
+        for &v in [0i32, -1, 63, -64, 64, -65, i32::MIN, i32::MAX].iter() {
+            let buf = serialize(&VarInt32::from(v)).unwrap();
+            assert_eq!(i32::from(deserialize_buffer::<VarInt32>(&buf).unwrap()), v);
+        }
+        for &v in [0i64, -1, -123456, i64::MIN, i64::MAX].iter() {
+            let buf = serialize(&VarInt64::from(v)).unwrap();
+            assert_eq!(i64::from(deserialize_buffer::<VarInt64>(&buf).unwrap()), v);
+        }
+        assert_eq!(serialize(&VarInt32::from(-1)).unwrap(), vec![0x7f]);
+    }
+
+    /// 每次最多读一个字节，末尾返回 Ok(0)
+    struct Trickle<'a>(&'a [u8]);
+
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((&b, rest)), Some(dst)) => {
+                    *dst = b;
+                    self.0 = rest;
+                    Ok(1)
+                },
+                _ => Ok(0),
+            }
+        }
+    }
+
+    struct Broken;
+
+    impl std::io::Read for Broken {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn test_short_reads() {
+        use super::{Uint64, VarUint32};
+        use crate::elements::Error;
+
+        let u = Uint32::deserialize(&mut Trickle(&[0x78, 0x56, 0x34, 0x12])).unwrap();
+        assert_eq!(u32::from(u), 0x12345678);
+        let s = String::deserialize(&mut Trickle(&[0x03, b'a', b'b', b'c'])).unwrap();
+        assert_eq!(s, "abc");
+
+        assert!(matches!(Uint32::deserialize(&mut Trickle(&[1, 2])), Err(Error::UnexpectedEof)));
+        assert!(matches!(Uint64::deserialize(&mut Trickle(&[0; 7])), Err(Error::UnexpectedEof)));
+        assert!(matches!(VarUint32::deserialize(&mut Trickle(&[0x80, 0x80])), Err(Error::UnexpectedEof)));
+        assert!(matches!(String::deserialize(&mut Trickle(&[0x03, b'a'])), Err(Error::UnexpectedEof)));
+        assert!(matches!(VarUint32::deserialize(&mut Broken), Err(Error::Io(std::io::ErrorKind::BrokenPipe))));
+    }
 }
 
 
@@ -356,12 +610,21 @@ impl Deserialize for Uint64 {
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
 		let mut buf = [0u8; 8];
-		reader.read(&mut buf)?;
+		reader.read_exact(&mut buf)?;
 		// todo check range
 		Ok(u64::from_le_bytes(buf).into())
 	}
 }
 
+impl Serialize for Uint64 {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		writer.write_all(&self.0.to_le_bytes())?;
+		Ok(())
+	}
+}
+
 impl From<u64> for Uint64 {
 	fn from(u: u64) -> Self { Uint64(u) }
 }
@@ -1,8 +1,11 @@
-use super::{Deserialize, Error};
+use super::{Deserialize, Serialize, Error, PayloadError};
 use std::io;
-use super::primitives::{VarUint32, CountedList, VarUint7};
-use super::types::FunctionType;
+use super::primitives::{VarUint32, CountedListWriter, VarUint7};
+use super::features::{CheckFeatures, FeatureCheck};
+use super::types::{FunctionType, RecGroup, SubType};
 use super::import_entry::{ImportEntry, TableType, ResizableLimits};
+#[cfg(feature = "exceptions")]
+use super::import_entry::TagType;
 use super::func::Func;
 use super::global_entry::GlobalEntry;
 use super::print_stream;
@@ -10,6 +13,7 @@ use super::segment::{ElementSegment};
 use crate::elements::segment::DataSegment;
 use super::func::FuncBody;
 use super::export_entry::ExportEntry;
+use crate::io::PositionReader;
 
 #[cfg(feature = "reduced-stack-buffer")]
 const ENTRIES_BUFFER_LENGTH: usize = 256;
@@ -37,12 +41,12 @@ impl SectionReader {
         )
     }
 
-    pub fn close(self) -> Result<(), io::Error> {
+    pub fn close(self) -> Result<(), Error> {
         let cursor = self.cursor;
         let buf_length = self.declared_length;
 
         if cursor.position() as usize != buf_length {
-            return Err(io::ErrorKind::InvalidData.into());
+            return Err(Error::InconsistentLength { expected: buf_length, actual: cursor.position() as usize });
         }
 
         Ok(())
@@ -53,6 +57,81 @@ impl SectionReader {
     }
 }
 
+/// Writes a length-prefixed section payload, the counterpart of `SectionReader`.
+pub(crate) fn write_section_payload<W, F>(writer: &mut W, f: F) -> Result<(), Error>
+    where W: io::Write, F: FnOnce(&mut Vec<u8>) -> Result<(), Error>
+{
+    let mut payload: Vec<u8> = Vec::new();
+    f(&mut payload)?;
+    VarUint32(payload.len() as u32).serialize(writer)?;
+    writer.write_all(&payload)?;
+    Ok(())
+}
+
+/// Decode `payload` with `read`, which has to consume all of it.
+pub(crate) fn read_whole<'p, T, F>(payload: &'p [u8], read: F) -> Result<T, PayloadError>
+    where F: FnOnce(&mut PositionReader<io::Cursor<&'p [u8]>>) -> Result<T, PayloadError>
+{
+    let mut cursor = io::Cursor::new(payload);
+    let mut reader = PositionReader::new(&mut cursor);
+    let value = read(&mut reader)?;
+    let actual = reader.position();
+    if actual != payload.len() {
+        return Err(PayloadError::at(actual, Error::InconsistentLength { expected: payload.len(), actual }));
+    }
+    Ok(value)
+}
+
+/// Value at the current position, errors are located at its start.
+pub(crate) fn read_value<T, R>(reader: &mut PositionReader<R>) -> Result<T, PayloadError>
+    where T: Deserialize<Error = Error>, R: io::Read
+{
+    let start = reader.position();
+    T::deserialize(reader).map_err(|kind| PayloadError::at(start, kind))
+}
+
+/// The rest of the payload, for sections kept as bytes.
+fn read_rest<R: io::Read>(reader: &mut PositionReader<R>) -> Result<Vec<u8>, PayloadError> {
+    let start = reader.position();
+    let mut rest = Vec::new();
+    io::Read::read_to_end(reader, &mut rest).map_err(|e| PayloadError::at(start, e.into()))?;
+    Ok(rest)
+}
+
+/// Counted vector of entries, each checked as soon as it is decoded.
+fn read_entries<T, R>(reader: &mut PositionReader<R>, check: &mut FeatureCheck) -> Result<Vec<T>, PayloadError>
+    where T: Deserialize<Error = Error> + CheckFeatures, R: io::Read
+{
+    let count: u32 = read_value::<VarUint32, _>(reader)?.into();
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let start = reader.position();
+        let entry: T = read_value(reader)?;
+        check.entry(&entry).map_err(|kind| PayloadError::at(start, kind))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Like `read_entries` for the bodies of the code section, errors also carry the index of the body.
+fn read_bodies<R: io::Read>(reader: &mut PositionReader<R>, check: &mut FeatureCheck) -> Result<Vec<FuncBody>, PayloadError> {
+    let count: u32 = read_value::<VarUint32, _>(reader)?.into();
+    let mut bodies = Vec::new();
+    for i in 0..count {
+        let body = FuncBody::read(reader, check).map_err(|e| PayloadError { body: Some(i), ..e })?;
+        bodies.push(body);
+    }
+    Ok(bodies)
+}
+
+/// Entries of a length-prefixed section payload, the counterpart of `CountedListWriter`.
+fn deserialize_entries<T, R>(reader: &mut R) -> Result<Vec<T>, Error>
+    where T: Deserialize<Error = Error> + CheckFeatures, R: io::Read
+{
+    let payload = SectionReader::new(reader)?.payload();
+    read_whole(&payload, |reader| read_entries(reader, &mut FeatureCheck::new(None))).map_err(|e| e.kind)
+}
+
 impl io::Read for SectionReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         self.cursor.read(buf)
@@ -71,6 +150,8 @@ pub enum Section {
     Function(FunctionSection),
     Table(TableSection),
     Memory(MemorySection),
+    #[cfg(feature = "exceptions")]
+    Tag(TagSection),
     Global(GlobalSection),
     Export(ExportSection),
     Start(u32),
@@ -84,67 +165,112 @@ impl Deserialize for Section {
     type Error = Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Error> {
-        let id: u8 = match VarUint7::deserialize(reader) {
-            Ok(v) => v.into(),
-            Err(_) => return Err(Error::UnexpectedEof)
-        };
-
-        let s: Section = match id {
-            0 => Section::Custom(
-                CustomSection::deserialize(reader)?
-            ),
-            1 => Section::Type(
-                TypeSection::deserialize(reader)?
-            ),
-            2 => Section::Import(
-                ImportSection::deserialize(reader)?
-            ),
-            3 => Section::Function(
-                FunctionSection::deserialize(reader)?
-            ),
-            4 => Section::Table(
-                TableSection::deserialize(reader)?
-            ),
-            5 => Section::Memory(
-                MemorySection::deserialize(reader)?
-            ),
-            6 => Section::Global(
-                GlobalSection::deserialize(reader)?
-            ),
-            7 => {
-                Section::Export(ExportSection::deserialize(reader)?)
-            },
-            8 => {
-                let mut section_reader = SectionReader::new(reader)?;
-                let start_idx = VarUint32::deserialize(&mut section_reader)?;
-                section_reader.close()?;
-                Section::Start(start_idx.into())
-            },
-            9 => Section::Element(
-              ElementSection::deserialize(reader)?
-            ),
-            10 => Section::Code(
-                CodeSection::deserialize(reader)?
-            ),
-            11 => {
-                Section::Data(DataSection::deserialize(reader)?)
-            },
+        let id: u8 = VarUint7::deserialize(reader)?.into();
+        let payload = SectionReader::new(reader)?.payload();
+        Section::read_payload(id, &payload, &mut FeatureCheck::new(None)).map_err(|e| e.kind)
+    }
+}
+
+impl Section {
+    /// Decode the payload of section `id`, checking every entry as soon as it is decoded.
+    pub(crate) fn read_payload(id: u8, payload: &[u8], check: &mut FeatureCheck) -> Result<Section, PayloadError> {
+        read_whole(payload, |reader| Ok(match id {
+            0 => Section::Custom(CustomSection { name: read_value(reader)?, payload: read_rest(reader)? }),
+            1 => Section::Type(TypeSection(read_entries(reader, check)?)),
+            2 => Section::Import(ImportSection(read_entries(reader, check)?)),
+            3 => Section::Function(FunctionSection(read_entries(reader, check)?)),
+            4 => Section::Table(TableSection(read_entries(reader, check)?)),
+            5 => Section::Memory(MemorySection(read_entries(reader, check)?)),
+            6 => Section::Global(GlobalSection(read_entries(reader, check)?)),
+            7 => Section::Export(ExportSection(read_entries(reader, check)?)),
+            8 => Section::Start(read_value::<VarUint32, _>(reader)?.into()),
+            9 => Section::Element(ElementSection(read_entries(reader, check)?)),
+            10 => Section::Code(CodeSection(read_bodies(reader, check)?)),
+            11 => Section::Data(DataSection(read_entries(reader, check)?)),
             12 => {
-                let mut section_reader = SectionReader::new(reader)?;
-                let count = VarUint32::deserialize(&mut section_reader)?;
-                section_reader.close()?;
-                Section::DataCount(count.into())
-            }
-            _ => {
-                let r = SectionReader::new(reader)?;
-                let payload = r.payload();
-                Section::Unparsed {
-                    id, payload
-                }
-            }
-        };
+                let section = Section::DataCount(read_value::<VarUint32, _>(reader)?.into());
+                check.entry(&section).map_err(|kind| PayloadError::at(0, kind))?;
+                section
+            },
+            #[cfg(feature = "exceptions")]
+            13 => Section::Tag(TagSection(read_entries(reader, check)?)),
+            _ => Section::Unparsed { id, payload: read_rest(reader)? },
+        }))
+    }
+
+    /// Section id used in the binary encoding.
+    pub fn id(&self) -> u8 {
+        match *self {
+            Section::Unparsed { id, .. } => id,
+            Section::Custom(_) => 0,
+            Section::Type(_) => 1,
+            Section::Import(_) => 2,
+            Section::Function(_) => 3,
+            Section::Table(_) => 4,
+            Section::Memory(_) => 5,
+            Section::Global(_) => 6,
+            Section::Export(_) => 7,
+            Section::Start(_) => 8,
+            Section::Element(_) => 9,
+            Section::Code(_) => 10,
+            Section::Data(_) => 11,
+            Section::DataCount(_) => 12,
+            #[cfg(feature = "exceptions")]
+            Section::Tag(_) => 13,
+        }
+    }
+}
 
-        Ok(s)
+/// Name of the section `id` in error messages.
+pub(crate) fn section_name(id: u8) -> &'static str {
+    match id {
+        0 => "custom",
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "data count",
+        13 => "tag",
+        _ => "unknown",
+    }
+}
+
+impl Serialize for Section {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        VarUint7(self.id()).serialize(writer)?;
+
+        match *self {
+            Section::Unparsed { ref payload, .. } => {
+                write_section_payload(writer, |w| {
+                    w.extend_from_slice(payload);
+                    Ok(())
+                })
+            },
+            Section::Custom(ref s) => s.serialize(writer),
+            Section::Type(ref s) => s.serialize(writer),
+            Section::Import(ref s) => s.serialize(writer),
+            Section::Function(ref s) => s.serialize(writer),
+            Section::Table(ref s) => s.serialize(writer),
+            Section::Memory(ref s) => s.serialize(writer),
+            #[cfg(feature = "exceptions")]
+            Section::Tag(ref s) => s.serialize(writer),
+            Section::Global(ref s) => s.serialize(writer),
+            Section::Export(ref s) => s.serialize(writer),
+            Section::Start(index) => write_section_payload(writer, |w| VarUint32(index).serialize(w)),
+            Section::Element(ref s) => s.serialize(writer),
+            Section::Code(ref s) => s.serialize(writer),
+            Section::Data(ref s) => s.serialize(writer),
+            Section::DataCount(count) => write_section_payload(writer, |w| VarUint32(count).serialize(w)),
+        }
     }
 }
 
@@ -174,21 +300,62 @@ impl Deserialize for CustomSection {
     }
 }
 
-// TypeSection
+impl Serialize for CustomSection {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| {
+            self.name.serialize(w)?;
+            w.extend_from_slice(&self.payload);
+            Ok(())
+        })
+    }
+}
+
+// TypeSection，类型索引按递归组展开后的顺序编号
 #[derive(Debug, Clone, PartialEq)]
-pub struct TypeSection(pub Vec<FunctionType>);
+pub struct TypeSection(pub Vec<RecGroup>);
+
+impl TypeSection {
+    /// All types in index order.
+    pub fn types(&self) -> impl Iterator<Item = &SubType> {
+        self.0.iter().flat_map(|group| group.0.iter())
+    }
+
+    /// Type `idx`, `None` if it is out of bounds.
+    pub fn get(&self, idx: u32) -> Option<&SubType> {
+        self.types().nth(idx as usize)
+    }
+
+    /// Function type `idx`, `None` if it is out of bounds or not a function type.
+    pub fn func_type(&self, idx: u32) -> Option<&FunctionType> {
+        self.get(idx).and_then(SubType::func_type)
+    }
+}
+
+/// Every function type in a group of its own.
+impl From<Vec<FunctionType>> for TypeSection {
+    fn from(types: Vec<FunctionType>) -> TypeSection {
+        TypeSection(types.into_iter().map(RecGroup::from).collect())
+    }
+}
 
 impl Deserialize for TypeSection {
     type Error = Error;
 
     fn deserialize<R: io::Read>(reader: &mut R) -> Result<TypeSection, Error> {
-        let mut rd = SectionReader::new(reader)?;
-        let types: Vec<FunctionType> = CountedList::deserialize(&mut rd)?.into_inner();
-        rd.close()?;
-        Ok(TypeSection(types))
+        Ok(TypeSection(deserialize_entries(reader)?))
     }    
 }
 
+impl Serialize for TypeSection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| CountedListWriter(&self.0).serialize(w))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ImportSection(pub Vec<ImportEntry>);
 
@@ -196,13 +363,18 @@ impl Deserialize for ImportSection {
     type Error = Error;
 
     fn deserialize<R: io::Read>(reader: &mut R) -> Result<ImportSection, Error> {
-        let mut rd = SectionReader::new(reader)?;
-        let imports: Vec<ImportEntry> = CountedList::deserialize(&mut rd)?.into_inner();
-        rd.close()?;
-        Ok(ImportSection(imports))
+        Ok(ImportSection(deserialize_entries(reader)?))
     }  
 }
 
+impl Serialize for ImportSection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| CountedListWriter(&self.0).serialize(w))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FunctionSection(pub Vec<Func>);
 
@@ -210,13 +382,18 @@ impl Deserialize for FunctionSection {
     type Error = Error;
 
     fn deserialize<R: io::Read>(reader: &mut R) -> Result<FunctionSection, Error> {
-        let mut rd = SectionReader::new(reader)?;
-        let funcs: Vec<Func> = CountedList::deserialize(&mut rd)?.into_inner();
-        rd.close()?;
-        Ok(FunctionSection(funcs))
+        Ok(FunctionSection(deserialize_entries(reader)?))
     }      
 }
 
+impl Serialize for FunctionSection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| CountedListWriter(&self.0).serialize(w))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct TableSection(pub Vec<TableType>);
 
@@ -224,14 +401,15 @@ impl Deserialize for TableSection {
     type Error = Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<TableSection, Error> {
-        let mut rd = SectionReader::new(reader)?;
-        let types: Vec<TableType> = CountedList::deserialize(&mut rd)?.into_inner();
-        rd.close()?;
-        Ok(
-            TableSection(
-                types
-            )
-        )
+        Ok(TableSection(deserialize_entries(reader)?))
+    }
+}
+
+impl Serialize for TableSection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| CountedListWriter(&self.0).serialize(w))
     }
 }
 
@@ -242,12 +420,38 @@ impl Deserialize for MemorySection {
     type Error = Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<MemorySection, Error> {
-        let mut rd = SectionReader::new(reader)?;
-        let v: Vec<ResizableLimits> = CountedList::deserialize(&mut rd)?.into_inner();
-        rd.close()?;
-        Ok(
-            MemorySection(v)
-        )
+        Ok(MemorySection(deserialize_entries(reader)?))
+    }
+}
+
+impl Serialize for MemorySection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| CountedListWriter(&self.0).serialize(w))
+    }
+}
+
+/// Tags declared by the module.
+#[cfg(feature = "exceptions")]
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct TagSection(pub Vec<TagType>);
+
+#[cfg(feature = "exceptions")]
+impl Deserialize for TagSection {
+    type Error = Error;
+
+    fn deserialize<R: io::Read>(reader: &mut R) -> Result<TagSection, Error> {
+        Ok(TagSection(deserialize_entries(reader)?))
+    }
+}
+
+#[cfg(feature = "exceptions")]
+impl Serialize for TagSection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| CountedListWriter(&self.0).serialize(w))
     }
 }
 
@@ -258,78 +462,95 @@ impl Deserialize for GlobalSection {
     type Error = Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<GlobalSection, Error> {
-        let mut rd = SectionReader::new(reader)?;
-        let v: Vec<GlobalEntry> = CountedList::deserialize(&mut rd)?.into_inner();
-        rd.close()?;
-        Ok(
-            GlobalSection(v)
-        )
+        Ok(GlobalSection(deserialize_entries(reader)?))
+    }
+}
+
+impl Serialize for GlobalSection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| CountedListWriter(&self.0).serialize(w))
     }
 }
 
 #[derive(Default, Debug, Clone, PartialEq)]
-pub struct ElementSection(Vec<ElementSegment>);
+pub struct ElementSection(pub Vec<ElementSegment>);
 
 impl Deserialize for ElementSection {
     type Error = Error;
 
     fn deserialize<R: io::Read>(reader: &mut R) -> Result<ElementSection, Error> {
-        let mut rd = SectionReader::new(reader)?;
-        let v: Vec<ElementSegment> = CountedList::deserialize(&mut rd)?.into_inner();
-        rd.close()?;
-        Ok(
-            ElementSection(v)
-        )
+        Ok(ElementSection(deserialize_entries(reader)?))
+    }
+}
+
+impl Serialize for ElementSection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| CountedListWriter(&self.0).serialize(w))
     }
 }
 
 #[derive(Default, Debug, Clone, PartialEq)]
-pub struct DataSection(Vec<DataSegment>);
+pub struct DataSection(pub Vec<DataSegment>);
 
 impl Deserialize for DataSection {
     type Error = Error;
 
     fn deserialize<R: io::Read>(reader: &mut R) -> Result<DataSection, Error> {
-        let mut rd = SectionReader::new(reader)?;
-        let v: Vec<DataSegment> = CountedList::deserialize(&mut rd)?.into_inner();
-        rd.close()?;
-        Ok(
-            DataSection(v)
-        )
+        Ok(DataSection(deserialize_entries(reader)?))
+    }
+}
+
+impl Serialize for DataSection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| CountedListWriter(&self.0).serialize(w))
     }
 }
 
 /// Section with function bodies of the module.
 #[derive(Default, Debug, Clone, PartialEq)]
-pub struct CodeSection(Vec<FuncBody>);
+pub struct CodeSection(pub Vec<FuncBody>);
 
 impl Deserialize for CodeSection {
     type Error = Error;
 
     fn deserialize<R: io::Read>(reader: &mut R) -> Result<CodeSection, Error> {
-        let mut rd = SectionReader::new(reader)?;
-        let v: Vec<FuncBody> = CountedList::deserialize(&mut rd)?.into_inner();
-        rd.close()?;
-        Ok(
-            CodeSection(v)
-        )
+        let payload = SectionReader::new(reader)?.payload();
+        let bodies = read_whole(&payload, |reader| read_bodies(reader, &mut FeatureCheck::new(None)));
+        Ok(CodeSection(bodies.map_err(|e| e.kind)?))
+    }
+}
+
+impl Serialize for CodeSection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| CountedListWriter(&self.0).serialize(w))
     }
 }
 
 /// List of exports definition.
 #[derive(Debug, Default, Clone, PartialEq)]
-pub struct ExportSection(Vec<ExportEntry>);
+pub struct ExportSection(pub Vec<ExportEntry>);
 
 impl Deserialize for ExportSection {
     type Error = Error;
 
     fn deserialize<R: io::Read>(reader: &mut R) -> Result<ExportSection, Error> {
-        let mut rd = SectionReader::new(reader)?;
-        let v: Vec<ExportEntry> = CountedList::deserialize(&mut rd)?.into_inner();
-        rd.close()?;
-        Ok(
-            ExportSection(v)
-        )
+        Ok(ExportSection(deserialize_entries(reader)?))
+    }
+}
+
+impl Serialize for ExportSection {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        write_section_payload(writer, |w| CountedListWriter(&self.0).serialize(w))
     }
 }
 
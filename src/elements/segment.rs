@@ -1,7 +1,8 @@
 use super::ops::InitExpr;
-use super::{Deserialize, Error};
+use super::types::TableElementType;
+use super::{Deserialize, Serialize, Error};
 use std::io;
-use crate::elements::primitives::{VarUint32, CountedList};
+use crate::elements::primitives::{Uint8, VarUint32, CountedList, CountedListWriter};
 
 #[cfg(feature = "reduced-stack-buffer")]
 const VALUES_BUFFER_LENGTH: usize = 256;
@@ -10,34 +11,127 @@ const VALUES_BUFFER_LENGTH: usize = 256;
 const VALUES_BUFFER_LENGTH: usize = 16384;
 
 /// Entry in the element section.
+///
+/// Active segments have an `offset` and are copied into table `index` at
+/// instantiation. Passive segments are only used by `table.init`, and
+/// declarative ones only declare the functions they reference.
 #[derive(Debug, Clone, PartialEq)]
 pub struct ElementSegment {
     pub index: u32,
     pub offset: Option<InitExpr>,
+    /// Type of the elements, always `AnyFunc` for function indices.
+    pub elem_type: TableElementType,
     pub members: Vec<u32>,
+    /// Members given as constant expressions, used in place of `members`.
+    pub exprs: Option<Vec<InitExpr>>,
+    pub declarative: bool,
+}
+
+impl ElementSegment {
+    /// Segment of function indices, active when `offset` is given and passive otherwise.
+    pub fn new(index: u32, offset: Option<InitExpr>, members: Vec<u32>) -> ElementSegment {
+        ElementSegment { index, offset, elem_type: TableElementType::AnyFunc, members, exprs: None, declarative: false }
+    }
+
+    pub fn is_passive(&self) -> bool {
+        self.offset.is_none() && !self.declarative
+    }
 }
 
 impl Deserialize for ElementSegment {
     type Error = Error;
 
     fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
-        let index: u32 = VarUint32::deserialize(reader)?.into();
-        let offset = InitExpr::deserialize(reader)?;
-        let members: Vec<u32> = CountedList::<VarUint32>::deserialize(reader)?
-            .into_inner()
-            .into_iter()
-            .map(Into::into)
-            .collect();
+        // bit 0: 非 active；bit 1: 显式表索引或 declarative；bit 2: 表达式形式的元素
+        let flags: u32 = VarUint32::deserialize(reader)?.into();
+        if flags > 7 {
+            return Err(Error::InvalidSegmentFlags(flags));
+        }
+
+        let (index, offset, declarative) = if flags & 0x01 == 0 {
+            let index = if flags & 0x02 != 0 { VarUint32::deserialize(reader)?.into() } else { 0 };
+            (index, Some(InitExpr::deserialize(reader)?), false)
+        } else {
+            (0, None, flags & 0x02 != 0)
+        };
+
+        let uses_exprs = flags & 0x04 != 0;
+        let mut elem_type = TableElementType::AnyFunc;
+        if flags & 0x03 != 0 {
+            if uses_exprs {
+                elem_type = TableElementType::deserialize(reader)?;
+            } else {
+                let kind: u8 = Uint8::deserialize(reader)?.into();
+                if kind != 0x00 {
+                    return Err(Error::UnknownTableElementType(kind as i8));
+                }
+            }
+        }
+
+        let (members, exprs) = if uses_exprs {
+            (Vec::new(), Some(CountedList::<InitExpr>::deserialize(reader)?.into_inner()))
+        } else {
+            let members = CountedList::<VarUint32>::deserialize(reader)?
+                .into_inner()
+                .into_iter()
+                .map(Into::into)
+                .collect();
+            (members, None)
+        };
 
         Ok(ElementSegment {
             index,
-            offset: Some(offset),
+            offset,
+            elem_type,
             members,
+            exprs,
+            declarative,
         })
     }
 }
 
-/// Data segment definition.
+impl Serialize for ElementSegment {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        // 表 0 的简写形式隐含 funcref，其它元素类型需要显式编码
+        let typed = self.exprs.is_some() && self.elem_type != TableElementType::AnyFunc;
+        let mut flags = match self.offset {
+            Some(_) if self.index != 0 || typed => 0x02,
+            Some(_) => 0x00,
+            None if self.declarative => 0x03,
+            None => 0x01,
+        };
+        if self.exprs.is_some() {
+            flags |= 0x04;
+        }
+        VarUint32(flags).serialize(writer)?;
+
+        if let Some(ref offset) = self.offset {
+            if flags & 0x02 != 0 {
+                VarUint32(self.index).serialize(writer)?;
+            }
+            offset.serialize(writer)?;
+        }
+        if flags & 0x03 != 0 {
+            if self.exprs.is_some() {
+                self.elem_type.serialize(writer)?;
+            } else {
+                Uint8(0x00).serialize(writer)?;
+            }
+        }
+
+        match self.exprs {
+            Some(ref exprs) => CountedListWriter(exprs).serialize(writer),
+            None => {
+                let members: Vec<VarUint32> = self.members.iter().map(|&m| VarUint32(m)).collect();
+                CountedListWriter(&members).serialize(writer)
+            },
+        }
+    }
+}
+
+/// Data segment definition, passive when there is no `offset`.
 #[derive(Clone, Debug, PartialEq)]
 pub struct DataSegment {
     pub index: u32,
@@ -49,15 +143,122 @@ impl Deserialize for DataSegment {
     type Error = Error;
 
     fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
-        let index = VarUint32::deserialize(reader)?;
-        let offset = InitExpr::deserialize(reader)?;
+        let flags: u32 = VarUint32::deserialize(reader)?.into();
+        let (index, offset) = match flags {
+            0x00 => (0, Some(InitExpr::deserialize(reader)?)),
+            0x01 => (0, None),
+            0x02 => (VarUint32::deserialize(reader)?.into(), Some(InitExpr::deserialize(reader)?)),
+            _ => return Err(Error::InvalidSegmentFlags(flags)),
+        };
         let value_len = u32::from(VarUint32::deserialize(reader)?) as usize;
         let value_buf = buffered_read!(VALUES_BUFFER_LENGTH, value_len, reader);
 
         Ok(DataSegment {
-            index: index.into(),
-            offset: Some(offset),
+            index,
+            offset,
             value: value_buf,
         })
     }
-}
\ No newline at end of file
+}
+
+impl Serialize for DataSegment {
+    type Error = Error;
+
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        match self.offset {
+            Some(ref offset) => {
+                if self.index != 0 {
+                    VarUint32(0x02).serialize(writer)?;
+                    VarUint32(self.index).serialize(writer)?;
+                } else {
+                    VarUint32(0x00).serialize(writer)?;
+                }
+                offset.serialize(writer)?;
+            },
+            None => VarUint32(0x01).serialize(writer)?,
+        }
+        VarUint32(self.value.len() as u32).serialize(writer)?;
+        writer.write_all(&self.value)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elements::{deserialize_buffer, serialize};
+    use crate::elements::ops::Instruction;
+
+    #[test]
+    fn test_element_encodings() {
+        let encodings: [&[u8]; 8] = [
+            &[0x00, 0x41, 0x00, 0x0b, 0x01, 0x00],
+            &[0x01, 0x00, 0x01, 0x00],
+            &[0x02, 0x01, 0x41, 0x00, 0x0b, 0x00, 0x01, 0x00],
+            &[0x03, 0x00, 0x01, 0x00],
+            &[0x04, 0x41, 0x00, 0x0b, 0x01, 0xd2, 0x00, 0x0b],
+            &[0x05, 0x70, 0x02, 0xd2, 0x00, 0x0b, 0xd0, 0x70, 0x0b],
+            &[0x06, 0x01, 0x41, 0x00, 0x0b, 0x6f, 0x00],
+            &[0x07, 0x70, 0x01, 0xd2, 0x00, 0x0b],
+        ];
+        for (flags, bytes) in encodings.iter().enumerate() {
+            let segment: ElementSegment = deserialize_buffer(bytes).unwrap();
+            assert_eq!(segment.offset.is_some(), flags & 0x01 == 0);
+            assert_eq!(segment.declarative, flags & 0x03 == 0x03);
+            assert_eq!(segment.exprs.is_some(), flags & 0x04 != 0);
+            assert_eq!(serialize(&segment).unwrap(), bytes.to_vec(), "encoding {}", flags);
+        }
+
+        let segment: ElementSegment = deserialize_buffer(encodings[5]).unwrap();
+        assert!(segment.is_passive());
+        assert_eq!(segment.exprs.unwrap()[1].0, vec![Instruction::RefNull(TableElementType::AnyFunc), Instruction::End]);
+        let segment: ElementSegment = deserialize_buffer(encodings[2]).unwrap();
+        assert_eq!((segment.index, segment.members), (1, vec![0]));
+        let segment: ElementSegment = deserialize_buffer(encodings[6]).unwrap();
+        assert_eq!(segment.elem_type, TableElementType::ExternRef);
+
+        // 表 0 的 externref 表达式段不能用隐含 funcref 的 0x04
+        let offset = InitExpr(vec![Instruction::I32Const(0), Instruction::End]);
+        let mut segment = ElementSegment::new(0, Some(offset), Vec::new());
+        segment.elem_type = TableElementType::ExternRef;
+        segment.exprs = Some(vec![InitExpr(vec![Instruction::RefNull(TableElementType::ExternRef), Instruction::End])]);
+        let bytes = serialize(&segment).unwrap();
+        assert_eq!(bytes, vec![0x06, 0x00, 0x41, 0x00, 0x0b, 0x6f, 0x01, 0xd0, 0x6f, 0x0b]);
+        assert_eq!(deserialize_buffer::<ElementSegment>(&bytes).unwrap(), segment);
+
+        #[cfg(feature = "gc")]
+        {
+            use crate::elements::types::{HeapType, RefType};
+
+            segment.elem_type = RefType::non_nullable(HeapType::Func).into();
+            segment.exprs = Some(vec![InitExpr(vec![Instruction::RefFunc(0), Instruction::End])]);
+            let bytes = serialize(&segment).unwrap();
+            assert_eq!(bytes[..2], [0x06, 0x00]);
+            assert_eq!(deserialize_buffer::<ElementSegment>(&bytes).unwrap(), segment);
+        }
+
+        match deserialize_buffer::<ElementSegment>(&[0x08, 0x00]) {
+            Err(Error::InvalidSegmentFlags(8)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+        // elemkind 只能是 0x00
+        assert!(deserialize_buffer::<ElementSegment>(&[0x01, 0x01, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_data_encodings() {
+        let passive: DataSegment = deserialize_buffer(&[0x01, 0x02, b'h', b'i']).unwrap();
+        assert_eq!(passive, DataSegment { index: 0, offset: None, value: b"hi".to_vec() });
+        assert_eq!(serialize(&passive).unwrap(), vec![0x01, 0x02, b'h', b'i']);
+
+        let explicit = [0x02, 0x01, 0x41, 0x08, 0x0b, 0x00];
+        let segment: DataSegment = deserialize_buffer(&explicit).unwrap();
+        assert_eq!(segment.index, 1);
+        assert_eq!(serialize(&segment).unwrap(), explicit.to_vec());
+
+        match deserialize_buffer::<DataSegment>(&[0x03, 0x00]) {
+            Err(Error::InvalidSegmentFlags(3)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,552 @@
+use std::io;
+
+use super::{Deserialize, Serialize, Error};
+use super::primitives::{Uint8, VarUint32};
+use super::ops::{read_memarg, write_memarg};
+
+// 带立即数的 SIMD 指令的子操作码
+mod opcodes {
+    pub const V128LOAD: u32 = 0x00;
+    pub const V128LOAD8X8S: u32 = 0x01;
+    pub const V128LOAD8X8U: u32 = 0x02;
+    pub const V128LOAD16X4S: u32 = 0x03;
+    pub const V128LOAD16X4U: u32 = 0x04;
+    pub const V128LOAD32X2S: u32 = 0x05;
+    pub const V128LOAD32X2U: u32 = 0x06;
+    pub const V128LOAD8SPLAT: u32 = 0x07;
+    pub const V128LOAD16SPLAT: u32 = 0x08;
+    pub const V128LOAD32SPLAT: u32 = 0x09;
+    pub const V128LOAD64SPLAT: u32 = 0x0a;
+    pub const V128STORE: u32 = 0x0b;
+    pub const V128CONST: u32 = 0x0c;
+    pub const I8X16SHUFFLE: u32 = 0x0d;
+
+    pub const I8X16EXTRACTLANES: u32 = 0x15;
+    pub const I8X16EXTRACTLANEU: u32 = 0x16;
+    pub const I8X16REPLACELANE: u32 = 0x17;
+    pub const I16X8EXTRACTLANES: u32 = 0x18;
+    pub const I16X8EXTRACTLANEU: u32 = 0x19;
+    pub const I16X8REPLACELANE: u32 = 0x1a;
+    pub const I32X4EXTRACTLANE: u32 = 0x1b;
+    pub const I32X4REPLACELANE: u32 = 0x1c;
+    pub const I64X2EXTRACTLANE: u32 = 0x1d;
+    pub const I64X2REPLACELANE: u32 = 0x1e;
+    pub const F32X4EXTRACTLANE: u32 = 0x1f;
+    pub const F32X4REPLACELANE: u32 = 0x20;
+    pub const F64X2EXTRACTLANE: u32 = 0x21;
+    pub const F64X2REPLACELANE: u32 = 0x22;
+
+    pub const V128LOAD8LANE: u32 = 0x54;
+    pub const V128LOAD16LANE: u32 = 0x55;
+    pub const V128LOAD32LANE: u32 = 0x56;
+    pub const V128LOAD64LANE: u32 = 0x57;
+    pub const V128STORE8LANE: u32 = 0x58;
+    pub const V128STORE16LANE: u32 = 0x59;
+    pub const V128STORE32LANE: u32 = 0x5a;
+    pub const V128STORE64LANE: u32 = 0x5b;
+    pub const V128LOAD32ZERO: u32 = 0x5c;
+    pub const V128LOAD64ZERO: u32 = 0x5d;
+}
+
+use self::opcodes::*;
+
+// 没有立即数的指令和它们的子操作码
+macro_rules! simd_instructions {
+    ($($variant: ident = $opcode: expr,)*) => {
+        /// Instruction of the fixed-width SIMD proposal, encoded after the 0xFD prefix.
+        ///
+        /// Loads and stores carry the alignment exponent and the offset like the
+        /// core memory instructions, lane instructions carry the lane index.
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        #[allow(missing_docs)]
+        pub enum SimdInstruction {
+            V128Load(u32, u64, u32),
+            V128Load8x8S(u32, u64, u32),
+            V128Load8x8U(u32, u64, u32),
+            V128Load16x4S(u32, u64, u32),
+            V128Load16x4U(u32, u64, u32),
+            V128Load32x2S(u32, u64, u32),
+            V128Load32x2U(u32, u64, u32),
+            V128Load8Splat(u32, u64, u32),
+            V128Load16Splat(u32, u64, u32),
+            V128Load32Splat(u32, u64, u32),
+            V128Load64Splat(u32, u64, u32),
+            V128Load32Zero(u32, u64, u32),
+            V128Load64Zero(u32, u64, u32),
+            V128Store(u32, u64, u32),
+
+            V128Load8Lane(u32, u64, u32, u8),
+            V128Load16Lane(u32, u64, u32, u8),
+            V128Load32Lane(u32, u64, u32, u8),
+            V128Load64Lane(u32, u64, u32, u8),
+            V128Store8Lane(u32, u64, u32, u8),
+            V128Store16Lane(u32, u64, u32, u8),
+            V128Store32Lane(u32, u64, u32, u8),
+            V128Store64Lane(u32, u64, u32, u8),
+
+            /// Little endian bytes of the constant.
+            V128Const(Box<[u8; 16]>),
+            /// Lane indices into the concatenation of both operands.
+            I8x16Shuffle(Box<[u8; 16]>),
+
+            I8x16ExtractLaneS(u8),
+            I8x16ExtractLaneU(u8),
+            I8x16ReplaceLane(u8),
+            I16x8ExtractLaneS(u8),
+            I16x8ExtractLaneU(u8),
+            I16x8ReplaceLane(u8),
+            I32x4ExtractLane(u8),
+            I32x4ReplaceLane(u8),
+            I64x2ExtractLane(u8),
+            I64x2ReplaceLane(u8),
+            F32x4ExtractLane(u8),
+            F32x4ReplaceLane(u8),
+            F64x2ExtractLane(u8),
+            F64x2ReplaceLane(u8),
+
+            $($variant,)*
+        }
+
+        /// Instruction without immediates encoded as `opcode`.
+        fn simple_simd_instruction(opcode: u32) -> Option<SimdInstruction> {
+            match opcode {
+                $($opcode => Some(SimdInstruction::$variant),)*
+                _ => None,
+            }
+        }
+
+        impl SimdInstruction {
+            /// Sub-opcode following the 0xFD prefix.
+            pub fn opcode(&self) -> u32 {
+                use self::SimdInstruction::*;
+
+                match *self {
+                    V128Load(..) => V128LOAD,
+                    V128Load8x8S(..) => V128LOAD8X8S,
+                    V128Load8x8U(..) => V128LOAD8X8U,
+                    V128Load16x4S(..) => V128LOAD16X4S,
+                    V128Load16x4U(..) => V128LOAD16X4U,
+                    V128Load32x2S(..) => V128LOAD32X2S,
+                    V128Load32x2U(..) => V128LOAD32X2U,
+                    V128Load8Splat(..) => V128LOAD8SPLAT,
+                    V128Load16Splat(..) => V128LOAD16SPLAT,
+                    V128Load32Splat(..) => V128LOAD32SPLAT,
+                    V128Load64Splat(..) => V128LOAD64SPLAT,
+                    V128Load32Zero(..) => V128LOAD32ZERO,
+                    V128Load64Zero(..) => V128LOAD64ZERO,
+                    V128Store(..) => V128STORE,
+                    V128Load8Lane(..) => V128LOAD8LANE,
+                    V128Load16Lane(..) => V128LOAD16LANE,
+                    V128Load32Lane(..) => V128LOAD32LANE,
+                    V128Load64Lane(..) => V128LOAD64LANE,
+                    V128Store8Lane(..) => V128STORE8LANE,
+                    V128Store16Lane(..) => V128STORE16LANE,
+                    V128Store32Lane(..) => V128STORE32LANE,
+                    V128Store64Lane(..) => V128STORE64LANE,
+                    V128Const(_) => V128CONST,
+                    I8x16Shuffle(_) => I8X16SHUFFLE,
+                    I8x16ExtractLaneS(_) => I8X16EXTRACTLANES,
+                    I8x16ExtractLaneU(_) => I8X16EXTRACTLANEU,
+                    I8x16ReplaceLane(_) => I8X16REPLACELANE,
+                    I16x8ExtractLaneS(_) => I16X8EXTRACTLANES,
+                    I16x8ExtractLaneU(_) => I16X8EXTRACTLANEU,
+                    I16x8ReplaceLane(_) => I16X8REPLACELANE,
+                    I32x4ExtractLane(_) => I32X4EXTRACTLANE,
+                    I32x4ReplaceLane(_) => I32X4REPLACELANE,
+                    I64x2ExtractLane(_) => I64X2EXTRACTLANE,
+                    I64x2ReplaceLane(_) => I64X2REPLACELANE,
+                    F32x4ExtractLane(_) => F32X4EXTRACTLANE,
+                    F32x4ReplaceLane(_) => F32X4REPLACELANE,
+                    F64x2ExtractLane(_) => F64X2EXTRACTLANE,
+                    F64x2ReplaceLane(_) => F64X2REPLACELANE,
+                    $($variant => $opcode,)*
+                }
+            }
+
+            /// Index of the memory accessed by a load or store, `None` for other instructions.
+            pub fn memory_index(&self) -> Option<u32> {
+                use self::SimdInstruction::*;
+
+                match *self {
+                    V128Load(_, _, memory) | V128Load8x8S(_, _, memory) | V128Load8x8U(_, _, memory)
+                    | V128Load16x4S(_, _, memory) | V128Load16x4U(_, _, memory) | V128Load32x2S(_, _, memory)
+                    | V128Load32x2U(_, _, memory) | V128Load8Splat(_, _, memory) | V128Load16Splat(_, _, memory)
+                    | V128Load32Splat(_, _, memory) | V128Load64Splat(_, _, memory) | V128Load32Zero(_, _, memory)
+                    | V128Load64Zero(_, _, memory) | V128Store(_, _, memory) => Some(memory),
+                    V128Load8Lane(_, _, memory, _) | V128Load16Lane(_, _, memory, _)
+                    | V128Load32Lane(_, _, memory, _) | V128Load64Lane(_, _, memory, _)
+                    | V128Store8Lane(_, _, memory, _) | V128Store16Lane(_, _, memory, _)
+                    | V128Store32Lane(_, _, memory, _) | V128Store64Lane(_, _, memory, _) => Some(memory),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+simd_instructions! {
+    I8x16Swizzle = 0x0e,
+    I8x16Splat = 0x0f,
+    I16x8Splat = 0x10,
+    I32x4Splat = 0x11,
+    I64x2Splat = 0x12,
+    F32x4Splat = 0x13,
+    F64x2Splat = 0x14,
+
+    I8x16Eq = 0x23,
+    I8x16Ne = 0x24,
+    I8x16LtS = 0x25,
+    I8x16LtU = 0x26,
+    I8x16GtS = 0x27,
+    I8x16GtU = 0x28,
+    I8x16LeS = 0x29,
+    I8x16LeU = 0x2a,
+    I8x16GeS = 0x2b,
+    I8x16GeU = 0x2c,
+    I16x8Eq = 0x2d,
+    I16x8Ne = 0x2e,
+    I16x8LtS = 0x2f,
+    I16x8LtU = 0x30,
+    I16x8GtS = 0x31,
+    I16x8GtU = 0x32,
+    I16x8LeS = 0x33,
+    I16x8LeU = 0x34,
+    I16x8GeS = 0x35,
+    I16x8GeU = 0x36,
+    I32x4Eq = 0x37,
+    I32x4Ne = 0x38,
+    I32x4LtS = 0x39,
+    I32x4LtU = 0x3a,
+    I32x4GtS = 0x3b,
+    I32x4GtU = 0x3c,
+    I32x4LeS = 0x3d,
+    I32x4LeU = 0x3e,
+    I32x4GeS = 0x3f,
+    I32x4GeU = 0x40,
+    F32x4Eq = 0x41,
+    F32x4Ne = 0x42,
+    F32x4Lt = 0x43,
+    F32x4Gt = 0x44,
+    F32x4Le = 0x45,
+    F32x4Ge = 0x46,
+    F64x2Eq = 0x47,
+    F64x2Ne = 0x48,
+    F64x2Lt = 0x49,
+    F64x2Gt = 0x4a,
+    F64x2Le = 0x4b,
+    F64x2Ge = 0x4c,
+
+    V128Not = 0x4d,
+    V128And = 0x4e,
+    V128AndNot = 0x4f,
+    V128Or = 0x50,
+    V128Xor = 0x51,
+    V128Bitselect = 0x52,
+    V128AnyTrue = 0x53,
+
+    F32x4DemoteF64x2Zero = 0x5e,
+    F64x2PromoteLowF32x4 = 0x5f,
+
+    I8x16Abs = 0x60,
+    I8x16Neg = 0x61,
+    I8x16Popcnt = 0x62,
+    I8x16AllTrue = 0x63,
+    I8x16Bitmask = 0x64,
+    I8x16NarrowI16x8S = 0x65,
+    I8x16NarrowI16x8U = 0x66,
+    F32x4Ceil = 0x67,
+    F32x4Floor = 0x68,
+    F32x4Trunc = 0x69,
+    F32x4Nearest = 0x6a,
+    I8x16Shl = 0x6b,
+    I8x16ShrS = 0x6c,
+    I8x16ShrU = 0x6d,
+    I8x16Add = 0x6e,
+    I8x16AddSatS = 0x6f,
+    I8x16AddSatU = 0x70,
+    I8x16Sub = 0x71,
+    I8x16SubSatS = 0x72,
+    I8x16SubSatU = 0x73,
+    F64x2Ceil = 0x74,
+    F64x2Floor = 0x75,
+    I8x16MinS = 0x76,
+    I8x16MinU = 0x77,
+    I8x16MaxS = 0x78,
+    I8x16MaxU = 0x79,
+    F64x2Trunc = 0x7a,
+    I8x16AvgrU = 0x7b,
+    I16x8ExtaddPairwiseI8x16S = 0x7c,
+    I16x8ExtaddPairwiseI8x16U = 0x7d,
+    I32x4ExtaddPairwiseI16x8S = 0x7e,
+    I32x4ExtaddPairwiseI16x8U = 0x7f,
+
+    I16x8Abs = 0x80,
+    I16x8Neg = 0x81,
+    I16x8Q15MulrSatS = 0x82,
+    I16x8AllTrue = 0x83,
+    I16x8Bitmask = 0x84,
+    I16x8NarrowI32x4S = 0x85,
+    I16x8NarrowI32x4U = 0x86,
+    I16x8ExtendLowI8x16S = 0x87,
+    I16x8ExtendHighI8x16S = 0x88,
+    I16x8ExtendLowI8x16U = 0x89,
+    I16x8ExtendHighI8x16U = 0x8a,
+    I16x8Shl = 0x8b,
+    I16x8ShrS = 0x8c,
+    I16x8ShrU = 0x8d,
+    I16x8Add = 0x8e,
+    I16x8AddSatS = 0x8f,
+    I16x8AddSatU = 0x90,
+    I16x8Sub = 0x91,
+    I16x8SubSatS = 0x92,
+    I16x8SubSatU = 0x93,
+    F64x2Nearest = 0x94,
+    I16x8Mul = 0x95,
+    I16x8MinS = 0x96,
+    I16x8MinU = 0x97,
+    I16x8MaxS = 0x98,
+    I16x8MaxU = 0x99,
+    I16x8AvgrU = 0x9b,
+    I16x8ExtmulLowI8x16S = 0x9c,
+    I16x8ExtmulHighI8x16S = 0x9d,
+    I16x8ExtmulLowI8x16U = 0x9e,
+    I16x8ExtmulHighI8x16U = 0x9f,
+
+    I32x4Abs = 0xa0,
+    I32x4Neg = 0xa1,
+    I32x4AllTrue = 0xa3,
+    I32x4Bitmask = 0xa4,
+    I32x4ExtendLowI16x8S = 0xa7,
+    I32x4ExtendHighI16x8S = 0xa8,
+    I32x4ExtendLowI16x8U = 0xa9,
+    I32x4ExtendHighI16x8U = 0xaa,
+    I32x4Shl = 0xab,
+    I32x4ShrS = 0xac,
+    I32x4ShrU = 0xad,
+    I32x4Add = 0xae,
+    I32x4Sub = 0xb1,
+    I32x4Mul = 0xb5,
+    I32x4MinS = 0xb6,
+    I32x4MinU = 0xb7,
+    I32x4MaxS = 0xb8,
+    I32x4MaxU = 0xb9,
+    I32x4DotI16x8S = 0xba,
+    I32x4ExtmulLowI16x8S = 0xbc,
+    I32x4ExtmulHighI16x8S = 0xbd,
+    I32x4ExtmulLowI16x8U = 0xbe,
+    I32x4ExtmulHighI16x8U = 0xbf,
+
+    I64x2Abs = 0xc0,
+    I64x2Neg = 0xc1,
+    I64x2AllTrue = 0xc3,
+    I64x2Bitmask = 0xc4,
+    I64x2ExtendLowI32x4S = 0xc7,
+    I64x2ExtendHighI32x4S = 0xc8,
+    I64x2ExtendLowI32x4U = 0xc9,
+    I64x2ExtendHighI32x4U = 0xca,
+    I64x2Shl = 0xcb,
+    I64x2ShrS = 0xcc,
+    I64x2ShrU = 0xcd,
+    I64x2Add = 0xce,
+    I64x2Sub = 0xd1,
+    I64x2Mul = 0xd5,
+    I64x2Eq = 0xd6,
+    I64x2Ne = 0xd7,
+    I64x2LtS = 0xd8,
+    I64x2GtS = 0xd9,
+    I64x2LeS = 0xda,
+    I64x2GeS = 0xdb,
+    I64x2ExtmulLowI32x4S = 0xdc,
+    I64x2ExtmulHighI32x4S = 0xdd,
+    I64x2ExtmulLowI32x4U = 0xde,
+    I64x2ExtmulHighI32x4U = 0xdf,
+
+    F32x4Abs = 0xe0,
+    F32x4Neg = 0xe1,
+    F32x4Sqrt = 0xe3,
+    F32x4Add = 0xe4,
+    F32x4Sub = 0xe5,
+    F32x4Mul = 0xe6,
+    F32x4Div = 0xe7,
+    F32x4Min = 0xe8,
+    F32x4Max = 0xe9,
+    F32x4Pmin = 0xea,
+    F32x4Pmax = 0xeb,
+    F64x2Abs = 0xec,
+    F64x2Neg = 0xed,
+    F64x2Sqrt = 0xef,
+    F64x2Add = 0xf0,
+    F64x2Sub = 0xf1,
+    F64x2Mul = 0xf2,
+    F64x2Div = 0xf3,
+    F64x2Min = 0xf4,
+    F64x2Max = 0xf5,
+    F64x2Pmin = 0xf6,
+    F64x2Pmax = 0xf7,
+
+    I32x4TruncSatF32x4S = 0xf8,
+    I32x4TruncSatF32x4U = 0xf9,
+    F32x4ConvertI32x4S = 0xfa,
+    F32x4ConvertI32x4U = 0xfb,
+    I32x4TruncSatF64x2SZero = 0xfc,
+    I32x4TruncSatF64x2UZero = 0xfd,
+    F64x2ConvertLowI32x4S = 0xfe,
+    F64x2ConvertLowI32x4U = 0xff,
+}
+
+impl Deserialize for SimdInstruction {
+    type Error = Error;
+
+    /// Reads the sub-opcode and immediates, the 0xFD prefix is already consumed.
+    fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
+        use self::SimdInstruction::*;
+
+        let val: u32 = VarUint32::deserialize(reader)?.into();
+        if let Some(instruction) = simple_simd_instruction(val) {
+            return Ok(instruction);
+        }
+
+        let memarg = |reader: &mut R| read_memarg(reader, |align, offset, memory| (align, offset, memory));
+        let lane = |reader: &mut R| -> Result<u8, Error> {
+            Ok(Uint8::deserialize(reader)?.into())
+        };
+        let bytes = |reader: &mut R| -> Result<Box<[u8; 16]>, Error> {
+            let mut buf = [0u8; 16];
+            reader.read_exact(&mut buf)?;
+            Ok(Box::new(buf))
+        };
+
+        let instruction = match val {
+            V128LOAD | V128LOAD8X8S | V128LOAD8X8U | V128LOAD16X4S | V128LOAD16X4U | V128LOAD32X2S
+            | V128LOAD32X2U | V128LOAD8SPLAT | V128LOAD16SPLAT | V128LOAD32SPLAT | V128LOAD64SPLAT
+            | V128LOAD32ZERO | V128LOAD64ZERO | V128STORE => {
+                let (align, offset, memory) = memarg(reader)?;
+                match val {
+                    V128LOAD => V128Load(align, offset, memory),
+                    V128LOAD8X8S => V128Load8x8S(align, offset, memory),
+                    V128LOAD8X8U => V128Load8x8U(align, offset, memory),
+                    V128LOAD16X4S => V128Load16x4S(align, offset, memory),
+                    V128LOAD16X4U => V128Load16x4U(align, offset, memory),
+                    V128LOAD32X2S => V128Load32x2S(align, offset, memory),
+                    V128LOAD32X2U => V128Load32x2U(align, offset, memory),
+                    V128LOAD8SPLAT => V128Load8Splat(align, offset, memory),
+                    V128LOAD16SPLAT => V128Load16Splat(align, offset, memory),
+                    V128LOAD32SPLAT => V128Load32Splat(align, offset, memory),
+                    V128LOAD64SPLAT => V128Load64Splat(align, offset, memory),
+                    V128LOAD32ZERO => V128Load32Zero(align, offset, memory),
+                    V128LOAD64ZERO => V128Load64Zero(align, offset, memory),
+                    _ => V128Store(align, offset, memory),
+                }
+            },
+            V128LOAD8LANE | V128LOAD16LANE | V128LOAD32LANE | V128LOAD64LANE
+            | V128STORE8LANE | V128STORE16LANE | V128STORE32LANE | V128STORE64LANE => {
+                let (align, offset, memory) = memarg(reader)?;
+                let lane = lane(reader)?;
+                match val {
+                    V128LOAD8LANE => V128Load8Lane(align, offset, memory, lane),
+                    V128LOAD16LANE => V128Load16Lane(align, offset, memory, lane),
+                    V128LOAD32LANE => V128Load32Lane(align, offset, memory, lane),
+                    V128LOAD64LANE => V128Load64Lane(align, offset, memory, lane),
+                    V128STORE8LANE => V128Store8Lane(align, offset, memory, lane),
+                    V128STORE16LANE => V128Store16Lane(align, offset, memory, lane),
+                    V128STORE32LANE => V128Store32Lane(align, offset, memory, lane),
+                    _ => V128Store64Lane(align, offset, memory, lane),
+                }
+            },
+            V128CONST => V128Const(bytes(reader)?),
+            I8X16SHUFFLE => I8x16Shuffle(bytes(reader)?),
+            I8X16EXTRACTLANES => I8x16ExtractLaneS(lane(reader)?),
+            I8X16EXTRACTLANEU => I8x16ExtractLaneU(lane(reader)?),
+            I8X16REPLACELANE => I8x16ReplaceLane(lane(reader)?),
+            I16X8EXTRACTLANES => I16x8ExtractLaneS(lane(reader)?),
+            I16X8EXTRACTLANEU => I16x8ExtractLaneU(lane(reader)?),
+            I16X8REPLACELANE => I16x8ReplaceLane(lane(reader)?),
+            I32X4EXTRACTLANE => I32x4ExtractLane(lane(reader)?),
+            I32X4REPLACELANE => I32x4ReplaceLane(lane(reader)?),
+            I64X2EXTRACTLANE => I64x2ExtractLane(lane(reader)?),
+            I64X2REPLACELANE => I64x2ReplaceLane(lane(reader)?),
+            F32X4EXTRACTLANE => F32x4ExtractLane(lane(reader)?),
+            F32X4REPLACELANE => F32x4ReplaceLane(lane(reader)?),
+            F64X2EXTRACTLANE => F64x2ExtractLane(lane(reader)?),
+            F64X2REPLACELANE => F64x2ReplaceLane(lane(reader)?),
+            _ => return Err(Error::UnknownSimdOpcode(val)),
+        };
+        Ok(instruction)
+    }
+}
+
+impl Serialize for SimdInstruction {
+    type Error = Error;
+
+    /// Writes the sub-opcode and immediates, without the 0xFD prefix.
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+        use self::SimdInstruction::*;
+
+        VarUint32(self.opcode()).serialize(writer)?;
+        match *self {
+            V128Load(align, offset, memory) | V128Load8x8S(align, offset, memory) | V128Load8x8U(align, offset, memory)
+            | V128Load16x4S(align, offset, memory) | V128Load16x4U(align, offset, memory) | V128Load32x2S(align, offset, memory)
+            | V128Load32x2U(align, offset, memory) | V128Load8Splat(align, offset, memory) | V128Load16Splat(align, offset, memory)
+            | V128Load32Splat(align, offset, memory) | V128Load64Splat(align, offset, memory) | V128Load32Zero(align, offset, memory)
+            | V128Load64Zero(align, offset, memory) | V128Store(align, offset, memory) => {
+                write_memarg(writer, align, offset, memory)?;
+            },
+            V128Load8Lane(align, offset, memory, lane) | V128Load16Lane(align, offset, memory, lane)
+            | V128Load32Lane(align, offset, memory, lane) | V128Load64Lane(align, offset, memory, lane)
+            | V128Store8Lane(align, offset, memory, lane) | V128Store16Lane(align, offset, memory, lane)
+            | V128Store32Lane(align, offset, memory, lane) | V128Store64Lane(align, offset, memory, lane) => {
+                write_memarg(writer, align, offset, memory)?;
+                Uint8(lane).serialize(writer)?;
+            },
+            V128Const(ref bytes) | I8x16Shuffle(ref bytes) => writer.write_all(&bytes[..])?,
+            I8x16ExtractLaneS(lane) | I8x16ExtractLaneU(lane) | I8x16ReplaceLane(lane)
+            | I16x8ExtractLaneS(lane) | I16x8ExtractLaneU(lane) | I16x8ReplaceLane(lane)
+            | I32x4ExtractLane(lane) | I32x4ReplaceLane(lane) | I64x2ExtractLane(lane)
+            | I64x2ReplaceLane(lane) | F32x4ExtractLane(lane) | F32x4ReplaceLane(lane)
+            | F64x2ExtractLane(lane) | F64x2ReplaceLane(lane) => Uint8(lane).serialize(writer)?,
+            _ => {},
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elements::ops::Instruction;
+    use crate::elements::{deserialize_buffer, serialize};
+
+    #[test]
+    fn test_simd_encoding() {
+        let cases: Vec<(Vec<u8>, SimdInstruction)> = vec![
+            (vec![0xfd, 0x00, 0x04, 0x10], SimdInstruction::V128Load(4, 16, 0)),
+            (vec![0xfd, 0x0f], SimdInstruction::I8x16Splat),
+            (vec![0xfd, 0x6e], SimdInstruction::I8x16Add),
+            // 子操作码是 LEB128，大于 0x7f 的占两个字节
+            (vec![0xfd, 0xae, 0x01], SimdInstruction::I32x4Add),
+            (vec![0xfd, 0xff, 0x01], SimdInstruction::F64x2ConvertLowI32x4U),
+            (vec![0xfd, 0x1b, 0x03], SimdInstruction::I32x4ExtractLane(3)),
+            (vec![0xfd, 0x57, 0x03, 0x00, 0x01], SimdInstruction::V128Load64Lane(3, 0, 0, 1)),
+        ];
+        for (bytes, instruction) in cases {
+            let instruction = Instruction::Simd(instruction);
+            assert_eq!(deserialize_buffer::<Instruction>(&bytes).unwrap(), instruction);
+            assert_eq!(serialize(&instruction).unwrap(), bytes);
+        }
+
+        let mut bytes = vec![0xfd, 0x0c];
+        bytes.extend(1..=16u8);
+        let mut expected = [0u8; 16];
+        for (i, b) in expected.iter_mut().enumerate() {
+            *b = i as u8 + 1;
+        }
+        let instruction = Instruction::Simd(SimdInstruction::V128Const(Box::new(expected)));
+        assert_eq!(deserialize_buffer::<Instruction>(&bytes).unwrap(), instruction);
+        assert_eq!(serialize(&instruction).unwrap(), bytes);
+
+        match deserialize_buffer::<Instruction>(&[0xfd, 0x9a, 0x01]) {
+            Err(Error::UnknownSimdOpcode(0x9a)) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -1,5 +1,7 @@
-use super::{Deserialize, Error};
-use super::primitives::{VarInt7, CountedList, VarUint7};
+use super::{Deserialize, Serialize, Error};
+use super::primitives::{VarInt7, CountedList, CountedListWriter, VarUint7, VarUint32, Uint8};
+#[cfg(any(feature = "multi_value", feature = "gc"))]
+use super::primitives::VarInt64;
 
 use std::io;
 
@@ -13,6 +15,186 @@ pub enum ValueType {
 	F32,
 	/// 64-bit float
 	F64,
+	/// Nullable reference to a function
+	FuncRef,
+	/// Nullable reference to a host object
+	ExternRef,
+	/// Nullable reference to a caught exception
+	#[cfg(feature = "exceptions")]
+	ExnRef,
+	/// 128-bit vector
+	#[cfg(feature = "simd")]
+	V128,
+	/// Typed reference, `(ref null func)` and `(ref null extern)` are always `FuncRef` and `ExternRef`.
+	#[cfg(feature = "gc")]
+	Ref(RefType),
+}
+
+impl ValueType {
+	/// Whether this is a reference type.
+	pub fn is_ref(self) -> bool {
+		match self {
+			ValueType::FuncRef | ValueType::ExternRef => true,
+			#[cfg(feature = "exceptions")]
+			ValueType::ExnRef => true,
+			#[cfg(feature = "gc")]
+			ValueType::Ref(_) => true,
+			_ => false,
+		}
+	}
+
+	/// Whether locals of this type have a default value, only non-nullable references have none.
+	pub fn is_defaultable(self) -> bool {
+		match self {
+			#[cfg(feature = "gc")]
+			ValueType::Ref(ref_type) => ref_type.nullable,
+			_ => true,
+		}
+	}
+
+	/// Reference type of a `funcref`, `externref` or typed reference.
+	#[cfg(feature = "gc")]
+	pub fn ref_type(self) -> Option<RefType> {
+		match self {
+			ValueType::FuncRef => Some(RefType::nullable(HeapType::Func)),
+			ValueType::ExternRef => Some(RefType::nullable(HeapType::Extern)),
+			ValueType::Ref(ref_type) => Some(ref_type),
+			_ => None,
+		}
+	}
+}
+
+/// Heap type of a typed reference.
+#[cfg(feature = "gc")]
+#[derive(Clone, Copy, Debug, PartialEq, Hash, Eq)]
+#[allow(missing_docs)]
+pub enum HeapType {
+	Func,
+	Extern,
+	Any,
+	Eq,
+	I31,
+	Struct,
+	Array,
+	/// Bottom of the `any` hierarchy.
+	None,
+	/// Bottom of the `func` hierarchy.
+	NoFunc,
+	/// Bottom of the `extern` hierarchy.
+	NoExtern,
+	/// Type defined in the type section.
+	Concrete(u32),
+}
+
+#[cfg(feature = "gc")]
+impl HeapType {
+	/// Abstract heap type of a negative s33 code, also the shorthand of its nullable reference.
+	fn from_code(code: i64) -> Option<HeapType> {
+		Some(match code {
+			-0x10 => HeapType::Func,
+			-0x11 => HeapType::Extern,
+			-0x12 => HeapType::Any,
+			-0x13 => HeapType::Eq,
+			-0x14 => HeapType::I31,
+			-0x15 => HeapType::Struct,
+			-0x16 => HeapType::Array,
+			-0x0f => HeapType::None,
+			-0x0d => HeapType::NoFunc,
+			-0x0e => HeapType::NoExtern,
+			_ => return None,
+		})
+	}
+
+	fn code(self) -> i64 {
+		match self {
+			HeapType::Func => -0x10,
+			HeapType::Extern => -0x11,
+			HeapType::Any => -0x12,
+			HeapType::Eq => -0x13,
+			HeapType::I31 => -0x14,
+			HeapType::Struct => -0x15,
+			HeapType::Array => -0x16,
+			HeapType::None => -0x0f,
+			HeapType::NoFunc => -0x0d,
+			HeapType::NoExtern => -0x0e,
+			HeapType::Concrete(idx) => idx as i64,
+		}
+	}
+}
+
+#[cfg(feature = "gc")]
+impl Deserialize for HeapType {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<HeapType, Error> {
+		// 抽象堆类型是负数，类型索引是非负的 s33
+		let code: i64 = VarInt64::deserialize(reader)?.into();
+		if code >= 0 {
+			if code > u32::MAX as i64 {
+				return Err(Error::Other("Heap type index out of range"));
+			}
+			return Ok(HeapType::Concrete(code as u32));
+		}
+		HeapType::from_code(code).ok_or(Error::UnknownValueType(code as i8))
+	}
+}
+
+#[cfg(feature = "gc")]
+impl Serialize for HeapType {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		VarInt64::from(self.code()).serialize(writer)
+	}
+}
+
+/// Reference to a heap type, `(ref null? ht)`.
+#[cfg(feature = "gc")]
+#[derive(Clone, Copy, Debug, PartialEq, Hash, Eq)]
+pub struct RefType {
+	pub nullable: bool,
+	pub heap: HeapType,
+}
+
+#[cfg(feature = "gc")]
+impl RefType {
+	pub fn nullable(heap: HeapType) -> RefType {
+		RefType { nullable: true, heap }
+	}
+
+	pub fn non_nullable(heap: HeapType) -> RefType {
+		RefType { nullable: false, heap }
+	}
+
+	/// Decode a reference type whose first byte `code` is already read.
+	fn read<R: io::Read>(code: i8, reader: &mut R) -> Result<RefType, Error> {
+		match code {
+			-0x1d => Ok(RefType::nullable(HeapType::deserialize(reader)?)),
+			-0x1c => Ok(RefType::non_nullable(HeapType::deserialize(reader)?)),
+			_ => HeapType::from_code(code as i64).map(RefType::nullable).ok_or(Error::UnknownValueType(code)),
+		}
+	}
+
+	fn write<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		match self.heap {
+			HeapType::Concrete(_) => {},
+			_ if self.nullable => return self.heap.serialize(writer),
+			_ => {},
+		}
+		VarInt7(if self.nullable { -0x1d } else { -0x1c }).serialize(writer)?;
+		self.heap.serialize(writer)
+	}
+}
+
+#[cfg(feature = "gc")]
+impl From<RefType> for ValueType {
+	fn from(ref_type: RefType) -> ValueType {
+		match ref_type {
+			RefType { nullable: true, heap: HeapType::Func } => ValueType::FuncRef,
+			RefType { nullable: true, heap: HeapType::Extern } => ValueType::ExternRef,
+			_ => ValueType::Ref(ref_type),
+		}
+	}
 }
 
 impl Deserialize for ValueType {
@@ -20,17 +202,55 @@ impl Deserialize for ValueType {
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<ValueType, Error> {
         let val: i8 = VarInt7::deserialize(reader)?.into();
+        ValueType::read(val, reader)
+    }
+}
 
+impl ValueType {
+    /// Decode a value type whose first byte `val` is already read.
+    #[cfg_attr(not(feature = "gc"), allow(unused_variables))]
+    fn read<R: io::Read>(val: i8, reader: &mut R) -> Result<ValueType, Error> {
         match val {
             -1 => Ok(ValueType::I32),
             -2 => Ok(ValueType::I64),
             -3 => Ok(ValueType::F32),
             -4 => Ok(ValueType::F64),
+            #[cfg(feature = "simd")]
+            -5 => Ok(ValueType::V128),
+            -0x10 => Ok(ValueType::FuncRef),
+            -0x11 => Ok(ValueType::ExternRef),
+            #[cfg(feature = "exceptions")]
+            -0x17 => Ok(ValueType::ExnRef),
+            #[cfg(feature = "gc")]
+            _ => Ok(RefType::read(val, reader)?.into()),
+            #[cfg(not(feature = "gc"))]
             _ => Err(Error::UnknownValueType(val)),
         }
     }
 }
 
+impl Serialize for ValueType {
+    type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let val: i8 = match *self {
+            ValueType::I32 => -0x01,
+            ValueType::I64 => -0x02,
+            ValueType::F32 => -0x03,
+            ValueType::F64 => -0x04,
+            #[cfg(feature = "simd")]
+            ValueType::V128 => -0x05,
+            ValueType::FuncRef => -0x10,
+            ValueType::ExternRef => -0x11,
+            #[cfg(feature = "exceptions")]
+            ValueType::ExnRef => -0x17,
+            #[cfg(feature = "gc")]
+            ValueType::Ref(ref_type) => return ref_type.write(writer),
+        };
+        VarInt7(val).serialize(writer)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub struct FunctionType {
     pub form: u8,
@@ -53,7 +273,13 @@ impl Deserialize for FunctionType {
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<FunctionType, Error> {
         let form: u8 = VarUint7::deserialize(reader)?.into();
+        FunctionType::read(form, reader)
+    }
+}
 
+impl FunctionType {
+    /// Decode the rest of a function type whose `form` is already read.
+    fn read<R: io::Read>(form: u8, reader: &mut R) -> Result<FunctionType, Error> {
         if form != 0x60 {
             return Err(Error::UnknownFunctionForm(form));
         }
@@ -61,10 +287,9 @@ impl Deserialize for FunctionType {
         let params: Vec<ValueType> = CountedList::deserialize(reader)?.into_inner();
         let results: Vec<ValueType> = CountedList::deserialize(reader)?.into_inner();
 
+        #[cfg(not(feature = "multi_value"))]
         if results.len() > 1 {
-            return Err(
-                Error::Other("Enable the multi_value feature to deserialize more than one function result")
-            );
+            return Err(Error::FeatureDisabled { feature: "multi_value", used: "Multiple function results" });
         }
 
         Ok(
@@ -77,11 +302,333 @@ impl Deserialize for FunctionType {
     }
 }
 
+impl Serialize for FunctionType {
+    type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        VarUint7(self.form).serialize(writer)?;
+        CountedListWriter(&self.params).serialize(writer)?;
+        CountedListWriter(&self.results).serialize(writer)
+    }
+}
+
+/// Type of a struct field or array element, packed integers are stored narrowed.
+#[cfg(feature = "gc")]
+#[derive(Clone, Copy, Debug, PartialEq, Hash, Eq)]
+pub enum StorageType {
+	Val(ValueType),
+	I8,
+	I16,
+}
+
+#[cfg(feature = "gc")]
+impl StorageType {
+	/// Type of the field on the operand stack, packed integers are `i32`.
+	pub fn unpacked(self) -> ValueType {
+		match self {
+			StorageType::Val(value_type) => value_type,
+			StorageType::I8 | StorageType::I16 => ValueType::I32,
+		}
+	}
+
+	pub fn is_packed(self) -> bool {
+		self != StorageType::Val(self.unpacked())
+	}
+}
+
+#[cfg(feature = "gc")]
+impl Deserialize for StorageType {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<StorageType, Error> {
+		let val: i8 = VarInt7::deserialize(reader)?.into();
+		match val {
+			-0x08 => Ok(StorageType::I8),
+			-0x09 => Ok(StorageType::I16),
+			_ => Ok(StorageType::Val(ValueType::read(val, reader)?)),
+		}
+	}
+}
+
+#[cfg(feature = "gc")]
+impl Serialize for StorageType {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		match *self {
+			StorageType::Val(value_type) => value_type.serialize(writer),
+			StorageType::I8 => VarInt7(-0x08).serialize(writer),
+			StorageType::I16 => VarInt7(-0x09).serialize(writer),
+		}
+	}
+}
+
+/// Struct field or array element.
+#[cfg(feature = "gc")]
+#[derive(Clone, Copy, Debug, PartialEq, Hash, Eq)]
+pub struct FieldType {
+	pub storage: StorageType,
+	pub mutable: bool,
+}
+
+#[cfg(feature = "gc")]
+impl Deserialize for FieldType {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<FieldType, Error> {
+		let storage = StorageType::deserialize(reader)?;
+		let mutable = match u8::from(Uint8::deserialize(reader)?) {
+			0 => false,
+			1 => true,
+			_ => return Err(Error::Other("Invalid field mutability")),
+		};
+		Ok(FieldType { storage, mutable })
+	}
+}
+
+#[cfg(feature = "gc")]
+impl Serialize for FieldType {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		self.storage.serialize(writer)?;
+		Uint8(self.mutable as u8).serialize(writer)
+	}
+}
+
+/// Structure of a defined type.
+#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+pub enum CompositeType {
+	Func(FunctionType),
+	#[cfg(feature = "gc")]
+	Struct(Vec<FieldType>),
+	#[cfg(feature = "gc")]
+	Array(FieldType),
+}
+
+impl CompositeType {
+	/// Decode a composite type whose `form` is already read.
+	fn read<R: io::Read>(form: u8, reader: &mut R) -> Result<CompositeType, Error> {
+		match form {
+			#[cfg(feature = "gc")]
+			0x5f => Ok(CompositeType::Struct(CountedList::deserialize(reader)?.into_inner())),
+			#[cfg(feature = "gc")]
+			0x5e => Ok(CompositeType::Array(FieldType::deserialize(reader)?)),
+			_ => Ok(CompositeType::Func(FunctionType::read(form, reader)?)),
+		}
+	}
+}
+
+impl Serialize for CompositeType {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		match *self {
+			CompositeType::Func(ref func) => func.serialize(writer),
+			#[cfg(feature = "gc")]
+			CompositeType::Struct(ref fields) => {
+				VarUint7(0x5f).serialize(writer)?;
+				CountedListWriter(fields).serialize(writer)
+			},
+			#[cfg(feature = "gc")]
+			CompositeType::Array(ref field) => {
+				VarUint7(0x5e).serialize(writer)?;
+				field.serialize(writer)
+			},
+		}
+	}
+}
+
+/// Entry of the type section: a composite type with its declared supertypes.
+///
+/// Without the `gc` feature every type is a final function type without supertypes.
+#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+pub struct SubType {
+	pub is_final: bool,
+	pub supertypes: Vec<u32>,
+	pub composite: CompositeType,
+}
+
+impl SubType {
+	/// The function type, `None` for struct and array types.
+	pub fn func_type(&self) -> Option<&FunctionType> {
+		match self.composite {
+			CompositeType::Func(ref func) => Some(func),
+			#[cfg(feature = "gc")]
+			_ => None,
+		}
+	}
+
+	/// Decode a subtype whose first byte `form` is already read.
+	fn read<R: io::Read>(form: u8, reader: &mut R) -> Result<SubType, Error> {
+		match form {
+			#[cfg(feature = "gc")]
+			0x50 | 0x4f => {
+				let supertypes = CountedList::<VarUint32>::deserialize(reader)?
+					.into_inner()
+					.into_iter()
+					.map(Into::into)
+					.collect();
+				let composite_form: u8 = VarUint7::deserialize(reader)?.into();
+				Ok(SubType {
+					is_final: form == 0x4f,
+					supertypes,
+					composite: CompositeType::read(composite_form, reader)?,
+				})
+			},
+			_ => Ok(CompositeType::read(form, reader)?.into()),
+		}
+	}
+}
+
+impl Deserialize for SubType {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<SubType, Error> {
+		let form: u8 = VarUint7::deserialize(reader)?.into();
+		SubType::read(form, reader)
+	}
+}
+
+/// A final type without supertypes.
+impl From<CompositeType> for SubType {
+	fn from(composite: CompositeType) -> SubType {
+		SubType { is_final: true, supertypes: Vec::new(), composite }
+	}
+}
+
+impl From<FunctionType> for SubType {
+	fn from(func: FunctionType) -> SubType {
+		CompositeType::Func(func).into()
+	}
+}
+
+impl Serialize for SubType {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		if !self.is_final || !self.supertypes.is_empty() {
+			VarUint7(if self.is_final { 0x4f } else { 0x50 }).serialize(writer)?;
+			let supertypes: Vec<VarUint32> = self.supertypes.iter().map(|&idx| VarUint32(idx)).collect();
+			CountedListWriter(&supertypes).serialize(writer)?;
+		}
+		self.composite.serialize(writer)
+	}
+}
+
+/// Recursive type group, whose types may refer to each other.
+///
+/// A type outside of a `rec` group is a group of its own.
+#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+pub struct RecGroup(pub Vec<SubType>);
+
+impl Deserialize for RecGroup {
+	type Error = Error;
+
+	fn deserialize<R: io::Read>(reader: &mut R) -> Result<RecGroup, Error> {
+		let form: u8 = VarUint7::deserialize(reader)?.into();
+		match form {
+			#[cfg(feature = "gc")]
+			0x4e => Ok(RecGroup(CountedList::deserialize(reader)?.into_inner())),
+			_ => Ok(RecGroup(vec![SubType::read(form, reader)?])),
+		}
+	}
+}
+
+impl Serialize for RecGroup {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		if self.0.len() != 1 {
+			VarUint7(0x4e).serialize(writer)?;
+			return CountedListWriter(&self.0).serialize(writer);
+		}
+		self.0[0].serialize(writer)
+	}
+}
+
+impl From<FunctionType> for RecGroup {
+	fn from(func: FunctionType) -> RecGroup {
+		RecGroup(vec![func.into()])
+	}
+}
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TableElementType {
 	/// A reference to a function with any signature.
 	AnyFunc,
+	/// A reference to an object of the host.
+	ExternRef,
+	/// A reference to a caught exception.
+	#[cfg(feature = "exceptions")]
+	ExnRef,
+	/// Typed reference other than `funcref` and `externref`.
+	#[cfg(feature = "gc")]
+	Ref(RefType),
+}
+
+impl TableElementType {
+	/// Type of the values stored in such a table.
+	pub fn value_type(self) -> ValueType {
+		match self {
+			TableElementType::AnyFunc => ValueType::FuncRef,
+			TableElementType::ExternRef => ValueType::ExternRef,
+			#[cfg(feature = "exceptions")]
+			TableElementType::ExnRef => ValueType::ExnRef,
+			#[cfg(feature = "gc")]
+			TableElementType::Ref(ref_type) => ValueType::Ref(ref_type),
+		}
+	}
+
+	/// Nullable reference to `heap`, the operand of `ref.null`.
+	#[cfg(feature = "gc")]
+	pub fn null_of(heap: HeapType) -> TableElementType {
+		TableElementType::from(RefType::nullable(heap))
+	}
+
+	/// Heap type of the references, `None` for `exnref`.
+	#[cfg(feature = "gc")]
+	pub fn heap_type(self) -> Option<HeapType> {
+		self.value_type().ref_type().map(|ref_type| ref_type.heap)
+	}
+
+	/// Decode the heap type operand of `ref.null`.
+	pub(crate) fn read_heap_type<R: io::Read>(reader: &mut R) -> Result<TableElementType, Error> {
+		#[cfg(feature = "gc")]
+		{
+			let code: i64 = VarInt64::deserialize(reader)?.into();
+			match code {
+				#[cfg(feature = "exceptions")]
+				-0x17 => Ok(TableElementType::ExnRef),
+				_ if code >= 0 && code <= u32::MAX as i64 => Ok(TableElementType::null_of(HeapType::Concrete(code as u32))),
+				_ => HeapType::from_code(code).map(TableElementType::null_of).ok_or(Error::UnknownTableElementType(code as i8)),
+			}
+		}
+
+		#[cfg(not(feature = "gc"))]
+		TableElementType::deserialize(reader)
+	}
+
+	/// Encode the heap type operand of `ref.null`.
+	pub(crate) fn write_heap_type<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+		match *self {
+			#[cfg(feature = "gc")]
+			TableElementType::Ref(ref_type) => ref_type.heap.serialize(writer),
+			_ => self.serialize(writer),
+		}
+	}
+}
+
+#[cfg(feature = "gc")]
+impl From<RefType> for TableElementType {
+	fn from(ref_type: RefType) -> TableElementType {
+		match ValueType::from(ref_type) {
+			ValueType::FuncRef => TableElementType::AnyFunc,
+			ValueType::ExternRef => TableElementType::ExternRef,
+			_ => TableElementType::Ref(ref_type),
+		}
+	}
 }
 
 impl Deserialize for TableElementType {
@@ -92,11 +639,34 @@ impl Deserialize for TableElementType {
 
         match val {
             -0x10 => Ok(TableElementType::AnyFunc),
+            -0x11 => Ok(TableElementType::ExternRef),
+            #[cfg(feature = "exceptions")]
+            -0x17 => Ok(TableElementType::ExnRef),
+            #[cfg(feature = "gc")]
+            -0x1d | -0x1c => Ok(RefType::read(val, reader)?.into()),
+            #[cfg(feature = "gc")]
+            _ if HeapType::from_code(val as i64).is_some() => Ok(RefType::read(val, reader)?.into()),
             _ => Err(Error::UnknownTableElementType(val)),
         }
     }   
 }
 
+impl Serialize for TableElementType {
+    type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let val: i8 = match *self {
+            TableElementType::AnyFunc => -0x10,
+            TableElementType::ExternRef => -0x11,
+            #[cfg(feature = "exceptions")]
+            TableElementType::ExnRef => -0x17,
+            #[cfg(feature = "gc")]
+            TableElementType::Ref(ref_type) => return ref_type.write(writer),
+        };
+        VarInt7(val).serialize(writer)
+    }
+}
+
 /// Block type which is basically `ValueType` + NoResult (to define blocks that have no return type)
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum BlockType {
@@ -104,6 +674,9 @@ pub enum BlockType {
 	Value(ValueType),
 	/// No specified block type
 	NoResult,
+	/// Function type of the block, which may take parameters and return several results.
+	#[cfg(feature = "multi_value")]
+	TypeIndex(u32),
 }
 
 
@@ -111,15 +684,124 @@ impl Deserialize for BlockType {
 	type Error = Error;
 
 	fn deserialize<R: io::Read>(reader: &mut R) -> Result<Self, Self::Error> {
-		let val: i8 = VarInt7::deserialize(reader)?.into();
+		// 单字节的值类型和 0x40 之外都是 s33 编码的类型索引
+		let first: u8 = Uint8::deserialize(reader)?.into();
 
-		match val {
-			-0x01 => Ok(BlockType::Value(ValueType::I32)),
-			-0x02 => Ok(BlockType::Value(ValueType::I64)),
-			-0x03 => Ok(BlockType::Value(ValueType::F32)),
-			-0x04 => Ok(BlockType::Value(ValueType::F64)),
-			-0x40 => Ok(BlockType::NoResult),
-			_ => Err(Error::UnknownValueType(val.into())),
+		match first {
+			0x7f => return Ok(BlockType::Value(ValueType::I32)),
+			0x7e => return Ok(BlockType::Value(ValueType::I64)),
+			0x7d => return Ok(BlockType::Value(ValueType::F32)),
+			0x7c => return Ok(BlockType::Value(ValueType::F64)),
+			#[cfg(feature = "simd")]
+			0x7b => return Ok(BlockType::Value(ValueType::V128)),
+			0x70 => return Ok(BlockType::Value(ValueType::FuncRef)),
+			0x6f => return Ok(BlockType::Value(ValueType::ExternRef)),
+			#[cfg(feature = "exceptions")]
+			0x69 => return Ok(BlockType::Value(ValueType::ExnRef)),
+			// 其他引用类型的首字节
+			#[cfg(feature = "gc")]
+			0x63 | 0x64 | 0x6a..=0x73 => {
+				return Ok(BlockType::Value(RefType::read(((first << 1) as i8) >> 1, reader)?.into()));
+			},
+			0x40 => return Ok(BlockType::NoResult),
+			_ => {},
 		}
+
+		#[cfg(feature = "multi_value")]
+		{
+			let mut index = (first & 0x7f) as i64;
+			let mut shift = 7;
+			let mut byte = first;
+			while byte & 0x80 != 0 {
+				// s33 最多 5 个字节
+				if shift >= 35 {
+					return Err(Error::Other("Invalid block type index"));
+				}
+				byte = Uint8::deserialize(reader)?.into();
+				index |= ((byte & 0x7f) as i64) << shift;
+				shift += 7;
+			}
+			if byte & 0x40 != 0 {
+				index |= -1i64 << shift;
+			}
+			if index < 0 || index > u32::MAX as i64 {
+				return Err(Error::UnknownValueType(((first << 1) as i8) >> 1));
+			}
+			Ok(BlockType::TypeIndex(index as u32))
+		}
+
+		#[cfg(not(feature = "multi_value"))]
+		Err(Error::UnknownValueType(((first << 1) as i8) >> 1))
 	}
-}
\ No newline at end of file
+}
+
+impl Serialize for BlockType {
+	type Error = Error;
+
+	fn serialize<W: io::Write>(&self, writer: &mut W) -> Result<(), Self::Error> {
+		match *self {
+			BlockType::Value(ref value_type) => value_type.serialize(writer),
+			BlockType::NoResult => VarInt7(-0x40).serialize(writer),
+			#[cfg(feature = "multi_value")]
+			BlockType::TypeIndex(idx) => VarInt64::from(idx as i64).serialize(writer),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::elements::{deserialize_buffer, serialize};
+
+	#[test]
+	fn test_block_type_encoding() {
+		assert_eq!(serialize(&BlockType::NoResult).unwrap(), vec![0x40]);
+		assert_eq!(deserialize_buffer::<BlockType>(&[0x7e]).unwrap(), BlockType::Value(ValueType::I64));
+		#[cfg(not(feature = "multi_value"))]
+		assert!(deserialize_buffer::<BlockType>(&[0x00]).is_err());
+	}
+
+	#[cfg(feature = "multi_value")]
+	#[test]
+	fn test_block_type_index() {
+		// s33 编码的类型索引可以超过一个字节
+		assert_eq!(deserialize_buffer::<BlockType>(&[0x00]).unwrap(), BlockType::TypeIndex(0));
+		assert_eq!(deserialize_buffer::<BlockType>(&[0x80, 0x01]).unwrap(), BlockType::TypeIndex(128));
+		assert_eq!(serialize(&BlockType::TypeIndex(128)).unwrap(), vec![0x80, 0x01]);
+		assert_eq!(serialize(&BlockType::TypeIndex(64)).unwrap(), vec![0xc0, 0x00]);
+		// 负的索引无效
+		assert!(deserialize_buffer::<BlockType>(&[0x41]).is_err());
+	}
+	#[cfg(feature = "gc")]
+	#[test]
+	fn test_rec_group_encoding() {
+		let node = SubType {
+			is_final: false,
+			supertypes: Vec::new(),
+			composite: CompositeType::Struct(vec![
+				FieldType { storage: StorageType::Val(ValueType::I32), mutable: true },
+				FieldType { storage: StorageType::Val(RefType::nullable(HeapType::Concrete(0)).into()), mutable: false },
+			]),
+		};
+		let bytes = SubType {
+			is_final: true,
+			supertypes: vec![0],
+			composite: CompositeType::Array(FieldType { storage: StorageType::I8, mutable: true }),
+		};
+		let group = RecGroup(vec![node, bytes]);
+		let encoded = vec![
+			0x4e, 0x02,
+			0x50, 0x00, 0x5f, 0x02, 0x7f, 0x01, 0x63, 0x00, 0x00,
+			0x4f, 0x01, 0x00, 0x5e, 0x78, 0x01,
+		];
+		assert_eq!(serialize(&group).unwrap(), encoded);
+		assert_eq!(deserialize_buffer::<RecGroup>(&encoded).unwrap(), group);
+
+		// 没有超类型的 final 类型不写前缀
+		let array: RecGroup = RecGroup(vec![CompositeType::Array(FieldType { storage: StorageType::I16, mutable: false }).into()]);
+		assert_eq!(serialize(&array).unwrap(), vec![0x5e, 0x77, 0x00]);
+		assert_eq!(deserialize_buffer::<RecGroup>(&[0x5e, 0x77, 0x00]).unwrap(), array);
+		let i31 = ValueType::from(RefType::non_nullable(HeapType::I31));
+		assert_eq!(serialize(&i31).unwrap(), vec![0x64, 0x6c]);
+	}
+}
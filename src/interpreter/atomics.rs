@@ -0,0 +1,176 @@
+//! Atomic instructions of the threads proposal.
+//!
+//! Execution is single threaded, so every atomic access is a plain access
+//! with an alignment check, and no agent can ever be waiting or notified.
+
+use crate::elements::atomics::AtomicInstruction;
+use super::memory::{effective_address, LittleEndian};
+use super::{Value, FromValue, MemoryInstance, MemoryRef, Trap, TrapKind};
+
+/// `memory.atomic.wait` result when the loaded value differs from the expected one.
+const WAIT_NOT_EQUAL: i32 = 1;
+/// `memory.atomic.wait` result when the timeout expired.
+const WAIT_TIMED_OUT: i32 = 2;
+
+fn pop<T: FromValue>(stack: &mut Vec<Value>) -> T {
+    stack.pop().and_then(Value::try_into).expect("operand types are validated")
+}
+
+fn push<T: Into<Value>>(stack: &mut Vec<Value>, value: T) {
+    stack.push(value.into());
+}
+
+/// Effective address of an access of `T`, which must be aligned to its size.
+fn address<T: LittleEndian>(stack: &mut Vec<Value>, memory: &MemoryInstance, offset: u64) -> Result<u64, Trap> {
+    let address = effective_address(stack, memory, offset)?;
+    if !address.is_multiple_of(T::SIZE as u64) {
+        return Err(TrapKind::UnalignedAtomic.into());
+    }
+    Ok(address)
+}
+
+/// Execute a validated atomic instruction on `stack`.
+pub(super) fn execute(instruction: &AtomicInstruction, stack: &mut Vec<Value>, memory: Option<&MemoryRef>) -> Result<(), Trap> {
+    use crate::elements::atomics::AtomicInstruction::*;
+
+    let memory = || memory.expect("memory is validated");
+
+    // 访问宽度为 $t，栈上的值类型为 $s，窄访问按零扩展
+    macro_rules! load {
+        ($t: ty, $s: ty, $offset: expr) => {{
+            let address = address::<$t>(stack, memory(), $offset)?;
+            let v: $t = memory().get_value(address)?;
+            push(stack, v as $s);
+        }};
+    }
+
+    macro_rules! store {
+        ($t: ty, $s: ty, $offset: expr) => {{
+            let v: $s = pop(stack);
+            let address = address::<$t>(stack, memory(), $offset)?;
+            memory().set_value(address, v as $t)?;
+        }};
+    }
+
+    macro_rules! rmw {
+        ($t: ty, $s: ty, $offset: expr, $f: expr) => {{
+            let v: $s = pop(stack);
+            let address = address::<$t>(stack, memory(), $offset)?;
+            let old: $t = memory().get_value(address)?;
+            memory().set_value(address, ($f)(old, v as $t))?;
+            push(stack, old as $s);
+        }};
+    }
+
+    // 期望值先截断到访问宽度再比较
+    macro_rules! cmpxchg {
+        ($t: ty, $s: ty, $offset: expr) => {{
+            let replacement: $s = pop(stack);
+            let expected: $s = pop(stack);
+            let address = address::<$t>(stack, memory(), $offset)?;
+            let old: $t = memory().get_value(address)?;
+            if old == expected as $t {
+                memory().set_value(address, replacement as $t)?;
+            }
+            push(stack, old as $s);
+        }};
+    }
+
+    macro_rules! wait {
+        ($t: ty, $offset: expr) => {{
+            let _timeout: i64 = pop(stack);
+            let expected: $t = pop(stack);
+            let address = address::<$t>(stack, memory(), $offset)?;
+            if !memory().is_shared() {
+                return Err(TrapKind::ExpectedSharedMemory.into());
+            }
+            let v: $t = memory().get_value(address)?;
+            // 没有别的线程能唤醒，值相等时只能超时返回
+            push(stack, if v == expected { WAIT_TIMED_OUT } else { WAIT_NOT_EQUAL });
+        }};
+    }
+
+    match *instruction {
+        AtomicFence => {},
+        MemoryAtomicNotify(_, offset, _) => {
+            let _count: u32 = pop(stack);
+            let address = address::<u32>(stack, memory(), offset)?;
+            memory().get_value::<u32>(address)?;
+            push(stack, 0i32);
+        },
+        MemoryAtomicWait32(_, offset, _) => wait!(u32, offset),
+        MemoryAtomicWait64(_, offset, _) => wait!(u64, offset),
+
+        I32AtomicLoad(_, offset, _) => load!(u32, u32, offset),
+        I64AtomicLoad(_, offset, _) => load!(u64, u64, offset),
+        I32AtomicLoad8U(_, offset, _) => load!(u8, u32, offset),
+        I32AtomicLoad16U(_, offset, _) => load!(u16, u32, offset),
+        I64AtomicLoad8U(_, offset, _) => load!(u8, u64, offset),
+        I64AtomicLoad16U(_, offset, _) => load!(u16, u64, offset),
+        I64AtomicLoad32U(_, offset, _) => load!(u32, u64, offset),
+        I32AtomicStore(_, offset, _) => store!(u32, u32, offset),
+        I64AtomicStore(_, offset, _) => store!(u64, u64, offset),
+        I32AtomicStore8(_, offset, _) => store!(u8, u32, offset),
+        I32AtomicStore16(_, offset, _) => store!(u16, u32, offset),
+        I64AtomicStore8(_, offset, _) => store!(u8, u64, offset),
+        I64AtomicStore16(_, offset, _) => store!(u16, u64, offset),
+        I64AtomicStore32(_, offset, _) => store!(u32, u64, offset),
+
+        I32AtomicRmwAdd(_, offset, _) => rmw!(u32, u32, offset, u32::wrapping_add),
+        I64AtomicRmwAdd(_, offset, _) => rmw!(u64, u64, offset, u64::wrapping_add),
+        I32AtomicRmw8AddU(_, offset, _) => rmw!(u8, u32, offset, u8::wrapping_add),
+        I32AtomicRmw16AddU(_, offset, _) => rmw!(u16, u32, offset, u16::wrapping_add),
+        I64AtomicRmw8AddU(_, offset, _) => rmw!(u8, u64, offset, u8::wrapping_add),
+        I64AtomicRmw16AddU(_, offset, _) => rmw!(u16, u64, offset, u16::wrapping_add),
+        I64AtomicRmw32AddU(_, offset, _) => rmw!(u32, u64, offset, u32::wrapping_add),
+
+        I32AtomicRmwSub(_, offset, _) => rmw!(u32, u32, offset, u32::wrapping_sub),
+        I64AtomicRmwSub(_, offset, _) => rmw!(u64, u64, offset, u64::wrapping_sub),
+        I32AtomicRmw8SubU(_, offset, _) => rmw!(u8, u32, offset, u8::wrapping_sub),
+        I32AtomicRmw16SubU(_, offset, _) => rmw!(u16, u32, offset, u16::wrapping_sub),
+        I64AtomicRmw8SubU(_, offset, _) => rmw!(u8, u64, offset, u8::wrapping_sub),
+        I64AtomicRmw16SubU(_, offset, _) => rmw!(u16, u64, offset, u16::wrapping_sub),
+        I64AtomicRmw32SubU(_, offset, _) => rmw!(u32, u64, offset, u32::wrapping_sub),
+
+        I32AtomicRmwAnd(_, offset, _) => rmw!(u32, u32, offset, |a, b| a & b),
+        I64AtomicRmwAnd(_, offset, _) => rmw!(u64, u64, offset, |a, b| a & b),
+        I32AtomicRmw8AndU(_, offset, _) => rmw!(u8, u32, offset, |a, b| a & b),
+        I32AtomicRmw16AndU(_, offset, _) => rmw!(u16, u32, offset, |a, b| a & b),
+        I64AtomicRmw8AndU(_, offset, _) => rmw!(u8, u64, offset, |a, b| a & b),
+        I64AtomicRmw16AndU(_, offset, _) => rmw!(u16, u64, offset, |a, b| a & b),
+        I64AtomicRmw32AndU(_, offset, _) => rmw!(u32, u64, offset, |a, b| a & b),
+
+        I32AtomicRmwOr(_, offset, _) => rmw!(u32, u32, offset, |a, b| a | b),
+        I64AtomicRmwOr(_, offset, _) => rmw!(u64, u64, offset, |a, b| a | b),
+        I32AtomicRmw8OrU(_, offset, _) => rmw!(u8, u32, offset, |a, b| a | b),
+        I32AtomicRmw16OrU(_, offset, _) => rmw!(u16, u32, offset, |a, b| a | b),
+        I64AtomicRmw8OrU(_, offset, _) => rmw!(u8, u64, offset, |a, b| a | b),
+        I64AtomicRmw16OrU(_, offset, _) => rmw!(u16, u64, offset, |a, b| a | b),
+        I64AtomicRmw32OrU(_, offset, _) => rmw!(u32, u64, offset, |a, b| a | b),
+
+        I32AtomicRmwXor(_, offset, _) => rmw!(u32, u32, offset, |a, b| a ^ b),
+        I64AtomicRmwXor(_, offset, _) => rmw!(u64, u64, offset, |a, b| a ^ b),
+        I32AtomicRmw8XorU(_, offset, _) => rmw!(u8, u32, offset, |a, b| a ^ b),
+        I32AtomicRmw16XorU(_, offset, _) => rmw!(u16, u32, offset, |a, b| a ^ b),
+        I64AtomicRmw8XorU(_, offset, _) => rmw!(u8, u64, offset, |a, b| a ^ b),
+        I64AtomicRmw16XorU(_, offset, _) => rmw!(u16, u64, offset, |a, b| a ^ b),
+        I64AtomicRmw32XorU(_, offset, _) => rmw!(u32, u64, offset, |a, b| a ^ b),
+
+        I32AtomicRmwXchg(_, offset, _) => rmw!(u32, u32, offset, |_, b| b),
+        I64AtomicRmwXchg(_, offset, _) => rmw!(u64, u64, offset, |_, b| b),
+        I32AtomicRmw8XchgU(_, offset, _) => rmw!(u8, u32, offset, |_, b| b),
+        I32AtomicRmw16XchgU(_, offset, _) => rmw!(u16, u32, offset, |_, b| b),
+        I64AtomicRmw8XchgU(_, offset, _) => rmw!(u8, u64, offset, |_, b| b),
+        I64AtomicRmw16XchgU(_, offset, _) => rmw!(u16, u64, offset, |_, b| b),
+        I64AtomicRmw32XchgU(_, offset, _) => rmw!(u32, u64, offset, |_, b| b),
+
+        I32AtomicRmwCmpxchg(_, offset, _) => cmpxchg!(u32, u32, offset),
+        I64AtomicRmwCmpxchg(_, offset, _) => cmpxchg!(u64, u64, offset),
+        I32AtomicRmw8CmpxchgU(_, offset, _) => cmpxchg!(u8, u32, offset),
+        I32AtomicRmw16CmpxchgU(_, offset, _) => cmpxchg!(u16, u32, offset),
+        I64AtomicRmw8CmpxchgU(_, offset, _) => cmpxchg!(u8, u64, offset),
+        I64AtomicRmw16CmpxchgU(_, offset, _) => cmpxchg!(u16, u64, offset),
+        I64AtomicRmw32CmpxchgU(_, offset, _) => cmpxchg!(u32, u64, offset),
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! Tags and exceptions of the exception handling proposal.
+
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::elements::types::FunctionType;
+use super::{Value, Error};
+
+/// Reference to a tag.
+#[derive(Clone, Debug)]
+pub struct TagRef(Rc<TagInstance>);
+
+impl Deref for TagRef {
+    type Target = TagInstance;
+    fn deref(&self) -> &TagInstance {
+        &self.0
+    }
+}
+
+impl PartialEq for TagRef {
+    fn eq(&self, other: &TagRef) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Tag instance, exceptions are caught by the identity of their tag.
+#[derive(Debug)]
+pub struct TagInstance {
+    signature: Rc<FunctionType>,
+}
+
+impl TagInstance {
+    /// Allocate a tag whose exceptions carry the parameters of `signature`.
+    pub fn alloc(signature: FunctionType) -> TagRef {
+        TagInstance::alloc_shared(Rc::new(signature))
+    }
+
+    pub(crate) fn alloc_shared(signature: Rc<FunctionType>) -> TagRef {
+        TagRef(Rc::new(TagInstance { signature }))
+    }
+
+    pub fn signature(&self) -> &FunctionType {
+        &self.signature
+    }
+}
+
+/// Reference to a thrown exception.
+#[derive(Clone, Debug)]
+pub struct ExnRef(Rc<Exception>);
+
+impl Deref for ExnRef {
+    type Target = Exception;
+    fn deref(&self) -> &Exception {
+        &self.0
+    }
+}
+
+impl PartialEq for ExnRef {
+    fn eq(&self, other: &ExnRef) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Exception of a tag with its payload.
+///
+/// Host functions throw one by returning `TrapKind::Exception`.
+#[derive(Debug)]
+pub struct Exception {
+    tag: TagRef,
+    payload: Vec<Value>,
+}
+
+impl Exception {
+    /// Allocate an exception, `payload` must match the parameters of `tag`.
+    pub fn alloc(tag: TagRef, payload: Vec<Value>) -> Result<ExnRef, Error> {
+        let params = &tag.signature().params;
+        if payload.len() != params.len() || payload.iter().zip(params.iter()).any(|(v, &t)| !v.has_type(t, None)) {
+            let types: Vec<_> = payload.iter().map(Value::value_type).collect();
+            return Err(Error::Function(format!(
+                "Exception payload {:?} does not match the tag parameters {:?}", types, tag.signature().params
+            )));
+        }
+        Ok(Exception::alloc_unchecked(tag, payload))
+    }
+
+    /// Allocate an exception whose payload is already known to match.
+    pub(crate) fn alloc_unchecked(tag: TagRef, payload: Vec<Value>) -> ExnRef {
+        ExnRef(Rc::new(Exception { tag, payload }))
+    }
+
+    pub fn tag(&self) -> &TagRef {
+        &self.tag
+    }
+
+    pub fn payload(&self) -> &[Value] {
+        &self.payload
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
+
+use crate::elements::ops::Instruction;
+use crate::elements::types::{FunctionType, ValueType};
+use crate::validation::ModuleContext;
+use super::module::ModuleInstance;
+use super::runner::Interpreter;
+use super::{Value, Trap, TrapKind};
+
+/// Reference to a function.
+#[derive(Clone, Debug)]
+pub struct FuncRef(Rc<FuncInstance>);
+
+impl Deref for FuncRef {
+    type Target = FuncInstance;
+    fn deref(&self) -> &FuncInstance {
+        &self.0
+    }
+}
+
+impl PartialEq for FuncRef {
+    fn eq(&self, other: &FuncRef) -> bool {
+        self.ptr_eq(other)
+    }
+}
+
+impl FuncRef {
+    /// Whether both references point to the same function instance.
+    pub fn ptr_eq(&self, other: &FuncRef) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Function implemented by the host.
+pub type HostFunc = dyn Fn(&[Value]) -> Result<Vec<Value>, Trap>;
+
+/// Function instance.
+pub struct FuncInstance(FuncInstanceInternal);
+
+pub(crate) enum FuncInstanceInternal {
+    Internal {
+        signature: Rc<FunctionType>,
+        module: Weak<ModuleInstance>,
+        body: Rc<FuncBody>,
+        /// Index in the function index space of the module.
+        index: u32,
+    },
+    Host {
+        signature: Rc<FunctionType>,
+        func: Rc<HostFunc>,
+    },
+}
+
+impl fmt::Debug for FuncInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            FuncInstanceInternal::Internal { ref signature, index, .. } => {
+                write!(f, "Internal {{ index: {}, signature: {:?} }}", index, signature)
+            },
+            FuncInstanceInternal::Host { ref signature, .. } => {
+                write!(f, "Host {{ signature: {:?} }}", signature)
+            },
+        }
+    }
+}
+
+impl FuncInstance {
+    pub(crate) fn alloc_internal(
+        module: Weak<ModuleInstance>,
+        signature: Rc<FunctionType>,
+        body: FuncBody,
+        index: u32,
+    ) -> FuncRef {
+        FuncRef(Rc::new(FuncInstance(FuncInstanceInternal::Internal {
+            signature,
+            module,
+            body: Rc::new(body),
+            index,
+        })))
+    }
+
+    /// Allocate a host function.
+    ///
+    /// `func` receives arguments matching `signature` and must return values matching its results.
+    pub fn alloc_host<F>(signature: FunctionType, func: F) -> FuncRef
+    where
+        F: Fn(&[Value]) -> Result<Vec<Value>, Trap> + 'static,
+    {
+        FuncRef(Rc::new(FuncInstance(FuncInstanceInternal::Host {
+            signature: Rc::new(signature),
+            func: Rc::new(func),
+        })))
+    }
+
+    pub fn signature(&self) -> &FunctionType {
+        match self.0 {
+            FuncInstanceInternal::Internal { ref signature, .. } => signature,
+            FuncInstanceInternal::Host { ref signature, .. } => signature,
+        }
+    }
+
+    pub(crate) fn as_internal(&self) -> &FuncInstanceInternal {
+        &self.0
+    }
+
+    /// Types of the module defining the function, `None` for host functions.
+    pub(crate) fn types(&self) -> Option<Rc<ModuleContext>> {
+        match self.0 {
+            FuncInstanceInternal::Internal { ref module, .. } => module.upgrade().map(|m| m.types()),
+            FuncInstanceInternal::Host { .. } => None,
+        }
+    }
+
+    /// Invoke the function with `args`, which must match its signature.
+    pub fn invoke(func: &FuncRef, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        check_args(func.signature(), args, func.types().as_deref())?;
+        let mut interpreter = Interpreter::new();
+        interpreter.invoke(func, args)
+    }
+}
+
+fn check_args(signature: &FunctionType, args: &[Value], types: Option<&ModuleContext>) -> Result<(), Trap> {
+    if signature.params.len() != args.len() {
+        return Err(TrapKind::UnexpectedSignature.into());
+    }
+    if signature.params.iter().zip(args.iter()).any(|(&t, v)| !v.has_type(t, types)) {
+        return Err(TrapKind::UnexpectedSignature.into());
+    }
+    Ok(())
+}
+
+/// Call a host function, checking the values it returns.
+///
+/// Defined types in the signature are resolved in `types`, the module of the caller.
+pub(crate) fn invoke_host(signature: &FunctionType, func: &HostFunc, args: &[Value], types: Option<&ModuleContext>) -> Result<Vec<Value>, Trap> {
+    let results = func(args)?;
+    if results.len() != signature.results.len()
+        || signature.results.iter().zip(results.iter()).any(|(&t, v)| !v.has_type(t, types))
+    {
+        return Err(TrapKind::Host(format!(
+            "host function returned {:?}, expected {:?}", results, signature.results
+        )).into());
+    }
+    Ok(results)
+}
+
+/// Whether two signatures have the same parameters and results.
+pub(crate) fn signature_matches(a: &FunctionType, b: &FunctionType) -> bool {
+    a.params == b.params && a.results == b.results
+}
+
+/// Function code prepared for execution.
+#[derive(Debug)]
+pub(crate) struct FuncBody {
+    /// Types of the declared locals, parameters excluded.
+    pub locals: Vec<ValueType>,
+    pub code: Vec<Instruction>,
+    /// Position of the matching `end` of every block instruction and `else`.
+    pub ends: Vec<u32>,
+    /// Position of the matching `else` of every `if`, `u32::MAX` if there is none.
+    pub elses: Vec<u32>,
+}
+
+impl FuncBody {
+    pub fn new(locals: Vec<ValueType>, code: Vec<Instruction>) -> FuncBody {
+        let mut ends = vec![u32::MAX; code.len()];
+        let mut elses = vec![u32::MAX; code.len()];
+        let mut blocks: Vec<usize> = Vec::new();
+
+        for (pc, instruction) in code.iter().enumerate() {
+            if instruction.is_block() {
+                blocks.push(pc);
+            } else if *instruction == Instruction::Else {
+                if let Some(&start) = blocks.last() {
+                    elses[start] = pc as u32;
+                }
+            } else if instruction.is_terminal() {
+                // 函数体最后的 end 没有对应的 block
+                if let Some(start) = blocks.pop() {
+                    ends[start] = pc as u32;
+                    if elses[start] != u32::MAX {
+                        ends[elses[start] as usize] = pc as u32;
+                    }
+                }
+            }
+        }
+
+        FuncBody { locals, code, ends, elses }
+    }
+}
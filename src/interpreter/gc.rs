@@ -0,0 +1,371 @@
+//! Structs, arrays and `i31` references of the GC proposal.
+//!
+//! Objects keep the types of the module which allocated them. Defined types
+//! are only compared within that module, objects passed to another module
+//! match its abstract types like `structref` but none of its defined types.
+
+use std::cell::RefCell;
+use std::convert::TryInto;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::elements::gc::GcInstruction;
+use crate::elements::types::{CompositeType, FieldType, HeapType, RefType, StorageType, ValueType};
+use crate::validation::ModuleContext;
+use super::module::ModuleInstance;
+use super::{Value, Trap, TrapKind};
+
+/// Reference of the `any` hierarchy.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnyRef {
+    /// Unboxed 31-bit integer, stored zero extended.
+    I31(u32),
+    /// Struct or array.
+    Object(ObjectRef),
+    /// Host reference converted by `any.convert_extern`.
+    Host(u32),
+}
+
+impl AnyRef {
+    /// Heap type of the reference seen from the module of `types`.
+    pub(crate) fn heap_type(&self, types: Option<&ModuleContext>) -> HeapType {
+        match *self {
+            AnyRef::I31(_) => HeapType::I31,
+            AnyRef::Object(ref object) => object.heap_type(types),
+            AnyRef::Host(_) => HeapType::Any,
+        }
+    }
+}
+
+/// Reference to a struct or array.
+#[derive(Clone)]
+pub struct ObjectRef(Rc<Object>);
+
+/// Struct or array instance.
+struct Object {
+    /// Types of the allocating module.
+    types: Rc<ModuleContext>,
+    type_idx: u32,
+    /// Fields of a struct or elements of an array, packed values are stored zero extended.
+    fields: RefCell<Vec<Value>>,
+}
+
+impl PartialEq for ObjectRef {
+    fn eq(&self, other: &ObjectRef) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for ObjectRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Object")
+            .field("type_idx", &self.0.type_idx)
+            .field("fields", &self.0.fields.borrow())
+            .finish()
+    }
+}
+
+impl ObjectRef {
+    fn alloc(types: Rc<ModuleContext>, type_idx: u32, fields: Vec<Value>) -> Value {
+        let object = ObjectRef(Rc::new(Object { types, type_idx, fields: RefCell::new(fields) }));
+        Value::AnyRef(Some(AnyRef::Object(object)))
+    }
+
+    /// Type index in the allocating module.
+    pub fn type_index(&self) -> u32 {
+        self.0.type_idx
+    }
+
+    /// Fields of a struct or elements of an array.
+    pub fn fields(&self) -> Vec<Value> {
+        self.0.fields.borrow().clone()
+    }
+
+    fn heap_type(&self, types: Option<&ModuleContext>) -> HeapType {
+        match types {
+            Some(types) if std::ptr::eq(types, &*self.0.types) => HeapType::Concrete(self.0.type_idx),
+            _ => match self.0.types.types[self.0.type_idx as usize].composite {
+                CompositeType::Array(_) => HeapType::Array,
+                _ => HeapType::Struct,
+            },
+        }
+    }
+
+    fn len(&self) -> u32 {
+        self.0.fields.borrow().len() as u32
+    }
+
+    fn get(&self, idx: u32) -> Result<Value, Trap> {
+        self.0.fields.borrow().get(idx as usize).cloned().ok_or_else(|| TrapKind::ArrayAccessOutOfBounds.into())
+    }
+
+    fn set(&self, idx: u32, value: Value) -> Result<(), Trap> {
+        let mut fields = self.0.fields.borrow_mut();
+        let slot = fields.get_mut(idx as usize).ok_or(TrapKind::ArrayAccessOutOfBounds)?;
+        *slot = value;
+        Ok(())
+    }
+
+    /// Write `values` starting at `offset`, nothing is written if they don't fit.
+    fn set_range(&self, offset: u32, values: Vec<Value>) -> Result<(), Trap> {
+        let mut fields = self.0.fields.borrow_mut();
+        check_range(offset, values.len() as u64, fields.len())?;
+        fields.splice(offset as usize..offset as usize + values.len(), values);
+        Ok(())
+    }
+}
+
+fn check_range(offset: u32, len: u64, size: usize) -> Result<(), Trap> {
+    if offset as u64 + len > size as u64 {
+        return Err(TrapKind::ArrayAccessOutOfBounds.into());
+    }
+    Ok(())
+}
+
+/// Null reference of the hierarchy of `heap`.
+pub(crate) fn null_of(types: &ModuleContext, heap: HeapType) -> Value {
+    Value::default(RefType::nullable(types.top_heap_type(heap)).into())
+}
+
+/// Type of the values a slot of `value_type` holds at runtime, defined types are replaced by their hierarchy.
+pub(crate) fn runtime_type(types: &ModuleContext, value_type: ValueType) -> ValueType {
+    match value_type {
+        ValueType::Ref(ref_type) => RefType::nullable(types.top_heap_type(ref_type.heap)).into(),
+        other => other,
+    }
+}
+
+fn default_value(types: &ModuleContext, storage: StorageType) -> Value {
+    match storage {
+        StorageType::Val(ValueType::Ref(ref_type)) => null_of(types, ref_type.heap),
+        StorageType::Val(t) => Value::default(t),
+        _ => Value::I32(0),
+    }
+}
+
+/// Truncate values stored into packed fields.
+fn pack(storage: StorageType, value: Value) -> Value {
+    match (storage, value) {
+        (StorageType::I8, Value::I32(v)) => Value::I32(v as u8 as i32),
+        (StorageType::I16, Value::I32(v)) => Value::I32(v as u16 as i32),
+        (_, value) => value,
+    }
+}
+
+/// Sign extend packed values read by `get_s`, they are stored zero extended.
+fn unpack(storage: StorageType, value: Value, signed: bool) -> Value {
+    match (storage, value) {
+        (StorageType::I8, Value::I32(v)) if signed => Value::I32(v as i8 as i32),
+        (StorageType::I16, Value::I32(v)) if signed => Value::I32(v as i16 as i32),
+        (_, value) => value,
+    }
+}
+
+/// Size of an array element in a data segment.
+fn storage_size(storage: StorageType) -> u64 {
+    match storage {
+        StorageType::I8 => 1,
+        StorageType::I16 => 2,
+        StorageType::Val(ValueType::I32) | StorageType::Val(ValueType::F32) => 4,
+        StorageType::Val(ValueType::I64) | StorageType::Val(ValueType::F64) => 8,
+        #[cfg(feature = "simd")]
+        StorageType::Val(ValueType::V128) => 16,
+        StorageType::Val(t) => unreachable!("arrays of {:?} are not read from data segments", t),
+    }
+}
+
+/// Decode little endian array elements read from a data segment.
+fn read_elements(storage: StorageType, bytes: &[u8]) -> Vec<Value> {
+    let size = storage_size(storage) as usize;
+    bytes.chunks_exact(size).map(|b| match storage {
+        StorageType::I8 => Value::I32(b[0] as i32),
+        StorageType::I16 => Value::I32(u16::from_le_bytes([b[0], b[1]]) as i32),
+        StorageType::Val(ValueType::I32) => Value::I32(i32::from_le_bytes(b.try_into().unwrap())),
+        StorageType::Val(ValueType::F32) => Value::F32(f32::from_le_bytes(b.try_into().unwrap())),
+        StorageType::Val(ValueType::I64) => Value::I64(i64::from_le_bytes(b.try_into().unwrap())),
+        StorageType::Val(ValueType::F64) => Value::F64(f64::from_le_bytes(b.try_into().unwrap())),
+        #[cfg(feature = "simd")]
+        StorageType::Val(ValueType::V128) => Value::V128(u128::from_le_bytes(b.try_into().unwrap())),
+        StorageType::Val(t) => unreachable!("arrays of {:?} are not read from data segments", t),
+    }).collect()
+}
+
+fn struct_fields(types: &ModuleContext, type_idx: u32) -> &[FieldType] {
+    types.struct_type(type_idx).expect("struct type index is validated")
+}
+
+fn array_elem(types: &ModuleContext, type_idx: u32) -> StorageType {
+    types.array_type(type_idx).expect("array type index is validated").storage
+}
+
+fn pop(stack: &mut Vec<Value>) -> Value {
+    stack.pop().expect("operand stack is validated")
+}
+
+fn pop_u32(stack: &mut Vec<Value>) -> u32 {
+    pop(stack).try_into().expect("operand types are validated")
+}
+
+fn pop_object(stack: &mut Vec<Value>) -> Result<ObjectRef, Trap> {
+    match pop(stack) {
+        Value::AnyRef(Some(AnyRef::Object(object))) => Ok(object),
+        Value::AnyRef(None) => Err(TrapKind::NullReference.into()),
+        other => unreachable!("validated operand {:?} is not a struct or array", other),
+    }
+}
+
+/// Execute `instruction` of a function of `module`, branches of `br_on_cast` are left to the caller.
+pub(crate) fn execute(instruction: &GcInstruction, stack: &mut Vec<Value>, module: &ModuleInstance) -> Result<(), Trap> {
+    use crate::elements::gc::GcInstruction::*;
+
+    let types = module.types();
+    match *instruction {
+        StructNew(type_idx) => {
+            let fields = struct_fields(&types, type_idx);
+            let values = stack.split_off(stack.len() - fields.len());
+            let values = fields.iter().zip(values).map(|(f, v)| pack(f.storage, v)).collect();
+            stack.push(ObjectRef::alloc(types.clone(), type_idx, values));
+        },
+        StructNewDefault(type_idx) => {
+            let values = struct_fields(&types, type_idx).iter().map(|f| default_value(&types, f.storage)).collect();
+            stack.push(ObjectRef::alloc(types.clone(), type_idx, values));
+        },
+        StructGet(type_idx, field) | StructGetS(type_idx, field) | StructGetU(type_idx, field) => {
+            let storage = struct_fields(&types, type_idx)[field as usize].storage;
+            let object = pop_object(stack)?;
+            let value = object.get(field).expect("field index is validated");
+            stack.push(unpack(storage, value, matches!(*instruction, StructGetS(..))));
+        },
+        StructSet(type_idx, field) => {
+            let storage = struct_fields(&types, type_idx)[field as usize].storage;
+            let value = pop(stack);
+            let object = pop_object(stack)?;
+            object.set(field, pack(storage, value)).expect("field index is validated");
+        },
+
+        ArrayNew(type_idx) => {
+            let len = pop_u32(stack);
+            let value = pack(array_elem(&types, type_idx), pop(stack));
+            stack.push(ObjectRef::alloc(types.clone(), type_idx, vec![value; len as usize]));
+        },
+        ArrayNewDefault(type_idx) => {
+            let len = pop_u32(stack);
+            let value = default_value(&types, array_elem(&types, type_idx));
+            stack.push(ObjectRef::alloc(types.clone(), type_idx, vec![value; len as usize]));
+        },
+        ArrayNewFixed(type_idx, len) => {
+            let storage = array_elem(&types, type_idx);
+            let values = stack.split_off(stack.len() - len as usize).into_iter().map(|v| pack(storage, v)).collect();
+            stack.push(ObjectRef::alloc(types.clone(), type_idx, values));
+        },
+        ArrayNewData(type_idx, segment) => {
+            let len = pop_u32(stack);
+            let offset = pop_u32(stack);
+            let storage = array_elem(&types, type_idx);
+            let bytes = module.data_range(segment, offset, len as u64 * storage_size(storage))?;
+            stack.push(ObjectRef::alloc(types.clone(), type_idx, read_elements(storage, &bytes)));
+        },
+        ArrayNewElem(type_idx, segment) => {
+            let len = pop_u32(stack);
+            let offset = pop_u32(stack);
+            let values = module.elem_range(segment, offset, len)?;
+            stack.push(ObjectRef::alloc(types.clone(), type_idx, values));
+        },
+        ArrayGet(type_idx) | ArrayGetS(type_idx) | ArrayGetU(type_idx) => {
+            let idx = pop_u32(stack);
+            let object = pop_object(stack)?;
+            let value = object.get(idx)?;
+            stack.push(unpack(array_elem(&types, type_idx), value, matches!(*instruction, ArrayGetS(_))));
+        },
+        ArraySet(type_idx) => {
+            let value = pack(array_elem(&types, type_idx), pop(stack));
+            let idx = pop_u32(stack);
+            pop_object(stack)?.set(idx, value)?;
+        },
+        ArrayLen => {
+            let object = pop_object(stack)?;
+            stack.push(Value::I32(object.len() as i32));
+        },
+        ArrayFill(type_idx) => {
+            let len = pop_u32(stack);
+            let value = pack(array_elem(&types, type_idx), pop(stack));
+            let offset = pop_u32(stack);
+            let object = pop_object(stack)?;
+            object.set_range(offset, vec![value; len as usize])?;
+        },
+        ArrayCopy(..) => {
+            let len = pop_u32(stack);
+            let src_offset = pop_u32(stack);
+            let src = pop_object(stack)?;
+            let dst_offset = pop_u32(stack);
+            let dst = pop_object(stack)?;
+            // 先整体读出，同一数组内重叠的区域也能正确复制
+            check_range(src_offset, len as u64, src.len() as usize)?;
+            let values = src.0.fields.borrow()[src_offset as usize..(src_offset + len) as usize].to_vec();
+            dst.set_range(dst_offset, values)?;
+        },
+        ArrayInitData(type_idx, segment) => {
+            let len = pop_u32(stack);
+            let src = pop_u32(stack);
+            let dst = pop_u32(stack);
+            let object = pop_object(stack)?;
+            check_range(dst, len as u64, object.len() as usize)?;
+            let storage = array_elem(&types, type_idx);
+            let bytes = module.data_range(segment, src, len as u64 * storage_size(storage))?;
+            object.set_range(dst, read_elements(storage, &bytes))?;
+        },
+        ArrayInitElem(_, segment) => {
+            let len = pop_u32(stack);
+            let src = pop_u32(stack);
+            let dst = pop_u32(stack);
+            let object = pop_object(stack)?;
+            check_range(dst, len as u64, object.len() as usize)?;
+            object.set_range(dst, module.elem_range(segment, src, len)?)?;
+        },
+
+        RefTest(target) => {
+            let value = pop(stack);
+            stack.push(Value::I32(value.has_type(target.into(), Some(&types)) as i32));
+        },
+        RefCast(target) => {
+            if !stack.last().expect("operand stack is validated").has_type(target.into(), Some(&types)) {
+                return Err(TrapKind::CastFailure.into());
+            }
+        },
+        BrOnCast(_) | BrOnCastFail(_) => unreachable!("br_on_cast is executed by the interpreter"),
+
+        AnyConvertExtern => {
+            let value = match pop(stack) {
+                Value::ExternRef(None) => Value::AnyRef(None),
+                Value::ExternRef(Some(host)) => Value::AnyRef(Some(AnyRef::Host(host))),
+                Value::Externalized(any) => Value::AnyRef(Some(any)),
+                other => unreachable!("validated operand {:?} is not an externref", other),
+            };
+            stack.push(value);
+        },
+        ExternConvertAny => {
+            let value = match pop(stack) {
+                Value::AnyRef(None) => Value::ExternRef(None),
+                Value::AnyRef(Some(AnyRef::Host(host))) => Value::ExternRef(Some(host)),
+                Value::AnyRef(Some(any)) => Value::Externalized(any),
+                other => unreachable!("validated operand {:?} is not an anyref", other),
+            };
+            stack.push(value);
+        },
+        RefI31 => {
+            let v = pop_u32(stack);
+            stack.push(Value::AnyRef(Some(AnyRef::I31(v & 0x7fff_ffff))));
+        },
+        I31GetS | I31GetU => {
+            let v = match pop(stack) {
+                Value::AnyRef(Some(AnyRef::I31(v))) => v,
+                Value::AnyRef(None) => return Err(TrapKind::NullReference.into()),
+                other => unreachable!("validated operand {:?} is not an i31ref", other),
+            };
+            // 第 30 位是符号位
+            let v = if *instruction == I31GetS { ((v << 1) as i32) >> 1 } else { v as i32 };
+            stack.push(Value::I32(v));
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::cell::RefCell;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::elements::types::ValueType;
+use super::{Value, Error};
+
+/// Reference to a global variable.
+#[derive(Clone, Debug)]
+pub struct GlobalRef(Rc<GlobalInstance>);
+
+impl Deref for GlobalRef {
+    type Target = GlobalInstance;
+    fn deref(&self) -> &GlobalInstance {
+        &self.0
+    }
+}
+
+/// Global variable instance.
+#[derive(Debug)]
+pub struct GlobalInstance {
+    value: RefCell<Value>,
+    mutable: bool,
+}
+
+impl GlobalInstance {
+    pub fn alloc(value: Value, mutable: bool) -> GlobalRef {
+        GlobalRef(Rc::new(GlobalInstance {
+            value: RefCell::new(value),
+            mutable,
+        }))
+    }
+
+    pub fn get(&self) -> Value {
+        self.value.borrow().clone()
+    }
+
+    /// Change the value, fails if the global is immutable or the type differs.
+    pub fn set(&self, value: Value) -> Result<(), Error> {
+        if !self.mutable {
+            return Err(Error::Function("Attempt to change an immutable global".to_string()));
+        }
+        if value.value_type() != self.value_type() {
+            return Err(Error::Function("Attempt to change the type of a global".to_string()));
+        }
+        *self.value.borrow_mut() = value;
+        Ok(())
+    }
+
+    pub fn is_mutable(&self) -> bool {
+        self.mutable
+    }
+
+    pub fn value_type(&self) -> ValueType {
+        self.value.borrow().value_type()
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use crate::elements::types::FunctionType;
+use crate::elements::import_entry::{GlobalType, ResizableLimits, TableType};
+use super::{Error, ExternKind, ExternVal, FuncRef, GlobalRef, LinkError, MemoryRef, ModuleRef, TableRef};
+#[cfg(feature = "exceptions")]
+use super::TagRef;
+
+/// Resolves the imports of a module during instantiation.
+///
+/// The resolved entities are checked against the declared import types by the instantiation,
+/// which fails with a `LinkError` if they don't match.
+pub trait ImportResolver {
+    fn resolve_func(&self, module_name: &str, field_name: &str, signature: &FunctionType) -> Result<FuncRef, Error>;
+
+    fn resolve_global(&self, module_name: &str, field_name: &str, global_type: &GlobalType) -> Result<GlobalRef, Error>;
+
+    fn resolve_memory(&self, module_name: &str, field_name: &str, limits: &ResizableLimits) -> Result<MemoryRef, Error>;
+
+    fn resolve_table(&self, module_name: &str, field_name: &str, table_type: &TableType) -> Result<TableRef, Error>;
+
+    /// Tags can't be imported unless the resolver overrides this.
+    #[cfg(feature = "exceptions")]
+    fn resolve_tag(&self, module_name: &str, field_name: &str, _signature: &FunctionType) -> Result<TagRef, Error> {
+        Err(LinkError::UnknownImport {
+            module: module_name.to_string(),
+            field: field_name.to_string(),
+            kind: ExternKind::Tag,
+        }.into())
+    }
+}
+
+/// Resolves imports from the exports of other module instances.
+#[derive(Default, Clone)]
+pub struct ImportsBuilder {
+    modules: HashMap<String, ModuleRef>,
+}
+
+impl ImportsBuilder {
+    pub fn new() -> ImportsBuilder {
+        ImportsBuilder::default()
+    }
+
+    /// Make the exports of `module` available under `name`.
+    pub fn with_module<N: Into<String>>(mut self, name: N, module: ModuleRef) -> ImportsBuilder {
+        self.push_module(name, module);
+        self
+    }
+
+    pub fn push_module<N: Into<String>>(&mut self, name: N, module: ModuleRef) {
+        self.modules.insert(name.into(), module);
+    }
+
+    fn lookup<'a>(&self, module_name: &'a str, field_name: &'a str) -> ImportLookup<'a> {
+        let value = self.modules.get(module_name).and_then(|module| module.export_by_name(field_name));
+        ImportLookup::new(module_name, field_name, value)
+    }
+}
+
+impl ImportResolver for ImportsBuilder {
+    fn resolve_func(&self, module_name: &str, field_name: &str, _signature: &FunctionType) -> Result<FuncRef, Error> {
+        self.lookup(module_name, field_name).func()
+    }
+
+    fn resolve_global(&self, module_name: &str, field_name: &str, _global_type: &GlobalType) -> Result<GlobalRef, Error> {
+        self.lookup(module_name, field_name).global()
+    }
+
+    fn resolve_memory(&self, module_name: &str, field_name: &str, _limits: &ResizableLimits) -> Result<MemoryRef, Error> {
+        self.lookup(module_name, field_name).memory()
+    }
+
+    fn resolve_table(&self, module_name: &str, field_name: &str, _table_type: &TableType) -> Result<TableRef, Error> {
+        self.lookup(module_name, field_name).table()
+    }
+
+    #[cfg(feature = "exceptions")]
+    fn resolve_tag(&self, module_name: &str, field_name: &str, _signature: &FunctionType) -> Result<TagRef, Error> {
+        self.lookup(module_name, field_name).tag()
+    }
+}
+
+/// Entity found under an import name, checked against the kind the import asks for.
+pub(crate) struct ImportLookup<'a> {
+    module: &'a str,
+    field: &'a str,
+    value: Option<ExternVal>,
+}
+
+impl<'a> ImportLookup<'a> {
+    pub(crate) fn new(module: &'a str, field: &'a str, value: Option<ExternVal>) -> ImportLookup<'a> {
+        ImportLookup { module, field, value }
+    }
+
+    fn expect(self, kind: ExternKind) -> Result<ExternVal, Error> {
+        let (module, field) = (self.module, self.field);
+        let value = self.value.ok_or_else(|| LinkError::UnknownImport {
+            module: module.to_string(),
+            field: field.to_string(),
+            kind,
+        })?;
+        if value.kind() != kind {
+            return Err(LinkError::KindMismatch {
+                module: module.to_string(),
+                field: field.to_string(),
+                expected: kind,
+                actual: value.kind(),
+            }.into());
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn func(self) -> Result<FuncRef, Error> {
+        match self.expect(ExternKind::Func)? {
+            ExternVal::Func(func) => Ok(func),
+            _ => unreachable!("kind is checked by expect"),
+        }
+    }
+
+    pub(crate) fn global(self) -> Result<GlobalRef, Error> {
+        match self.expect(ExternKind::Global)? {
+            ExternVal::Global(global) => Ok(global),
+            _ => unreachable!("kind is checked by expect"),
+        }
+    }
+
+    pub(crate) fn memory(self) -> Result<MemoryRef, Error> {
+        match self.expect(ExternKind::Memory)? {
+            ExternVal::Memory(memory) => Ok(memory),
+            _ => unreachable!("kind is checked by expect"),
+        }
+    }
+
+    pub(crate) fn table(self) -> Result<TableRef, Error> {
+        match self.expect(ExternKind::Table)? {
+            ExternVal::Table(table) => Ok(table),
+            _ => unreachable!("kind is checked by expect"),
+        }
+    }
+
+    #[cfg(feature = "exceptions")]
+    pub(crate) fn tag(self) -> Result<TagRef, Error> {
+        match self.expect(ExternKind::Tag)? {
+            ExternVal::Tag(tag) => Ok(tag),
+            _ => unreachable!("kind is checked by expect"),
+        }
+    }
+}
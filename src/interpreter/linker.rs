@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::elements::module::Module;
+use crate::elements::types::FunctionType;
+use crate::elements::import_entry::{GlobalType, ResizableLimits, TableType};
+use super::imports::ImportLookup;
+use super::{
+    Error, ExternKind, ExternVal, FuncInstance, FuncRef, GlobalRef, ImportResolver, MemoryRef, ModuleInstance, ModuleRef,
+    TableRef, Trap, Value,
+};
+#[cfg(feature = "exceptions")]
+use super::TagRef;
+
+/// Reason an import can't be linked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkError {
+    /// Nothing is registered under the import name.
+    UnknownImport { module: String, field: String, kind: ExternKind },
+    /// An entity of another kind is registered under the import name.
+    KindMismatch { module: String, field: String, expected: ExternKind, actual: ExternKind },
+    /// Function signatures differ.
+    SignatureMismatch { module: String, field: String, expected: FunctionType, actual: FunctionType },
+    /// Global value type or mutability differs.
+    GlobalTypeMismatch { module: String, field: String, expected: GlobalType, actual: GlobalType },
+    /// Memory limits don't match the declared ones.
+    MemoryLimitsMismatch { module: String, field: String, expected: ResizableLimits, actual: ResizableLimits },
+    /// Table limits don't match the declared ones.
+    TableTypeMismatch { module: String, field: String, expected: TableType, actual: TableType },
+    /// Tag parameters differ.
+    #[cfg(feature = "exceptions")]
+    TagTypeMismatch { module: String, field: String, expected: FunctionType, actual: FunctionType },
+    /// The name is already defined in the linker.
+    Duplicate { module: String, field: String },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LinkError::UnknownImport { ref module, ref field, kind } => {
+                write!(f, "unknown import {}.{} of kind {}", module, field, kind)
+            },
+            LinkError::KindMismatch { ref module, ref field, expected, actual } => {
+                write!(f, "import {}.{} expects a {}, found a {}", module, field, expected, actual)
+            },
+            LinkError::SignatureMismatch { ref module, ref field, ref expected, ref actual } => write!(
+                f, "function {}.{} has signature {:?} -> {:?}, expected {:?} -> {:?}",
+                module, field, actual.params, actual.results, expected.params, expected.results
+            ),
+            LinkError::GlobalTypeMismatch { ref module, ref field, ref expected, ref actual } => write!(
+                f, "global {}.{} has type {:?} (mutable: {}), expected {:?} (mutable: {})",
+                module, field, actual.content_type, actual.is_mutable, expected.content_type, expected.is_mutable
+            ),
+            LinkError::MemoryLimitsMismatch { ref module, ref field, ref expected, ref actual } => write!(
+                f, "memory {}.{} has limits {:?}, incompatible with {:?}", module, field, actual, expected
+            ),
+            LinkError::TableTypeMismatch { ref module, ref field, ref expected, ref actual } => write!(
+                f, "table {}.{} has type {:?}, incompatible with {:?}", module, field, actual, expected
+            ),
+            #[cfg(feature = "exceptions")]
+            LinkError::TagTypeMismatch { ref module, ref field, ref expected, ref actual } => write!(
+                f, "tag {}.{} has parameters {:?}, expected {:?}", module, field, actual.params, expected.params
+            ),
+            LinkError::Duplicate { ref module, ref field } => write!(f, "{}.{} is already defined", module, field),
+        }
+    }
+}
+
+/// Whether `actual` limits are a subtype of `expected`, as required for imports.
+pub(crate) fn limits_match(expected: &ResizableLimits, actual: &ResizableLimits) -> bool {
+    if actual.initial < expected.initial || actual.shared != expected.shared || actual.memory64 != expected.memory64 {
+        return false;
+    }
+    match (expected.maximum, actual.maximum) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(expected_max), Some(actual_max)) => actual_max <= expected_max,
+    }
+}
+
+/// Collection of named host entities and module exports used to resolve imports.
+///
+/// ```ignore
+/// let mut linker = Linker::new();
+/// linker.func("env", "print", FunctionType { form: 0x60, params: vec![ValueType::I32], results: vec![] }, |args| {
+///     println!("{:?}", args);
+///     Ok(vec![])
+/// })?;
+/// let instance = linker.instantiate(&module)?;
+/// ```
+#[derive(Default, Clone)]
+pub struct Linker {
+    definitions: HashMap<(String, String), ExternVal>,
+}
+
+impl Linker {
+    pub fn new() -> Linker {
+        Linker::default()
+    }
+
+    /// Define `value` under `module`.`field`.
+    pub fn define(&mut self, module: &str, field: &str, value: ExternVal) -> Result<&mut Linker, Error> {
+        let key = (module.to_string(), field.to_string());
+        if self.definitions.contains_key(&key) {
+            return Err(LinkError::Duplicate { module: key.0, field: key.1 }.into());
+        }
+        self.definitions.insert(key, value);
+        Ok(self)
+    }
+
+    /// Define a host function with `signature` implemented by `func`.
+    pub fn func<F>(&mut self, module: &str, field: &str, signature: FunctionType, func: F) -> Result<&mut Linker, Error>
+    where
+        F: Fn(&[Value]) -> Result<Vec<Value>, Trap> + 'static,
+    {
+        self.define(module, field, ExternVal::Func(FuncInstance::alloc_host(signature, func)))
+    }
+
+    pub fn memory(&mut self, module: &str, field: &str, memory: MemoryRef) -> Result<&mut Linker, Error> {
+        self.define(module, field, ExternVal::Memory(memory))
+    }
+
+    pub fn table(&mut self, module: &str, field: &str, table: TableRef) -> Result<&mut Linker, Error> {
+        self.define(module, field, ExternVal::Table(table))
+    }
+
+    pub fn global(&mut self, module: &str, field: &str, global: GlobalRef) -> Result<&mut Linker, Error> {
+        self.define(module, field, ExternVal::Global(global))
+    }
+
+    #[cfg(feature = "exceptions")]
+    pub fn tag(&mut self, module: &str, field: &str, tag: TagRef) -> Result<&mut Linker, Error> {
+        self.define(module, field, ExternVal::Tag(tag))
+    }
+
+    /// Define every export of `instance` under `module`.
+    pub fn instance(&mut self, module: &str, instance: &ModuleRef) -> Result<&mut Linker, Error> {
+        for (field, value) in instance.exports() {
+            self.define(module, &field, value)?;
+        }
+        Ok(self)
+    }
+
+    pub fn get(&self, module: &str, field: &str) -> Option<&ExternVal> {
+        self.definitions.get(&(module.to_string(), field.to_string()))
+    }
+
+    /// Validate and instantiate `module` with imports resolved from this linker.
+    pub fn instantiate(&self, module: &Module) -> Result<ModuleRef, Error> {
+        ModuleInstance::new(module, self)
+    }
+
+    fn lookup<'a>(&self, module: &'a str, field: &'a str) -> ImportLookup<'a> {
+        ImportLookup::new(module, field, self.get(module, field).cloned())
+    }
+}
+
+impl ImportResolver for Linker {
+    fn resolve_func(&self, module_name: &str, field_name: &str, _signature: &FunctionType) -> Result<FuncRef, Error> {
+        self.lookup(module_name, field_name).func()
+    }
+
+    fn resolve_global(&self, module_name: &str, field_name: &str, _global_type: &GlobalType) -> Result<GlobalRef, Error> {
+        self.lookup(module_name, field_name).global()
+    }
+
+    fn resolve_memory(&self, module_name: &str, field_name: &str, _limits: &ResizableLimits) -> Result<MemoryRef, Error> {
+        self.lookup(module_name, field_name).memory()
+    }
+
+    fn resolve_table(&self, module_name: &str, field_name: &str, _table_type: &TableType) -> Result<TableRef, Error> {
+        self.lookup(module_name, field_name).table()
+    }
+
+    #[cfg(feature = "exceptions")]
+    fn resolve_tag(&self, module_name: &str, field_name: &str, _signature: &FunctionType) -> Result<TagRef, Error> {
+        self.lookup(module_name, field_name).tag()
+    }
+}
@@ -0,0 +1,242 @@
+use std::cell::RefCell;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::elements::import_entry::ResizableLimits;
+use crate::validation::{LINEAR_MEMORY_MAX_PAGES, LINEAR_MEMORY64_MAX_PAGES};
+use super::{Error, Trap, TrapKind, Value};
+
+/// Size of a page of linear memory, 64 KiB.
+pub const LINEAR_MEMORY_PAGE_SIZE: usize = 65536;
+
+/// Reference to a linear memory.
+#[derive(Clone, Debug)]
+pub struct MemoryRef(Rc<MemoryInstance>);
+
+impl Deref for MemoryRef {
+    type Target = MemoryInstance;
+    fn deref(&self) -> &MemoryInstance {
+        &self.0
+    }
+}
+
+/// Linear memory instance.
+pub struct MemoryInstance {
+    buffer: RefCell<Vec<u8>>,
+    initial: u64,
+    maximum: Option<u64>,
+    shared: bool,
+    memory64: bool,
+}
+
+impl fmt::Debug for MemoryInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryInstance")
+            .field("pages", &self.current_pages())
+            .field("initial", &self.initial)
+            .field("maximum", &self.maximum)
+            .field("shared", &self.shared)
+            .field("memory64", &self.memory64)
+            .finish()
+    }
+}
+
+impl MemoryInstance {
+    /// Allocate a memory of `initial` pages, which may grow up to `maximum` pages.
+    pub fn alloc(initial: u64, maximum: Option<u64>) -> Result<MemoryRef, Error> {
+        MemoryInstance::alloc_with(initial, maximum, false, false)
+    }
+
+    /// Allocate a shared memory, which must have a maximum size.
+    #[cfg(feature = "threads")]
+    pub fn alloc_shared(initial: u64, maximum: u64) -> Result<MemoryRef, Error> {
+        MemoryInstance::alloc_with(initial, Some(maximum), true, false)
+    }
+
+    /// Allocate a memory addressed with `i64`.
+    #[cfg(feature = "memory64")]
+    pub fn alloc64(initial: u64, maximum: Option<u64>) -> Result<MemoryRef, Error> {
+        MemoryInstance::alloc_with(initial, maximum, false, true)
+    }
+
+    /// Allocate a memory of the declared `limits`.
+    pub(crate) fn from_limits(limits: &ResizableLimits) -> Result<MemoryRef, Error> {
+        MemoryInstance::alloc_with(limits.initial, limits.maximum, limits.shared, limits.memory64)
+    }
+
+    fn alloc_with(initial: u64, maximum: Option<u64>, shared: bool, memory64: bool) -> Result<MemoryRef, Error> {
+        let bound = if memory64 { LINEAR_MEMORY64_MAX_PAGES } else { LINEAR_MEMORY_MAX_PAGES };
+        let invalid = || Error::Instantiation(format!("Invalid memory limits {} {:?}", initial, maximum));
+        if initial > bound || maximum.is_some_and(|m| m > bound || m < initial) {
+            return Err(invalid());
+        }
+        let mut buffer = Vec::new();
+        let len = byte_len(initial).ok_or_else(invalid)?;
+        buffer.try_reserve_exact(len).map_err(|_| invalid())?;
+        buffer.resize(len, 0);
+        Ok(MemoryRef(Rc::new(MemoryInstance {
+            buffer: RefCell::new(buffer),
+            initial,
+            maximum,
+            shared,
+            memory64,
+        })))
+    }
+
+    pub fn initial(&self) -> u64 {
+        self.initial
+    }
+
+    pub fn maximum(&self) -> Option<u64> {
+        self.maximum
+    }
+
+    /// Whether the memory is shared between threads.
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Whether the memory is addressed with `i64`.
+    pub fn is_64(&self) -> bool {
+        self.memory64
+    }
+
+    /// Limits the memory currently satisfies, as checked against imports.
+    pub fn limits(&self) -> ResizableLimits {
+        ResizableLimits {
+            initial: self.current_pages(),
+            maximum: self.maximum,
+            shared: self.shared,
+            memory64: self.memory64,
+        }
+    }
+
+    pub fn current_pages(&self) -> u64 {
+        (self.buffer.borrow().len() / LINEAR_MEMORY_PAGE_SIZE) as u64
+    }
+
+    /// Grow by `delta` pages, returning the previous size or `None` if the limit is exceeded.
+    pub fn grow(&self, delta: u64) -> Option<u64> {
+        let current = self.current_pages();
+        let bound = if self.memory64 { LINEAR_MEMORY64_MAX_PAGES } else { LINEAR_MEMORY_MAX_PAGES };
+        let max = self.maximum.unwrap_or(bound);
+        let new = current.checked_add(delta).filter(|&n| n <= max)?;
+        let len = byte_len(new)?;
+        let mut buffer = self.buffer.borrow_mut();
+        // 分配失败时和超出上限一样返回 -1
+        let additional = len - buffer.len();
+        buffer.try_reserve_exact(additional).ok()?;
+        buffer.resize(len, 0);
+        Some(current)
+    }
+
+    fn checked_range(&self, offset: u64, len: usize) -> Result<std::ops::Range<usize>, Trap> {
+        let start = usize::try_from(offset).map_err(|_| TrapKind::MemoryAccessOutOfBounds)?;
+        let end = start.checked_add(len).ok_or(TrapKind::MemoryAccessOutOfBounds)?;
+        if end > self.buffer.borrow().len() {
+            return Err(TrapKind::MemoryAccessOutOfBounds.into());
+        }
+        Ok(start..end)
+    }
+
+    /// Copy `len` bytes starting at `offset`.
+    pub fn get(&self, offset: u64, len: usize) -> Result<Vec<u8>, Trap> {
+        let range = self.checked_range(offset, len)?;
+        Ok(self.buffer.borrow()[range].to_vec())
+    }
+
+    /// Write `value` starting at `offset`.
+    pub fn set(&self, offset: u64, value: &[u8]) -> Result<(), Trap> {
+        let range = self.checked_range(offset, value.len())?;
+        self.buffer.borrow_mut()[range].copy_from_slice(value);
+        Ok(())
+    }
+
+    /// Set `len` bytes starting at `offset` to `value`.
+    pub fn fill(&self, offset: u64, value: u8, len: usize) -> Result<(), Trap> {
+        let range = self.checked_range(offset, len)?;
+        self.buffer.borrow_mut()[range].fill(value);
+        Ok(())
+    }
+
+    pub fn get_value<T: LittleEndian>(&self, offset: u64) -> Result<T, Trap> {
+        let range = self.checked_range(offset, T::SIZE)?;
+        Ok(T::from_le(&self.buffer.borrow()[range]))
+    }
+
+    pub fn set_value<T: LittleEndian>(&self, offset: u64, value: T) -> Result<(), Trap> {
+        let range = self.checked_range(offset, T::SIZE)?;
+        value.to_le(&mut self.buffer.borrow_mut()[range]);
+        Ok(())
+    }
+
+    /// Run `f` with the whole memory as a slice.
+    pub fn with_direct_access<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+        f(&self.buffer.borrow())
+    }
+
+    /// Run `f` with the whole memory as a mutable slice.
+    pub fn with_direct_access_mut<R, F: FnOnce(&mut [u8]) -> R>(&self, f: F) -> R {
+        f(&mut self.buffer.borrow_mut())
+    }
+}
+
+/// Pop an address or length operand of `memory`, an `i64` for 64-bit memories and an `i32` otherwise.
+pub(super) fn pop_address(stack: &mut Vec<Value>, memory: &MemoryInstance) -> u64 {
+    let value = stack.pop().expect("operand stack is validated");
+    let address = if memory.is_64() {
+        value.try_into::<u64>()
+    } else {
+        value.try_into::<u32>().map(u64::from)
+    };
+    address.expect("operand types are validated")
+}
+
+/// Pop a base address and add the static `offset` of a memory access.
+pub(super) fn effective_address(stack: &mut Vec<Value>, memory: &MemoryInstance, offset: u64) -> Result<u64, Trap> {
+    // 64 位内存的地址加偏移可能溢出
+    pop_address(stack, memory)
+        .checked_add(offset)
+        .ok_or_else(|| TrapKind::MemoryAccessOutOfBounds.into())
+}
+
+/// Push an address or size result of `memory`, matching the type of its addresses.
+pub(super) fn push_address(stack: &mut Vec<Value>, memory: &MemoryInstance, value: u64) {
+    stack.push(if memory.is_64() { Value::I64(value as i64) } else { Value::I32(value as i32) });
+}
+
+/// Size in bytes of `pages` pages, if it fits the address space of the host.
+fn byte_len(pages: u64) -> Option<usize> {
+    usize::try_from(pages).ok()?.checked_mul(LINEAR_MEMORY_PAGE_SIZE)
+}
+
+/// Values which are stored in memory in little endian.
+pub trait LittleEndian: Sized {
+    const SIZE: usize;
+    fn from_le(buf: &[u8]) -> Self;
+    fn to_le(self, buf: &mut [u8]);
+}
+
+macro_rules! impl_little_endian {
+    ($($t: ty),*) => {
+        $(
+            impl LittleEndian for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+
+                fn from_le(buf: &[u8]) -> $t {
+                    <$t>::from_le_bytes(buf.try_into().expect("buffer has the size of the value"))
+                }
+
+                fn to_le(self, buf: &mut [u8]) {
+                    buf.copy_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    }
+}
+
+impl_little_endian!(i8, u8, i16, u16, i32, u32, i64, u64, f32, f64);
+#[cfg(feature = "simd")]
+impl_little_endian!(u128);
@@ -0,0 +1,1294 @@
+//! Stack-based interpreter executing validated modules.
+//!
+//! ```ignore
+//! let instance = ModuleInstance::new(&module, &ImportsBuilder::default())?;
+//! let result = instance.invoke_export("add", &[Value::I32(1), Value::I32(2)])?;
+//! ```
+
+use std::fmt;
+
+use crate::validation::ValidationError;
+
+mod value;
+mod memory;
+mod table;
+mod global;
+mod func;
+mod imports;
+mod linker;
+mod module;
+mod runner;
+#[cfg(feature = "simd")]
+mod simd;
+#[cfg(feature = "threads")]
+mod atomics;
+#[cfg(feature = "exceptions")]
+mod exception;
+#[cfg(feature = "gc")]
+mod gc;
+
+pub use self::value::{Value, FromValue};
+pub use self::memory::{MemoryInstance, MemoryRef, LINEAR_MEMORY_PAGE_SIZE};
+pub use self::table::{TableInstance, TableRef};
+pub use self::global::{GlobalInstance, GlobalRef};
+pub use self::func::{FuncInstance, FuncRef, HostFunc};
+pub use self::imports::{ImportResolver, ImportsBuilder};
+pub use self::linker::{LinkError, Linker};
+pub use self::module::{ModuleInstance, ModuleRef, ExternKind, ExternVal};
+#[cfg(feature = "exceptions")]
+pub use self::exception::{Exception, ExnRef, TagInstance, TagRef};
+#[cfg(feature = "gc")]
+pub use self::gc::{AnyRef, ObjectRef};
+
+/// Reason of a trap.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrapKind {
+    /// `unreachable` was executed.
+    Unreachable,
+    /// Load, store or segment initialization out of the bounds of a memory.
+    MemoryAccessOutOfBounds,
+    /// Access out of the bounds of a table.
+    TableAccessOutOfBounds,
+    /// Indirect call to an uninitialized table element.
+    ElemUninitialized,
+    /// Integer division or remainder by zero.
+    DivisionByZero,
+    /// Integer overflow in division or float-to-int conversion.
+    IntegerOverflow,
+    /// Float-to-int conversion of NaN.
+    InvalidConversionToInt,
+    /// Call stack or value stack exhausted.
+    StackOverflow,
+    /// Indirect call or invocation with a mismatched signature.
+    UnexpectedSignature,
+    /// Atomic access to an address which is not a multiple of the access size.
+    #[cfg(feature = "threads")]
+    UnalignedAtomic,
+    /// `memory.atomic.wait` on a memory which is not shared.
+    #[cfg(feature = "threads")]
+    ExpectedSharedMemory,
+    /// `throw_ref`, `call_ref` or a field access of a null reference.
+    #[cfg(any(feature = "exceptions", feature = "gc"))]
+    NullReference,
+    /// Array element or range out of the bounds of an array.
+    #[cfg(feature = "gc")]
+    ArrayAccessOutOfBounds,
+    /// `ref.cast` of a reference which does not have the target type.
+    #[cfg(feature = "gc")]
+    CastFailure,
+    /// Exception which was not caught, thrown by wasm or by a host function.
+    #[cfg(feature = "exceptions")]
+    Exception(ExnRef),
+    /// Error raised by the host.
+    Host(String),
+}
+
+impl fmt::Display for TrapKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TrapKind::Unreachable => write!(f, "unreachable executed"),
+            TrapKind::MemoryAccessOutOfBounds => write!(f, "out of bounds memory access"),
+            TrapKind::TableAccessOutOfBounds => write!(f, "out of bounds table access"),
+            TrapKind::ElemUninitialized => write!(f, "uninitialized element"),
+            TrapKind::DivisionByZero => write!(f, "integer divide by zero"),
+            TrapKind::IntegerOverflow => write!(f, "integer overflow"),
+            TrapKind::InvalidConversionToInt => write!(f, "invalid conversion to integer"),
+            TrapKind::StackOverflow => write!(f, "call stack exhausted"),
+            TrapKind::UnexpectedSignature => write!(f, "indirect call type mismatch"),
+            #[cfg(feature = "threads")]
+            TrapKind::UnalignedAtomic => write!(f, "unaligned atomic"),
+            #[cfg(feature = "threads")]
+            TrapKind::ExpectedSharedMemory => write!(f, "expected shared memory"),
+            #[cfg(any(feature = "exceptions", feature = "gc"))]
+            TrapKind::NullReference => write!(f, "null reference"),
+            #[cfg(feature = "gc")]
+            TrapKind::ArrayAccessOutOfBounds => write!(f, "out of bounds array access"),
+            #[cfg(feature = "gc")]
+            TrapKind::CastFailure => write!(f, "cast failure"),
+            #[cfg(feature = "exceptions")]
+            TrapKind::Exception(ref exn) => write!(f, "uncaught exception {:?}", exn.payload()),
+            TrapKind::Host(ref msg) => write!(f, "host error: {}", msg),
+        }
+    }
+}
+
+/// Error terminating the execution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trap {
+    kind: TrapKind,
+    backtrace: Vec<TraceFrame>,
+}
+
+/// Wasm function active when a trap occurred.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    /// Index in the function index space of its module.
+    pub func_index: u32,
+    /// Name from the `name` section of the module.
+    pub name: Option<String>,
+}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name {
+            Some(ref name) => write!(f, "func[{}] <{}>", self.func_index, name),
+            None => write!(f, "func[{}]", self.func_index),
+        }
+    }
+}
+
+impl Trap {
+    pub fn new(kind: TrapKind) -> Trap {
+        Trap { kind, backtrace: Vec::new() }
+    }
+
+    pub fn kind(&self) -> &TrapKind {
+        &self.kind
+    }
+
+    /// Wasm functions on the call stack at the trap, innermost first.
+    pub fn backtrace(&self) -> &[TraceFrame] {
+        &self.backtrace
+    }
+
+    pub(crate) fn push_frame(&mut self, frame: TraceFrame) {
+        self.backtrace.push(frame);
+    }
+}
+
+impl From<TrapKind> for Trap {
+    fn from(kind: TrapKind) -> Trap {
+        Trap::new(kind)
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "trap: {}", self.kind)?;
+        for frame in self.backtrace.iter() {
+            write!(f, "\n  at {}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+/// Interpreter error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Module is not valid.
+    Validation(ValidationError),
+    /// An import can't be resolved or doesn't match its declared type.
+    Link(Box<LinkError>),
+    /// Module can't be instantiated.
+    Instantiation(String),
+    /// Function does not exist or is called with wrong arguments.
+    Function(String),
+    /// Execution trapped.
+    Trap(Trap),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Validation(ref e) => write!(f, "Validation: {}", e),
+            Error::Link(ref e) => write!(f, "Link: {}", e),
+            Error::Instantiation(ref msg) => write!(f, "Instantiation: {}", msg),
+            Error::Function(ref msg) => write!(f, "Function: {}", msg),
+            Error::Trap(ref trap) => write!(f, "{}", trap),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Error {}
+
+impl From<Trap> for Error {
+    fn from(trap: Trap) -> Error {
+        Error::Trap(trap)
+    }
+}
+
+impl From<TrapKind> for Error {
+    fn from(kind: TrapKind) -> Error {
+        Error::Trap(Trap::new(kind))
+    }
+}
+
+impl From<LinkError> for Error {
+    fn from(e: LinkError) -> Error {
+        Error::Link(Box::new(e))
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(e: ValidationError) -> Error {
+        Error::Validation(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elements::deserialize_buffer;
+    use crate::elements::module::Module;
+    use crate::elements::func::{Func, FuncBody, Local};
+    use crate::elements::ops::{Instruction, Instructions};
+    use crate::elements::sections::{Section, TypeSection, FunctionSection, CodeSection, ExportSection, MemorySection, ImportSection};
+    use crate::elements::export_entry::{ExportEntry, Internal};
+    use crate::elements::import_entry::{External, ImportEntry, ResizableLimits};
+    use crate::elements::types::{BlockType, FunctionType, ValueType};
+    use crate::tests::SAMPLE_MODULE;
+
+    /// Module exporting one function `f` of the given signature, with a memory of one page.
+    fn single_func_module(params: Vec<ValueType>, results: Vec<ValueType>, locals: Vec<Local>, code: Vec<Instruction>) -> Module {
+        let mut m = Module::default();
+        m.sections.push(Section::Type(TypeSection::from(vec![FunctionType { form: 0x60, params, results }])));
+        m.sections.push(Section::Function(FunctionSection(vec![Func(0)])));
+        m.sections.push(Section::Memory(MemorySection(vec![ResizableLimits { initial: 1, maximum: None, shared: false, memory64: false }])));
+        m.sections.push(Section::Export(ExportSection(vec![ExportEntry {
+            field_str: "f".to_string(),
+            internal: Internal::Function(0),
+        }])));
+        m.sections.push(Section::Code(CodeSection(vec![FuncBody {
+            locals,
+            instructions: Instructions(code),
+        }])));
+        m
+    }
+
+    fn run(module: &Module, args: &[Value]) -> Result<Vec<Value>, Error> {
+        let instance = ModuleInstance::new(module, &ImportsBuilder::default())?;
+        instance.invoke_export("f", args)
+    }
+
+    fn trap_kind(result: Result<Vec<Value>, Error>) -> TrapKind {
+        match result {
+            Err(Error::Trap(trap)) => trap.kind().clone(),
+            other => panic!("expected a trap, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sample_module() {
+        use crate::elements::ops::Instruction::*;
+
+        let env = single_func_module(vec![], vec![], vec![], vec![Nop, End]);
+        let env = ModuleInstance::new(&env, &ImportsBuilder::default()).unwrap();
+
+        let m: Module = deserialize_buffer(SAMPLE_MODULE).unwrap();
+        let imports = ImportsBuilder::new().with_module("env", env);
+        let instance = ModuleInstance::new(&m, &imports).unwrap();
+
+        let result = instance.invoke_export("add", &[Value::I32(40), Value::I32(2)]).unwrap();
+        assert_eq!(result, vec![Value::I32(42)]);
+
+        match instance.export_by_name("mem") {
+            Some(ExternVal::Memory(memory)) => assert_eq!(memory.get(8, 2).unwrap(), b"hi".to_vec()),
+            other => panic!("unexpected export {:?}", other),
+        }
+
+        assert!(instance.invoke_export("add", &[Value::I32(1)]).is_err());
+        assert!(instance.invoke_export("missing", &[]).is_err());
+        assert!(ModuleInstance::new(&m, &ImportsBuilder::default()).is_err());
+    }
+
+    #[test]
+    fn test_control_flow() {
+        use crate::elements::ops::Instruction::*;
+
+        // 循环计算 n!
+        let factorial = single_func_module(
+            vec![ValueType::I64],
+            vec![ValueType::I64],
+            vec![Local { count: 1, value_type: ValueType::I64 }],
+            vec![
+                I64Const(1),
+                SetLocal(1),
+                Block(BlockType::NoResult),
+                Loop(BlockType::NoResult),
+                GetLocal(0),
+                I64Eqz,
+                BrIf(1),
+                GetLocal(1),
+                GetLocal(0),
+                I64Mul,
+                SetLocal(1),
+                GetLocal(0),
+                I64Const(1),
+                I64Sub,
+                SetLocal(0),
+                Br(0),
+                End,
+                End,
+                GetLocal(1),
+                End,
+            ],
+        );
+        assert_eq!(run(&factorial, &[Value::I64(10)]).unwrap(), vec![Value::I64(3628800)]);
+
+        let select = single_func_module(
+            vec![ValueType::I32],
+            vec![ValueType::I32],
+            vec![],
+            vec![
+                GetLocal(0),
+                If(BlockType::Value(ValueType::I32)),
+                I32Const(1),
+                Else,
+                I32Const(2),
+                End,
+                End,
+            ],
+        );
+        assert_eq!(run(&select, &[Value::I32(7)]).unwrap(), vec![Value::I32(1)]);
+        assert_eq!(run(&select, &[Value::I32(0)]).unwrap(), vec![Value::I32(2)]);
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        use crate::elements::ops::Instruction::*;
+
+        // f(n) = n == 0 ? 0 : f(n - 1) + 1
+        let count = single_func_module(
+            vec![ValueType::I32],
+            vec![ValueType::I32],
+            vec![],
+            vec![
+                GetLocal(0),
+                I32Eqz,
+                If(BlockType::Value(ValueType::I32)),
+                I32Const(0),
+                Else,
+                GetLocal(0),
+                I32Const(1),
+                I32Sub,
+                Call(0),
+                I32Const(1),
+                I32Add,
+                End,
+                End,
+            ],
+        );
+        assert_eq!(run(&count, &[Value::I32(10000)]).unwrap(), vec![Value::I32(10000)]);
+        assert_eq!(trap_kind(run(&count, &[Value::I32(1_000_000)])), TrapKind::StackOverflow);
+    }
+
+    #[test]
+    fn test_traps() {
+        use crate::elements::ops::Instruction::*;
+
+        let unreachable = single_func_module(vec![], vec![], vec![], vec![Unreachable, End]);
+        assert_eq!(trap_kind(run(&unreachable, &[])), TrapKind::Unreachable);
+
+        let div = single_func_module(
+            vec![ValueType::I32, ValueType::I32],
+            vec![ValueType::I32],
+            vec![],
+            vec![GetLocal(0), GetLocal(1), I32DivS, End],
+        );
+        assert_eq!(run(&div, &[Value::I32(7), Value::I32(2)]).unwrap(), vec![Value::I32(3)]);
+        assert_eq!(trap_kind(run(&div, &[Value::I32(1), Value::I32(0)])), TrapKind::DivisionByZero);
+        assert_eq!(trap_kind(run(&div, &[Value::I32(i32::MIN), Value::I32(-1)])), TrapKind::IntegerOverflow);
+
+        let load = single_func_module(
+            vec![ValueType::I32],
+            vec![ValueType::I32],
+            vec![],
+            vec![GetLocal(0), I32Load(2, 0, 0), End],
+        );
+        assert_eq!(run(&load, &[Value::I32(65532)]).unwrap(), vec![Value::I32(0)]);
+        assert_eq!(trap_kind(run(&load, &[Value::I32(65533)])), TrapKind::MemoryAccessOutOfBounds);
+
+        let trunc = single_func_module(
+            vec![ValueType::F64],
+            vec![ValueType::I32],
+            vec![],
+            vec![GetLocal(0), I32TruncSF64, End],
+        );
+        assert_eq!(run(&trunc, &[Value::F64(-2.9)]).unwrap(), vec![Value::I32(-2)]);
+        assert_eq!(trap_kind(run(&trunc, &[Value::F64(f64::NAN)])), TrapKind::InvalidConversionToInt);
+        assert_eq!(trap_kind(run(&trunc, &[Value::F64(3e9)])), TrapKind::IntegerOverflow);
+    }
+
+    #[test]
+    fn test_saturating_truncation() {
+        let m = crate::wat::parse(r#"
+            (module
+              (func (export "i32_s") (param f64) (result i32) (i32.trunc_sat_f64_s (local.get 0)))
+              (func (export "i32_u") (param f32) (result i32) (i32.trunc_sat_f32_u (local.get 0)))
+              (func (export "i64_u") (param f64) (result i64) (i64.trunc_sat_f64_u (local.get 0))))
+        "#).unwrap();
+        crate::validation::validate(&m).unwrap();
+
+        let instance = ModuleInstance::new(&m, &ImportsBuilder::default()).unwrap();
+        let call = |name: &str, arg: Value| instance.invoke_export(name, &[arg]).unwrap().remove(0);
+        assert_eq!(call("i32_s", Value::F64(-2.9)), Value::I32(-2));
+        assert_eq!(call("i32_s", Value::F64(3e9)), Value::I32(i32::MAX));
+        assert_eq!(call("i32_s", Value::F64(f64::NEG_INFINITY)), Value::I32(i32::MIN));
+        assert_eq!(call("i32_s", Value::F64(f64::NAN)), Value::I32(0));
+        assert_eq!(call("i32_u", Value::F32(-1.5)), Value::I32(0));
+        assert_eq!(call("i32_u", Value::F32(5e9)), Value::I32(-1));
+        assert_eq!(call("i64_u", Value::F64(1e20)), Value::I64(-1));
+    }
+
+    #[test]
+    fn test_sign_extension() {
+        let m = crate::wat::parse(r#"
+            (module
+              (func (export "i32_8") (param i32) (result i32) (i32.extend8_s (local.get 0)))
+              (func (export "i32_16") (param i32) (result i32) (i32.extend16_s (local.get 0)))
+              (func (export "i64_8") (param i64) (result i64) (i64.extend8_s (local.get 0)))
+              (func (export "i64_16") (param i64) (result i64) (i64.extend16_s (local.get 0)))
+              (func (export "i64_32") (param i64) (result i64) (i64.extend32_s (local.get 0))))
+        "#).unwrap();
+        crate::validation::validate(&m).unwrap();
+
+        let instance = ModuleInstance::new(&m, &ImportsBuilder::default()).unwrap();
+        let call = |name: &str, arg: Value| instance.invoke_export(name, &[arg]).unwrap().remove(0);
+        assert_eq!(call("i32_8", Value::I32(0x7f)), Value::I32(0x7f));
+        assert_eq!(call("i32_8", Value::I32(0x1280)), Value::I32(-128));
+        assert_eq!(call("i32_16", Value::I32(0x18000)), Value::I32(-32768));
+        assert_eq!(call("i64_8", Value::I64(0xff)), Value::I64(-1));
+        assert_eq!(call("i64_16", Value::I64(0x7fff)), Value::I64(0x7fff));
+        assert_eq!(call("i64_32", Value::I64(0x1_8000_0000)), Value::I64(-0x8000_0000));
+
+        let invalid = single_func_module(vec![ValueType::I64], vec![ValueType::I32], vec![], vec![
+            Instruction::GetLocal(0), Instruction::I32Extend8S, Instruction::End,
+        ]);
+        assert!(crate::validation::validate(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_bulk_memory() {
+        use crate::validation::{validate, ValidationError};
+
+        let m = crate::wat::parse(r#"
+            (module
+              (type $t (func (result i32)))
+              (memory 1)
+              (table 4 funcref)
+              (data $hello "hello")
+              (data (i32.const 0) "abc")
+              (elem $fs func $one $two)
+              (elem declare func $one)
+              (elem (i32.const 3) funcref (ref.func $two))
+              (func $one (result i32) i32.const 1)
+              (func $two (result i32) i32.const 2)
+              (func (export "memory.init") (param i32 i32 i32) (memory.init $hello (local.get 0) (local.get 1) (local.get 2)))
+              (func (export "data.drop") (data.drop $hello))
+              (func (export "memory.copy") (param i32 i32 i32) (memory.copy (local.get 0) (local.get 1) (local.get 2)))
+              (func (export "memory.fill") (param i32 i32 i32) (memory.fill (local.get 0) (local.get 1) (local.get 2)))
+              (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+              (func (export "table.init") (param i32 i32 i32) (table.init $fs (local.get 0) (local.get 1) (local.get 2)))
+              (func (export "elem.drop") (elem.drop $fs))
+              (func (export "table.copy") (param i32 i32 i32) (table.copy (local.get 0) (local.get 1) (local.get 2)))
+              (func (export "call") (param i32) (result i32) (call_indirect (type $t) (local.get 0))))
+        "#).unwrap();
+
+        let instance = ModuleInstance::new(&m, &ImportsBuilder::default()).unwrap();
+        let call = |name: &str, args: &[i32]| {
+            let args: Vec<Value> = args.iter().map(|&v| Value::I32(v)).collect();
+            instance.invoke_export(name, &args)
+        };
+        let load = |addr: i32| call("load", &[addr]).unwrap().remove(0);
+
+        call("memory.init", &[10, 1, 3]).unwrap();
+        assert_eq!(load(12), Value::I32(b'l' as i32));
+        call("memory.init", &[0, 5, 0]).unwrap();
+        assert_eq!(trap_kind(call("memory.init", &[65535, 0, 2])), TrapKind::MemoryAccessOutOfBounds);
+        assert_eq!(trap_kind(call("memory.init", &[0, 4, 2])), TrapKind::MemoryAccessOutOfBounds);
+
+        // 重叠的复制按 memmove 语义
+        call("memory.copy", &[1, 0, 3]).unwrap();
+        assert_eq!((load(0), load(1), load(3)), (Value::I32(b'a' as i32), Value::I32(b'a' as i32), Value::I32(b'c' as i32)));
+        call("memory.fill", &[100, 0x2a, 5]).unwrap();
+        assert_eq!((load(104), load(105)), (Value::I32(0x2a), Value::I32(0)));
+        assert_eq!(trap_kind(call("memory.fill", &[65530, 0, 7])), TrapKind::MemoryAccessOutOfBounds);
+
+        call("data.drop", &[]).unwrap();
+        call("memory.init", &[0, 0, 0]).unwrap();
+        assert_eq!(trap_kind(call("memory.init", &[0, 0, 1])), TrapKind::MemoryAccessOutOfBounds);
+
+        assert_eq!(call("call", &[3]).unwrap(), vec![Value::I32(2)]);
+        assert_eq!(trap_kind(call("call", &[0])), TrapKind::ElemUninitialized);
+        call("table.init", &[0, 0, 2]).unwrap();
+        assert_eq!(call("call", &[1]).unwrap(), vec![Value::I32(2)]);
+        call("table.copy", &[2, 0, 2]).unwrap();
+        assert_eq!(call("call", &[2]).unwrap(), vec![Value::I32(1)]);
+        assert_eq!(trap_kind(call("table.copy", &[3, 0, 2])), TrapKind::TableAccessOutOfBounds);
+        call("elem.drop", &[]).unwrap();
+        assert_eq!(trap_kind(call("table.init", &[0, 0, 1])), TrapKind::TableAccessOutOfBounds);
+
+        // memory.init 需要 DataCount section，且数量要和 data section 一致
+        let mut missing = m.clone();
+        missing.sections.retain(|s| !matches!(*s, Section::DataCount(_)));
+        match validate(&missing) {
+            Err(ValidationError::Function { ref error, .. }) => assert_eq!(**error, ValidationError::MissingDataCount),
+            other => panic!("unexpected {:?}", other),
+        }
+        let mut inconsistent = m.clone();
+        for section in inconsistent.sections.iter_mut() {
+            if let Section::DataCount(ref mut count) = *section {
+                *count = 3;
+            }
+        }
+        match validate(&inconsistent) {
+            Err(ValidationError::InconsistentDataCount { declared: 3, segments: 2 }) => {},
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_reference_types() {
+        use crate::elements::types::TableElementType;
+        use crate::validation::{validate, ValidationError};
+
+        let m = crate::wat::parse(r#"
+            (module
+              (type $t (func (result i32)))
+              (table $ext (export "ext") 2 externref)
+              (table $fs 2 10 funcref)
+              (elem (table $fs) (i32.const 0) func $seven)
+              (func $seven (result i32) i32.const 7)
+              (func (export "get") (param i32) (result externref) (table.get $ext (local.get 0)))
+              (func (export "set") (param i32 externref) (table.set $ext (local.get 0) (local.get 1)))
+              (func (export "is_null") (param externref) (result i32) (ref.is_null (local.get 0)))
+              (func (export "size") (result i32) (table.size $fs))
+              (func (export "grow") (param i32) (result i32) (table.grow $fs (ref.func $seven) (local.get 0)))
+              (func (export "fill") (param i32 i32) (table.fill $fs (local.get 0) (ref.null func) (local.get 1)))
+              (func (export "call") (param i32) (result i32) (call_indirect $fs (type $t) (local.get 0)))
+              (func (export "pick") (param externref externref i32) (result externref)
+                (select (result externref) (local.get 0) (local.get 1) (local.get 2))))
+        "#).unwrap();
+
+        let instance = ModuleInstance::new(&m, &ImportsBuilder::default()).unwrap();
+        let call = |name: &str, args: &[Value]| instance.invoke_export(name, args);
+        let i32s = |v: i32| Value::I32(v);
+
+        assert_eq!(call("get", &[i32s(0)]).unwrap(), vec![Value::ExternRef(None)]);
+        call("set", &[i32s(1), Value::ExternRef(Some(42))]).unwrap();
+        assert_eq!(call("get", &[i32s(1)]).unwrap(), vec![Value::ExternRef(Some(42))]);
+        assert_eq!(trap_kind(call("get", &[i32s(2)])), TrapKind::TableAccessOutOfBounds);
+        assert_eq!(call("is_null", &[Value::ExternRef(None)]).unwrap(), vec![i32s(1)]);
+        assert_eq!(call("is_null", &[Value::ExternRef(Some(0))]).unwrap(), vec![i32s(0)]);
+        assert_eq!(
+            call("pick", &[Value::ExternRef(Some(1)), Value::ExternRef(None), i32s(1)]).unwrap(),
+            vec![Value::ExternRef(Some(1))]
+        );
+
+        // 第二张表上的 call_indirect
+        assert_eq!(call("call", &[i32s(0)]).unwrap(), vec![i32s(7)]);
+        assert_eq!(trap_kind(call("call", &[i32s(1)])), TrapKind::ElemUninitialized);
+        assert_eq!(call("grow", &[i32s(3)]).unwrap(), vec![i32s(2)]);
+        assert_eq!(call("size", &[]).unwrap(), vec![i32s(5)]);
+        assert_eq!(call("call", &[i32s(4)]).unwrap(), vec![i32s(7)]);
+        assert_eq!(call("grow", &[i32s(6)]).unwrap(), vec![i32s(-1)]);
+        call("fill", &[i32s(0), i32s(5)]).unwrap();
+        assert_eq!(trap_kind(call("call", &[i32s(4)])), TrapKind::ElemUninitialized);
+        assert_eq!(trap_kind(call("fill", &[i32s(3), i32s(3)])), TrapKind::TableAccessOutOfBounds);
+
+        match instance.export_by_name("ext") {
+            Some(ExternVal::Table(table)) => {
+                assert_eq!(table.elem_type(), TableElementType::ExternRef);
+                assert_eq!(table.get(1).unwrap(), Value::ExternRef(Some(42)));
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let undeclared = crate::wat::parse("(module (func (result funcref) (ref.func 0)))").unwrap();
+        match validate(&undeclared) {
+            Err(ValidationError::Function { ref error, .. }) => assert_eq!(**error, ValidationError::UndeclaredFuncRef(0)),
+            other => panic!("unexpected {:?}", other),
+        }
+        let untyped_select = crate::wat::parse(r#"
+            (module
+              (func (param externref externref i32) (result externref)
+                (select (local.get 0) (local.get 1) (local.get 2))))
+        "#).unwrap();
+        assert!(validate(&untyped_select).is_err());
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn test_simd() {
+        use crate::validation::{validate, ValidationError};
+
+        let m = crate::wat::parse(r#"
+            (module
+              (memory 1)
+              (data (i32.const 0) "\01\02\03\04\05\06\07\08\09\0a\0b\0c\0d\0e\0f\10")
+              (func (export "add") (param v128 v128) (result v128) (i32x4.add (local.get 0) (local.get 1)))
+              (func (export "all_true") (param v128) (result i32) (i32x4.all_true (local.get 0)))
+              (func (export "add_sat") (result v128)
+                (i8x16.add_sat_s (v128.const i8x16 127 -128 1 0 0 0 0 0 0 0 0 0 0 0 0 0)
+                                 (v128.const i8x16 1 -1 1 0 0 0 0 0 0 0 0 0 0 0 0 0)))
+              (func (export "dot") (result v128)
+                (i32x4.dot_i16x8_s (v128.const i16x8 1 2 3 4 -5 6 32767 32767)
+                                   (v128.const i16x8 10 20 30 40 50 60 32767 32767)))
+              (func (export "extmul") (result v128)
+                (i64x2.extmul_high_i32x4_u (v128.const i32x4 0 0 -1 2) (v128.const i32x4 0 0 2 3)))
+              (func (export "narrow") (result v128)
+                (i8x16.narrow_i16x8_u (v128.const i16x8 -1 300 5 0 0 0 0 0) (v128.const i16x8 255 0 0 0 0 0 0 0)))
+              (func (export "shr") (result v128) (i16x8.shr_s (v128.const i16x8 -4 8 0 0 0 0 0 0) (i32.const 17)))
+              (func (export "bitmask") (result i32)
+                (i8x16.bitmask (v128.const i8x16 -1 0 -1 0 0 0 0 0 0 0 0 0 0 0 0 -128)))
+              (func (export "shuffle") (result v128)
+                (i8x16.shuffle 31 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+                  (v128.load (i32.const 0)) (v128.const i8x16 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 -1)))
+              (func (export "extract") (result i32) (i8x16.extract_lane_u 15 (v128.load (i32.const 0))))
+              (func (export "load_lane") (result v128) (v128.load16_lane 2 (i32.const 4) (v128.const i64x2 0 0)))
+              (func (export "store_lane") (param v128) (result i32)
+                (v128.store32_lane offset=16 1 (i32.const 0) (local.get 0))
+                (i32.load offset=16 (i32.const 0)))
+              (func (export "fmin") (result v128) (f32x4.min (v128.const f32x4 nan 0 -0 1) (v128.const f32x4 1 -0 0 2)))
+              (func (export "trunc_sat") (result v128) (i32x4.trunc_sat_f32x4_s (v128.const f32x4 nan 1e10 -1e10 -2.5)))
+              (func (export "nearest") (result v128) (f64x2.nearest (v128.const f64x2 2.5 -3.5))))
+        "#).unwrap();
+
+        let instance = ModuleInstance::new(&m, &ImportsBuilder::default()).unwrap();
+        let call = |name: &str, args: &[Value]| -> u128 {
+            match instance.invoke_export(name, args).unwrap()[..] {
+                [Value::V128(v)] => v,
+                [Value::I32(v)] => v as u32 as u128,
+                ref other => panic!("unexpected {:?}", other),
+            }
+        };
+        let i32x4 = |lanes: [i32; 4]| lanes.iter().rev().fold(0u128, |v, &x| v << 32 | x as u32 as u128);
+        let bytes = |lanes: &[u8]| {
+            let mut v = [0u8; 16];
+            v[..lanes.len()].copy_from_slice(lanes);
+            u128::from_le_bytes(v)
+        };
+
+        let (a, b) = (Value::V128(i32x4([1, 2, 3, -1])), Value::V128(i32x4([10, 20, 30, 1])));
+        assert_eq!(call("add", &[a, b]), i32x4([11, 22, 33, 0]));
+        assert_eq!(call("all_true", &[Value::V128(i32x4([1, 2, 3, -1]))]), 1);
+        assert_eq!(call("all_true", &[Value::V128(i32x4([1, 0, 3, -1]))]), 0);
+        assert_eq!(call("add_sat", &[]), bytes(&[0x7f, 0x80, 2]));
+        assert_eq!(call("dot", &[]), i32x4([50, 250, 110, 2 * 32767 * 32767]));
+        assert_eq!(call("extmul", &[]), 6 << 64 | 0x1_ffff_fffe);
+        assert_eq!(call("narrow", &[]), bytes(&[0, 255, 5, 0, 0, 0, 0, 0, 255]));
+        assert_eq!(call("shr", &[]), bytes(&[0xfe, 0xff, 4]));
+        assert_eq!(call("bitmask", &[]), 0x8005);
+        assert_eq!(call("shuffle", &[]), bytes(&[0xff, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1]));
+        assert_eq!(call("extract", &[]), 16);
+        assert_eq!(call("load_lane", &[]), bytes(&[0, 0, 0, 0, 5, 6]));
+        assert_eq!(call("store_lane", &[Value::V128(i32x4([1, 0x12345678, 3, 4]))]), 0x12345678);
+        assert_eq!(call("trunc_sat", &[]), i32x4([0, i32::MAX, i32::MIN, -2]));
+
+        let fmin = call("fmin", &[]);
+        let lane = |i: u32| f32::from_bits((fmin >> (32 * i)) as u32);
+        assert!(lane(0).is_nan());
+        assert_eq!((lane(1).to_bits(), lane(2).to_bits(), lane(3)), (0x8000_0000, 0x8000_0000, 1.0));
+        let nearest = call("nearest", &[]);
+        assert_eq!((f64::from_bits(nearest as u64), f64::from_bits((nearest >> 64) as u64)), (2.0, -4.0));
+
+        let bad_lane = crate::wat::parse(r#"
+            (module (func (result i32) (i8x16.extract_lane_s 16 (v128.const i64x2 0 0))))
+        "#).unwrap();
+        match validate(&bad_lane) {
+            Err(ValidationError::Function { ref error, .. }) => {
+                assert_eq!(**error, ValidationError::InvalidLaneIndex { lane: 16, lanes: 16 });
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_atomics() {
+        use crate::validation::{validate, ValidationError};
+
+        let m = crate::wat::parse(r#"
+            (module
+              (memory 1 1 shared)
+              (func (export "add") (param i32 i32) (result i32) (i32.atomic.rmw.add (local.get 0) (local.get 1)))
+              (func (export "load") (param i32) (result i32) (i32.atomic.load (local.get 0)))
+              (func (export "cmpxchg8") (param i32 i32 i32) (result i32)
+                (i32.atomic.rmw8.cmpxchg_u (local.get 0) (local.get 1) (local.get 2)))
+              (func (export "xchg") (param i32 i64) (result i64) (i64.atomic.rmw.xchg offset=8 (local.get 0) (local.get 1)))
+              (func (export "wait") (param i32 i32) (result i32) (memory.atomic.wait32 (local.get 0) (local.get 1) (i64.const 0)))
+              (func (export "notify") (param i32) (result i32) (memory.atomic.notify (local.get 0) (i32.const 1)))
+              (func (export "fence") atomic.fence))
+        "#).unwrap();
+
+        let instance = ModuleInstance::new(&m, &ImportsBuilder::default()).unwrap();
+        let call = |name: &str, args: &[Value]| instance.invoke_export(name, args);
+        let i32s = |v: i32| Value::I32(v);
+
+        assert_eq!(call("add", &[i32s(0), i32s(5)]).unwrap(), vec![i32s(0)]);
+        assert_eq!(call("add", &[i32s(0), i32s(3)]).unwrap(), vec![i32s(5)]);
+        assert_eq!(call("load", &[i32s(0)]).unwrap(), vec![i32s(8)]);
+        assert_eq!(trap_kind(call("add", &[i32s(2), i32s(1)])), TrapKind::UnalignedAtomic);
+        assert_eq!(trap_kind(call("load", &[i32s(65536)])), TrapKind::MemoryAccessOutOfBounds);
+
+        // 期望值截断到 8 位后比较
+        assert_eq!(call("cmpxchg8", &[i32s(0), i32s(0x108), i32s(0x1ff)]).unwrap(), vec![i32s(8)]);
+        assert_eq!(call("cmpxchg8", &[i32s(0), i32s(1), i32s(2)]).unwrap(), vec![i32s(0xff)]);
+        assert_eq!(call("load", &[i32s(0)]).unwrap(), vec![i32s(0xff)]);
+        assert_eq!(call("xchg", &[i32s(0), Value::I64(7)]).unwrap(), vec![Value::I64(0)]);
+        assert_eq!(call("xchg", &[i32s(0), Value::I64(-1)]).unwrap(), vec![Value::I64(7)]);
+
+        assert_eq!(call("wait", &[i32s(0), i32s(0xff)]).unwrap(), vec![i32s(2)]);
+        assert_eq!(call("wait", &[i32s(0), i32s(0)]).unwrap(), vec![i32s(1)]);
+        assert_eq!(call("notify", &[i32s(0)]).unwrap(), vec![i32s(0)]);
+        assert_eq!(call("fence", &[]).unwrap(), vec![]);
+
+        let unshared = crate::wat::parse(r#"
+            (module
+              (memory 1)
+              (func (export "wait") (result i32) (memory.atomic.wait64 (i32.const 0) (i64.const 0) (i64.const -1))))
+        "#).unwrap();
+        let instance = ModuleInstance::new(&unshared, &ImportsBuilder::default()).unwrap();
+        assert_eq!(trap_kind(instance.invoke_export("wait", &[])), TrapKind::ExpectedSharedMemory);
+
+        let misaligned = crate::wat::parse(r#"
+            (module (memory 1 1 shared) (func (result i32) (i32.atomic.load align=2 (i32.const 0))))
+        "#).unwrap();
+        match validate(&misaligned) {
+            Err(ValidationError::Function { ref error, .. }) => {
+                assert_eq!(**error, ValidationError::InvalidAtomicAlignment { align: 1, natural: 2 });
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+        let unbounded = crate::wat::parse("(module (memory 1 shared))").unwrap();
+        assert_eq!(validate(&unbounded).err(), Some(ValidationError::InvalidLimits("shared memory must have a maximum")));
+    }
+
+    #[cfg(feature = "memory64")]
+    #[test]
+    fn test_memory64() {
+        use crate::validation::{validate, ValidationError};
+
+        let m = crate::wat::parse(r#"
+            (module
+              (memory i64 1 4)
+              (data (i64.const 8) "ab")
+              (func (export "load") (param i64) (result i32) (i32.load8_u (local.get 0)))
+              (func (export "store") (param i64 i64) (i64.store offset=0x1_0000_0000 (local.get 0) (local.get 1)))
+              (func (export "size") (result i64) memory.size)
+              (func (export "grow") (param i64) (result i64) (memory.grow (local.get 0)))
+              (func (export "fill") (param i64 i32 i64) (memory.fill (local.get 0) (local.get 1) (local.get 2))))
+        "#).unwrap();
+
+        let instance = ModuleInstance::new(&m, &ImportsBuilder::default()).unwrap();
+        let call = |name: &str, args: &[Value]| instance.invoke_export(name, args);
+        let i64s = |v: i64| Value::I64(v);
+
+        assert_eq!(call("load", &[i64s(9)]).unwrap(), vec![Value::I32(b'b' as i32)]);
+        assert_eq!(call("size", &[]).unwrap(), vec![i64s(1)]);
+        assert_eq!(call("grow", &[i64s(1)]).unwrap(), vec![i64s(1)]);
+        assert_eq!(call("grow", &[i64s(3)]).unwrap(), vec![i64s(-1)]);
+        assert_eq!(call("size", &[]).unwrap(), vec![i64s(2)]);
+        call("fill", &[i64s(65536), Value::I32(7), i64s(2)]).unwrap();
+        assert_eq!(call("load", &[i64s(65537)]).unwrap(), vec![Value::I32(7)]);
+
+        // 超过 4 GiB 的地址和溢出的偏移都越界
+        assert_eq!(trap_kind(call("load", &[i64s(1 << 32)])), TrapKind::MemoryAccessOutOfBounds);
+        assert_eq!(trap_kind(call("store", &[i64s(0), i64s(1)])), TrapKind::MemoryAccessOutOfBounds);
+        assert_eq!(trap_kind(call("store", &[i64s(-1), i64s(1)])), TrapKind::MemoryAccessOutOfBounds);
+
+        let mismatched = crate::wat::parse(r#"
+            (module (memory i64 1) (func (result i32) (i32.load (i32.const 0))))
+        "#).unwrap();
+        match validate(&mismatched) {
+            Err(ValidationError::Function { ref error, .. }) => {
+                assert_eq!(**error, ValidationError::TypeMismatch { expected: Some(ValueType::I64), actual: Some(ValueType::I32) });
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+        let far = crate::wat::parse(r#"
+            (module (memory 1) (func (result i32) (i32.load offset=0x1_0000_0000 (i32.const 0))))
+        "#).unwrap();
+        match validate(&far) {
+            Err(ValidationError::Function { ref error, .. }) => {
+                assert_eq!(**error, ValidationError::InvalidOffset(1 << 32));
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+        // 表的 limits 不能带 64 位标志
+        let table: Module = deserialize_buffer(&[0, b'a', b's', b'm', 1, 0, 0, 0, 4, 4, 1, 0x70, 0x04, 1]).unwrap();
+        assert_eq!(validate(&table).err(), Some(ValidationError::InvalidLimits("tables can't be 64-bit")));
+    }
+
+    #[cfg(feature = "multi_memory")]
+    #[test]
+    fn test_multi_memory() {
+        use crate::validation::{validate, ValidationError};
+
+        let m = crate::wat::parse(r#"
+            (module
+              (memory $a 1)
+              (memory $b 1 2)
+              (data (memory $b) (i32.const 0) "xy")
+              (func (export "load_a") (param i32) (result i32) (i32.load8_u (local.get 0)))
+              (func (export "load_b") (param i32) (result i32) (i32.load8_u $b (local.get 0)))
+              (func (export "store_b") (param i32 i32) (i32.store8 1 offset=1 (local.get 0) (local.get 1)))
+              (func (export "size_b") (result i32) (memory.size $b))
+              (func (export "grow_b") (param i32) (result i32) (memory.grow $b (local.get 0)))
+              (func (export "copy") (memory.copy $a $b (i32.const 10) (i32.const 0) (i32.const 2)))
+              (func (export "fill_b") (memory.fill $b (i32.const 5) (i32.const 0x7a) (i32.const 1))))
+        "#).unwrap();
+
+        let instance = ModuleInstance::new(&m, &ImportsBuilder::default()).unwrap();
+        let call = |name: &str, args: &[Value]| instance.invoke_export(name, args);
+        let i32s = |v: i32| Value::I32(v);
+
+        assert_eq!(call("load_b", &[i32s(1)]).unwrap(), vec![i32s(b'y' as i32)]);
+        assert_eq!(call("load_a", &[i32s(1)]).unwrap(), vec![i32s(0)]);
+        call("copy", &[]).unwrap();
+        assert_eq!(call("load_a", &[i32s(11)]).unwrap(), vec![i32s(b'y' as i32)]);
+        call("store_b", &[i32s(3), i32s(9)]).unwrap();
+        assert_eq!(call("load_b", &[i32s(4)]).unwrap(), vec![i32s(9)]);
+        call("fill_b", &[]).unwrap();
+        assert_eq!(call("load_b", &[i32s(5)]).unwrap(), vec![i32s(0x7a)]);
+
+        assert_eq!(call("grow_b", &[i32s(1)]).unwrap(), vec![i32s(1)]);
+        assert_eq!(call("grow_b", &[i32s(1)]).unwrap(), vec![i32s(-1)]);
+        assert_eq!(call("size_b", &[]).unwrap(), vec![i32s(2)]);
+
+        let unknown = crate::wat::parse("(module (memory 1) (func (result i32) (memory.size 1)))").unwrap();
+        match validate(&unknown) {
+            Err(ValidationError::Function { ref error, .. }) => assert_eq!(**error, ValidationError::UnknownMemory(1)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "tail_call")]
+    #[test]
+    fn test_tail_call() {
+        use crate::validation::{validate, ValidationError};
+
+        let m = crate::wat::parse(r#"
+            (module
+              (type $pred (func (param i32) (result i32)))
+              (table 2 funcref)
+              (elem (i32.const 0) $even $odd)
+              (func $even (export "even") (type $pred)
+                (if (result i32) (i32.eqz (local.get 0))
+                  (then (i32.const 1))
+                  (else (return_call $odd (i32.sub (local.get 0) (i32.const 1))))))
+              (func $odd (type $pred)
+                (if (result i32) (i32.eqz (local.get 0))
+                  (then (i32.const 0))
+                  (else (return_call_indirect (type $pred) (i32.sub (local.get 0) (i32.const 1)) (i32.const 0)))))
+              (func $sum (export "sum") (param i64 i64) (result i64)
+                ;; 多余的操作数留在调用者的栈上
+                (i64.const 99)
+                (drop (i64.const 7))
+                (if (i64.eqz (local.get 0)) (then (return (local.get 1))))
+                (return_call $sum
+                  (i64.sub (local.get 0) (i64.const 1))
+                  (i64.add (local.get 1) (local.get 0)))))
+        "#).unwrap();
+
+        let instance = ModuleInstance::new(&m, &ImportsBuilder::default()).unwrap();
+        // 深度远超调用栈限制
+        assert_eq!(instance.invoke_export("even", &[Value::I32(100_001)]).unwrap(), vec![Value::I32(0)]);
+        assert_eq!(instance.invoke_export("even", &[Value::I32(100_000)]).unwrap(), vec![Value::I32(1)]);
+        assert_eq!(
+            instance.invoke_export("sum", &[Value::I64(100_000), Value::I64(0)]).unwrap(),
+            vec![Value::I64(5_000_050_000)]
+        );
+
+        let mismatch = crate::wat::parse(r#"
+            (module
+              (func $f (result i64) (i64.const 0))
+              (func (result i32) (return_call $f)))
+        "#).unwrap();
+        match validate(&mismatch) {
+            Err(ValidationError::Function { ref error, .. }) => assert_eq!(
+                **error,
+                ValidationError::TailCallMismatch { expected: vec![ValueType::I32], actual: vec![ValueType::I64] }
+            ),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "exceptions")]
+    #[test]
+    fn test_exceptions() {
+        use crate::validation::validate;
+
+        let m = crate::wat::parse(r#"
+            (module
+              (import "host" "boom" (func $boom (param i32)))
+              (import "host" "err" (tag $err (param i32)))
+              (tag $e (export "e") (param i32))
+              (tag $other (export "other"))
+              (func $thrower (param i32)
+                (if (i32.eqz (local.get 0)) (then (throw $other)))
+                (throw $e (local.get 0)))
+              ;; 捕获 $e 时返回 payload，其他异常返回 -1
+              (func (export "catch") (param i32) (result i32)
+                (block $all
+                  (block $caught (result i32)
+                    (try_table (catch $e $caught) (catch_all $all)
+                      (call $thrower (local.get 0)))
+                    (return (i32.const 0)))
+                  (return))
+                (i32.const -1))
+              (func (export "rethrow") (param i32) (result i32)
+                (block $h (result i32)
+                  (try_table (result i32) (catch $e $h)
+                    (block $r (result exnref)
+                      (try_table (catch_all_ref $r)
+                        (call $thrower (local.get 0)))
+                      (return (i32.const 0)))
+                    (throw_ref))))
+              (func (export "host") (param i32) (result i32)
+                (block $h (result i32)
+                  (try_table (catch $err $h)
+                    (call $boom (local.get 0)))
+                  (i32.const -1))))
+        "#).unwrap();
+
+        let signature = FunctionType { form: 0x60, params: vec![ValueType::I32], results: vec![] };
+        let err = TagInstance::alloc(signature.clone());
+        let raised = err.clone();
+        let mut linker = Linker::new();
+        linker.tag("host", "err", err).unwrap();
+        linker.func("host", "boom", signature, move |args| {
+            Err(TrapKind::Exception(Exception::alloc(raised.clone(), args.to_vec()).unwrap()).into())
+        }).unwrap();
+        let instance = linker.instantiate(&m).unwrap();
+
+        assert_eq!(instance.invoke_export("catch", &[Value::I32(7)]).unwrap(), vec![Value::I32(7)]);
+        assert_eq!(instance.invoke_export("catch", &[Value::I32(0)]).unwrap(), vec![Value::I32(-1)]);
+        assert_eq!(instance.invoke_export("rethrow", &[Value::I32(5)]).unwrap(), vec![Value::I32(5)]);
+        assert_eq!(instance.invoke_export("host", &[Value::I32(9)]).unwrap(), vec![Value::I32(9)]);
+
+        // $other 没有被捕获，重新抛出的仍是同一个异常
+        let other = match instance.export_by_name("other") {
+            Some(ExternVal::Tag(tag)) => tag,
+            export => panic!("unexpected export {:?}", export),
+        };
+        match trap_kind(instance.invoke_export("rethrow", &[Value::I32(0)])) {
+            TrapKind::Exception(exn) => {
+                assert_eq!(exn.tag(), &other);
+                assert!(exn.payload().is_empty());
+            },
+            kind => panic!("unexpected trap {:?}", kind),
+        }
+        assert!(Exception::alloc(other, vec![Value::I32(1)]).is_err());
+
+        let mismatch = crate::wat::parse(r#"
+            (module
+              (tag $e (param i64))
+              (func (result i32)
+                (block $h (result i32)
+                  (try_table (catch $e $h) (nop))
+                  (i32.const 0))))
+        "#).unwrap();
+        assert!(validate(&mismatch).is_err());
+    }
+
+    #[cfg(feature = "gc")]
+    #[test]
+    fn test_gc() {
+        let m = crate::wat::parse(r#"
+            (module
+              (type $node (sub (struct (field $value (mut i32)) (field $next (ref null $node)))))
+              (type $point (sub final $node (struct (field (mut i32)) (field (ref null $node)) (field $y i8))))
+              (type $bytes (array (mut i8)))
+              (type $binop (func (param i32 i32) (result i32)))
+              (memory 1)
+              (data $d "\01\02\ff")
+              (global $origin (ref $node) (struct.new $node (i32.const 40) (ref.null none)))
+              (elem declare func $add)
+              (func $add (type $binop) (i32.add (local.get 0) (local.get 1)))
+              ;; 构造 n..1 的链表再求和
+              (func (export "sum") (param $n i32) (result i32)
+                (local $list (ref null $node)) (local $cur (ref $node)) (local $total i32)
+                (block $done
+                  (loop $build
+                    (br_if $done (i32.eqz (local.get $n)))
+                    (local.set $list (struct.new $node (local.get $n) (local.get $list)))
+                    (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                    (br $build)))
+                (block $end
+                  (loop $walk
+                    (local.set $cur (br_on_null $end (local.get $list)))
+                    (local.set $total (i32.add (local.get $total) (struct.get $node $value (local.get $cur))))
+                    (local.set $list (struct.get $node $next (local.get $cur)))
+                    (br $walk)))
+                (local.get $total))
+              (func $classify (param $x anyref) (result i32)
+                (if (ref.test (ref $point) (local.get $x)) (then (return (i32.const 3))))
+                (if (ref.test (ref $node) (local.get $x)) (then (return (i32.const 2))))
+                (if (ref.test (ref i31) (local.get $x))
+                  (then (return (i31.get_s (ref.cast (ref i31) (local.get $x))))))
+                (i32.const 0))
+              (func (export "classify") (param i32) (result i32)
+                (call $classify
+                  (if (result anyref) (i32.eqz (local.get 0))
+                    (then (struct.new $point (i32.const 1) (ref.null none) (i32.const 7)))
+                    (else (if (result anyref) (i32.eq (local.get 0) (i32.const 1))
+                      (then (global.get $origin))
+                      (else (if (result anyref) (i32.eq (local.get 0) (i32.const 2))
+                        (then (ref.i31 (i32.const -5)))
+                        (else (ref.null any)))))))))
+              (func (export "unbox") (param i32) (result i32)
+                (block $is_i31 (result (ref i31))
+                  (br_on_cast $is_i31 anyref (ref i31)
+                    (if (result anyref) (local.get 0)
+                      (then (ref.i31 (i32.const 9)))
+                      (else (global.get $origin))))
+                  (drop)
+                  (return (i32.const -1)))
+                (i31.get_u))
+              (func (export "bytes") (param i32) (result i32)
+                (local $a (ref null $bytes))
+                (local.set $a (array.new_data $bytes $d (i32.const 0) (i32.const 3)))
+                (array.set $bytes (local.get $a) (i32.const 0) (i32.const 0x1ff))
+                (i32.add
+                  (i32.mul (array.len (local.get $a)) (i32.const 1000))
+                  (i32.add (array.get_u $bytes (local.get $a) (i32.const 0)) (array.get_s $bytes (local.get $a) (local.get 0)))))
+              (func (export "point_y") (result i32)
+                (struct.get_u $point $y (struct.new $point (i32.const 0) (ref.null none) (i32.const -1))))
+              (func (export "same") (result i32)
+                (ref.eq (global.get $origin) (global.get $origin)))
+              (func (export "call") (param i32 i32) (result i32)
+                (call_ref $binop (local.get 0) (local.get 1) (ref.func $add)))
+              (func (export "bad_cast")
+                (drop (ref.cast (ref $point) (global.get $origin))))
+              (func (export "null_field") (result i32)
+                (struct.get $node $value (ref.null $node)))
+              (func (export "out_of_bounds") (result i32)
+                (array.get_u $bytes (array.new_default $bytes (i32.const 2)) (i32.const 2))))
+        "#).unwrap();
+
+        let instance = Linker::new().instantiate(&m).unwrap();
+        let call = |name: &str, args: &[Value]| instance.invoke_export(name, args).unwrap();
+        assert_eq!(call("sum", &[Value::I32(10)]), vec![Value::I32(55)]);
+        assert_eq!(call("classify", &[Value::I32(0)]), vec![Value::I32(3)]);
+        assert_eq!(call("classify", &[Value::I32(1)]), vec![Value::I32(2)]);
+        assert_eq!(call("classify", &[Value::I32(2)]), vec![Value::I32(-5)]);
+        assert_eq!(call("classify", &[Value::I32(3)]), vec![Value::I32(0)]);
+        assert_eq!(call("unbox", &[Value::I32(1)]), vec![Value::I32(9)]);
+        assert_eq!(call("unbox", &[Value::I32(0)]), vec![Value::I32(-1)]);
+        // 写入 0x1ff 被截断为 0xff，第 2 个字节有符号读出 -1
+        assert_eq!(call("bytes", &[Value::I32(2)]), vec![Value::I32(3000 + 255 - 1)]);
+        assert_eq!(call("bytes", &[Value::I32(1)]), vec![Value::I32(3000 + 255 + 2)]);
+        assert_eq!(call("point_y", &[]), vec![Value::I32(255)]);
+        assert_eq!(call("same", &[]), vec![Value::I32(1)]);
+        assert_eq!(call("call", &[Value::I32(2), Value::I32(3)]), vec![Value::I32(5)]);
+
+        assert_eq!(trap_kind(instance.invoke_export("bad_cast", &[])), TrapKind::CastFailure);
+        assert_eq!(trap_kind(instance.invoke_export("null_field", &[])), TrapKind::NullReference);
+        assert_eq!(trap_kind(instance.invoke_export("out_of_bounds", &[])), TrapKind::ArrayAccessOutOfBounds);
+        assert_eq!(trap_kind(instance.invoke_export("bytes", &[Value::I32(3)])), TrapKind::ArrayAccessOutOfBounds);
+    }
+
+    #[test]
+    fn test_extended_const() {
+        let m = crate::wat::parse(r#"
+            (module
+              (import "env" "base" (global $base i32))
+              (memory 1)
+              (global $end (export "end") i32 (i32.add (global.get $base) (i32.const 3)))
+              (global (export "scaled") i64 (i64.mul (i64.const 1000) (i64.sub (i64.const 0) (i64.const 7))))
+              (data (i32.sub (i32.mul (global.get $base) (i32.const 2)) (i32.const 1)) "xy")
+              (func (export "load") (param i32) (result i32)
+                (i32.load8_u (local.get 0))))
+        "#).unwrap();
+
+        let mut linker = Linker::new();
+        linker.global("env", "base", GlobalInstance::alloc(Value::I32(8), false)).unwrap();
+        let instance = linker.instantiate(&m).unwrap();
+        let global = |name: &str| match instance.export_by_name(name) {
+            Some(ExternVal::Global(global)) => global.get(),
+            other => panic!("unexpected export {:?}", other),
+        };
+        assert_eq!(global("end"), Value::I32(11));
+        assert_eq!(global("scaled"), Value::I64(-7000));
+        assert_eq!(instance.invoke_export("load", &[Value::I32(15)]).unwrap(), vec![Value::I32(b'x' as i32)]);
+        assert_eq!(instance.invoke_export("load", &[Value::I32(16)]).unwrap(), vec![Value::I32(b'y' as i32)]);
+    }
+
+    #[test]
+    fn test_trap_backtrace() {
+        use crate::elements::ops::Instruction::*;
+        use crate::elements::name_section::NameSection;
+        use crate::elements::sections::{CustomSection, Section};
+
+        // f(n) = n == 0 ? unreachable : f(n - 1)
+        let mut m = single_func_module(
+            vec![ValueType::I32],
+            vec![],
+            vec![],
+            vec![
+                GetLocal(0),
+                I32Eqz,
+                If(BlockType::NoResult),
+                Unreachable,
+                End,
+                GetLocal(0),
+                I32Const(1),
+                I32Sub,
+                Call(0),
+                End,
+            ],
+        );
+        let mut names = NameSection::default();
+        names.functions.insert(0, "countdown".to_string());
+        m.sections.push(Section::Custom(CustomSection {
+            name: NameSection::NAME.to_string(),
+            payload: crate::elements::serialize(&names).unwrap(),
+        }));
+
+        let trap = match run(&m, &[Value::I32(2)]) {
+            Err(Error::Trap(trap)) => trap,
+            other => panic!("expected a trap, got {:?}", other),
+        };
+        assert_eq!(trap.kind(), &TrapKind::Unreachable);
+        assert_eq!(trap.backtrace().len(), 3);
+        assert_eq!(trap.backtrace()[0], TraceFrame { func_index: 0, name: Some("countdown".to_string()) });
+        assert_eq!(trap.to_string(), "trap: unreachable executed\n  at func[0] <countdown>\n  at func[0] <countdown>\n  at func[0] <countdown>");
+
+        match run(&m, &[Value::I32(1_000_000)]) {
+            Err(Error::Trap(trap)) => assert_eq!(trap.backtrace().len(), 64),
+            other => panic!("expected a trap, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "multi_value")]
+    #[test]
+    fn test_multi_value() {
+        let m = crate::wat::parse(r#"
+            (module
+              (func $swap (export "swap") (param i32 i32) (result i32 i32)
+                local.get 1
+                local.get 0)
+              (func (export "pair") (param i32) (result i32 i32)
+                local.get 0
+                (block (param i32) (result i32 i32)
+                  i32.const 10)
+                call $swap)
+              ;; 循环参数作为累加器：sum(1..=n)
+              (func (export "sum") (param $n i32) (result i32)
+                i32.const 0
+                (loop $again (param i32) (result i32)
+                  local.get $n
+                  i32.add
+                  (local.tee $n (i32.sub (local.get $n) (i32.const 1)))
+                  br_if $again)))
+        "#).unwrap();
+
+        let instance = ModuleInstance::new(&m, &ImportsBuilder::default()).unwrap();
+        assert_eq!(instance.invoke_export("swap", &[Value::I32(1), Value::I32(2)]).unwrap(), vec![Value::I32(2), Value::I32(1)]);
+        assert_eq!(instance.invoke_export("pair", &[Value::I32(7)]).unwrap(), vec![Value::I32(10), Value::I32(7)]);
+        assert_eq!(instance.invoke_export("sum", &[Value::I32(100)]).unwrap(), vec![Value::I32(5050)]);
+
+        // 块类型引用的类型必须存在
+        let invalid = single_func_module(vec![], vec![], vec![], vec![Instruction::Block(BlockType::TypeIndex(5)), Instruction::End, Instruction::End]);
+        assert!(crate::validation::validate(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_host_functions() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let m: Module = deserialize_buffer(SAMPLE_MODULE).unwrap();
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+
+        let mut linker = Linker::new();
+        linker.func("env", "f", FunctionType { form: 0x60, params: vec![], results: vec![] }, move |_| {
+            counter.set(counter.get() + 1);
+            Ok(vec![])
+        }).unwrap();
+        let instance = linker.instantiate(&m).unwrap();
+        // start 函数调用了一次 env.f
+        assert_eq!(calls.get(), 1);
+
+        let mut other = Linker::new();
+        other.instance("sample", &instance).unwrap();
+        match other.get("sample", "add") {
+            Some(ExternVal::Func(add)) => {
+                assert_eq!(FuncInstance::invoke(add, &[Value::I32(2), Value::I32(3)]).unwrap(), vec![Value::I32(5)]);
+            },
+            other => panic!("unexpected definition {:?}", other),
+        }
+
+        let failing = FuncInstance::alloc_host(FunctionType::default(), |_| Err(TrapKind::Host("boom".to_string()).into()));
+        assert_eq!(FuncInstance::invoke(&failing, &[]).unwrap_err().kind(), &TrapKind::Host("boom".to_string()));
+    }
+
+    #[test]
+    fn test_link_errors() {
+        use crate::elements::import_entry::GlobalType;
+
+        let m: Module = deserialize_buffer(SAMPLE_MODULE).unwrap();
+        let link_error = |linker: &Linker| match linker.instantiate(&m) {
+            Err(Error::Link(e)) => *e,
+            other => panic!("expected a link error, got {:?}", other),
+        };
+
+        let linker = Linker::new();
+        assert_eq!(link_error(&linker), LinkError::UnknownImport {
+            module: "env".to_string(),
+            field: "f".to_string(),
+            kind: ExternKind::Func,
+        });
+
+        let mut linker = Linker::new();
+        linker.global("env", "f", GlobalInstance::alloc(Value::I32(0), false)).unwrap();
+        assert_eq!(link_error(&linker), LinkError::KindMismatch {
+            module: "env".to_string(),
+            field: "f".to_string(),
+            expected: ExternKind::Func,
+            actual: ExternKind::Global,
+        });
+
+        let mut linker = Linker::new();
+        let signature = FunctionType { form: 0x60, params: vec![ValueType::I32], results: vec![] };
+        linker.func("env", "f", signature.clone(), |_| Ok(vec![])).unwrap();
+        match link_error(&linker) {
+            LinkError::SignatureMismatch { actual, .. } => assert_eq!(actual, signature),
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(linker.func("env", "f", signature, |_| Ok(vec![])).is_err());
+
+        // 导入的 memory 需要满足声明的 limits
+        let mut imports = Module::default();
+        imports.sections.push(Section::Import(ImportSection(vec![
+            ImportEntry {
+                module_str: "env".to_string(),
+                field_str: "mem".to_string(),
+                external: External::Memory(ResizableLimits { initial: 2, maximum: Some(4), shared: false, memory64: false }),
+            },
+            ImportEntry {
+                module_str: "env".to_string(),
+                field_str: "g".to_string(),
+                external: External::Global(GlobalType { content_type: ValueType::I64, is_mutable: true }),
+            },
+        ])));
+        let module_error = |linker: &Linker| match linker.instantiate(&imports) {
+            Err(Error::Link(e)) => *e,
+            other => panic!("expected a link error, got {:?}", other),
+        };
+
+        let mut linker = Linker::new();
+        linker.memory("env", "mem", MemoryInstance::alloc(1, Some(4)).unwrap()).unwrap();
+        match module_error(&linker) {
+            LinkError::MemoryLimitsMismatch { actual, .. } => assert_eq!(actual.initial, 1),
+            other => panic!("unexpected error {:?}", other),
+        }
+
+        let mut linker = Linker::new();
+        linker.memory("env", "mem", MemoryInstance::alloc(3, None).unwrap()).unwrap();
+        assert!(matches!(module_error(&linker), LinkError::MemoryLimitsMismatch { .. }));
+
+        let mut linker = Linker::new();
+        linker.memory("env", "mem", MemoryInstance::alloc(3, Some(4)).unwrap()).unwrap();
+        linker.global("env", "g", GlobalInstance::alloc(Value::I64(0), false)).unwrap();
+        assert!(matches!(module_error(&linker), LinkError::GlobalTypeMismatch { .. }));
+
+        let mut linker = Linker::new();
+        linker.memory("env", "mem", MemoryInstance::alloc(3, Some(4)).unwrap()).unwrap();
+        linker.global("env", "g", GlobalInstance::alloc(Value::I64(0), true)).unwrap();
+        linker.instantiate(&imports).unwrap();
+    }
+}
@@ -0,0 +1,548 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
+
+use crate::elements::module::Module;
+use crate::elements::ops::InitExpr;
+use crate::elements::const_expr::{ConstEnv, ConstExprError, ConstValue};
+use crate::elements::types::{FunctionType, TableElementType};
+use crate::elements::import_entry::{External, GlobalType, ResizableLimits, TableType};
+use crate::elements::export_entry::Internal;
+use crate::elements::name_section::NameMap;
+use crate::validation::{validate, ModuleContext, ValidatedModule};
+use super::func::{signature_matches, FuncBody};
+use super::linker::limits_match;
+use super::{
+    Error, Value, FuncInstance, FuncRef, TableInstance, TableRef, MemoryInstance, MemoryRef,
+    GlobalInstance, GlobalRef, ImportResolver, LinkError, Trap, TrapKind,
+};
+#[cfg(feature = "exceptions")]
+use super::{TagInstance, TagRef};
+#[cfg(feature = "gc")]
+use crate::elements::gc::GcInstruction;
+#[cfg(feature = "gc")]
+use super::gc;
+
+/// Exported or imported entity.
+#[derive(Clone, Debug)]
+pub enum ExternVal {
+    Func(FuncRef),
+    Table(TableRef),
+    Memory(MemoryRef),
+    Global(GlobalRef),
+    #[cfg(feature = "exceptions")]
+    Tag(TagRef),
+}
+
+/// Kind of an imported or exported entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternKind {
+    Func,
+    Table,
+    Memory,
+    Global,
+    #[cfg(feature = "exceptions")]
+    Tag,
+}
+
+impl ExternKind {
+    pub fn of_external(external: &External) -> ExternKind {
+        match *external {
+            External::Function(_) => ExternKind::Func,
+            External::Table(_) => ExternKind::Table,
+            External::Memory(_) => ExternKind::Memory,
+            External::Global(_) => ExternKind::Global,
+            #[cfg(feature = "exceptions")]
+            External::Tag(_) => ExternKind::Tag,
+        }
+    }
+}
+
+impl fmt::Display for ExternKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            ExternKind::Func => "function",
+            ExternKind::Table => "table",
+            ExternKind::Memory => "memory",
+            ExternKind::Global => "global",
+            #[cfg(feature = "exceptions")]
+            ExternKind::Tag => "tag",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl ExternVal {
+    pub fn kind(&self) -> ExternKind {
+        match *self {
+            ExternVal::Func(_) => ExternKind::Func,
+            ExternVal::Table(_) => ExternKind::Table,
+            ExternVal::Memory(_) => ExternKind::Memory,
+            ExternVal::Global(_) => ExternKind::Global,
+            #[cfg(feature = "exceptions")]
+            ExternVal::Tag(_) => ExternKind::Tag,
+        }
+    }
+}
+
+/// Reference to a module instance.
+#[derive(Clone, Debug)]
+pub struct ModuleRef(Rc<ModuleInstance>);
+
+impl Deref for ModuleRef {
+    type Target = ModuleInstance;
+    fn deref(&self) -> &ModuleInstance {
+        &self.0
+    }
+}
+
+/// Instantiated module, owning its index spaces.
+#[derive(Debug, Default)]
+pub struct ModuleInstance {
+    /// Index spaces from validation, defined types are resolved in them at runtime.
+    context: RefCell<Rc<ModuleContext>>,
+    /// Function types, `None` for struct and array types.
+    types: RefCell<Vec<Option<Rc<FunctionType>>>>,
+    funcs: RefCell<Vec<FuncRef>>,
+    tables: RefCell<Vec<TableRef>>,
+    memories: RefCell<Vec<MemoryRef>>,
+    globals: RefCell<Vec<GlobalRef>>,
+    #[cfg(feature = "exceptions")]
+    tags: RefCell<Vec<TagRef>>,
+    exports: RefCell<HashMap<String, ExternVal>>,
+    /// Element and data segments, emptied once dropped.
+    elem_segments: RefCell<Vec<Vec<Value>>>,
+    data_segments: RefCell<Vec<Vec<u8>>>,
+    /// Function names from the `name` section, used in trap backtraces.
+    func_names: RefCell<NameMap>,
+}
+
+impl ModuleInstance {
+    /// Validate and instantiate `module`, then run its start function.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<I: ImportResolver>(module: &Module, imports: &I) -> Result<ModuleRef, Error> {
+        let validated = validate(module)?;
+        ModuleInstance::instantiate(&validated, imports)
+    }
+
+    /// Instantiate an already validated module, then run its start function.
+    pub fn instantiate<I: ImportResolver>(validated: &ValidatedModule, imports: &I) -> Result<ModuleRef, Error> {
+        let module = validated.module();
+        let instance = ModuleRef(Rc::new(ModuleInstance::default()));
+
+        *instance.context.borrow_mut() = Rc::new(validated.context().clone());
+        {
+            let mut types = instance.types.borrow_mut();
+            if let Some(section) = module.type_section() {
+                types.extend(section.types().map(|t| t.func_type().cloned().map(Rc::new)));
+            }
+        }
+
+        if let Ok(Some(names)) = module.name_section() {
+            *instance.func_names.borrow_mut() = names.functions;
+        }
+
+        instance.resolve_imports(module, imports)?;
+        instance.alloc_entities(module, Rc::downgrade(&instance.0))?;
+        instance.collect_exports(module);
+        instance.init_segments(module)?;
+
+        if let Some(start) = module.start_section() {
+            let func = instance.func_by_index(start).expect("start function index is validated");
+            FuncInstance::invoke(&func, &[])?;
+        }
+
+        Ok(instance)
+    }
+
+    fn resolve_imports<I: ImportResolver>(&self, module: &Module, imports: &I) -> Result<(), Error> {
+        let entries = match module.import_section() {
+            Some(section) => &section.0,
+            None => return Ok(()),
+        };
+
+        for entry in entries.iter() {
+            let (module_name, field_name) = (&entry.module_str[..], &entry.field_str[..]);
+            match entry.external {
+                External::Function(type_idx) => {
+                    let signature = self.type_by_index(type_idx).expect("type index is validated");
+                    let func = imports.resolve_func(module_name, field_name, &signature)?;
+                    if !signature_matches(func.signature(), &signature) {
+                        return Err(LinkError::SignatureMismatch {
+                            module: module_name.to_string(),
+                            field: field_name.to_string(),
+                            expected: (*signature).clone(),
+                            actual: func.signature().clone(),
+                        }.into());
+                    }
+                    self.funcs.borrow_mut().push(func);
+                },
+                External::Table(ref table_type) => {
+                    let table = imports.resolve_table(module_name, field_name, table_type)?;
+                    let actual = TableType {
+                        elem_type: table.elem_type(),
+                        limits: ResizableLimits {
+                            initial: table.current_size() as u64,
+                            maximum: table.maximum().map(u64::from),
+                            shared: false,
+                            memory64: false,
+                        },
+                    };
+                    if actual.elem_type != table_type.elem_type || !limits_match(&table_type.limits, &actual.limits) {
+                        return Err(LinkError::TableTypeMismatch {
+                            module: module_name.to_string(),
+                            field: field_name.to_string(),
+                            expected: *table_type,
+                            actual,
+                        }.into());
+                    }
+                    self.tables.borrow_mut().push(table);
+                },
+                External::Memory(ref limits) => {
+                    let memory = imports.resolve_memory(module_name, field_name, limits)?;
+                    let actual = memory.limits();
+                    if !limits_match(limits, &actual) {
+                        return Err(LinkError::MemoryLimitsMismatch {
+                            module: module_name.to_string(),
+                            field: field_name.to_string(),
+                            expected: *limits,
+                            actual,
+                        }.into());
+                    }
+                    self.memories.borrow_mut().push(memory);
+                },
+                External::Global(ref global_type) => {
+                    let global = imports.resolve_global(module_name, field_name, global_type)?;
+                    let actual = GlobalType { content_type: global.value_type(), is_mutable: global.is_mutable() };
+                    let matches = actual.is_mutable == global_type.is_mutable
+                        && global.get().has_type(global_type.content_type, Some(&self.types()));
+                    if !matches {
+                        return Err(LinkError::GlobalTypeMismatch {
+                            module: module_name.to_string(),
+                            field: field_name.to_string(),
+                            expected: global_type.clone(),
+                            actual,
+                        }.into());
+                    }
+                    self.globals.borrow_mut().push(global);
+                },
+                #[cfg(feature = "exceptions")]
+                External::Tag(ref tag_type) => {
+                    let signature = self.type_by_index(tag_type.type_index).expect("type index is validated");
+                    let tag = imports.resolve_tag(module_name, field_name, &signature)?;
+                    if !signature_matches(tag.signature(), &signature) {
+                        return Err(LinkError::TagTypeMismatch {
+                            module: module_name.to_string(),
+                            field: field_name.to_string(),
+                            expected: (*signature).clone(),
+                            actual: tag.signature().clone(),
+                        }.into());
+                    }
+                    self.tags.borrow_mut().push(tag);
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn alloc_entities(&self, module: &Module, weak: Weak<ModuleInstance>) -> Result<(), Error> {
+        let imported_funcs = self.funcs.borrow().len();
+
+        let signatures = module.function_section().map(|s| &s.0[..]).unwrap_or(&[]);
+        let bodies = module.code_section().map(|s| &s.0[..]).unwrap_or(&[]);
+        for (i, (func, body)) in signatures.iter().zip(bodies.iter()).enumerate() {
+            let signature = self.type_by_index(func.0).expect("type index is validated");
+            #[cfg(feature = "gc")]
+            let types = self.types();
+            let locals = body.locals
+                .iter()
+                .flat_map(|l| {
+                    #[cfg(feature = "gc")]
+                    let value_type = gc::runtime_type(&types, l.value_type);
+                    #[cfg(not(feature = "gc"))]
+                    let value_type = l.value_type;
+                    std::iter::repeat_n(value_type, l.count as usize)
+                })
+                .collect();
+            let body = FuncBody::new(locals, body.instructions.elements().to_vec());
+            let index = (imported_funcs + i) as u32;
+            let func = FuncInstance::alloc_internal(weak.clone(), signature, body, index);
+            self.funcs.borrow_mut().push(func);
+        }
+
+        if let Some(section) = module.table_section() {
+            for table_type in section.0.iter() {
+                let table = TableInstance::alloc_filled(
+                    table_type.elem_type,
+                    self.null(table_type.elem_type),
+                    table_type.limits.initial as u32,
+                    table_type.limits.maximum.map(|m| m as u32),
+                );
+                self.tables.borrow_mut().push(table);
+            }
+        }
+
+        if let Some(section) = module.memory_section() {
+            for limits in section.0.iter() {
+                let memory = MemoryInstance::from_limits(limits)?;
+                self.memories.borrow_mut().push(memory);
+            }
+        }
+
+        #[cfg(feature = "exceptions")]
+        if let Some(section) = module.tag_section() {
+            for tag_type in section.0.iter() {
+                let signature = self.type_by_index(tag_type.type_index).expect("type index is validated");
+                self.tags.borrow_mut().push(TagInstance::alloc_shared(signature));
+            }
+        }
+
+        if let Some(section) = module.global_section() {
+            for entry in section.0.iter() {
+                let value = self.eval_init_expr(&entry.init_expr);
+                let global = GlobalInstance::alloc(value, entry.global_type.is_mutable);
+                self.globals.borrow_mut().push(global);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn collect_exports(&self, module: &Module) {
+        let entries = match module.export_section() {
+            Some(section) => &section.0,
+            None => return,
+        };
+
+        let mut exports = self.exports.borrow_mut();
+        for entry in entries.iter() {
+            let value = match entry.internal {
+                Internal::Function(idx) => ExternVal::Func(self.func_by_index(idx).expect("export index is validated")),
+                Internal::Table(idx) => ExternVal::Table(self.table_by_index(idx).expect("export index is validated")),
+                Internal::Memory(idx) => ExternVal::Memory(self.memory_by_index(idx).expect("export index is validated")),
+                Internal::Global(idx) => ExternVal::Global(self.global_by_index(idx).expect("export index is validated")),
+                #[cfg(feature = "exceptions")]
+                Internal::Tag(idx) => ExternVal::Tag(self.tag_by_index(idx).expect("export index is validated")),
+            };
+            exports.insert(entry.field_str.clone(), value);
+        }
+    }
+
+    fn init_segments(&self, module: &Module) -> Result<(), Error> {
+        let elements = module.elements_section().map(|s| &s.0[..]).unwrap_or(&[]);
+        for segment in elements.iter() {
+            let values: Vec<Value> = match segment.exprs {
+                Some(ref exprs) => exprs.iter().map(|e| self.eval_init_expr(e)).collect(),
+                None => segment.members
+                    .iter()
+                    .map(|&idx| Value::FuncRef(Some(self.func_by_index(idx).expect("function index is validated"))))
+                    .collect(),
+            };
+            self.elem_segments.borrow_mut().push(if segment.is_passive() { values.clone() } else { Vec::new() });
+
+            // active 段写入后即被丢弃，declarative 段直接丢弃
+            if let Some(ref expr) = segment.offset {
+                let offset = self.eval_offset(expr);
+                let table = self.table_by_index(segment.index).expect("table index is validated");
+                table.set_range(offset, &values)?;
+            }
+        }
+
+        let data = module.data_section().map(|s| &s.0[..]).unwrap_or(&[]);
+        for segment in data.iter() {
+            let value = if segment.offset.is_none() { segment.value.clone() } else { Vec::new() };
+            self.data_segments.borrow_mut().push(value);
+
+            if let Some(ref expr) = segment.offset {
+                let memory = self.memory_by_index(segment.index).expect("memory index is validated");
+                // 64 位内存的偏移是 i64
+                let offset = match self.eval_init_expr(expr) {
+                    Value::I64(v) if memory.is_64() => v as u64,
+                    Value::I32(v) => v as u32 as u64,
+                    other => unreachable!("offset {:?} of a validated data segment has the wrong type", other),
+                };
+                memory.set(offset, &segment.value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Evaluate a validated constant expression.
+    fn eval_init_expr(&self, expr: &InitExpr) -> Value {
+        match expr.evaluate(self) {
+            Ok(value) => value.into(),
+            Err(e) => unreachable!("validated constant expression failed: {}", e),
+        }
+    }
+
+    fn eval_offset(&self, expr: &InitExpr) -> u32 {
+        match self.eval_init_expr(expr) {
+            Value::I32(v) => v as u32,
+            other => unreachable!("offset {:?} of a validated segment is not i32", other),
+        }
+    }
+
+    pub fn export_by_name(&self, name: &str) -> Option<ExternVal> {
+        self.exports.borrow().get(name).cloned()
+    }
+
+    /// All exports of the instance.
+    pub fn exports(&self) -> Vec<(String, ExternVal)> {
+        self.exports.borrow().iter().map(|(name, value)| (name.clone(), value.clone())).collect()
+    }
+
+    pub fn func_by_index(&self, idx: u32) -> Option<FuncRef> {
+        self.funcs.borrow().get(idx as usize).cloned()
+    }
+
+    pub fn table_by_index(&self, idx: u32) -> Option<TableRef> {
+        self.tables.borrow().get(idx as usize).cloned()
+    }
+
+    pub fn memory_by_index(&self, idx: u32) -> Option<MemoryRef> {
+        self.memories.borrow().get(idx as usize).cloned()
+    }
+
+    pub fn global_by_index(&self, idx: u32) -> Option<GlobalRef> {
+        self.globals.borrow().get(idx as usize).cloned()
+    }
+
+    #[cfg(feature = "exceptions")]
+    pub fn tag_by_index(&self, idx: u32) -> Option<TagRef> {
+        self.tags.borrow().get(idx as usize).cloned()
+    }
+
+    /// Copy `len` bytes of data segment `segment` from `src` into `memory` at `dst`.
+    pub(crate) fn memory_init(&self, memory: &MemoryRef, segment: u32, dst: u64, src: u32, len: u32) -> Result<(), Trap> {
+        let segments = self.data_segments.borrow();
+        let data = &segments[segment as usize];
+        let end = src as u64 + len as u64;
+        if end > data.len() as u64 {
+            return Err(TrapKind::MemoryAccessOutOfBounds.into());
+        }
+        memory.set(dst, &data[src as usize..end as usize])
+    }
+
+    /// Copy `len` bytes of data segment `segment` starting at `src`.
+    #[cfg(feature = "gc")]
+    pub(crate) fn data_range(&self, segment: u32, src: u32, len: u64) -> Result<Vec<u8>, Trap> {
+        let segments = self.data_segments.borrow();
+        let data = &segments[segment as usize];
+        let end = src as u64 + len;
+        if end > data.len() as u64 {
+            return Err(TrapKind::MemoryAccessOutOfBounds.into());
+        }
+        Ok(data[src as usize..end as usize].to_vec())
+    }
+
+    /// Copy `len` elements of element segment `segment` starting at `src`.
+    #[cfg(feature = "gc")]
+    pub(crate) fn elem_range(&self, segment: u32, src: u32, len: u32) -> Result<Vec<Value>, Trap> {
+        let segments = self.elem_segments.borrow();
+        let elements = &segments[segment as usize];
+        let end = src as u64 + len as u64;
+        if end > elements.len() as u64 {
+            return Err(TrapKind::TableAccessOutOfBounds.into());
+        }
+        Ok(elements[src as usize..end as usize].to_vec())
+    }
+
+    pub(crate) fn data_drop(&self, segment: u32) {
+        self.data_segments.borrow_mut()[segment as usize] = Vec::new();
+    }
+
+    /// Copy `len` elements of element segment `segment` from `src` into `table` at `dst`.
+    pub(crate) fn table_init(&self, table: &TableRef, segment: u32, dst: u32, src: u32, len: u32) -> Result<(), Trap> {
+        let segments = self.elem_segments.borrow();
+        let elements = &segments[segment as usize];
+        let end = src as u64 + len as u64;
+        if end > elements.len() as u64 {
+            return Err(TrapKind::TableAccessOutOfBounds.into());
+        }
+        table.set_range(dst, &elements[src as usize..end as usize])
+    }
+
+    pub(crate) fn elem_drop(&self, segment: u32) {
+        self.elem_segments.borrow_mut()[segment as usize] = Vec::new();
+    }
+
+    /// Name of the function `idx` given by the `name` section.
+    pub fn func_name(&self, idx: u32) -> Option<String> {
+        self.func_names.borrow().get(&idx).cloned()
+    }
+
+    /// Function type `idx`, `None` if it is out of bounds or a struct or array type.
+    pub fn type_by_index(&self, idx: u32) -> Option<Rc<FunctionType>> {
+        self.types.borrow().get(idx as usize).cloned().flatten()
+    }
+
+    /// Index spaces of the module, used to check values of defined types.
+    pub(crate) fn types(&self) -> Rc<ModuleContext> {
+        self.context.borrow().clone()
+    }
+
+    /// Invoke the exported function `name` with `args`.
+    pub fn invoke_export(&self, name: &str, args: &[Value]) -> Result<Vec<Value>, Error> {
+        let func = match self.export_by_name(name) {
+            Some(ExternVal::Func(func)) => func,
+            Some(_) => return Err(Error::Function(format!("Export {} is not a function", name))),
+            None => return Err(Error::Function(format!("Export {} not found", name))),
+        };
+
+        let signature = func.signature();
+        let types = func.types();
+        let matches = args.len() == signature.params.len()
+            && args.iter().zip(signature.params.iter()).all(|(v, &t)| v.has_type(t, types.as_deref()));
+        if !matches {
+            let arg_types: Vec<_> = args.iter().map(Value::value_type).collect();
+            return Err(Error::Function(format!(
+                "Function {} expects arguments {:?}, got {:?}", name, signature.params, arg_types
+            )));
+        }
+
+        Ok(FuncInstance::invoke(&func, args)?)
+    }
+}
+
+/// Constant expressions read the globals and functions allocated so far.
+impl ConstEnv for ModuleInstance {
+    type Ref = Value;
+
+    fn global(&self, idx: u32) -> Result<ConstValue<Value>, ConstExprError> {
+        let global = self.global_by_index(idx).ok_or(ConstExprError::UnknownGlobal(idx))?;
+        if global.is_mutable() {
+            return Err(ConstExprError::MutableGlobal(idx));
+        }
+        Ok(global.get().into())
+    }
+
+    fn null(&self, elem_type: TableElementType) -> Value {
+        match elem_type {
+            #[cfg(feature = "gc")]
+            TableElementType::Ref(ref_type) => gc::null_of(&self.types(), ref_type.heap),
+            _ => Value::default(elem_type.value_type()),
+        }
+    }
+
+    fn func(&self, idx: u32) -> Result<Value, ConstExprError> {
+        let func = self.func_by_index(idx).ok_or(ConstExprError::UnknownFunction(idx))?;
+        Ok(Value::FuncRef(Some(func)))
+    }
+
+    /// Allocations run on a stack of values like in function bodies.
+    #[cfg(feature = "gc")]
+    fn gc(&self, instruction: &GcInstruction, stack: &mut Vec<ConstValue<Value>>) -> Result<ConstValue<Value>, ConstExprError> {
+        let mut values: Vec<Value> = stack.drain(..).map(Value::from).collect();
+        match gc::execute(instruction, &mut values, self) {
+            Ok(()) => {},
+            Err(trap) => unreachable!("constant instruction {:?} trapped: {}", instruction, trap),
+        }
+        let result = values.pop().expect("constant instructions push a value");
+        stack.extend(values.into_iter().map(ConstValue::from));
+        Ok(result.into())
+    }
+}
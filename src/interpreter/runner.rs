@@ -0,0 +1,941 @@
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use crate::elements::const_expr::ConstEnv;
+use crate::elements::types::BlockType;
+use super::func::{invoke_host, signature_matches, FuncBody, FuncInstanceInternal};
+use super::memory::{effective_address, pop_address, push_address, LittleEndian};
+use super::module::ModuleInstance;
+use super::{Value, FromValue, FuncRef, MemoryRef, TraceFrame, Trap, TrapKind};
+#[cfg(feature = "exceptions")]
+use crate::elements::ops::{Catch, Instruction};
+#[cfg(feature = "exceptions")]
+use super::{Exception, ExnRef};
+#[cfg(feature = "gc")]
+use crate::elements::gc::{BrOnCastData, GcInstruction};
+
+/// Maximum number of nested calls.
+pub const DEFAULT_CALL_STACK_LIMIT: usize = 16 * 1024;
+
+/// Maximum number of values on the operand stack, locals excluded.
+pub const DEFAULT_VALUE_STACK_LIMIT: usize = 1024 * 1024;
+
+/// Maximum number of frames recorded in the backtrace of a trap.
+const BACKTRACE_LIMIT: usize = 64;
+
+/// Branch target.
+#[derive(Debug, Clone, Copy)]
+struct Label {
+    /// Number of values a branch carries to the target.
+    arity: usize,
+    /// Operand stack height at the start of the block.
+    height: usize,
+    /// Position to continue at after a branch.
+    target: usize,
+    /// Branching to a loop keeps its label.
+    is_loop: bool,
+    /// Position of the `try_table` whose handlers are active inside the block.
+    #[cfg(feature = "exceptions")]
+    try_table: Option<usize>,
+}
+
+/// Activation of a wasm function.
+struct Frame {
+    module: Rc<ModuleInstance>,
+    func_index: u32,
+    body: Rc<FuncBody>,
+    memory: Option<MemoryRef>,
+    locals: Vec<Value>,
+    labels: Vec<Label>,
+    pc: usize,
+}
+
+impl Frame {
+    /// Memory `idx` of the module, memory 0 is cached in the frame.
+    fn memory(&self, idx: u32) -> MemoryRef {
+        match self.memory {
+            Some(ref memory) if idx == 0 => memory.clone(),
+            _ => self.module.memory_by_index(idx).expect("memory index is validated"),
+        }
+    }
+
+    /// Number of parameters and results of a block, only type indices need the module.
+    fn block_arity(&self, block_type: &BlockType) -> (usize, usize) {
+        match *block_type {
+            BlockType::NoResult => (0, 0),
+            BlockType::Value(_) => (0, 1),
+            #[cfg(feature = "multi_value")]
+            BlockType::TypeIndex(idx) => {
+                let ty = self.module.type_by_index(idx).expect("block type index is validated");
+                (ty.params.len(), ty.results.len())
+            },
+        }
+    }
+}
+
+#[cfg(feature = "exceptions")]
+impl Frame {
+    /// Innermost handler of the frame catching `exn`, with the position of its `try_table` label.
+    fn handler(&self, exn: &ExnRef) -> Option<(usize, Catch)> {
+        for (idx, label) in self.labels.iter().enumerate().rev() {
+            let data = match label.try_table.map(|pc| &self.body.code[pc]) {
+                Some(Instruction::TryTable(data)) => data,
+                _ => continue,
+            };
+            let caught = data.catches.iter().find(|catch| match catch.tag() {
+                Some(tag) => self.module.tag_by_index(tag).as_ref() == Some(exn.tag()),
+                None => true,
+            });
+            if let Some(catch) = caught {
+                return Some((idx, *catch));
+            }
+        }
+        None
+    }
+}
+
+/// What the interpreter has to do after running a frame.
+enum Outcome {
+    /// The frame finished, its results are on top of the stack.
+    Return,
+    /// The frame called a function, arguments are on top of the stack.
+    Call(FuncRef),
+    /// The frame tail called a function, arguments are on top of the stack.
+    #[cfg(feature = "tail_call")]
+    TailCall(FuncRef),
+}
+
+/// Executes wasm functions using an explicit call stack,
+/// so deep recursion in wasm does not exhaust the native stack.
+pub(crate) struct Interpreter {
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter {
+            stack: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Run `func` to completion, `args` must already be checked against its signature.
+    pub fn invoke(&mut self, func: &FuncRef, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        self.stack.extend(args.iter().cloned());
+        self.call(func.clone(), None)?;
+        self.run()?;
+        Ok(self.stack.drain(..).collect())
+    }
+
+    fn run(&mut self) -> Result<(), Trap> {
+        while let Some(mut frame) = self.frames.pop() {
+            let result = match self.run_frame(&mut frame) {
+                Ok(Outcome::Return) => Ok(()),
+                Ok(Outcome::Call(func)) => {
+                    let caller = frame.module.clone();
+                    self.frames.push(frame);
+                    self.call(func, Some(&*caller))
+                },
+                #[cfg(feature = "tail_call")]
+                Ok(Outcome::TailCall(func)) => {
+                    // 调用者的帧不再入栈，只保留参数，调用深度和栈高度都不增长
+                    let args = self.stack.len() - func.signature().params.len();
+                    self.stack.drain(frame.labels[0].height..args);
+                    self.call(func, Some(&*frame.module))
+                },
+                Err(trap) => {
+                    self.frames.push(frame);
+                    Err(trap)
+                },
+            };
+            if let Err(mut trap) = result {
+                #[cfg(feature = "exceptions")]
+                if self.catch(&trap) {
+                    continue;
+                }
+                self.record_backtrace(&mut trap);
+                return Err(trap);
+            }
+        }
+        Ok(())
+    }
+
+    /// Unwind to the innermost handler of the exception carried by `trap`, `false` if there is none.
+    #[cfg(feature = "exceptions")]
+    fn catch(&mut self, trap: &Trap) -> bool {
+        let exn = match *trap.kind() {
+            TrapKind::Exception(ref exn) => exn,
+            _ => return false,
+        };
+        let found = self.frames.iter().rposition(|frame| frame.handler(exn).is_some());
+        let mut frame = match found {
+            Some(idx) => {
+                self.frames.truncate(idx + 1);
+                self.frames.pop().expect("frame index is in bounds")
+            },
+            None => return false,
+        };
+        let (idx, catch) = frame.handler(exn).expect("handler was found above");
+
+        // 丢弃 try_table 内的操作数，带着 payload 跳转到 catch 的 label
+        self.stack.truncate(frame.labels[idx].height);
+        if catch.tag().is_some() {
+            self.stack.extend(exn.payload().iter().cloned());
+        }
+        if catch.is_ref() {
+            self.stack.push(Value::ExnRef(Some(exn.clone())));
+        }
+        frame.labels.truncate(idx);
+        self.branch(&mut frame, catch.label());
+        self.frames.push(frame);
+        true
+    }
+
+    /// Append the active frames to the backtrace of `trap`, innermost first.
+    fn record_backtrace(&self, trap: &mut Trap) {
+        // 嵌套调用中的 trap 已经带有内层的 backtrace
+        for frame in self.frames.iter().rev() {
+            if trap.backtrace().len() >= BACKTRACE_LIMIT {
+                break;
+            }
+            trap.push_frame(TraceFrame {
+                func_index: frame.func_index,
+                name: frame.module.func_name(frame.func_index),
+            });
+        }
+    }
+
+    /// Enter `func`, taking its arguments from the stack.
+    ///
+    /// Results of host functions are checked against the types of the `caller` module.
+    fn call(&mut self, func: FuncRef, caller: Option<&ModuleInstance>) -> Result<(), Trap> {
+        match *func.as_internal() {
+            FuncInstanceInternal::Internal { ref signature, ref module, ref body, index } => {
+                if self.frames.len() >= DEFAULT_CALL_STACK_LIMIT {
+                    return Err(TrapKind::StackOverflow.into());
+                }
+                let module = module
+                    .upgrade()
+                    .ok_or_else(|| TrapKind::Host("module instance of the function was dropped".to_string()))?;
+
+                let mut locals = self.stack.split_off(self.stack.len() - signature.params.len());
+                locals.extend(body.locals.iter().map(|&t| Value::default(t)));
+                if self.stack.len() + locals.len() > DEFAULT_VALUE_STACK_LIMIT {
+                    return Err(TrapKind::StackOverflow.into());
+                }
+
+                // 函数体本身就是一个 block，跳出它就是返回
+                let label = Label {
+                    arity: signature.results.len(),
+                    height: self.stack.len(),
+                    target: body.code.len(),
+                    is_loop: false,
+                    #[cfg(feature = "exceptions")]
+                    try_table: None,
+                };
+                self.frames.push(Frame {
+                    memory: module.memory_by_index(0),
+                    module,
+                    func_index: index,
+                    body: body.clone(),
+                    locals,
+                    labels: vec![label],
+                    pc: 0,
+                });
+                Ok(())
+            },
+            FuncInstanceInternal::Host { ref signature, ref func } => {
+                let args = self.stack.split_off(self.stack.len() - signature.params.len());
+                let types = caller.map(|module| module.types());
+                let results = invoke_host(signature, &**func, &args, types.as_deref())?;
+                self.stack.extend(results);
+                Ok(())
+            },
+        }
+    }
+
+    fn push<T: Into<Value>>(&mut self, value: T) {
+        self.stack.push(value.into());
+    }
+
+    fn pop_value(&mut self) -> Value {
+        self.stack.pop().expect("operand stack is validated")
+    }
+
+    fn pop<T: FromValue>(&mut self) -> T {
+        self.pop_value().try_into().expect("operand types are validated")
+    }
+
+    /// Pop the three `i32` operands of a bulk table instruction, in push order.
+    fn pop_triple(&mut self) -> (u32, u32, u32) {
+        let c: u32 = self.pop();
+        let b: u32 = self.pop();
+        let a: u32 = self.pop();
+        (a, b, c)
+    }
+
+    fn branch(&mut self, frame: &mut Frame, depth: u32) {
+        let idx = frame.labels.len() - 1 - depth as usize;
+        let label = frame.labels[idx];
+        let keep = self.stack.len() - label.arity;
+        self.stack.drain(label.height..keep);
+        frame.labels.truncate(if label.is_loop { idx + 1 } else { idx });
+        frame.pc = label.target;
+    }
+
+    /// Function called by `call_indirect`, the element index is popped from the stack.
+    fn indirect_callee(&mut self, frame: &Frame, type_idx: u32, table_idx: u32) -> Result<FuncRef, Trap> {
+        let table = frame.module.table_by_index(table_idx).expect("table index is validated");
+        let elem_idx: u32 = self.pop();
+        let func = match table.get(elem_idx)? {
+            Value::FuncRef(Some(func)) => func,
+            _ => return Err(TrapKind::ElemUninitialized.into()),
+        };
+        let expected = frame.module.type_by_index(type_idx).expect("type index is validated");
+        if !signature_matches(func.signature(), &expected) {
+            return Err(TrapKind::UnexpectedSignature.into());
+        }
+        Ok(func)
+    }
+
+    /// Function called by `call_ref`, popped from the stack.
+    #[cfg(feature = "gc")]
+    fn ref_callee(&mut self) -> Result<FuncRef, Trap> {
+        match self.pop_value() {
+            Value::FuncRef(Some(func)) => Ok(func),
+            _ => Err(TrapKind::NullReference.into()),
+        }
+    }
+
+    /// Branch to the label of `br_on_cast` if the operand does (or with `on_fail` does not) match the target type.
+    #[cfg(feature = "gc")]
+    fn br_on_cast(&mut self, frame: &mut Frame, data: &BrOnCastData, on_fail: bool) {
+        let value = self.stack.last().expect("operand stack is validated");
+        if value.has_type(data.to.into(), Some(&frame.module.types())) != on_fail {
+            self.branch(frame, data.label);
+        }
+    }
+
+    fn load<T: LittleEndian, U: Into<Value>, F: Fn(T) -> U>(&mut self, frame: &Frame, offset: u64, idx: u32, f: F) -> Result<(), Trap> {
+        let memory = frame.memory(idx);
+        let address = effective_address(&mut self.stack, &memory, offset)?;
+        let value: T = memory.get_value(address)?;
+        self.push(f(value));
+        Ok(())
+    }
+
+    fn store<T: FromValue, U: LittleEndian, F: Fn(T) -> U>(&mut self, frame: &Frame, offset: u64, idx: u32, f: F) -> Result<(), Trap> {
+        let value: T = self.pop();
+        let memory = frame.memory(idx);
+        let address = effective_address(&mut self.stack, &memory, offset)?;
+        memory.set_value(address, f(value))
+    }
+
+    fn run_frame(&mut self, frame: &mut Frame) -> Result<Outcome, Trap> {
+        use crate::elements::ops::Instruction::*;
+
+        macro_rules! unop {
+            ($t: ty, $f: expr) => {{
+                let v: $t = self.pop();
+                self.push(($f)(v));
+            }};
+        }
+
+        macro_rules! binop {
+            ($t: ty, $f: expr) => {{
+                let b: $t = self.pop();
+                let a: $t = self.pop();
+                self.push(($f)(a, b));
+            }};
+        }
+
+        macro_rules! binop_trap {
+            ($t: ty, $f: expr) => {{
+                let b: $t = self.pop();
+                let a: $t = self.pop();
+                let result: Result<$t, TrapKind> = ($f)(a, b);
+                self.push(result?);
+            }};
+        }
+
+        macro_rules! cvtop_trap {
+            ($from: ty, $f: expr) => {{
+                let v: $from = self.pop();
+                self.push(($f)(v)?);
+            }};
+        }
+
+        let body = frame.body.clone();
+        loop {
+            let pc = frame.pc;
+            let instruction = match body.code.get(pc) {
+                Some(instruction) => instruction,
+                None => return Ok(Outcome::Return),
+            };
+            frame.pc += 1;
+
+            match *instruction {
+                Unreachable => return Err(TrapKind::Unreachable.into()),
+                Nop => {},
+                Block(ref block_type) => {
+                    let (params, results) = frame.block_arity(block_type);
+                    frame.labels.push(Label {
+                        arity: results,
+                        height: self.stack.len() - params,
+                        target: body.ends[pc] as usize + 1,
+                        is_loop: false,
+                        #[cfg(feature = "exceptions")]
+                        try_table: None,
+                    });
+                },
+                Loop(ref block_type) => {
+                    let (params, _) = frame.block_arity(block_type);
+                    frame.labels.push(Label {
+                        arity: params,
+                        height: self.stack.len() - params,
+                        target: pc + 1,
+                        is_loop: true,
+                        #[cfg(feature = "exceptions")]
+                        try_table: None,
+                    });
+                },
+                If(ref block_type) => {
+                    let condition: i32 = self.pop();
+                    let (params, results) = frame.block_arity(block_type);
+                    let label = Label {
+                        arity: results,
+                        height: self.stack.len() - params,
+                        target: body.ends[pc] as usize + 1,
+                        is_loop: false,
+                        #[cfg(feature = "exceptions")]
+                        try_table: None,
+                    };
+                    if condition != 0 {
+                        frame.labels.push(label);
+                    } else if body.elses[pc] != u32::MAX {
+                        frame.labels.push(label);
+                        frame.pc = body.elses[pc] as usize + 1;
+                    } else {
+                        frame.pc = body.ends[pc] as usize + 1;
+                    }
+                },
+                Else => {
+                    // then 分支执行完毕，直接跳到 end 之后
+                    frame.labels.pop();
+                    frame.pc = body.ends[pc] as usize + 1;
+                },
+                End => {
+                    frame.labels.pop();
+                },
+                #[cfg(feature = "exceptions")]
+                TryTable(ref data) => {
+                    let (params, results) = frame.block_arity(&data.block_type);
+                    frame.labels.push(Label {
+                        arity: results,
+                        height: self.stack.len() - params,
+                        target: body.ends[pc] as usize + 1,
+                        is_loop: false,
+                        try_table: Some(pc),
+                    });
+                },
+                #[cfg(feature = "exceptions")]
+                Throw(tag_idx) => {
+                    let tag = frame.module.tag_by_index(tag_idx).expect("tag index is validated");
+                    let payload = self.stack.split_off(self.stack.len() - tag.signature().params.len());
+                    return Err(TrapKind::Exception(Exception::alloc_unchecked(tag, payload)).into());
+                },
+                #[cfg(feature = "exceptions")]
+                ThrowRef => match self.pop_value() {
+                    Value::ExnRef(Some(exn)) => return Err(TrapKind::Exception(exn).into()),
+                    _ => return Err(TrapKind::NullReference.into()),
+                },
+                Br(depth) => self.branch(frame, depth),
+                BrIf(depth) => {
+                    let condition: i32 = self.pop();
+                    if condition != 0 {
+                        self.branch(frame, depth);
+                    }
+                },
+                BrTable(ref data) => {
+                    let index: u32 = self.pop();
+                    let depth = data.table.get(index as usize).cloned().unwrap_or(data.default);
+                    self.branch(frame, depth);
+                },
+                Return => {
+                    let depth = frame.labels.len() as u32 - 1;
+                    self.branch(frame, depth);
+                },
+
+                Call(func_idx) => {
+                    let func = frame.module.func_by_index(func_idx).expect("function index is validated");
+                    return Ok(Outcome::Call(func));
+                },
+                CallIndirect(type_idx, table_idx) => {
+                    let func = self.indirect_callee(frame, type_idx, table_idx)?;
+                    return Ok(Outcome::Call(func));
+                },
+                #[cfg(feature = "tail_call")]
+                ReturnCall(func_idx) => {
+                    let func = frame.module.func_by_index(func_idx).expect("function index is validated");
+                    return Ok(Outcome::TailCall(func));
+                },
+                #[cfg(feature = "tail_call")]
+                ReturnCallIndirect(type_idx, table_idx) => {
+                    let func = self.indirect_callee(frame, type_idx, table_idx)?;
+                    return Ok(Outcome::TailCall(func));
+                },
+                #[cfg(feature = "gc")]
+                CallRef(_) => {
+                    let func = self.ref_callee()?;
+                    return Ok(Outcome::Call(func));
+                },
+                #[cfg(all(feature = "gc", feature = "tail_call"))]
+                ReturnCallRef(_) => {
+                    let func = self.ref_callee()?;
+                    return Ok(Outcome::TailCall(func));
+                },
+
+                Drop => {
+                    self.pop_value();
+                },
+                Select | TypedSelect(_) => {
+                    let condition: i32 = self.pop();
+                    let b = self.pop_value();
+                    let a = self.pop_value();
+                    self.stack.push(if condition != 0 { a } else { b });
+                },
+
+                GetLocal(idx) => {
+                    let value = frame.locals[idx as usize].clone();
+                    self.stack.push(value);
+                },
+                SetLocal(idx) => {
+                    frame.locals[idx as usize] = self.pop_value();
+                },
+                TeeLocal(idx) => {
+                    frame.locals[idx as usize] = self.stack.last().expect("operand stack is validated").clone();
+                },
+                GetGlobal(idx) => {
+                    let global = frame.module.global_by_index(idx).expect("global index is validated");
+                    self.stack.push(global.get());
+                },
+                SetGlobal(idx) => {
+                    let global = frame.module.global_by_index(idx).expect("global index is validated");
+                    let value = self.pop_value();
+                    global.set(value).expect("global mutability and type are validated");
+                },
+
+                I32Load(_, offset, idx) => self.load(frame, offset, idx, |v: i32| v)?,
+                I64Load(_, offset, idx) => self.load(frame, offset, idx, |v: i64| v)?,
+                F32Load(_, offset, idx) => self.load(frame, offset, idx, |v: f32| v)?,
+                F64Load(_, offset, idx) => self.load(frame, offset, idx, |v: f64| v)?,
+                I32Load8S(_, offset, idx) => self.load(frame, offset, idx, |v: i8| v as i32)?,
+                I32Load8U(_, offset, idx) => self.load(frame, offset, idx, |v: u8| v as i32)?,
+                I32Load16S(_, offset, idx) => self.load(frame, offset, idx, |v: i16| v as i32)?,
+                I32Load16U(_, offset, idx) => self.load(frame, offset, idx, |v: u16| v as i32)?,
+                I64Load8S(_, offset, idx) => self.load(frame, offset, idx, |v: i8| v as i64)?,
+                I64Load8U(_, offset, idx) => self.load(frame, offset, idx, |v: u8| v as i64)?,
+                I64Load16S(_, offset, idx) => self.load(frame, offset, idx, |v: i16| v as i64)?,
+                I64Load16U(_, offset, idx) => self.load(frame, offset, idx, |v: u16| v as i64)?,
+                I64Load32S(_, offset, idx) => self.load(frame, offset, idx, |v: i32| v as i64)?,
+                I64Load32U(_, offset, idx) => self.load(frame, offset, idx, |v: u32| v as i64)?,
+                I32Store(_, offset, idx) => self.store(frame, offset, idx, |v: i32| v)?,
+                I64Store(_, offset, idx) => self.store(frame, offset, idx, |v: i64| v)?,
+                F32Store(_, offset, idx) => self.store(frame, offset, idx, |v: f32| v)?,
+                F64Store(_, offset, idx) => self.store(frame, offset, idx, |v: f64| v)?,
+                I32Store8(_, offset, idx) => self.store(frame, offset, idx, |v: i32| v as u8)?,
+                I32Store16(_, offset, idx) => self.store(frame, offset, idx, |v: i32| v as u16)?,
+                I64Store8(_, offset, idx) => self.store(frame, offset, idx, |v: i64| v as u8)?,
+                I64Store16(_, offset, idx) => self.store(frame, offset, idx, |v: i64| v as u16)?,
+                I64Store32(_, offset, idx) => self.store(frame, offset, idx, |v: i64| v as u32)?,
+
+                CurrentMemory(idx) => {
+                    let memory = frame.memory(idx);
+                    push_address(&mut self.stack, &memory, memory.current_pages());
+                },
+                GrowMemory(idx) => {
+                    let memory = frame.memory(idx);
+                    let delta = pop_address(&mut self.stack, &memory);
+                    let result = memory.grow(delta).unwrap_or(u64::MAX);
+                    push_address(&mut self.stack, &memory, result);
+                },
+
+                I32Const(v) => self.push(v),
+                I64Const(v) => self.push(v),
+                F32Const(bits) => self.push(f32::from_bits(bits)),
+                F64Const(bits) => self.push(f64::from_bits(bits)),
+
+                I32Eqz => unop!(i32, |v| v == 0),
+                I32Eq => binop!(i32, |a, b| a == b),
+                I32Ne => binop!(i32, |a, b| a != b),
+                I32LtS => binop!(i32, |a, b| a < b),
+                I32LtU => binop!(u32, |a, b| a < b),
+                I32GtS => binop!(i32, |a, b| a > b),
+                I32GtU => binop!(u32, |a, b| a > b),
+                I32LeS => binop!(i32, |a, b| a <= b),
+                I32LeU => binop!(u32, |a, b| a <= b),
+                I32GeS => binop!(i32, |a, b| a >= b),
+                I32GeU => binop!(u32, |a, b| a >= b),
+
+                I64Eqz => unop!(i64, |v| v == 0),
+                I64Eq => binop!(i64, |a, b| a == b),
+                I64Ne => binop!(i64, |a, b| a != b),
+                I64LtS => binop!(i64, |a, b| a < b),
+                I64LtU => binop!(u64, |a, b| a < b),
+                I64GtS => binop!(i64, |a, b| a > b),
+                I64GtU => binop!(u64, |a, b| a > b),
+                I64LeS => binop!(i64, |a, b| a <= b),
+                I64LeU => binop!(u64, |a, b| a <= b),
+                I64GeS => binop!(i64, |a, b| a >= b),
+                I64GeU => binop!(u64, |a, b| a >= b),
+
+                F32Eq => binop!(f32, |a, b| a == b),
+                F32Ne => binop!(f32, |a, b| a != b),
+                F32Lt => binop!(f32, |a, b| a < b),
+                F32Gt => binop!(f32, |a, b| a > b),
+                F32Le => binop!(f32, |a, b| a <= b),
+                F32Ge => binop!(f32, |a, b| a >= b),
+
+                F64Eq => binop!(f64, |a, b| a == b),
+                F64Ne => binop!(f64, |a, b| a != b),
+                F64Lt => binop!(f64, |a, b| a < b),
+                F64Gt => binop!(f64, |a, b| a > b),
+                F64Le => binop!(f64, |a, b| a <= b),
+                F64Ge => binop!(f64, |a, b| a >= b),
+
+                I32Clz => unop!(i32, |v: i32| v.leading_zeros()),
+                I32Ctz => unop!(i32, |v: i32| v.trailing_zeros()),
+                I32Popcnt => unop!(i32, |v: i32| v.count_ones()),
+                I32Add => binop!(i32, |a: i32, b| a.wrapping_add(b)),
+                I32Sub => binop!(i32, |a: i32, b| a.wrapping_sub(b)),
+                I32Mul => binop!(i32, |a: i32, b| a.wrapping_mul(b)),
+                I32DivS => binop_trap!(i32, |a: i32, b: i32| {
+                    if b == 0 {
+                        Err(TrapKind::DivisionByZero)
+                    } else {
+                        a.checked_div(b).ok_or(TrapKind::IntegerOverflow)
+                    }
+                }),
+                I32DivU => binop_trap!(u32, |a: u32, b: u32| a.checked_div(b).ok_or(TrapKind::DivisionByZero)),
+                I32RemS => binop_trap!(i32, |a: i32, b: i32| {
+                    if b == 0 { Err(TrapKind::DivisionByZero) } else { Ok(a.wrapping_rem(b)) }
+                }),
+                I32RemU => binop_trap!(u32, |a: u32, b: u32| a.checked_rem(b).ok_or(TrapKind::DivisionByZero)),
+                I32And => binop!(i32, |a, b| a & b),
+                I32Or => binop!(i32, |a, b| a | b),
+                I32Xor => binop!(i32, |a, b| a ^ b),
+                I32Shl => binop!(i32, |a: i32, b: i32| a.wrapping_shl(b as u32)),
+                I32ShrS => binop!(i32, |a: i32, b: i32| a.wrapping_shr(b as u32)),
+                I32ShrU => binop!(u32, |a: u32, b: u32| a.wrapping_shr(b)),
+                I32Rotl => binop!(u32, |a: u32, b: u32| a.rotate_left(b % 32)),
+                I32Rotr => binop!(u32, |a: u32, b: u32| a.rotate_right(b % 32)),
+
+                I64Clz => unop!(i64, |v: i64| v.leading_zeros() as i64),
+                I64Ctz => unop!(i64, |v: i64| v.trailing_zeros() as i64),
+                I64Popcnt => unop!(i64, |v: i64| v.count_ones() as i64),
+                I64Add => binop!(i64, |a: i64, b| a.wrapping_add(b)),
+                I64Sub => binop!(i64, |a: i64, b| a.wrapping_sub(b)),
+                I64Mul => binop!(i64, |a: i64, b| a.wrapping_mul(b)),
+                I64DivS => binop_trap!(i64, |a: i64, b: i64| {
+                    if b == 0 {
+                        Err(TrapKind::DivisionByZero)
+                    } else {
+                        a.checked_div(b).ok_or(TrapKind::IntegerOverflow)
+                    }
+                }),
+                I64DivU => binop_trap!(u64, |a: u64, b: u64| a.checked_div(b).ok_or(TrapKind::DivisionByZero)),
+                I64RemS => binop_trap!(i64, |a: i64, b: i64| {
+                    if b == 0 { Err(TrapKind::DivisionByZero) } else { Ok(a.wrapping_rem(b)) }
+                }),
+                I64RemU => binop_trap!(u64, |a: u64, b: u64| a.checked_rem(b).ok_or(TrapKind::DivisionByZero)),
+                I64And => binop!(i64, |a, b| a & b),
+                I64Or => binop!(i64, |a, b| a | b),
+                I64Xor => binop!(i64, |a, b| a ^ b),
+                I64Shl => binop!(i64, |a: i64, b: i64| a.wrapping_shl(b as u32)),
+                I64ShrS => binop!(i64, |a: i64, b: i64| a.wrapping_shr(b as u32)),
+                I64ShrU => binop!(u64, |a: u64, b: u64| a.wrapping_shr(b as u32)),
+                I64Rotl => binop!(u64, |a: u64, b: u64| a.rotate_left((b % 64) as u32)),
+                I64Rotr => binop!(u64, |a: u64, b: u64| a.rotate_right((b % 64) as u32)),
+
+                F32Abs => unop!(f32, |v: f32| v.abs()),
+                F32Neg => unop!(f32, |v: f32| -v),
+                F32Ceil => unop!(f32, |v: f32| v.ceil()),
+                F32Floor => unop!(f32, |v: f32| v.floor()),
+                F32Trunc => unop!(f32, |v: f32| v.trunc()),
+                F32Nearest => unop!(f32, |v: f32| v.round_ties_even()),
+                F32Sqrt => unop!(f32, |v: f32| v.sqrt()),
+                F32Add => binop!(f32, |a, b| a + b),
+                F32Sub => binop!(f32, |a, b| a - b),
+                F32Mul => binop!(f32, |a, b| a * b),
+                F32Div => binop!(f32, |a, b| a / b),
+                F32Min => binop!(f32, |a: f32, b: f32| a.wasm_min(b)),
+                F32Max => binop!(f32, |a: f32, b: f32| a.wasm_max(b)),
+                F32Copysign => binop!(f32, |a: f32, b: f32| a.copysign(b)),
+                F64Abs => unop!(f64, |v: f64| v.abs()),
+                F64Neg => unop!(f64, |v: f64| -v),
+                F64Ceil => unop!(f64, |v: f64| v.ceil()),
+                F64Floor => unop!(f64, |v: f64| v.floor()),
+                F64Trunc => unop!(f64, |v: f64| v.trunc()),
+                F64Nearest => unop!(f64, |v: f64| v.round_ties_even()),
+                F64Sqrt => unop!(f64, |v: f64| v.sqrt()),
+                F64Add => binop!(f64, |a, b| a + b),
+                F64Sub => binop!(f64, |a, b| a - b),
+                F64Mul => binop!(f64, |a, b| a * b),
+                F64Div => binop!(f64, |a, b| a / b),
+                F64Min => binop!(f64, |a: f64, b: f64| a.wasm_min(b)),
+                F64Max => binop!(f64, |a: f64, b: f64| a.wasm_max(b)),
+                F64Copysign => binop!(f64, |a: f64, b: f64| a.copysign(b)),
+
+                I32WrapI64 => unop!(i64, |v: i64| v as i32),
+                I32TruncSF32 => cvtop_trap!(f32, |v: f32| trunc_i32(v as f64)),
+                I32TruncUF32 => cvtop_trap!(f32, |v: f32| trunc_u32(v as f64)),
+                I32TruncSF64 => cvtop_trap!(f64, trunc_i32),
+                I32TruncUF64 => cvtop_trap!(f64, trunc_u32),
+                I64ExtendSI32 => unop!(i32, |v: i32| v as i64),
+                I64ExtendUI32 => unop!(u32, |v: u32| v as i64),
+                I64TruncSF32 => cvtop_trap!(f32, |v: f32| trunc_i64(v as f64)),
+                I64TruncUF32 => cvtop_trap!(f32, |v: f32| trunc_u64(v as f64)),
+                I64TruncSF64 => cvtop_trap!(f64, trunc_i64),
+                I64TruncUF64 => cvtop_trap!(f64, trunc_u64),
+                F32ConvertSI32 => unop!(i32, |v: i32| v as f32),
+                F32ConvertUI32 => unop!(u32, |v: u32| v as f32),
+                F32ConvertSI64 => unop!(i64, |v: i64| v as f32),
+                F32ConvertUI64 => unop!(u64, |v: u64| v as f32),
+                F32DemoteF64 => unop!(f64, |v: f64| v as f32),
+                F64ConvertSI32 => unop!(i32, |v: i32| v as f64),
+                F64ConvertUI32 => unop!(u32, |v: u32| v as f64),
+                F64ConvertSI64 => unop!(i64, |v: i64| v as f64),
+                F64ConvertUI64 => unop!(u64, |v: u64| v as f64),
+                F64PromoteF32 => unop!(f32, |v: f32| v as f64),
+
+                I32ReinterpretF32 => unop!(f32, |v: f32| v.to_bits()),
+                I64ReinterpretF64 => unop!(f64, |v: f64| v.to_bits()),
+                F32ReinterpretI32 => unop!(u32, f32::from_bits),
+                F64ReinterpretI64 => unop!(u64, f64::from_bits),
+
+                I32Extend8S => unop!(i32, |v: i32| v as i8 as i32),
+                I32Extend16S => unop!(i32, |v: i32| v as i16 as i32),
+                I64Extend8S => unop!(i64, |v: i64| v as i8 as i64),
+                I64Extend16S => unop!(i64, |v: i64| v as i16 as i64),
+                I64Extend32S => unop!(i64, |v: i64| v as i32 as i64),
+
+                RefNull(elem_type) => self.stack.push(frame.module.null(elem_type)),
+                RefIsNull => {
+                    let value = self.pop_value();
+                    self.push(value.is_null());
+                },
+                RefFunc(idx) => {
+                    let func = frame.module.func_by_index(idx).expect("function index is validated");
+                    self.stack.push(Value::FuncRef(Some(func)));
+                },
+                #[cfg(feature = "gc")]
+                RefEq => {
+                    let b = self.pop_value();
+                    let a = self.pop_value();
+                    self.push(a == b);
+                },
+                #[cfg(feature = "gc")]
+                RefAsNonNull => {
+                    if self.stack.last().expect("operand stack is validated").is_null() {
+                        return Err(TrapKind::NullReference.into());
+                    }
+                },
+                #[cfg(feature = "gc")]
+                BrOnNull(depth) => {
+                    if self.stack.last().expect("operand stack is validated").is_null() {
+                        self.pop_value();
+                        self.branch(frame, depth);
+                    }
+                },
+                #[cfg(feature = "gc")]
+                BrOnNonNull(depth) => {
+                    if self.stack.last().expect("operand stack is validated").is_null() {
+                        self.pop_value();
+                    } else {
+                        self.branch(frame, depth);
+                    }
+                },
+
+                TableGet(table_idx) => {
+                    let table = frame.module.table_by_index(table_idx).expect("table index is validated");
+                    let idx: u32 = self.pop();
+                    let value = table.get(idx)?;
+                    self.stack.push(value);
+                },
+                TableSet(table_idx) => {
+                    let table = frame.module.table_by_index(table_idx).expect("table index is validated");
+                    let value = self.pop_value();
+                    let idx: u32 = self.pop();
+                    table.set(idx, value)?;
+                },
+                TableSize(table_idx) => {
+                    let table = frame.module.table_by_index(table_idx).expect("table index is validated");
+                    self.push(table.current_size());
+                },
+                TableGrow(table_idx) => {
+                    let table = frame.module.table_by_index(table_idx).expect("table index is validated");
+                    let delta: u32 = self.pop();
+                    let init = self.pop_value();
+                    let result = table.grow(delta, init).map(|old| old as i32).unwrap_or(-1);
+                    self.push(result);
+                },
+                TableFill(table_idx) => {
+                    let table = frame.module.table_by_index(table_idx).expect("table index is validated");
+                    let len: u32 = self.pop();
+                    let value = self.pop_value();
+                    let dst: u32 = self.pop();
+                    table.fill(dst, len, value)?;
+                },
+
+                MemoryInit(segment, idx) => {
+                    let len: u32 = self.pop();
+                    let src: u32 = self.pop();
+                    let memory = frame.memory(idx);
+                    let dst = pop_address(&mut self.stack, &memory);
+                    frame.module.memory_init(&memory, segment, dst, src, len)?;
+                },
+                DataDrop(segment) => frame.module.data_drop(segment),
+                MemoryCopy(dst_idx, src_idx) => {
+                    let dst_memory = frame.memory(dst_idx);
+                    let src_memory = frame.memory(src_idx);
+                    // 长度取两者中较窄的地址类型
+                    let len = pop_address(&mut self.stack, if src_memory.is_64() { &dst_memory } else { &src_memory });
+                    let src = pop_address(&mut self.stack, &src_memory);
+                    let dst = pop_address(&mut self.stack, &dst_memory);
+                    let len = usize::try_from(len).map_err(|_| TrapKind::MemoryAccessOutOfBounds)?;
+                    // 先整体读出，重叠的区域也能正确复制
+                    let bytes = src_memory.get(src, len)?;
+                    dst_memory.set(dst, &bytes)?;
+                },
+                MemoryFill(idx) => {
+                    let memory = frame.memory(idx);
+                    let len = pop_address(&mut self.stack, &memory);
+                    let value: u32 = self.pop();
+                    let dst = pop_address(&mut self.stack, &memory);
+                    let len = usize::try_from(len).map_err(|_| TrapKind::MemoryAccessOutOfBounds)?;
+                    memory.fill(dst, value as u8, len)?;
+                },
+                TableInit(segment, table_idx) => {
+                    let (dst, src, len) = self.pop_triple();
+                    let table = frame.module.table_by_index(table_idx).expect("table index is validated");
+                    frame.module.table_init(&table, segment, dst, src, len)?;
+                },
+                ElemDrop(segment) => frame.module.elem_drop(segment),
+                TableCopy(dst_idx, src_idx) => {
+                    let (dst, src, len) = self.pop_triple();
+                    let dst_table = frame.module.table_by_index(dst_idx).expect("table index is validated");
+                    let src_table = frame.module.table_by_index(src_idx).expect("table index is validated");
+                    let values = src_table.get_range(src, len)?;
+                    dst_table.set_range(dst, &values)?;
+                },
+
+                // `as` 本身就是饱和转换，NaN 变成 0
+                I32TruncSatSF32 => unop!(f32, |v: f32| v as i32),
+                I32TruncSatUF32 => unop!(f32, |v: f32| v as u32),
+                I32TruncSatSF64 => unop!(f64, |v: f64| v as i32),
+                I32TruncSatUF64 => unop!(f64, |v: f64| v as u32),
+                I64TruncSatSF32 => unop!(f32, |v: f32| v as i64),
+                I64TruncSatUF32 => unop!(f32, |v: f32| v as u64),
+                I64TruncSatSF64 => unop!(f64, |v: f64| v as i64),
+                I64TruncSatUF64 => unop!(f64, |v: f64| v as u64),
+
+                #[cfg(feature = "simd")]
+                Simd(ref simd) => {
+                    let memory = simd.memory_index().map(|idx| frame.memory(idx));
+                    super::simd::execute(simd, &mut self.stack, memory.as_ref())?
+                },
+                #[cfg(feature = "threads")]
+                Atomic(ref atomic) => {
+                    let memory = atomic.memarg().map(|(_, _, idx)| frame.memory(idx));
+                    super::atomics::execute(atomic, &mut self.stack, memory.as_ref())?
+                },
+                #[cfg(feature = "gc")]
+                Gc(GcInstruction::BrOnCast(ref data)) => self.br_on_cast(frame, data, false),
+                #[cfg(feature = "gc")]
+                Gc(GcInstruction::BrOnCastFail(ref data)) => self.br_on_cast(frame, data, true),
+                #[cfg(feature = "gc")]
+                Gc(ref gc) => super::gc::execute(gc, &mut self.stack, &frame.module)?,
+            }
+        }
+    }
+}
+
+fn trunc_i32(v: f64) -> Result<i32, TrapKind> {
+    if v.is_nan() {
+        return Err(TrapKind::InvalidConversionToInt);
+    }
+    let t = v.trunc();
+    if !(-2147483648.0..2147483648.0).contains(&t) {
+        return Err(TrapKind::IntegerOverflow);
+    }
+    Ok(t as i32)
+}
+
+fn trunc_u32(v: f64) -> Result<u32, TrapKind> {
+    if v.is_nan() {
+        return Err(TrapKind::InvalidConversionToInt);
+    }
+    let t = v.trunc();
+    if t <= -1.0 || t >= 4294967296.0 {
+        return Err(TrapKind::IntegerOverflow);
+    }
+    Ok(t as u32)
+}
+
+fn trunc_i64(v: f64) -> Result<i64, TrapKind> {
+    if v.is_nan() {
+        return Err(TrapKind::InvalidConversionToInt);
+    }
+    let t = v.trunc();
+    if !(-9223372036854775808.0..9223372036854775808.0).contains(&t) {
+        return Err(TrapKind::IntegerOverflow);
+    }
+    Ok(t as i64)
+}
+
+fn trunc_u64(v: f64) -> Result<u64, TrapKind> {
+    if v.is_nan() {
+        return Err(TrapKind::InvalidConversionToInt);
+    }
+    let t = v.trunc();
+    if t <= -1.0 || t >= 18446744073709551616.0 {
+        return Err(TrapKind::IntegerOverflow);
+    }
+    Ok(t as u64)
+}
+
+/// `min` and `max` with wasm semantics: NaN is propagated and `-0 < +0`.
+pub(super) trait WasmFloat: Copy {
+    fn wasm_min(self, other: Self) -> Self;
+    fn wasm_max(self, other: Self) -> Self;
+}
+
+macro_rules! impl_wasm_float {
+    ($t: ty) => {
+        impl WasmFloat for $t {
+            fn wasm_min(self, other: $t) -> $t {
+                if self.is_nan() || other.is_nan() {
+                    return self + other;
+                }
+                if self == other {
+                    return if self.is_sign_negative() { self } else { other };
+                }
+                self.min(other)
+            }
+
+            fn wasm_max(self, other: $t) -> $t {
+                if self.is_nan() || other.is_nan() {
+                    return self + other;
+                }
+                if self == other {
+                    return if self.is_sign_positive() { self } else { other };
+                }
+                self.max(other)
+            }
+        }
+    }
+}
+
+impl_wasm_float!(f32);
+impl_wasm_float!(f64);
@@ -0,0 +1,451 @@
+//! Portable scalar implementation of the SIMD instructions.
+//!
+//! A `v128` is kept as a `u128` whose little endian bytes are the lanes.
+
+use crate::elements::simd::SimdInstruction;
+use super::memory::{effective_address, LittleEndian};
+use super::runner::WasmFloat;
+use super::{Value, FromValue, MemoryRef, Trap};
+
+/// Lane `i` of `v`, lanes have the width of `T`.
+fn lane<T: LittleEndian>(v: u128, i: usize) -> T {
+    T::from_le(&v.to_le_bytes()[i * T::SIZE..(i + 1) * T::SIZE])
+}
+
+/// Vector whose lane `i` is `f(i)`.
+fn build<T: LittleEndian, F: Fn(usize) -> T>(f: F) -> u128 {
+    let mut bytes = [0u8; 16];
+    for (i, chunk) in bytes.chunks_mut(T::SIZE).enumerate() {
+        f(i).to_le(chunk);
+    }
+    u128::from_le_bytes(bytes)
+}
+
+fn lanes<T: LittleEndian>() -> usize {
+    16 / T::SIZE
+}
+
+fn unary<T: LittleEndian, F: Fn(T) -> T>(a: u128, f: F) -> u128 {
+    build(|i| f(lane(a, i)))
+}
+
+fn binary<T: LittleEndian, F: Fn(T, T) -> T>(a: u128, b: u128, f: F) -> u128 {
+    build(|i| f(lane(a, i), lane(b, i)))
+}
+
+/// Lane-wise comparison, true lanes are all ones.
+fn compare<T: LittleEndian, F: Fn(T, T) -> bool>(a: u128, b: u128, f: F) -> u128 {
+    let mut bytes = [0u8; 16];
+    for (i, chunk) in bytes.chunks_mut(T::SIZE).enumerate() {
+        if f(lane(a, i), lane(b, i)) {
+            chunk.fill(0xff);
+        }
+    }
+    u128::from_le_bytes(bytes)
+}
+
+/// Lane-wise shift, the count is taken modulo the lane width.
+fn shift<T: LittleEndian, F: Fn(T, u32) -> T>(a: u128, count: u32, f: F) -> u128 {
+    let count = count % (T::SIZE as u32 * 8);
+    build(|i| f(lane(a, i), count))
+}
+
+/// Convert the low (`high == false`) or high half of the lanes of `a` to lanes twice as wide.
+fn extend<T: LittleEndian, U: LittleEndian, F: Fn(T) -> U>(a: u128, high: bool, f: F) -> u128 {
+    let base = if high { lanes::<U>() } else { 0 };
+    build(|i| f(lane(a, base + i)))
+}
+
+/// Multiply the low or high halves of `a` and `b` into lanes twice as wide.
+fn extmul<T: LittleEndian, U: LittleEndian, F: Fn(T, T) -> U>(a: u128, b: u128, high: bool, f: F) -> u128 {
+    let base = if high { lanes::<U>() } else { 0 };
+    build(|i| f(lane(a, base + i), lane(b, base + i)))
+}
+
+/// Combine adjacent lanes of `a` into lanes twice as wide.
+fn pairwise<T: LittleEndian, U: LittleEndian, F: Fn(T, T) -> U>(a: u128, f: F) -> u128 {
+    build(|i| f(lane(a, 2 * i), lane(a, 2 * i + 1)))
+}
+
+/// Saturate the lanes of `a` followed by those of `b` into lanes half as wide.
+fn narrow<T: LittleEndian, U: LittleEndian, F: Fn(T) -> U>(a: u128, b: u128, f: F) -> u128 {
+    let half = lanes::<T>();
+    build(|i| if i < half { f(lane(a, i)) } else { f(lane(b, i - half)) })
+}
+
+fn all_true<T: LittleEndian + PartialEq + Default>(a: u128) -> bool {
+    (0..lanes::<T>()).all(|i| lane::<T>(a, i) != T::default())
+}
+
+/// Collect the sign bits of the lanes, lanes are given as `i64` for all widths.
+fn bitmask<T: LittleEndian + Into<i64>>(a: u128) -> u32 {
+    (0..lanes::<T>()).fold(0, |mask, i| mask | ((lane::<T>(a, i).into() < 0) as u32) << i)
+}
+
+fn pmin<T: PartialOrd>(a: T, b: T) -> T {
+    if b < a { b } else { a }
+}
+
+fn pmax<T: PartialOrd>(a: T, b: T) -> T {
+    if a < b { b } else { a }
+}
+
+fn pop<T: FromValue>(stack: &mut Vec<Value>) -> T {
+    stack.pop().and_then(Value::try_into).expect("operand types are validated")
+}
+
+fn push<T: Into<Value>>(stack: &mut Vec<Value>, value: T) {
+    stack.push(value.into());
+}
+
+/// Execute a validated SIMD instruction on `stack`.
+pub(super) fn execute(instruction: &SimdInstruction, stack: &mut Vec<Value>, memory: Option<&MemoryRef>) -> Result<(), Trap> {
+    use crate::elements::simd::SimdInstruction::*;
+
+    let memory = || memory.expect("memory is validated");
+
+    macro_rules! load {
+        ($t: ty, $offset: expr, $f: expr) => {{
+            let address = effective_address(stack, memory(), $offset)?;
+            let v: $t = memory().get_value(address)?;
+            push(stack, ($f)(v));
+        }};
+    }
+
+    // 取出 lane 宽度的值，其余 lane 为 0 或复制
+    macro_rules! load_lane {
+        ($t: ty, $offset: expr, $lane: expr) => {{
+            let v: u128 = pop(stack);
+            let address = effective_address(stack, memory(), $offset)?;
+            let x: $t = memory().get_value(address)?;
+            push(stack, build::<$t, _>(|i| if i == $lane as usize { x } else { lane(v, i) }));
+        }};
+    }
+
+    macro_rules! store_lane {
+        ($t: ty, $offset: expr, $lane: expr) => {{
+            let v: u128 = pop(stack);
+            let address = effective_address(stack, memory(), $offset)?;
+            memory().set_value(address, lane::<$t>(v, $lane as usize))?;
+        }};
+    }
+
+    macro_rules! unop {
+        ($f: expr) => {{
+            let a: u128 = pop(stack);
+            push(stack, ($f)(a));
+        }};
+    }
+
+    macro_rules! binop {
+        ($f: expr) => {{
+            let b: u128 = pop(stack);
+            let a: u128 = pop(stack);
+            push(stack, ($f)(a, b));
+        }};
+    }
+
+    macro_rules! shiftop {
+        ($t: ty, $f: expr) => {{
+            let count: u32 = pop(stack);
+            let a: u128 = pop(stack);
+            push(stack, shift::<$t, _>(a, count, $f));
+        }};
+    }
+
+    macro_rules! extract {
+        ($t: ty, $lane: expr, $f: expr) => {{
+            let a: u128 = pop(stack);
+            push(stack, ($f)(lane::<$t>(a, $lane as usize)));
+        }};
+    }
+
+    macro_rules! replace {
+        ($t: ty, $scalar: ty, $lane: expr, $f: expr) => {{
+            let x: $scalar = pop(stack);
+            let a: u128 = pop(stack);
+            let x: $t = ($f)(x);
+            push(stack, build::<$t, _>(|i| if i == $lane as usize { x } else { lane(a, i) }));
+        }};
+    }
+
+    macro_rules! splat {
+        ($t: ty, $scalar: ty) => {{
+            let x: $scalar = pop(stack);
+            push(stack, build::<$t, _>(|_| x as $t));
+        }};
+    }
+
+    match *instruction {
+        V128Load(_, offset, _) => load!(u128, offset, |v: u128| v),
+        V128Load8x8S(_, offset, _) => load!(u64, offset, |v: u64| extend::<i8, i16, _>(v as u128, false, |x| x as i16)),
+        V128Load8x8U(_, offset, _) => load!(u64, offset, |v: u64| extend::<u8, u16, _>(v as u128, false, |x| x as u16)),
+        V128Load16x4S(_, offset, _) => load!(u64, offset, |v: u64| extend::<i16, i32, _>(v as u128, false, |x| x as i32)),
+        V128Load16x4U(_, offset, _) => load!(u64, offset, |v: u64| extend::<u16, u32, _>(v as u128, false, |x| x as u32)),
+        V128Load32x2S(_, offset, _) => load!(u64, offset, |v: u64| extend::<i32, i64, _>(v as u128, false, |x| x as i64)),
+        V128Load32x2U(_, offset, _) => load!(u64, offset, |v: u64| extend::<u32, u64, _>(v as u128, false, |x| x as u64)),
+        V128Load8Splat(_, offset, _) => load!(u8, offset, |v: u8| build(|_| v)),
+        V128Load16Splat(_, offset, _) => load!(u16, offset, |v: u16| build(|_| v)),
+        V128Load32Splat(_, offset, _) => load!(u32, offset, |v: u32| build(|_| v)),
+        V128Load64Splat(_, offset, _) => load!(u64, offset, |v: u64| build(|_| v)),
+        V128Load32Zero(_, offset, _) => load!(u32, offset, |v: u32| v as u128),
+        V128Load64Zero(_, offset, _) => load!(u64, offset, |v: u64| v as u128),
+        V128Store(_, offset, _) => {
+            let v: u128 = pop(stack);
+            let address = effective_address(stack, memory(), offset)?;
+            memory().set_value(address, v)?;
+        },
+        V128Load8Lane(_, offset, _, i) => load_lane!(u8, offset, i),
+        V128Load16Lane(_, offset, _, i) => load_lane!(u16, offset, i),
+        V128Load32Lane(_, offset, _, i) => load_lane!(u32, offset, i),
+        V128Load64Lane(_, offset, _, i) => load_lane!(u64, offset, i),
+        V128Store8Lane(_, offset, _, i) => store_lane!(u8, offset, i),
+        V128Store16Lane(_, offset, _, i) => store_lane!(u16, offset, i),
+        V128Store32Lane(_, offset, _, i) => store_lane!(u32, offset, i),
+        V128Store64Lane(_, offset, _, i) => store_lane!(u64, offset, i),
+
+        V128Const(ref bytes) => push(stack, u128::from_le_bytes(**bytes)),
+        I8x16Shuffle(ref indices) => binop!(|a: u128, b: u128| build::<u8, _>(|i| {
+            let j = indices[i] as usize;
+            if j < 16 { lane(a, j) } else { lane(b, j - 16) }
+        })),
+        I8x16Swizzle => binop!(|a: u128, b: u128| build::<u8, _>(|i| {
+            let j = lane::<u8>(b, i) as usize;
+            if j < 16 { lane(a, j) } else { 0 }
+        })),
+
+        I8x16Splat => splat!(u8, u32),
+        I16x8Splat => splat!(u16, u32),
+        I32x4Splat => splat!(u32, u32),
+        I64x2Splat => splat!(u64, u64),
+        F32x4Splat => splat!(f32, f32),
+        F64x2Splat => splat!(f64, f64),
+
+        I8x16ExtractLaneS(i) => extract!(i8, i, |x: i8| x as i32),
+        I8x16ExtractLaneU(i) => extract!(u8, i, |x: u8| x as u32),
+        I16x8ExtractLaneS(i) => extract!(i16, i, |x: i16| x as i32),
+        I16x8ExtractLaneU(i) => extract!(u16, i, |x: u16| x as u32),
+        I32x4ExtractLane(i) => extract!(u32, i, |x: u32| x),
+        I64x2ExtractLane(i) => extract!(u64, i, |x: u64| x),
+        F32x4ExtractLane(i) => extract!(f32, i, |x: f32| x),
+        F64x2ExtractLane(i) => extract!(f64, i, |x: f64| x),
+        I8x16ReplaceLane(i) => replace!(u8, u32, i, |x: u32| x as u8),
+        I16x8ReplaceLane(i) => replace!(u16, u32, i, |x: u32| x as u16),
+        I32x4ReplaceLane(i) => replace!(u32, u32, i, |x: u32| x),
+        I64x2ReplaceLane(i) => replace!(u64, u64, i, |x: u64| x),
+        F32x4ReplaceLane(i) => replace!(f32, f32, i, |x: f32| x),
+        F64x2ReplaceLane(i) => replace!(f64, f64, i, |x: f64| x),
+
+        I8x16Eq => binop!(|a, b| compare::<u8, _>(a, b, |x, y| x == y)),
+        I8x16Ne => binop!(|a, b| compare::<u8, _>(a, b, |x, y| x != y)),
+        I8x16LtS => binop!(|a, b| compare::<i8, _>(a, b, |x, y| x < y)),
+        I8x16LtU => binop!(|a, b| compare::<u8, _>(a, b, |x, y| x < y)),
+        I8x16GtS => binop!(|a, b| compare::<i8, _>(a, b, |x, y| x > y)),
+        I8x16GtU => binop!(|a, b| compare::<u8, _>(a, b, |x, y| x > y)),
+        I8x16LeS => binop!(|a, b| compare::<i8, _>(a, b, |x, y| x <= y)),
+        I8x16LeU => binop!(|a, b| compare::<u8, _>(a, b, |x, y| x <= y)),
+        I8x16GeS => binop!(|a, b| compare::<i8, _>(a, b, |x, y| x >= y)),
+        I8x16GeU => binop!(|a, b| compare::<u8, _>(a, b, |x, y| x >= y)),
+        I16x8Eq => binop!(|a, b| compare::<u16, _>(a, b, |x, y| x == y)),
+        I16x8Ne => binop!(|a, b| compare::<u16, _>(a, b, |x, y| x != y)),
+        I16x8LtS => binop!(|a, b| compare::<i16, _>(a, b, |x, y| x < y)),
+        I16x8LtU => binop!(|a, b| compare::<u16, _>(a, b, |x, y| x < y)),
+        I16x8GtS => binop!(|a, b| compare::<i16, _>(a, b, |x, y| x > y)),
+        I16x8GtU => binop!(|a, b| compare::<u16, _>(a, b, |x, y| x > y)),
+        I16x8LeS => binop!(|a, b| compare::<i16, _>(a, b, |x, y| x <= y)),
+        I16x8LeU => binop!(|a, b| compare::<u16, _>(a, b, |x, y| x <= y)),
+        I16x8GeS => binop!(|a, b| compare::<i16, _>(a, b, |x, y| x >= y)),
+        I16x8GeU => binop!(|a, b| compare::<u16, _>(a, b, |x, y| x >= y)),
+        I32x4Eq => binop!(|a, b| compare::<u32, _>(a, b, |x, y| x == y)),
+        I32x4Ne => binop!(|a, b| compare::<u32, _>(a, b, |x, y| x != y)),
+        I32x4LtS => binop!(|a, b| compare::<i32, _>(a, b, |x, y| x < y)),
+        I32x4LtU => binop!(|a, b| compare::<u32, _>(a, b, |x, y| x < y)),
+        I32x4GtS => binop!(|a, b| compare::<i32, _>(a, b, |x, y| x > y)),
+        I32x4GtU => binop!(|a, b| compare::<u32, _>(a, b, |x, y| x > y)),
+        I32x4LeS => binop!(|a, b| compare::<i32, _>(a, b, |x, y| x <= y)),
+        I32x4LeU => binop!(|a, b| compare::<u32, _>(a, b, |x, y| x <= y)),
+        I32x4GeS => binop!(|a, b| compare::<i32, _>(a, b, |x, y| x >= y)),
+        I32x4GeU => binop!(|a, b| compare::<u32, _>(a, b, |x, y| x >= y)),
+        I64x2Eq => binop!(|a, b| compare::<u64, _>(a, b, |x, y| x == y)),
+        I64x2Ne => binop!(|a, b| compare::<u64, _>(a, b, |x, y| x != y)),
+        I64x2LtS => binop!(|a, b| compare::<i64, _>(a, b, |x, y| x < y)),
+        I64x2GtS => binop!(|a, b| compare::<i64, _>(a, b, |x, y| x > y)),
+        I64x2LeS => binop!(|a, b| compare::<i64, _>(a, b, |x, y| x <= y)),
+        I64x2GeS => binop!(|a, b| compare::<i64, _>(a, b, |x, y| x >= y)),
+        F32x4Eq => binop!(|a, b| compare::<f32, _>(a, b, |x, y| x == y)),
+        F32x4Ne => binop!(|a, b| compare::<f32, _>(a, b, |x, y| x != y)),
+        F32x4Lt => binop!(|a, b| compare::<f32, _>(a, b, |x, y| x < y)),
+        F32x4Gt => binop!(|a, b| compare::<f32, _>(a, b, |x, y| x > y)),
+        F32x4Le => binop!(|a, b| compare::<f32, _>(a, b, |x, y| x <= y)),
+        F32x4Ge => binop!(|a, b| compare::<f32, _>(a, b, |x, y| x >= y)),
+        F64x2Eq => binop!(|a, b| compare::<f64, _>(a, b, |x, y| x == y)),
+        F64x2Ne => binop!(|a, b| compare::<f64, _>(a, b, |x, y| x != y)),
+        F64x2Lt => binop!(|a, b| compare::<f64, _>(a, b, |x, y| x < y)),
+        F64x2Gt => binop!(|a, b| compare::<f64, _>(a, b, |x, y| x > y)),
+        F64x2Le => binop!(|a, b| compare::<f64, _>(a, b, |x, y| x <= y)),
+        F64x2Ge => binop!(|a, b| compare::<f64, _>(a, b, |x, y| x >= y)),
+
+        V128Not => unop!(|a: u128| !a),
+        V128And => binop!(|a: u128, b: u128| a & b),
+        V128AndNot => binop!(|a: u128, b: u128| a & !b),
+        V128Or => binop!(|a: u128, b: u128| a | b),
+        V128Xor => binop!(|a: u128, b: u128| a ^ b),
+        V128Bitselect => {
+            let c: u128 = pop(stack);
+            binop!(|a: u128, b: u128| (a & c) | (b & !c));
+        },
+        V128AnyTrue => unop!(|a: u128| a != 0),
+        I8x16AllTrue => unop!(all_true::<u8>),
+        I16x8AllTrue => unop!(all_true::<u16>),
+        I32x4AllTrue => unop!(all_true::<u32>),
+        I64x2AllTrue => unop!(all_true::<u64>),
+        I8x16Bitmask => unop!(bitmask::<i8>),
+        I16x8Bitmask => unop!(bitmask::<i16>),
+        I32x4Bitmask => unop!(bitmask::<i32>),
+        I64x2Bitmask => unop!(bitmask::<i64>),
+
+        I8x16Abs => unop!(|a| unary::<i8, _>(a, i8::wrapping_abs)),
+        I8x16Neg => unop!(|a| unary::<i8, _>(a, i8::wrapping_neg)),
+        I8x16Popcnt => unop!(|a| unary::<u8, _>(a, |x| x.count_ones() as u8)),
+        I8x16NarrowI16x8S => binop!(|a, b| narrow::<i16, i8, _>(a, b, |x| x.clamp(i8::MIN as i16, i8::MAX as i16) as i8)),
+        I8x16NarrowI16x8U => binop!(|a, b| narrow::<i16, u8, _>(a, b, |x| x.clamp(0, u8::MAX as i16) as u8)),
+        I8x16Shl => shiftop!(u8, |x: u8, n| x << n),
+        I8x16ShrS => shiftop!(i8, |x: i8, n| x >> n),
+        I8x16ShrU => shiftop!(u8, |x: u8, n| x >> n),
+        I8x16Add => binop!(|a, b| binary::<u8, _>(a, b, u8::wrapping_add)),
+        I8x16AddSatS => binop!(|a, b| binary::<i8, _>(a, b, i8::saturating_add)),
+        I8x16AddSatU => binop!(|a, b| binary::<u8, _>(a, b, u8::saturating_add)),
+        I8x16Sub => binop!(|a, b| binary::<u8, _>(a, b, u8::wrapping_sub)),
+        I8x16SubSatS => binop!(|a, b| binary::<i8, _>(a, b, i8::saturating_sub)),
+        I8x16SubSatU => binop!(|a, b| binary::<u8, _>(a, b, u8::saturating_sub)),
+        I8x16MinS => binop!(|a, b| binary::<i8, _>(a, b, i8::min)),
+        I8x16MinU => binop!(|a, b| binary::<u8, _>(a, b, u8::min)),
+        I8x16MaxS => binop!(|a, b| binary::<i8, _>(a, b, i8::max)),
+        I8x16MaxU => binop!(|a, b| binary::<u8, _>(a, b, u8::max)),
+        I8x16AvgrU => binop!(|a, b| binary::<u8, _>(a, b, |x, y| (x as u16 + y as u16).div_ceil(2) as u8)),
+
+        I16x8ExtaddPairwiseI8x16S => unop!(|a| pairwise::<i8, i16, _>(a, |x, y| x as i16 + y as i16)),
+        I16x8ExtaddPairwiseI8x16U => unop!(|a| pairwise::<u8, u16, _>(a, |x, y| x as u16 + y as u16)),
+        I32x4ExtaddPairwiseI16x8S => unop!(|a| pairwise::<i16, i32, _>(a, |x, y| x as i32 + y as i32)),
+        I32x4ExtaddPairwiseI16x8U => unop!(|a| pairwise::<u16, u32, _>(a, |x, y| x as u32 + y as u32)),
+
+        I16x8Abs => unop!(|a| unary::<i16, _>(a, i16::wrapping_abs)),
+        I16x8Neg => unop!(|a| unary::<i16, _>(a, i16::wrapping_neg)),
+        I16x8Q15MulrSatS => binop!(|a, b| binary::<i16, _>(a, b, |x, y| {
+            let product = (x as i32 * y as i32 + 0x4000) >> 15;
+            product.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+        })),
+        I16x8NarrowI32x4S => binop!(|a, b| narrow::<i32, i16, _>(a, b, |x| x.clamp(i16::MIN as i32, i16::MAX as i32) as i16)),
+        I16x8NarrowI32x4U => binop!(|a, b| narrow::<i32, u16, _>(a, b, |x| x.clamp(0, u16::MAX as i32) as u16)),
+        I16x8ExtendLowI8x16S => unop!(|a| extend::<i8, i16, _>(a, false, |x| x as i16)),
+        I16x8ExtendHighI8x16S => unop!(|a| extend::<i8, i16, _>(a, true, |x| x as i16)),
+        I16x8ExtendLowI8x16U => unop!(|a| extend::<u8, u16, _>(a, false, |x| x as u16)),
+        I16x8ExtendHighI8x16U => unop!(|a| extend::<u8, u16, _>(a, true, |x| x as u16)),
+        I16x8Shl => shiftop!(u16, |x: u16, n| x << n),
+        I16x8ShrS => shiftop!(i16, |x: i16, n| x >> n),
+        I16x8ShrU => shiftop!(u16, |x: u16, n| x >> n),
+        I16x8Add => binop!(|a, b| binary::<u16, _>(a, b, u16::wrapping_add)),
+        I16x8AddSatS => binop!(|a, b| binary::<i16, _>(a, b, i16::saturating_add)),
+        I16x8AddSatU => binop!(|a, b| binary::<u16, _>(a, b, u16::saturating_add)),
+        I16x8Sub => binop!(|a, b| binary::<u16, _>(a, b, u16::wrapping_sub)),
+        I16x8SubSatS => binop!(|a, b| binary::<i16, _>(a, b, i16::saturating_sub)),
+        I16x8SubSatU => binop!(|a, b| binary::<u16, _>(a, b, u16::saturating_sub)),
+        I16x8Mul => binop!(|a, b| binary::<u16, _>(a, b, u16::wrapping_mul)),
+        I16x8MinS => binop!(|a, b| binary::<i16, _>(a, b, i16::min)),
+        I16x8MinU => binop!(|a, b| binary::<u16, _>(a, b, u16::min)),
+        I16x8MaxS => binop!(|a, b| binary::<i16, _>(a, b, i16::max)),
+        I16x8MaxU => binop!(|a, b| binary::<u16, _>(a, b, u16::max)),
+        I16x8AvgrU => binop!(|a, b| binary::<u16, _>(a, b, |x, y| (x as u32 + y as u32).div_ceil(2) as u16)),
+        I16x8ExtmulLowI8x16S => binop!(|a, b| extmul::<i8, i16, _>(a, b, false, |x, y| x as i16 * y as i16)),
+        I16x8ExtmulHighI8x16S => binop!(|a, b| extmul::<i8, i16, _>(a, b, true, |x, y| x as i16 * y as i16)),
+        I16x8ExtmulLowI8x16U => binop!(|a, b| extmul::<u8, u16, _>(a, b, false, |x, y| x as u16 * y as u16)),
+        I16x8ExtmulHighI8x16U => binop!(|a, b| extmul::<u8, u16, _>(a, b, true, |x, y| x as u16 * y as u16)),
+
+        I32x4Abs => unop!(|a| unary::<i32, _>(a, i32::wrapping_abs)),
+        I32x4Neg => unop!(|a| unary::<i32, _>(a, i32::wrapping_neg)),
+        I32x4ExtendLowI16x8S => unop!(|a| extend::<i16, i32, _>(a, false, |x| x as i32)),
+        I32x4ExtendHighI16x8S => unop!(|a| extend::<i16, i32, _>(a, true, |x| x as i32)),
+        I32x4ExtendLowI16x8U => unop!(|a| extend::<u16, u32, _>(a, false, |x| x as u32)),
+        I32x4ExtendHighI16x8U => unop!(|a| extend::<u16, u32, _>(a, true, |x| x as u32)),
+        I32x4Shl => shiftop!(u32, |x: u32, n| x << n),
+        I32x4ShrS => shiftop!(i32, |x: i32, n| x >> n),
+        I32x4ShrU => shiftop!(u32, |x: u32, n| x >> n),
+        I32x4Add => binop!(|a, b| binary::<u32, _>(a, b, u32::wrapping_add)),
+        I32x4Sub => binop!(|a, b| binary::<u32, _>(a, b, u32::wrapping_sub)),
+        I32x4Mul => binop!(|a, b| binary::<u32, _>(a, b, u32::wrapping_mul)),
+        I32x4MinS => binop!(|a, b| binary::<i32, _>(a, b, i32::min)),
+        I32x4MinU => binop!(|a, b| binary::<u32, _>(a, b, u32::min)),
+        I32x4MaxS => binop!(|a, b| binary::<i32, _>(a, b, i32::max)),
+        I32x4MaxU => binop!(|a, b| binary::<u32, _>(a, b, u32::max)),
+        I32x4DotI16x8S => binop!(|a: u128, b: u128| build::<i32, _>(|i| {
+            let (a0, a1) = (lane::<i16>(a, 2 * i) as i32, lane::<i16>(a, 2 * i + 1) as i32);
+            let (b0, b1) = (lane::<i16>(b, 2 * i) as i32, lane::<i16>(b, 2 * i + 1) as i32);
+            (a0 * b0).wrapping_add(a1 * b1)
+        })),
+        I32x4ExtmulLowI16x8S => binop!(|a, b| extmul::<i16, i32, _>(a, b, false, |x, y| x as i32 * y as i32)),
+        I32x4ExtmulHighI16x8S => binop!(|a, b| extmul::<i16, i32, _>(a, b, true, |x, y| x as i32 * y as i32)),
+        I32x4ExtmulLowI16x8U => binop!(|a, b| extmul::<u16, u32, _>(a, b, false, |x, y| x as u32 * y as u32)),
+        I32x4ExtmulHighI16x8U => binop!(|a, b| extmul::<u16, u32, _>(a, b, true, |x, y| x as u32 * y as u32)),
+
+        I64x2Abs => unop!(|a| unary::<i64, _>(a, i64::wrapping_abs)),
+        I64x2Neg => unop!(|a| unary::<i64, _>(a, i64::wrapping_neg)),
+        I64x2ExtendLowI32x4S => unop!(|a| extend::<i32, i64, _>(a, false, |x| x as i64)),
+        I64x2ExtendHighI32x4S => unop!(|a| extend::<i32, i64, _>(a, true, |x| x as i64)),
+        I64x2ExtendLowI32x4U => unop!(|a| extend::<u32, u64, _>(a, false, |x| x as u64)),
+        I64x2ExtendHighI32x4U => unop!(|a| extend::<u32, u64, _>(a, true, |x| x as u64)),
+        I64x2Shl => shiftop!(u64, |x: u64, n| x << n),
+        I64x2ShrS => shiftop!(i64, |x: i64, n| x >> n),
+        I64x2ShrU => shiftop!(u64, |x: u64, n| x >> n),
+        I64x2Add => binop!(|a, b| binary::<u64, _>(a, b, u64::wrapping_add)),
+        I64x2Sub => binop!(|a, b| binary::<u64, _>(a, b, u64::wrapping_sub)),
+        I64x2Mul => binop!(|a, b| binary::<u64, _>(a, b, u64::wrapping_mul)),
+        I64x2ExtmulLowI32x4S => binop!(|a, b| extmul::<i32, i64, _>(a, b, false, |x, y| x as i64 * y as i64)),
+        I64x2ExtmulHighI32x4S => binop!(|a, b| extmul::<i32, i64, _>(a, b, true, |x, y| x as i64 * y as i64)),
+        I64x2ExtmulLowI32x4U => binop!(|a, b| extmul::<u32, u64, _>(a, b, false, |x, y| x as u64 * y as u64)),
+        I64x2ExtmulHighI32x4U => binop!(|a, b| extmul::<u32, u64, _>(a, b, true, |x, y| x as u64 * y as u64)),
+
+        F32x4Ceil => unop!(|a| unary::<f32, _>(a, f32::ceil)),
+        F32x4Floor => unop!(|a| unary::<f32, _>(a, f32::floor)),
+        F32x4Trunc => unop!(|a| unary::<f32, _>(a, f32::trunc)),
+        F32x4Nearest => unop!(|a| unary::<f32, _>(a, f32::round_ties_even)),
+        F32x4Abs => unop!(|a| unary::<f32, _>(a, f32::abs)),
+        F32x4Neg => unop!(|a| unary::<f32, _>(a, |x: f32| -x)),
+        F32x4Sqrt => unop!(|a| unary::<f32, _>(a, f32::sqrt)),
+        F32x4Add => binop!(|a, b| binary::<f32, _>(a, b, |x, y| x + y)),
+        F32x4Sub => binop!(|a, b| binary::<f32, _>(a, b, |x, y| x - y)),
+        F32x4Mul => binop!(|a, b| binary::<f32, _>(a, b, |x, y| x * y)),
+        F32x4Div => binop!(|a, b| binary::<f32, _>(a, b, |x, y| x / y)),
+        F32x4Min => binop!(|a, b| binary::<f32, _>(a, b, f32::wasm_min)),
+        F32x4Max => binop!(|a, b| binary::<f32, _>(a, b, f32::wasm_max)),
+        F32x4Pmin => binop!(|a, b| binary::<f32, _>(a, b, pmin)),
+        F32x4Pmax => binop!(|a, b| binary::<f32, _>(a, b, pmax)),
+        F64x2Ceil => unop!(|a| unary::<f64, _>(a, f64::ceil)),
+        F64x2Floor => unop!(|a| unary::<f64, _>(a, f64::floor)),
+        F64x2Trunc => unop!(|a| unary::<f64, _>(a, f64::trunc)),
+        F64x2Nearest => unop!(|a| unary::<f64, _>(a, f64::round_ties_even)),
+        F64x2Abs => unop!(|a| unary::<f64, _>(a, f64::abs)),
+        F64x2Neg => unop!(|a| unary::<f64, _>(a, |x: f64| -x)),
+        F64x2Sqrt => unop!(|a| unary::<f64, _>(a, f64::sqrt)),
+        F64x2Add => binop!(|a, b| binary::<f64, _>(a, b, |x, y| x + y)),
+        F64x2Sub => binop!(|a, b| binary::<f64, _>(a, b, |x, y| x - y)),
+        F64x2Mul => binop!(|a, b| binary::<f64, _>(a, b, |x, y| x * y)),
+        F64x2Div => binop!(|a, b| binary::<f64, _>(a, b, |x, y| x / y)),
+        F64x2Min => binop!(|a, b| binary::<f64, _>(a, b, f64::wasm_min)),
+        F64x2Max => binop!(|a, b| binary::<f64, _>(a, b, f64::wasm_max)),
+        F64x2Pmin => binop!(|a, b| binary::<f64, _>(a, b, pmin)),
+        F64x2Pmax => binop!(|a, b| binary::<f64, _>(a, b, pmax)),
+
+        // `as` 是饱和转换，NaN 变成 0
+        F32x4DemoteF64x2Zero => unop!(|a| build::<f32, _>(|i| if i < 2 { lane::<f64>(a, i) as f32 } else { 0.0 })),
+        F64x2PromoteLowF32x4 => unop!(|a| build::<f64, _>(|i| lane::<f32>(a, i) as f64)),
+        I32x4TruncSatF32x4S => unop!(|a| build::<i32, _>(|i| lane::<f32>(a, i) as i32)),
+        I32x4TruncSatF32x4U => unop!(|a| build::<u32, _>(|i| lane::<f32>(a, i) as u32)),
+        F32x4ConvertI32x4S => unop!(|a| build::<f32, _>(|i| lane::<i32>(a, i) as f32)),
+        F32x4ConvertI32x4U => unop!(|a| build::<f32, _>(|i| lane::<u32>(a, i) as f32)),
+        I32x4TruncSatF64x2SZero => unop!(|a| build::<i32, _>(|i| if i < 2 { lane::<f64>(a, i) as i32 } else { 0 })),
+        I32x4TruncSatF64x2UZero => unop!(|a| build::<u32, _>(|i| if i < 2 { lane::<f64>(a, i) as u32 } else { 0 })),
+        F64x2ConvertLowI32x4S => unop!(|a| build::<f64, _>(|i| lane::<i32>(a, i) as f64)),
+        F64x2ConvertLowI32x4U => unop!(|a| build::<f64, _>(|i| lane::<u32>(a, i) as f64)),
+    }
+    Ok(())
+}
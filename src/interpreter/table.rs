@@ -0,0 +1,128 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::elements::types::TableElementType;
+use super::{Trap, TrapKind, Value};
+
+/// Reference to a table.
+#[derive(Clone, Debug)]
+pub struct TableRef(Rc<TableInstance>);
+
+impl Deref for TableRef {
+    type Target = TableInstance;
+    fn deref(&self) -> &TableInstance {
+        &self.0
+    }
+}
+
+/// Table of references, all of the same element type.
+pub struct TableInstance {
+    elem_type: TableElementType,
+    elements: RefCell<Vec<Value>>,
+    initial: u32,
+    maximum: Option<u32>,
+}
+
+impl fmt::Debug for TableInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TableInstance")
+            .field("elem_type", &self.elem_type)
+            .field("size", &self.current_size())
+            .field("initial", &self.initial)
+            .field("maximum", &self.maximum)
+            .finish()
+    }
+}
+
+impl TableInstance {
+    /// Allocate a table of `initial` null elements.
+    pub fn alloc(elem_type: TableElementType, initial: u32, maximum: Option<u32>) -> TableRef {
+        TableInstance::alloc_filled(elem_type, Value::default(elem_type.value_type()), initial, maximum)
+    }
+
+    /// Allocate a table of `initial` copies of `init`.
+    pub(crate) fn alloc_filled(elem_type: TableElementType, init: Value, initial: u32, maximum: Option<u32>) -> TableRef {
+        TableRef(Rc::new(TableInstance {
+            elem_type,
+            elements: RefCell::new(vec![init; initial as usize]),
+            initial,
+            maximum,
+        }))
+    }
+
+    pub fn elem_type(&self) -> TableElementType {
+        self.elem_type
+    }
+
+    pub fn initial(&self) -> u32 {
+        self.initial
+    }
+
+    pub fn maximum(&self) -> Option<u32> {
+        self.maximum
+    }
+
+    pub fn current_size(&self) -> u32 {
+        self.elements.borrow().len() as u32
+    }
+
+    /// Grow by `delta` copies of `init`, returning the previous size or `None` if the limit is exceeded.
+    pub fn grow(&self, delta: u32, init: Value) -> Option<u32> {
+        let current = self.current_size();
+        let max = self.maximum.unwrap_or(u32::MAX);
+        let new = current.checked_add(delta).filter(|&n| n <= max)?;
+        self.elements.borrow_mut().resize(new as usize, init);
+        Some(current)
+    }
+
+    pub fn get(&self, offset: u32) -> Result<Value, Trap> {
+        self.elements
+            .borrow()
+            .get(offset as usize)
+            .cloned()
+            .ok_or_else(|| TrapKind::TableAccessOutOfBounds.into())
+    }
+
+    /// Copy `len` elements starting at `offset`.
+    pub fn get_range(&self, offset: u32, len: u32) -> Result<Vec<Value>, Trap> {
+        let elements = self.elements.borrow();
+        let end = offset as u64 + len as u64;
+        if end > elements.len() as u64 {
+            return Err(TrapKind::TableAccessOutOfBounds.into());
+        }
+        Ok(elements[offset as usize..end as usize].to_vec())
+    }
+
+    /// Write `values` starting at `offset`, nothing is written if they don't fit.
+    pub fn set_range(&self, offset: u32, values: &[Value]) -> Result<(), Trap> {
+        let mut elements = self.elements.borrow_mut();
+        let end = offset as u64 + values.len() as u64;
+        if end > elements.len() as u64 {
+            return Err(TrapKind::TableAccessOutOfBounds.into());
+        }
+        elements[offset as usize..end as usize].clone_from_slice(values);
+        Ok(())
+    }
+
+    pub fn set(&self, offset: u32, value: Value) -> Result<(), Trap> {
+        let mut elements = self.elements.borrow_mut();
+        let slot = elements.get_mut(offset as usize).ok_or(TrapKind::TableAccessOutOfBounds)?;
+        *slot = value;
+        Ok(())
+    }
+
+    /// Set `len` elements starting at `offset` to `value`.
+    pub fn fill(&self, offset: u32, len: u32, value: Value) -> Result<(), Trap> {
+        let mut elements = self.elements.borrow_mut();
+        let end = offset as u64 + len as u64;
+        if end > elements.len() as u64 {
+            return Err(TrapKind::TableAccessOutOfBounds.into());
+        }
+        for slot in elements[offset as usize..end as usize].iter_mut() {
+            *slot = value.clone();
+        }
+        Ok(())
+    }
+}
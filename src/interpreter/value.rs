@@ -0,0 +1,206 @@
+use crate::elements::types::ValueType;
+use crate::elements::const_expr::ConstValue;
+use crate::validation::ModuleContext;
+use super::FuncRef;
+#[cfg(feature = "exceptions")]
+use super::ExnRef;
+#[cfg(feature = "gc")]
+use crate::elements::types::{HeapType, RefType};
+#[cfg(feature = "gc")]
+use super::AnyRef;
+
+/// Runtime value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    /// Function reference, `None` is the null reference.
+    FuncRef(Option<FuncRef>),
+    /// Opaque reference to a host object.
+    ExternRef(Option<u32>),
+    /// Reference to a caught exception.
+    #[cfg(feature = "exceptions")]
+    ExnRef(Option<ExnRef>),
+    /// 128-bit vector, lanes are stored little endian.
+    #[cfg(feature = "simd")]
+    V128(u128),
+    /// Reference of the `any` hierarchy, `None` is the null reference.
+    #[cfg(feature = "gc")]
+    AnyRef(Option<AnyRef>),
+    /// Internal object converted by `extern.convert_any`.
+    #[cfg(feature = "gc")]
+    Externalized(AnyRef),
+}
+
+impl Value {
+    /// Zero value of the given type, used for locals and globals.
+    ///
+    /// References default to null. Defined types are only known to their
+    /// module, which maps them to their hierarchy first.
+    pub fn default(value_type: ValueType) -> Value {
+        match value_type {
+            ValueType::I32 => Value::I32(0),
+            ValueType::I64 => Value::I64(0),
+            ValueType::F32 => Value::F32(0.0),
+            ValueType::F64 => Value::F64(0.0),
+            ValueType::FuncRef => Value::FuncRef(None),
+            ValueType::ExternRef => Value::ExternRef(None),
+            #[cfg(feature = "exceptions")]
+            ValueType::ExnRef => Value::ExnRef(None),
+            #[cfg(feature = "simd")]
+            ValueType::V128 => Value::V128(0),
+            #[cfg(feature = "gc")]
+            ValueType::Ref(ref_type) => match ref_type.heap {
+                HeapType::Func | HeapType::NoFunc => Value::FuncRef(None),
+                HeapType::Extern | HeapType::NoExtern => Value::ExternRef(None),
+                _ => Value::AnyRef(None),
+            },
+        }
+    }
+
+    /// Whether this is a null reference.
+    pub fn is_null(&self) -> bool {
+        match *self {
+            Value::FuncRef(None) | Value::ExternRef(None) => true,
+            #[cfg(feature = "exceptions")]
+            Value::ExnRef(None) => true,
+            #[cfg(feature = "gc")]
+            Value::AnyRef(None) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the value can be used where `value_type` is expected.
+    ///
+    /// Defined types are resolved in `types`, the module expecting the value,
+    /// and never match without it.
+    #[cfg_attr(not(feature = "gc"), allow(unused_variables))]
+    pub fn has_type(&self, value_type: ValueType, types: Option<&ModuleContext>) -> bool {
+        #[cfg(feature = "gc")]
+        if let Some(expected) = value_type.ref_type() {
+            return self.ref_type(types).is_some_and(|actual| {
+                let empty = ModuleContext::default();
+                let types = types.unwrap_or(&empty);
+                match (self, expected.heap) {
+                    // 函数按签名匹配，可以跨模块
+                    (Value::FuncRef(Some(func)), HeapType::Concrete(idx)) => {
+                        types.func_type(idx).is_ok_and(|ty| super::func::signature_matches(func.signature(), ty))
+                    },
+                    _ => types.ref_matches(actual, expected),
+                }
+            });
+        }
+        self.value_type() == value_type
+    }
+
+    /// Most precise reference type of the value seen from the module of `types`.
+    ///
+    /// Objects allocated by other modules only have their abstract type.
+    #[cfg(feature = "gc")]
+    fn ref_type(&self, types: Option<&ModuleContext>) -> Option<RefType> {
+        let heap = match *self {
+            Value::FuncRef(None) => return Some(RefType::nullable(HeapType::NoFunc)),
+            Value::ExternRef(None) => return Some(RefType::nullable(HeapType::NoExtern)),
+            Value::AnyRef(None) => return Some(RefType::nullable(HeapType::None)),
+            Value::FuncRef(Some(_)) => HeapType::Func,
+            Value::ExternRef(Some(_)) | Value::Externalized(_) => HeapType::Extern,
+            Value::AnyRef(Some(ref any)) => any.heap_type(types),
+            _ => return None,
+        };
+        Some(RefType::non_nullable(heap))
+    }
+
+    pub fn value_type(&self) -> ValueType {
+        match *self {
+            Value::I32(_) => ValueType::I32,
+            Value::I64(_) => ValueType::I64,
+            Value::F32(_) => ValueType::F32,
+            Value::F64(_) => ValueType::F64,
+            Value::FuncRef(_) => ValueType::FuncRef,
+            Value::ExternRef(_) => ValueType::ExternRef,
+            #[cfg(feature = "exceptions")]
+            Value::ExnRef(_) => ValueType::ExnRef,
+            #[cfg(feature = "simd")]
+            Value::V128(_) => ValueType::V128,
+            #[cfg(feature = "gc")]
+            Value::AnyRef(_) => RefType::nullable(HeapType::Any).into(),
+            #[cfg(feature = "gc")]
+            Value::Externalized(_) => ValueType::ExternRef,
+        }
+    }
+
+    /// Convert into a Rust value, `None` if the type does not match.
+    pub fn try_into<T: FromValue>(self) -> Option<T> {
+        T::from_value(self)
+    }
+}
+
+/// Types which can be extracted from a `Value`.
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+macro_rules! impl_value_conversion {
+    ($variant: ident, $t: ty, $repr: ty) => {
+        impl From<$t> for Value {
+            fn from(v: $t) -> Value {
+                Value::$variant(v as $repr)
+            }
+        }
+
+        impl FromValue for $t {
+            fn from_value(value: Value) -> Option<$t> {
+                match value {
+                    Value::$variant(v) => Some(v as $t),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+impl_value_conversion!(I32, i32, i32);
+impl_value_conversion!(I32, u32, i32);
+impl_value_conversion!(I64, i64, i64);
+impl_value_conversion!(I64, u64, i64);
+impl_value_conversion!(F32, f32, f32);
+impl_value_conversion!(F64, f64, f64);
+#[cfg(feature = "simd")]
+impl_value_conversion!(V128, u128, u128);
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Value {
+        Value::I32(v as i32)
+    }
+}
+
+/// Numbers of constant expressions become values, references already are.
+impl From<ConstValue<Value>> for Value {
+    fn from(v: ConstValue<Value>) -> Value {
+        match v {
+            ConstValue::I32(v) => Value::I32(v),
+            ConstValue::I64(v) => Value::I64(v),
+            ConstValue::F32(bits) => Value::F32(f32::from_bits(bits)),
+            ConstValue::F64(bits) => Value::F64(f64::from_bits(bits)),
+            #[cfg(feature = "simd")]
+            ConstValue::V128(v) => Value::V128(v),
+            ConstValue::Ref(value) => value,
+        }
+    }
+}
+
+impl From<Value> for ConstValue<Value> {
+    fn from(v: Value) -> ConstValue<Value> {
+        match v {
+            Value::I32(v) => ConstValue::I32(v),
+            Value::I64(v) => ConstValue::I64(v),
+            Value::F32(v) => ConstValue::F32(v.to_bits()),
+            Value::F64(v) => ConstValue::F64(v.to_bits()),
+            #[cfg(feature = "simd")]
+            Value::V128(v) => ConstValue::V128(v),
+            reference => ConstValue::Ref(reference),
+        }
+    }
+}
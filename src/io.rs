@@ -23,4 +23,64 @@ impl<T: io::Read> io::Read for BufReader<'_, T> {
         }
         Ok(l)
     }
-}
\ No newline at end of file
+}
+
+/// 记录读取过的所有字节，用于保留模块的原始编码
+pub struct RecordingReader<'a, T: io::Read> {
+    reader: &'a mut T,
+    recorded: Vec<u8>,
+}
+
+impl<'a, T: io::Read> RecordingReader<'a, T> {
+    pub fn new(r: &'a mut T) -> RecordingReader<'a, T> {
+        RecordingReader {
+            reader: r,
+            recorded: Vec::new(),
+        }
+    }
+
+    /// Number of bytes read so far.
+    pub fn position(&self) -> usize {
+        self.recorded.len()
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.recorded
+    }
+}
+
+impl<T: io::Read> io::Read for RecordingReader<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let l = self.reader.read(buf)?;
+        self.recorded.extend_from_slice(&buf[..l]);
+        Ok(l)
+    }
+}
+
+/// 记录已经读取的字节数，用于报告解码错误的位置
+pub struct PositionReader<'a, T: io::Read> {
+    reader: &'a mut T,
+    position: usize,
+}
+
+impl<'a, T: io::Read> PositionReader<'a, T> {
+    pub fn new(r: &'a mut T) -> PositionReader<'a, T> {
+        PositionReader {
+            reader: r,
+            position: 0,
+        }
+    }
+
+    /// Number of bytes read so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<T: io::Read> io::Read for PositionReader<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let l = self.reader.read(buf)?;
+        self.position += l;
+        Ok(l)
+    }
+}
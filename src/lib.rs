@@ -0,0 +1,8 @@
+pub mod elements;
+pub mod io;
+pub mod validation;
+pub mod interpreter;
+pub mod wat;
+
+#[cfg(test)]
+mod tests;
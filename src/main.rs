@@ -1,30 +1,528 @@
-mod elements;
-mod tests;
-mod io;
+use std::env;
+use std::fmt::{self, Write};
+use std::fs;
+use std::process;
 
-use elements::module::Module;
-use std::fmt;
+use learning_wasm::elements::Deserialize;
+use learning_wasm::elements::module::Module;
+use learning_wasm::elements::primitives::VarUint32;
+use learning_wasm::elements::sections::Section;
+use learning_wasm::elements::types::{FunctionType, ValueType};
+#[cfg(feature = "gc")]
+use learning_wasm::elements::types::HeapType;
+use learning_wasm::elements::import_entry::{External, ResizableLimits};
+use learning_wasm::elements::export_entry::Internal;
+use learning_wasm::validation::validate;
+use learning_wasm::wat::Printer;
 
+const USAGE: &str = "\
+usage: learning-wasm <command> <file> [args]
 
-type AInt = i32;
-type BInt = i32;
+commands:
+  dump <file>                     section headers with offsets and sizes
+  imports <file>                  imported functions, tables, memories and globals
+  exports <file>                  exported entities
+  disasm <file> <func> [--folded] text format of a function, by index or name
+  validate <file>                 check the module against the validation rules
+  stats <file>                    counts of entities and instructions";
 
-trait A {
-    fn a(&self);
+/// Failure of a command, printed to stderr before exiting with `code`.
+#[derive(Debug)]
+struct CliError {
+    message: String,
+    code: i32,
 }
 
-impl A for AInt {
-    fn a(&self) {
-        println!("aint");
+impl CliError {
+    fn usage<M: Into<String>>(message: M) -> CliError {
+        CliError { message: format!("{}\n\n{}", message.into(), USAGE), code: 2 }
     }
-}
 
+    fn failure<M: Into<String>>(message: M) -> CliError {
+        CliError { message: message.into(), code: 1 }
+    }
+}
 
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
 
 fn main() {
-    let m = Module::default();
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(output) => print!("{}", output),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(e.code);
+        },
+    }
+}
+
+/// Run the command of `args` and return what it prints.
+fn run(args: &[String]) -> Result<String, CliError> {
+    let command = match args.first() {
+        Some(command) => command.as_str(),
+        None => return Err(CliError::usage("missing command")),
+    };
+    if command == "help" || command == "--help" || command == "-h" {
+        return Ok(format!("{}\n", USAGE));
+    }
+    let path = args.get(1).ok_or_else(|| CliError::usage("missing file argument"))?;
+    let rest = &args[2..];
+
+    let check_args = |max: usize| if rest.len() > max {
+        Err(CliError::usage(format!("unexpected argument `{}`", rest[max])))
+    } else {
+        Ok(())
+    };
+    match command {
+        "dump" | "imports" | "exports" | "validate" | "stats" => check_args(0)?,
+        "disasm" => check_args(2)?,
+        _ => return Err(CliError::usage(format!("unknown command `{}`", command))),
+    }
+
+    // 只有 dump 需要每个 section 的位置，其它命令不保留原始编码
+    let module = load(path, command == "dump")?;
+    match command {
+        "dump" => dump(&module),
+        "imports" => Ok(imports(&module)),
+        "exports" => Ok(exports(&module)),
+        "disasm" => disasm(&module, rest),
+        "validate" => match validate(&module) {
+            Ok(_) => Ok(format!("{}: valid\n", path)),
+            Err(e) => Err(CliError::failure(format!("{}: invalid module: {}", path, e))),
+        },
+        _ => Ok(stats(&module)),
+    }
+}
+
+/// Decode the module at `path`, keeping its original encoding if `preserve` is set.
+fn load(path: &str, preserve: bool) -> Result<Module, CliError> {
+    let bytes = fs::read(path).map_err(|e| CliError::failure(format!("{}: {}", path, e)))?;
+    let mut cursor = std::io::Cursor::new(&bytes[..]);
+    let module = if preserve {
+        Module::deserialize_preserving_encoding(&mut cursor)
+    } else {
+        Module::deserialize(&mut cursor)
+    };
+    module.map_err(|e| CliError::failure(format!("{}: failed to parse module: {}", path, e)))
+}
+
+fn section_name(section: &Section) -> String {
+    let name = match *section {
+        Section::Custom(ref custom) => return format!("custom \"{}\"", custom.name),
+        Section::Unparsed { .. } => "unknown",
+        Section::Type(_) => "type",
+        Section::Import(_) => "import",
+        Section::Function(_) => "function",
+        Section::Table(_) => "table",
+        Section::Memory(_) => "memory",
+        Section::Global(_) => "global",
+        #[cfg(feature = "exceptions")]
+        Section::Tag(_) => "tag",
+        Section::Export(_) => "export",
+        Section::Start(_) => "start",
+        Section::Element(_) => "elem",
+        Section::DataCount(_) => "datacount",
+        Section::Code(_) => "code",
+        Section::Data(_) => "data",
+    };
+    name.to_string()
+}
+
+/// Number of entries of a vector section.
+fn section_count(section: &Section) -> Option<usize> {
+    match *section {
+        Section::Type(ref s) => Some(s.types().count()),
+        Section::Import(ref s) => Some(s.0.len()),
+        Section::Function(ref s) => Some(s.0.len()),
+        Section::Table(ref s) => Some(s.0.len()),
+        Section::Memory(ref s) => Some(s.0.len()),
+        Section::Global(ref s) => Some(s.0.len()),
+        #[cfg(feature = "exceptions")]
+        Section::Tag(ref s) => Some(s.0.len()),
+        Section::Export(ref s) => Some(s.0.len()),
+        Section::Element(ref s) => Some(s.0.len()),
+        Section::Code(ref s) => Some(s.0.len()),
+        Section::Data(ref s) => Some(s.0.len()),
+        _ => None,
+    }
 }
 
+fn dump(module: &Module) -> Result<String, CliError> {
+    let encoding = module.encoding.as_ref()
+        .ok_or_else(|| CliError::failure("section offsets are only known for modules loaded with their encoding"))?;
+    let mut out = String::new();
+    writeln!(out, "{:>3}  {:<20} {:>10} {:>10} {:>10}  count", "id", "section", "start", "end", "size").unwrap();
+    for (i, section) in module.sections.iter().enumerate() {
+        let range = &encoding.sections[i];
+        // 跳过 id 和长度前缀，只统计内容
+        let mut header = &encoding.bytes[range.start + 1..range.end];
+        let size: u32 = VarUint32::deserialize(&mut header).map(Into::into).unwrap_or(0);
+        let start = range.end - size as usize;
+        let count = match *section {
+            Section::Start(idx) => format!("func {}", idx),
+            Section::DataCount(count) => count.to_string(),
+            _ => section_count(section).map(|c| c.to_string()).unwrap_or_default(),
+        };
+        writeln!(
+            out, "{:>3}  {:<20} {:#010x} {:#010x} {:#010x}  {}",
+            section.id(), section_name(section), start, range.end, size, count
+        ).unwrap();
+    }
+    Ok(out)
+}
+
+fn value_type(t: ValueType) -> String {
+    let name = match t {
+        ValueType::I32 => "i32",
+        ValueType::I64 => "i64",
+        ValueType::F32 => "f32",
+        ValueType::F64 => "f64",
+        ValueType::FuncRef => "funcref",
+        ValueType::ExternRef => "externref",
+        #[cfg(feature = "exceptions")]
+        ValueType::ExnRef => "exnref",
+        #[cfg(feature = "simd")]
+        ValueType::V128 => "v128",
+        #[cfg(feature = "gc")]
+        ValueType::Ref(ref_type) => {
+            // 抽象堆类型的 Debug 名字小写后就是文本格式的关键字
+            let heap = match ref_type.heap {
+                HeapType::Concrete(idx) => idx.to_string(),
+                heap => format!("{:?}", heap).to_lowercase(),
+            };
+            return format!("(ref {}{})", if ref_type.nullable { "null " } else { "" }, heap);
+        },
+    };
+    name.to_string()
+}
 
+/// Limits as in the text format, e.g. `i64 1 10 shared`.
+fn limits(limits: &ResizableLimits) -> String {
+    let mut out = String::new();
+    if limits.memory64 {
+        out.push_str("i64 ");
+    }
+    out.push_str(&limits.initial.to_string());
+    if let Some(maximum) = limits.maximum {
+        write!(out, " {}", maximum).unwrap();
+    }
+    if limits.shared {
+        out.push_str(" shared");
+    }
+    out
+}
 
+fn signature(ty: Option<&FunctionType>) -> String {
+    match ty {
+        Some(ty) => {
+            let params: Vec<_> = ty.params.iter().map(|&t| value_type(t)).collect();
+            let results: Vec<_> = ty.results.iter().map(|&t| value_type(t)).collect();
+            format!("({}) -> ({})", params.join(", "), results.join(", "))
+        },
+        None => "<unknown type>".to_string(),
+    }
+}
+
+fn func_type(module: &Module, type_idx: u32) -> Option<&FunctionType> {
+    module.type_section().and_then(|s| s.func_type(type_idx))
+}
+
+/// Type index of every function, imported ones first.
+fn func_types(module: &Module) -> Vec<u32> {
+    let mut types: Vec<u32> = module.import_section()
+        .map(|s| s.0.iter().filter_map(|e| match e.external {
+            External::Function(idx) => Some(idx),
+            _ => None,
+        }).collect())
+        .unwrap_or_default();
+    if let Some(section) = module.function_section() {
+        types.extend(section.0.iter().map(|f| f.0));
+    }
+    types
+}
+
+fn imports(module: &Module) -> String {
+    let mut out = String::new();
+    let entries = module.import_section().map(|s| &s.0[..]).unwrap_or(&[]);
+    let (mut funcs, mut tables, mut memories, mut globals) = (0, 0, 0, 0);
+    #[cfg(feature = "exceptions")]
+    let mut tags = 0;
+    for entry in entries.iter() {
+        let desc = match entry.external {
+            External::Function(type_idx) => {
+                funcs += 1;
+                format!("func[{}] type={} {}", funcs - 1, type_idx, signature(func_type(module, type_idx)))
+            },
+            External::Table(ref table_type) => {
+                tables += 1;
+                format!("table[{}] {} {}", tables - 1, limits(&table_type.limits), value_type(table_type.elem_type.value_type()))
+            },
+            External::Memory(ref memory) => {
+                memories += 1;
+                format!("memory[{}] {}", memories - 1, limits(memory))
+            },
+            External::Global(ref global_type) => {
+                globals += 1;
+                let mutability = if global_type.is_mutable { "mut " } else { "" };
+                format!("global[{}] {}{}", globals - 1, mutability, value_type(global_type.content_type))
+            },
+            #[cfg(feature = "exceptions")]
+            External::Tag(ref tag) => {
+                tags += 1;
+                format!("tag[{}] type={} {}", tags - 1, tag.type_index, signature(func_type(module, tag.type_index)))
+            },
+        };
+        writeln!(out, "{} <- {}.{}", desc, entry.module_str, entry.field_str).unwrap();
+    }
+    out
+}
+
+fn exports(module: &Module) -> String {
+    let mut out = String::new();
+    let types = func_types(module);
+    let entries = module.export_section().map(|s| &s.0[..]).unwrap_or(&[]);
+    for entry in entries.iter() {
+        let desc = match entry.internal {
+            Internal::Function(idx) => {
+                let ty = types.get(idx as usize).and_then(|&t| func_type(module, t));
+                format!("func[{}] {}", idx, signature(ty))
+            },
+            Internal::Table(idx) => format!("table[{}]", idx),
+            Internal::Memory(idx) => format!("memory[{}]", idx),
+            Internal::Global(idx) => format!("global[{}]", idx),
+            #[cfg(feature = "exceptions")]
+            Internal::Tag(idx) => format!("tag[{}]", idx),
+        };
+        writeln!(out, "{} -> {:?}", desc, entry.field_str).unwrap();
+    }
+    out
+}
+
+fn disasm(module: &Module, args: &[String]) -> Result<String, CliError> {
+    let mut folded = false;
+    let mut func = None;
+    for arg in args.iter() {
+        if arg == "--folded" {
+            folded = true;
+        } else if func.is_none() {
+            func = Some(arg);
+        } else {
+            return Err(CliError::usage(format!("unexpected argument `{}`", arg)));
+        }
+    }
+    let func = func.ok_or_else(|| CliError::usage("missing function index or export name"))?;
+
+    // 先按序号解析，否则按导出名查找，最后查 name section
+    let func_idx = match func.parse::<u32>() {
+        Ok(idx) => idx,
+        Err(_) => module.export_section()
+            .and_then(|s| s.0.iter().find_map(|e| match e.internal {
+                Internal::Function(idx) if e.field_str == *func => Some(idx),
+                _ => None,
+            }))
+            .or_else(|| {
+                let names = module.name_section().ok().flatten()?;
+                names.functions.iter().find(|&(_, name)| name == func).map(|(&idx, _)| idx)
+            })
+            .ok_or_else(|| CliError::failure(format!("no function named {:?}", func)))?,
+    };
+    Printer::new()
+        .folded(folded)
+        .print_func(module, func_idx)
+        .ok_or_else(|| CliError::failure(format!("function {} is imported or doesn't exist", func_idx)))
+}
+
+fn stats(module: &Module) -> String {
+    let mut out = String::new();
+    let entries = module.import_section().map(|s| &s.0[..]).unwrap_or(&[]);
+    let imported = |f: fn(&External) -> bool| entries.iter().filter(|e| f(&e.external)).count();
+    let bodies = module.code_section().map(|s| &s.0[..]).unwrap_or(&[]);
+    let instructions: usize = bodies.iter().map(|b| b.instructions.elements().len()).sum();
+    let largest = bodies.iter().enumerate().max_by_key(|&(_, b)| b.instructions.elements().len());
+    let count = |n: Option<usize>| n.unwrap_or(0);
+
+    writeln!(out, "version:       {}", module.version).unwrap();
+    writeln!(out, "sections:      {} ({} custom)", module.sections.len(), module.custom_sections().count()).unwrap();
+    writeln!(out, "types:         {}", count(module.type_section().map(|s| s.types().count()))).unwrap();
+    writeln!(out, "imports:       {}", entries.len()).unwrap();
+    writeln!(
+        out,
+        "functions:     {} ({} imported)",
+        imported(|e| matches!(*e, External::Function(_))) + bodies.len(),
+        imported(|e| matches!(*e, External::Function(_)))
+    ).unwrap();
+    writeln!(
+        out,
+        "tables:        {} ({} imported)",
+        imported(|e| matches!(*e, External::Table(_))) + count(module.table_section().map(|s| s.0.len())),
+        imported(|e| matches!(*e, External::Table(_)))
+    ).unwrap();
+    writeln!(
+        out,
+        "memories:      {} ({} imported)",
+        imported(|e| matches!(*e, External::Memory(_))) + count(module.memory_section().map(|s| s.0.len())),
+        imported(|e| matches!(*e, External::Memory(_)))
+    ).unwrap();
+    writeln!(
+        out,
+        "globals:       {} ({} imported)",
+        imported(|e| matches!(*e, External::Global(_))) + count(module.global_section().map(|s| s.0.len())),
+        imported(|e| matches!(*e, External::Global(_)))
+    ).unwrap();
+    writeln!(out, "exports:       {}", count(module.export_section().map(|s| s.0.len()))).unwrap();
+    writeln!(out, "elem segments: {}", count(module.elements_section().map(|s| s.0.len()))).unwrap();
+    writeln!(out, "data segments: {}", count(module.data_section().map(|s| s.0.len()))).unwrap();
+    writeln!(out, "instructions:  {}", instructions).unwrap();
+    if let Some((i, body)) = largest {
+        let imported_funcs = imported(|e| matches!(*e, External::Function(_)));
+        writeln!(out, "largest func:  {} ({} instructions)", imported_funcs + i, body.instructions.elements().len()).unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use learning_wasm::elements::serialize;
+
+    const SAMPLE: &str = r#"
+        (module
+          (import "env" "log" (func $log (param i32)))
+          (memory (export "mem") 1)
+          (func $add (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1))))
+    "#;
+
+    /// Run `args` on a temporary file holding `bytes`, the file is inserted after the command.
+    fn run_on(name: &str, bytes: &[u8], args: &[&str]) -> Result<String, CliError> {
+        // 测试并行运行，每个文件名都不同
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT.fetch_add(1, Ordering::SeqCst);
+        let path = env::temp_dir().join(format!("learning-wasm-{}-{}-{}.wasm", process::id(), id, name));
+        fs::write(&path, bytes).unwrap();
+
+        let mut full: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        full.insert(1, path.to_str().unwrap().to_string());
+        let result = run(&full);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    /// Run `command` on the sample module, extra arguments follow the file.
+    fn run_sample(command: &str, extra: &[&str]) -> Result<String, CliError> {
+        let module = learning_wasm::wat::parse(SAMPLE).unwrap();
+        let mut args = vec![command];
+        args.extend_from_slice(extra);
+        run_on(command, &serialize(&module).unwrap(), &args)
+    }
+
+    fn code(result: Result<String, CliError>) -> i32 {
+        match result {
+            Ok(_) => 0,
+            Err(e) => e.code,
+        }
+    }
+
+    #[test]
+    fn test_dump() {
+        let expected = concat!(
+            " id  section                   start        end       size  count\n",
+            "  1  type                 0x0000000a 0x00000015 0x0000000b  2\n",
+            "  2  import               0x00000017 0x00000022 0x0000000b  1\n",
+            "  3  function             0x00000024 0x00000026 0x00000002  1\n",
+            "  5  memory               0x00000028 0x0000002b 0x00000003  1\n",
+            "  7  export               0x0000002d 0x0000003a 0x0000000d  2\n",
+            " 10  code                 0x0000003c 0x00000045 0x00000009  1\n",
+        );
+        assert_eq!(run_sample("dump", &[]).unwrap(), expected);
+        assert_eq!(dump(&Module::default()).unwrap_err().code, 1);
+    }
+
+    #[test]
+    fn test_imports_exports() {
+        assert_eq!(run_sample("imports", &[]).unwrap(), "func[0] type=0 (i32) -> () <- env.log\n");
+        let module = learning_wasm::wat::parse(r#"(module (import "m" "t" (table 1 10 funcref)) (import "m" "mem" (memory 1)))"#).unwrap();
+        assert_eq!(
+            run_on("imports", &serialize(&module).unwrap(), &["imports"]).unwrap(),
+            "table[0] 1 10 funcref <- m.t\nmemory[0] 1 <- m.mem\n"
+        );
+        assert_eq!(
+            run_sample("exports", &[]).unwrap(),
+            "memory[0] -> \"mem\"\nfunc[1] (i32, i32) -> (i32) -> \"add\"\n"
+        );
+    }
+
+    #[test]
+    fn test_disasm() {
+        assert_eq!(
+            run_sample("disasm", &["add"]).unwrap(),
+            "(func (;1;) (type 1) (param i32 i32) (result i32)\n  local.get 0\n  local.get 1\n  i32.add)\n"
+        );
+        assert_eq!(
+            run_sample("disasm", &["1", "--folded"]).unwrap(),
+            "(func (;1;) (type 1) (param i32 i32) (result i32)\n  (i32.add (local.get 0) (local.get 1)))\n"
+        );
+        assert_eq!(code(run_sample("disasm", &["sub"])), 1);
+        // 导入的函数没有函数体
+        assert_eq!(code(run_sample("disasm", &["0"])), 1);
+        assert_eq!(code(run_sample("disasm", &[])), 2);
+        assert_eq!(code(run_sample("disasm", &["0", "1"])), 2);
+    }
+
+    #[test]
+    fn test_validate() {
+        let output = run_sample("validate", &[]).unwrap();
+        assert!(output.ends_with("-validate.wasm: valid\n"), "{}", output);
+
+        let invalid = learning_wasm::wat::parse("(module (func (result i32)))").unwrap();
+        let err = run_on("invalid", &serialize(&invalid).unwrap(), &["validate"]).unwrap_err();
+        assert_eq!(err.code, 1);
+        assert!(err.message.contains("invalid module"), "{}", err);
+    }
+
+    #[test]
+    fn test_stats() {
+        assert_eq!(run_sample("stats", &[]).unwrap(), "\
+version:       1
+sections:      6 (0 custom)
+types:         2
+imports:       1
+functions:     2 (1 imported)
+tables:        0 (0 imported)
+memories:      1 (0 imported)
+globals:       0 (0 imported)
+exports:       2
+elem segments: 0
+data segments: 0
+instructions:  4
+largest func:  1 (4 instructions)
+");
+    }
+
+    #[test]
+    fn test_usage() {
+        let run_args = |args: &[&str]| run(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+        assert_eq!(run_args(&["help"]).unwrap(), format!("{}\n", USAGE));
+        assert_eq!(code(run_args(&[])), 2);
+        assert_eq!(code(run_args(&["dump"])), 2);
+        assert_eq!(code(run_args(&["frob", "a.wasm"])), 2);
+        assert_eq!(code(run_sample("stats", &["extra"])), 2);
+        let err = run_args(&["frob", "a.wasm"]).unwrap_err();
+        assert!(err.message.starts_with("unknown command `frob`\n\nusage:"), "{}", err);
+    }
+
+    #[test]
+    fn test_load_failure() {
+        let err = run_on("garbage", b"\0asm\x01\0\0\0\x01\x05", &["dump"]).unwrap_err();
+        assert_eq!(err.code, 1);
+        assert!(err.message.contains("failed to parse module"), "{}", err);
+
+        let missing = env::temp_dir().join("learning-wasm-missing.wasm");
+        assert_eq!(code(run(&["stats".to_string(), missing.to_str().unwrap().to_string()])), 1);
+    }
+}
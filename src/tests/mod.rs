@@ -15,3 +15,35 @@ impl Read for ByteStream<'_> {
         Ok(min)
     }    
 }
+
+
+/// 覆盖所有标准 section 的小模块，用于 round-trip 等测试
+pub const SAMPLE_MODULE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    // type: (i32, i32) -> i32, () -> ()
+    0x01, 0x0a, 0x02, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x00, 0x00,
+    // import: "env" "f" (func (type 1))
+    0x02, 0x09, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x01, 0x66, 0x00, 0x01,
+    // function
+    0x03, 0x03, 0x02, 0x00, 0x01,
+    // table: anyfunc, min 1
+    0x04, 0x04, 0x01, 0x70, 0x00, 0x01,
+    // memory: min 1, max 2
+    0x05, 0x04, 0x01, 0x01, 0x01, 0x02,
+    // global: (mut i32) (i32.const 42)
+    0x06, 0x06, 0x01, 0x7f, 0x01, 0x41, 0x2a, 0x0b,
+    // export: "add" func 1, "mem" memory 0
+    0x07, 0x0d, 0x02, 0x03, 0x61, 0x64, 0x64, 0x00, 0x01, 0x03, 0x6d, 0x65, 0x6d, 0x02, 0x00,
+    // start: func 2
+    0x08, 0x01, 0x02,
+    // element: table 0, offset 0, [func 1]
+    0x09, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, 0x01,
+    // code
+    0x0a, 0x14, 0x02,
+    0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b,
+    0x0a, 0x01, 0x01, 0x7f, 0x02, 0x40, 0x01, 0x0b, 0x10, 0x00, 0x0b,
+    // data: memory 0, offset 8, "hi"
+    0x0b, 0x08, 0x01, 0x00, 0x41, 0x08, 0x0b, 0x02, 0x68, 0x69,
+    // custom: "x"
+    0x00, 0x04, 0x01, 0x78, 0x01, 0x02,
+];
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+
+use crate::elements::module::Module;
+use crate::elements::ops::Instruction;
+use crate::elements::export_entry::Internal;
+use crate::elements::types::{FunctionType, SubType, TableElementType};
+#[cfg(feature = "gc")]
+use crate::elements::types::{CompositeType, FieldType};
+use crate::elements::import_entry::{External, TableType, ResizableLimits, GlobalType};
+use super::ValidationError;
+
+/// Index spaces of a module, imports first.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleContext {
+    /// Types of the type section, in index order.
+    pub types: Vec<SubType>,
+    /// Index of the first type equivalent to each type, equivalent types have equal recursion groups.
+    #[cfg(feature = "gc")]
+    pub(super) canonical: Vec<u32>,
+    /// Type index of every function.
+    pub functions: Vec<u32>,
+    pub tables: Vec<TableType>,
+    pub memories: Vec<ResizableLimits>,
+    pub globals: Vec<GlobalType>,
+    /// Type index of every tag.
+    #[cfg(feature = "exceptions")]
+    pub tags: Vec<u32>,
+    /// Element type of every element segment.
+    pub elements: Vec<TableElementType>,
+    /// Number of data segments declared by the data count section.
+    pub data_count: Option<u32>,
+    pub imported_functions: usize,
+    pub imported_globals: usize,
+    /// Functions which may be referenced by `ref.func` in function bodies.
+    pub refs: HashSet<u32>,
+}
+
+impl ModuleContext {
+    pub fn new(module: &Module) -> Result<ModuleContext, ValidationError> {
+        let mut context = ModuleContext::default();
+
+        if let Some(types) = module.type_section() {
+            context.types = types.types().cloned().collect();
+            #[cfg(feature = "gc")]
+            {
+                context.canonical = super::subtype::canonicalize(&types.0);
+            }
+        }
+
+        if let Some(imports) = module.import_section() {
+            for entry in imports.0.iter() {
+                match entry.external {
+                    External::Function(type_idx) => {
+                        context.functions.push(type_idx);
+                        context.imported_functions += 1;
+                    },
+                    External::Table(ref table_type) => context.tables.push(*table_type),
+                    External::Memory(ref limits) => context.memories.push(*limits),
+                    External::Global(ref global_type) => {
+                        context.globals.push(global_type.clone());
+                        context.imported_globals += 1;
+                    },
+                    #[cfg(feature = "exceptions")]
+                    External::Tag(ref tag_type) => context.tags.push(tag_type.type_index),
+                }
+            }
+        }
+
+        if let Some(functions) = module.function_section() {
+            for func in functions.0.iter() {
+                context.func_type(func.0)?;
+                context.functions.push(func.0);
+            }
+        }
+
+        if let Some(tables) = module.table_section() {
+            context.tables.extend(tables.0.iter().cloned());
+        }
+
+        if let Some(memories) = module.memory_section() {
+            context.memories.extend(memories.0.iter().cloned());
+        }
+
+        #[cfg(feature = "exceptions")]
+        if let Some(tags) = module.tag_section() {
+            context.tags.extend(tags.0.iter().map(|t| t.type_index));
+        }
+
+        if let Some(globals) = module.global_section() {
+            context.globals.extend(globals.0.iter().map(|g| g.global_type.clone()));
+        }
+
+        // ref.func 只能引用在函数体之外出现过的函数
+        if let Some(globals) = module.global_section() {
+            for entry in globals.0.iter() {
+                context.refs.extend(func_refs(&entry.init_expr.0));
+            }
+        }
+        if let Some(exports) = module.export_section() {
+            context.refs.extend(exports.0.iter().filter_map(|e| match e.internal {
+                Internal::Function(idx) => Some(idx),
+                _ => None,
+            }));
+        }
+        if let Some(elements) = module.elements_section() {
+            for segment in elements.0.iter() {
+                context.elements.push(segment.elem_type);
+                context.refs.extend(segment.members.iter().cloned());
+                for expr in segment.exprs.iter().flatten() {
+                    context.refs.extend(func_refs(&expr.0));
+                }
+            }
+        }
+        context.data_count = module.data_count_section();
+
+        Ok(context)
+    }
+
+    pub fn sub_type(&self, type_idx: u32) -> Result<&SubType, ValidationError> {
+        self.types.get(type_idx as usize).ok_or(ValidationError::UnknownType(type_idx))
+    }
+
+    /// Signature at `type_idx` of the type section.
+    pub fn func_type(&self, type_idx: u32) -> Result<&FunctionType, ValidationError> {
+        self.sub_type(type_idx)?
+            .func_type()
+            .ok_or(ValidationError::UnexpectedType { index: type_idx, expected: "func" })
+    }
+
+    /// Fields of the struct type at `type_idx`.
+    #[cfg(feature = "gc")]
+    pub fn struct_type(&self, type_idx: u32) -> Result<&[FieldType], ValidationError> {
+        match self.sub_type(type_idx)?.composite {
+            CompositeType::Struct(ref fields) => Ok(fields),
+            _ => Err(ValidationError::UnexpectedType { index: type_idx, expected: "struct" }),
+        }
+    }
+
+    /// Element of the array type at `type_idx`.
+    #[cfg(feature = "gc")]
+    pub fn array_type(&self, type_idx: u32) -> Result<FieldType, ValidationError> {
+        match self.sub_type(type_idx)?.composite {
+            CompositeType::Array(field) => Ok(field),
+            _ => Err(ValidationError::UnexpectedType { index: type_idx, expected: "array" }),
+        }
+    }
+
+    /// Signature of the function at `func_idx` of the function index space.
+    pub fn function_type(&self, func_idx: u32) -> Result<&FunctionType, ValidationError> {
+        let type_idx = *self.functions.get(func_idx as usize).ok_or(ValidationError::UnknownFunction(func_idx))?;
+        self.func_type(type_idx)
+    }
+
+    pub fn table(&self, idx: u32) -> Result<&TableType, ValidationError> {
+        self.tables.get(idx as usize).ok_or(ValidationError::UnknownTable(idx))
+    }
+
+    pub fn memory(&self, idx: u32) -> Result<&ResizableLimits, ValidationError> {
+        self.memories.get(idx as usize).ok_or(ValidationError::UnknownMemory(idx))
+    }
+
+    pub fn global(&self, idx: u32) -> Result<&GlobalType, ValidationError> {
+        self.globals.get(idx as usize).ok_or(ValidationError::UnknownGlobal(idx))
+    }
+
+    /// Signature of tag `idx`, its parameters are the payload of the exceptions.
+    #[cfg(feature = "exceptions")]
+    pub fn tag(&self, idx: u32) -> Result<&FunctionType, ValidationError> {
+        let type_idx = *self.tags.get(idx as usize).ok_or(ValidationError::UnknownTag(idx))?;
+        self.func_type(type_idx)
+    }
+
+    /// Element type of segment `idx`.
+    pub fn elem_segment(&self, idx: u32) -> Result<TableElementType, ValidationError> {
+        self.elements.get(idx as usize).cloned().ok_or(ValidationError::UnknownElemSegment(idx))
+    }
+
+    /// Data segments can only be referenced from code when the data count section is present.
+    pub fn data_segment(&self, idx: u32) -> Result<(), ValidationError> {
+        match self.data_count {
+            None => Err(ValidationError::MissingDataCount),
+            Some(count) if idx >= count => Err(ValidationError::UnknownDataSegment(idx)),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+fn func_refs(code: &[Instruction]) -> impl Iterator<Item = u32> + '_ {
+    code.iter().filter_map(|i| match *i {
+        Instruction::RefFunc(idx) => Some(idx),
+        _ => None,
+    })
+}
@@ -0,0 +1,1125 @@
+use crate::elements::func::FuncBody;
+use crate::elements::ops::Instruction;
+#[cfg(feature = "simd")]
+use crate::elements::simd::SimdInstruction;
+#[cfg(feature = "threads")]
+use crate::elements::atomics::AtomicInstruction;
+use crate::elements::types::{ValueType, BlockType};
+#[cfg(feature = "tail_call")]
+use crate::elements::types::FunctionType;
+#[cfg(feature = "gc")]
+use crate::elements::gc::{BrOnCastData, GcInstruction};
+#[cfg(feature = "gc")]
+use crate::elements::types::{FieldType, HeapType, RefType, StorageType};
+use super::{ModuleContext, ValidationError};
+
+/// Operand type, `None` stands for the unknown type produced by an unreachable stack.
+type Operand = Option<ValueType>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FrameKind {
+    Function,
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+#[derive(Debug, Clone)]
+struct ControlFrame {
+    kind: FrameKind,
+    start_types: Vec<ValueType>,
+    end_types: Vec<ValueType>,
+    /// Height of the operand stack when the frame was entered.
+    height: usize,
+    /// Number of initialized locals when the frame was entered.
+    inits: usize,
+    unreachable: bool,
+}
+
+impl ControlFrame {
+    /// Types a branch to this frame has to provide.
+    fn label_types(&self) -> &[ValueType] {
+        if self.kind == FrameKind::Loop {
+            &self.start_types
+        } else {
+            &self.end_types
+        }
+    }
+}
+
+/// Validate the body of function `index` with the algorithm from the appendix of the specification.
+pub(super) fn validate_function(context: &ModuleContext, index: u32, body: &FuncBody) -> Result<(), ValidationError> {
+    let func_type = context.function_type(index)?;
+
+    let mut locals: Vec<(u64, ValueType)> = Vec::new();
+    let mut total = 0u64;
+    for &param in func_type.params.iter() {
+        total += 1;
+        locals.push((total, param));
+    }
+    let params = total;
+    for local in body.locals.iter() {
+        if local.count == 0 {
+            continue;
+        }
+        context.check_value_type(local.value_type)?;
+        total += local.count as u64;
+        locals.push((total, local.value_type));
+    }
+
+    let mut validator = FunctionValidator {
+        context,
+        locals,
+        params,
+        initialized: Vec::new(),
+        return_types: func_type.results.clone(),
+        operands: Vec::new(),
+        frames: Vec::new(),
+    };
+    validator.push_frame(FrameKind::Function, Vec::new(), func_type.results.clone());
+
+    let code = body.instructions.elements();
+    for (position, instruction) in code.iter().enumerate() {
+        let wrap = |error| ValidationError::Function { index, position, error: Box::new(error) };
+        if validator.frames.is_empty() {
+            return Err(wrap(ValidationError::TrailingInstructions));
+        }
+        validator.step(instruction).map_err(wrap)?;
+    }
+
+    if !validator.frames.is_empty() {
+        return Err(ValidationError::Function {
+            index,
+            position: code.len(),
+            error: Box::new(ValidationError::UnmatchedControl("block")),
+        });
+    }
+
+    Ok(())
+}
+
+struct FunctionValidator<'a> {
+    context: &'a ModuleContext,
+    /// Locals grouped by type, each entry holds the index past its last local.
+    locals: Vec<(u64, ValueType)>,
+    /// Number of parameters, which are always initialized.
+    params: u64,
+    /// Locals without a default value which are set in the enclosing blocks.
+    initialized: Vec<u32>,
+    return_types: Vec<ValueType>,
+    operands: Vec<Operand>,
+    frames: Vec<ControlFrame>,
+}
+
+impl<'a> FunctionValidator<'a> {
+    fn push_operand(&mut self, operand: Operand) {
+        self.operands.push(operand);
+    }
+
+    fn push_operands(&mut self, types: &[ValueType]) {
+        for &t in types.iter() {
+            self.operands.push(Some(t));
+        }
+    }
+
+    fn pop_operand(&mut self) -> Result<Operand, ValidationError> {
+        let frame = self.frames.last().expect("frame stack is not empty while validating");
+        if self.operands.len() == frame.height {
+            if frame.unreachable {
+                return Ok(None);
+            }
+            return Err(ValidationError::StackUnderflow);
+        }
+        Ok(self.operands.pop().expect("operand stack is higher than the frame height"))
+    }
+
+    fn pop_expected(&mut self, expected: ValueType) -> Result<Operand, ValidationError> {
+        match self.pop_operand()? {
+            None => Ok(None),
+            Some(actual) if self.context.matches(actual, expected) => Ok(Some(actual)),
+            Some(actual) => Err(ValidationError::TypeMismatch { expected: Some(expected), actual: Some(actual) }),
+        }
+    }
+
+    fn pop_operands(&mut self, types: &[ValueType]) -> Result<(), ValidationError> {
+        for &t in types.iter().rev() {
+            self.pop_expected(t)?;
+        }
+        Ok(())
+    }
+
+    fn push_frame(&mut self, kind: FrameKind, start_types: Vec<ValueType>, end_types: Vec<ValueType>) {
+        let height = self.operands.len();
+        self.push_operands(&start_types);
+        self.frames.push(ControlFrame {
+            kind,
+            start_types,
+            end_types,
+            height,
+            inits: self.initialized.len(),
+            unreachable: false,
+        });
+    }
+
+    fn pop_frame(&mut self) -> Result<ControlFrame, ValidationError> {
+        let (end_types, height) = match self.frames.last() {
+            Some(frame) => (frame.end_types.clone(), frame.height),
+            None => return Err(ValidationError::UnmatchedControl("end")),
+        };
+        self.pop_operands(&end_types)?;
+        if self.operands.len() != height {
+            return Err(ValidationError::UnbalancedStack { expected: height, actual: self.operands.len() });
+        }
+        let frame = self.frames.pop().expect("frame stack checked above");
+        // 块内设置的局部变量在块外仍视为未初始化
+        self.initialized.truncate(frame.inits);
+        Ok(frame)
+    }
+
+    fn set_unreachable(&mut self) {
+        let frame = self.frames.last_mut().expect("frame stack is not empty while validating");
+        self.operands.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    /// Tail call to a function of type `ty`, whose results must be the results of the caller.
+    #[cfg(feature = "tail_call")]
+    fn return_call(&mut self, ty: &FunctionType) -> Result<(), ValidationError> {
+        if !self.context.all_match(&ty.results, &self.return_types) {
+            return Err(ValidationError::TailCallMismatch {
+                expected: self.return_types.clone(),
+                actual: ty.results.clone(),
+            });
+        }
+        self.pop_operands(&ty.params)?;
+        self.set_unreachable();
+        Ok(())
+    }
+
+    fn label_types(&self, depth: u32) -> Result<Vec<ValueType>, ValidationError> {
+        let depth = depth as usize;
+        if depth >= self.frames.len() {
+            return Err(ValidationError::UnknownLabel(depth as u32));
+        }
+        Ok(self.frames[self.frames.len() - 1 - depth].label_types().to_vec())
+    }
+
+    fn local_type(&self, idx: u32) -> Result<ValueType, ValidationError> {
+        let pos = self.locals.partition_point(|&(end, _)| end <= idx as u64);
+        self.locals.get(pos).map(|&(_, t)| t).ok_or(ValidationError::UnknownLocal(idx))
+    }
+
+    /// Whether local `idx` of type `t` holds a value when it is read.
+    fn is_initialized(&self, idx: u32, t: ValueType) -> bool {
+        t.is_defaultable() || (idx as u64) < self.params || self.initialized.contains(&idx)
+    }
+
+    fn set_local(&mut self, idx: u32) -> Result<ValueType, ValidationError> {
+        let t = self.local_type(idx)?;
+        self.pop_expected(t)?;
+        if !self.is_initialized(idx, t) {
+            self.initialized.push(idx);
+        }
+        Ok(t)
+    }
+
+    /// Check that `actual` matches `expected`.
+    fn check_matches(&self, actual: ValueType, expected: ValueType) -> Result<(), ValidationError> {
+        if !self.context.matches(actual, expected) {
+            return Err(ValidationError::TypeMismatch { expected: Some(expected), actual: Some(actual) });
+        }
+        Ok(())
+    }
+
+    fn block_type(&self, block_type: &BlockType) -> Result<(Vec<ValueType>, Vec<ValueType>), ValidationError> {
+        match *block_type {
+            BlockType::NoResult => Ok((Vec::new(), Vec::new())),
+            BlockType::Value(t) => {
+                self.context.check_value_type(t)?;
+                Ok((Vec::new(), vec![t]))
+            },
+            #[cfg(feature = "multi_value")]
+            BlockType::TypeIndex(idx) => {
+                let ty = self.context.func_type(idx)?;
+                Ok((ty.params.clone(), ty.results.clone()))
+            },
+        }
+    }
+
+    fn unop(&mut self, t: ValueType) -> Result<(), ValidationError> {
+        self.pop_expected(t)?;
+        self.push_operand(Some(t));
+        Ok(())
+    }
+
+    fn binop(&mut self, t: ValueType) -> Result<(), ValidationError> {
+        self.pop_expected(t)?;
+        self.pop_expected(t)?;
+        self.push_operand(Some(t));
+        Ok(())
+    }
+
+    fn testop(&mut self, t: ValueType) -> Result<(), ValidationError> {
+        self.pop_expected(t)?;
+        self.push_operand(Some(ValueType::I32));
+        Ok(())
+    }
+
+    fn relop(&mut self, t: ValueType) -> Result<(), ValidationError> {
+        self.pop_expected(t)?;
+        self.pop_expected(t)?;
+        self.push_operand(Some(ValueType::I32));
+        Ok(())
+    }
+
+    fn cvtop(&mut self, from: ValueType, to: ValueType) -> Result<(), ValidationError> {
+        self.pop_expected(from)?;
+        self.push_operand(Some(to));
+        Ok(())
+    }
+
+    /// Check that table `idx` holds references of type `expected`.
+    fn table_type(&self, idx: u32, expected: ValueType) -> Result<(), ValidationError> {
+        let actual = self.context.table(idx)?.elem_type.value_type();
+        self.check_matches(actual, expected)
+    }
+
+    /// Type of the addresses of memory `idx`, `i64` for 64-bit memories.
+    fn address_type(&self, idx: u32) -> Result<ValueType, ValidationError> {
+        Ok(if self.context.memory(idx)?.memory64 { ValueType::I64 } else { ValueType::I32 })
+    }
+
+    /// Check the immediates of a memory access, returning the address type.
+    fn check_memarg(&self, align: u32, offset: u64, memory: u32, natural: u32) -> Result<ValueType, ValidationError> {
+        let address = self.address_type(memory)?;
+        if align > natural {
+            return Err(ValidationError::InvalidAlignment { align, max: natural });
+        }
+        if address == ValueType::I32 && offset > u32::MAX as u64 {
+            return Err(ValidationError::InvalidOffset(offset));
+        }
+        Ok(address)
+    }
+
+    fn load(&mut self, align: u32, offset: u64, memory: u32, natural: u32, t: ValueType) -> Result<(), ValidationError> {
+        let address = self.check_memarg(align, offset, memory, natural)?;
+        self.pop_expected(address)?;
+        self.push_operand(Some(t));
+        Ok(())
+    }
+
+    fn store(&mut self, align: u32, offset: u64, memory: u32, natural: u32, t: ValueType) -> Result<(), ValidationError> {
+        let address = self.check_memarg(align, offset, memory, natural)?;
+        self.pop_expected(t)?;
+        self.pop_expected(address)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "simd")]
+    fn lane(&self, lane: u8, lanes: u8) -> Result<(), ValidationError> {
+        if lane >= lanes {
+            return Err(ValidationError::InvalidLaneIndex { lane, lanes });
+        }
+        Ok(())
+    }
+
+    /// `[addr v128] -> [v128]` load or `[addr v128] -> []` store of a single lane.
+    #[cfg(feature = "simd")]
+    fn lane_memory(&mut self, align: u32, offset: u64, memory: u32, natural: u32, lane: u8, is_load: bool) -> Result<(), ValidationError> {
+        use crate::elements::types::ValueType::V128;
+
+        self.lane(lane, 16 >> natural)?;
+        let address = self.check_memarg(align, offset, memory, natural)?;
+        self.pop_operands(&[address, V128])?;
+        if is_load {
+            self.push_operand(Some(V128));
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "simd")]
+    fn simd(&mut self, instruction: &SimdInstruction) -> Result<(), ValidationError> {
+        use crate::elements::simd::SimdInstruction::*;
+        use crate::elements::types::ValueType::{I32, I64, F32, F64, V128};
+
+        match *instruction {
+            V128Load(align, offset, memory) => self.load(align, offset, memory, 4, V128)?,
+            V128Load8x8S(align, offset, memory) | V128Load8x8U(align, offset, memory) | V128Load16x4S(align, offset, memory) | V128Load16x4U(align, offset, memory)
+                | V128Load32x2S(align, offset, memory) | V128Load32x2U(align, offset, memory) | V128Load64Splat(align, offset, memory)
+                | V128Load64Zero(align, offset, memory) => self.load(align, offset, memory, 3, V128)?,
+            V128Load8Splat(align, offset, memory) => self.load(align, offset, memory, 0, V128)?,
+            V128Load16Splat(align, offset, memory) => self.load(align, offset, memory, 1, V128)?,
+            V128Load32Splat(align, offset, memory) | V128Load32Zero(align, offset, memory) => self.load(align, offset, memory, 2, V128)?,
+            V128Store(align, offset, memory) => self.store(align, offset, memory, 4, V128)?,
+            V128Load8Lane(align, offset, memory, lane) => self.lane_memory(align, offset, memory, 0, lane, true)?,
+            V128Load16Lane(align, offset, memory, lane) => self.lane_memory(align, offset, memory, 1, lane, true)?,
+            V128Load32Lane(align, offset, memory, lane) => self.lane_memory(align, offset, memory, 2, lane, true)?,
+            V128Load64Lane(align, offset, memory, lane) => self.lane_memory(align, offset, memory, 3, lane, true)?,
+            V128Store8Lane(align, offset, memory, lane) => self.lane_memory(align, offset, memory, 0, lane, false)?,
+            V128Store16Lane(align, offset, memory, lane) => self.lane_memory(align, offset, memory, 1, lane, false)?,
+            V128Store32Lane(align, offset, memory, lane) => self.lane_memory(align, offset, memory, 2, lane, false)?,
+            V128Store64Lane(align, offset, memory, lane) => self.lane_memory(align, offset, memory, 3, lane, false)?,
+
+            V128Const(_) => self.push_operand(Some(V128)),
+            I8x16Shuffle(ref lanes) => {
+                for &lane in lanes.iter() {
+                    self.lane(lane, 32)?;
+                }
+                self.binop(V128)?;
+            },
+
+            I8x16ExtractLaneS(lane) | I8x16ExtractLaneU(lane) => {
+                self.lane(lane, 16)?;
+                self.cvtop(V128, I32)?;
+            },
+            I16x8ExtractLaneS(lane) | I16x8ExtractLaneU(lane) => {
+                self.lane(lane, 8)?;
+                self.cvtop(V128, I32)?;
+            },
+            I32x4ExtractLane(lane) => {
+                self.lane(lane, 4)?;
+                self.cvtop(V128, I32)?;
+            },
+            I64x2ExtractLane(lane) => {
+                self.lane(lane, 2)?;
+                self.cvtop(V128, I64)?;
+            },
+            F32x4ExtractLane(lane) => {
+                self.lane(lane, 4)?;
+                self.cvtop(V128, F32)?;
+            },
+            F64x2ExtractLane(lane) => {
+                self.lane(lane, 2)?;
+                self.cvtop(V128, F64)?;
+            },
+            I8x16ReplaceLane(lane) | I16x8ReplaceLane(lane) | I32x4ReplaceLane(lane) | I64x2ReplaceLane(lane)
+                | F32x4ReplaceLane(lane) | F64x2ReplaceLane(lane) => {
+                let (lanes, t) = match *instruction {
+                    I8x16ReplaceLane(_) => (16, I32),
+                    I16x8ReplaceLane(_) => (8, I32),
+                    I32x4ReplaceLane(_) => (4, I32),
+                    I64x2ReplaceLane(_) => (2, I64),
+                    F32x4ReplaceLane(_) => (4, F32),
+                    _ => (2, F64),
+                };
+                self.lane(lane, lanes)?;
+                self.pop_operands(&[V128, t])?;
+                self.push_operand(Some(V128));
+            },
+
+            I8x16Splat | I16x8Splat | I32x4Splat => self.cvtop(I32, V128)?,
+            I64x2Splat => self.cvtop(I64, V128)?,
+            F32x4Splat => self.cvtop(F32, V128)?,
+            F64x2Splat => self.cvtop(F64, V128)?,
+
+            V128AnyTrue | I8x16AllTrue | I8x16Bitmask | I16x8AllTrue | I16x8Bitmask | I32x4AllTrue
+                | I32x4Bitmask | I64x2AllTrue | I64x2Bitmask => self.testop(V128)?,
+
+            I8x16Shl | I8x16ShrS | I8x16ShrU | I16x8Shl | I16x8ShrS | I16x8ShrU | I32x4Shl | I32x4ShrS
+                | I32x4ShrU | I64x2Shl | I64x2ShrS | I64x2ShrU => {
+                self.pop_operands(&[V128, I32])?;
+                self.push_operand(Some(V128));
+            },
+
+            V128Bitselect => {
+                self.pop_operands(&[V128, V128, V128])?;
+                self.push_operand(Some(V128));
+            },
+
+            V128Not | F32x4DemoteF64x2Zero | F64x2PromoteLowF32x4 | I8x16Abs | I8x16Neg | I8x16Popcnt
+                | F32x4Ceil | F32x4Floor | F32x4Trunc | F32x4Nearest | F64x2Ceil | F64x2Floor | F64x2Trunc
+                | F64x2Nearest | I16x8ExtaddPairwiseI8x16S | I16x8ExtaddPairwiseI8x16U
+                | I32x4ExtaddPairwiseI16x8S | I32x4ExtaddPairwiseI16x8U | I16x8Abs | I16x8Neg
+                | I16x8ExtendLowI8x16S | I16x8ExtendHighI8x16S | I16x8ExtendLowI8x16U | I16x8ExtendHighI8x16U
+                | I32x4Abs | I32x4Neg | I32x4ExtendLowI16x8S | I32x4ExtendHighI16x8S | I32x4ExtendLowI16x8U
+                | I32x4ExtendHighI16x8U | I64x2Abs | I64x2Neg | I64x2ExtendLowI32x4S | I64x2ExtendHighI32x4S
+                | I64x2ExtendLowI32x4U | I64x2ExtendHighI32x4U | F32x4Abs | F32x4Neg | F32x4Sqrt | F64x2Abs
+                | F64x2Neg | F64x2Sqrt | I32x4TruncSatF32x4S | I32x4TruncSatF32x4U | F32x4ConvertI32x4S
+                | F32x4ConvertI32x4U | I32x4TruncSatF64x2SZero | I32x4TruncSatF64x2UZero
+                | F64x2ConvertLowI32x4S | F64x2ConvertLowI32x4U => self.unop(V128)?,
+
+            // 其余指令都是 [v128 v128] -> [v128]
+            _ => self.binop(V128)?,
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "threads")]
+    fn atomic(&mut self, instruction: &AtomicInstruction) -> Result<(), ValidationError> {
+        use crate::elements::atomics::AtomicInstruction::*;
+        use crate::elements::types::ValueType::{I32, I64};
+
+        let (t, natural, (align, offset, memory)) = match (instruction.value_type(), instruction.natural_alignment(), instruction.memarg()) {
+            (Some(t), Some(natural), Some(memarg)) => (t, natural, memarg),
+            // atomic.fence
+            _ => return Ok(()),
+        };
+        let a = self.address_type(memory)?;
+        if align != natural {
+            return Err(ValidationError::InvalidAtomicAlignment { align, natural });
+        }
+        self.check_memarg(align, offset, memory, natural)?;
+
+        match *instruction {
+            MemoryAtomicNotify(..) => {
+                self.pop_operands(&[a, I32])?;
+                self.push_operand(Some(I32));
+            },
+            MemoryAtomicWait32(..) | MemoryAtomicWait64(..) => {
+                self.pop_operands(&[a, t, I64])?;
+                self.push_operand(Some(I32));
+            },
+            I32AtomicLoad(..) | I64AtomicLoad(..) | I32AtomicLoad8U(..) | I32AtomicLoad16U(..)
+                | I64AtomicLoad8U(..) | I64AtomicLoad16U(..) | I64AtomicLoad32U(..) => {
+                self.pop_expected(a)?;
+                self.push_operand(Some(t));
+            },
+            I32AtomicStore(..) | I64AtomicStore(..) | I32AtomicStore8(..) | I32AtomicStore16(..)
+                | I64AtomicStore8(..) | I64AtomicStore16(..) | I64AtomicStore32(..) => {
+                self.pop_operands(&[a, t])?;
+            },
+            I32AtomicRmwCmpxchg(..) | I64AtomicRmwCmpxchg(..) | I32AtomicRmw8CmpxchgU(..) | I32AtomicRmw16CmpxchgU(..)
+                | I64AtomicRmw8CmpxchgU(..) | I64AtomicRmw16CmpxchgU(..) | I64AtomicRmw32CmpxchgU(..) => {
+                self.pop_operands(&[a, t, t])?;
+                self.push_operand(Some(t));
+            },
+            // 其余都是 read-modify-write：[addr t] -> [t]
+            _ => {
+                self.pop_operands(&[a, t])?;
+                self.push_operand(Some(t));
+            },
+        }
+        Ok(())
+    }
+
+    /// Pop a reference of any type, `None` if the operand is unknown.
+    #[cfg(feature = "gc")]
+    fn pop_ref(&mut self) -> Result<Option<RefType>, ValidationError> {
+        match self.pop_operand()? {
+            None => Ok(None),
+            Some(t) => match t.ref_type() {
+                Some(r) => Ok(Some(r)),
+                None => Err(ValidationError::TypeMismatch { expected: None, actual: Some(t) }),
+            },
+        }
+    }
+
+    /// Field `field` of struct type `type_idx`.
+    #[cfg(feature = "gc")]
+    fn struct_field(&self, type_idx: u32, field: u32) -> Result<FieldType, ValidationError> {
+        self.context.struct_type(type_idx)?
+            .get(field as usize)
+            .cloned()
+            .ok_or_else(|| ValidationError::Other(format!("Unknown field {} of type {}", field, type_idx)))
+    }
+
+    /// Type read by a `get`, signed or unsigned extension is required exactly for packed fields.
+    #[cfg(feature = "gc")]
+    fn read_field(field: FieldType, extend: bool) -> Result<ValueType, ValidationError> {
+        if field.storage.is_packed() != extend {
+            return Err(ValidationError::Other(if extend {
+                "get_s and get_u are only allowed for packed fields".to_string()
+            } else {
+                "Packed fields must be read with get_s or get_u".to_string()
+            }));
+        }
+        Ok(field.storage.unpacked())
+    }
+
+    #[cfg(feature = "gc")]
+    fn mutable_field(field: FieldType, type_idx: u32) -> Result<ValueType, ValidationError> {
+        if !field.mutable {
+            return Err(ValidationError::Other(format!("Field of type {} is immutable", type_idx)));
+        }
+        Ok(field.storage.unpacked())
+    }
+
+    /// Check that the references of element segment `segment` can be stored in an array of `elem`.
+    #[cfg(feature = "gc")]
+    fn elem_segment_into(&self, segment: u32, elem: FieldType) -> Result<(), ValidationError> {
+        let t = self.context.elem_segment(segment)?.value_type();
+        self.check_matches(t, elem.storage.unpacked())
+    }
+
+    /// Arrays initialized from data segments must have numeric or vector elements.
+    #[cfg(feature = "gc")]
+    fn data_segment_into(&self, segment: u32, elem: FieldType) -> Result<(), ValidationError> {
+        if let StorageType::Val(t) = elem.storage {
+            if t.is_ref() {
+                return Err(ValidationError::Other("Arrays of references can't be read from data segments".to_string()));
+            }
+        }
+        self.context.data_segment(segment)
+    }
+
+    /// Check a cast target and pop its operand, whose type is the top of the target's hierarchy.
+    #[cfg(feature = "gc")]
+    fn pop_cast_operand(&mut self, target: RefType) -> Result<Option<RefType>, ValidationError> {
+        self.context.check_value_type(target.into())?;
+        let top = RefType::nullable(self.context.top_heap_type(target.heap));
+        let operand = self.pop_expected(top.into())?;
+        Ok(operand.and_then(|t| t.ref_type()))
+    }
+
+    #[cfg(feature = "gc")]
+    fn br_on_cast(&mut self, data: &BrOnCastData, on_fail: bool) -> Result<(), ValidationError> {
+        self.context.check_value_type(data.from.into())?;
+        self.context.check_value_type(data.to.into())?;
+        if !self.context.ref_matches(data.to, data.from) {
+            return Err(ValidationError::TypeMismatch { expected: Some(data.from.into()), actual: Some(data.to.into()) });
+        }
+        // 不满足转换时剩下的类型：目标可空时剩下的值一定非空
+        let rest = RefType { nullable: data.from.nullable && !data.to.nullable, heap: data.from.heap };
+        let (taken, kept) = if on_fail { (rest, data.to) } else { (data.to, rest) };
+
+        self.pop_expected(data.from.into())?;
+        let mut types = self.label_types(data.label)?;
+        let target = types.pop().ok_or_else(|| ValidationError::Other(format!(
+            "br_on_cast target {} must take a reference", data.label
+        )))?;
+        self.check_matches(taken.into(), target)?;
+        self.pop_operands(&types)?;
+        self.push_operands(&types);
+        self.push_operand(Some(kept.into()));
+        Ok(())
+    }
+
+    #[cfg(feature = "gc")]
+    fn gc(&mut self, instruction: &GcInstruction) -> Result<(), ValidationError> {
+        use crate::elements::gc::GcInstruction::*;
+        use crate::elements::types::ValueType::I32;
+
+        match *instruction {
+            StructNew(type_idx) => {
+                let fields: Vec<ValueType> = self.context.struct_type(type_idx)?.iter().map(|f| f.storage.unpacked()).collect();
+                self.pop_operands(&fields)?;
+                self.push_operand(Some(concrete(type_idx, false)));
+            },
+            StructNewDefault(type_idx) => {
+                if let Some(t) = self.context.struct_type(type_idx)?.iter().map(|f| f.storage.unpacked()).find(|t| !t.is_defaultable()) {
+                    return Err(ValidationError::Other(format!("Field type {:?} of type {} has no default value", t, type_idx)));
+                }
+                self.push_operand(Some(concrete(type_idx, false)));
+            },
+            StructGet(type_idx, field) | StructGetS(type_idx, field) | StructGetU(type_idx, field) => {
+                let extend = !matches!(*instruction, StructGet(..));
+                let t = Self::read_field(self.struct_field(type_idx, field)?, extend)?;
+                self.pop_expected(concrete(type_idx, true))?;
+                self.push_operand(Some(t));
+            },
+            StructSet(type_idx, field) => {
+                let t = Self::mutable_field(self.struct_field(type_idx, field)?, type_idx)?;
+                self.pop_operands(&[concrete(type_idx, true), t])?;
+            },
+            ArrayNew(type_idx) => {
+                let t = self.context.array_type(type_idx)?.storage.unpacked();
+                self.pop_operands(&[t, I32])?;
+                self.push_operand(Some(concrete(type_idx, false)));
+            },
+            ArrayNewDefault(type_idx) => {
+                let t = self.context.array_type(type_idx)?.storage.unpacked();
+                if !t.is_defaultable() {
+                    return Err(ValidationError::Other(format!("Element type {:?} of type {} has no default value", t, type_idx)));
+                }
+                self.pop_expected(I32)?;
+                self.push_operand(Some(concrete(type_idx, false)));
+            },
+            ArrayNewFixed(type_idx, len) => {
+                let t = self.context.array_type(type_idx)?.storage.unpacked();
+                for _ in 0..len {
+                    self.pop_expected(t)?;
+                }
+                self.push_operand(Some(concrete(type_idx, false)));
+            },
+            ArrayNewData(type_idx, segment) => {
+                self.data_segment_into(segment, self.context.array_type(type_idx)?)?;
+                self.pop_operands(&[I32, I32])?;
+                self.push_operand(Some(concrete(type_idx, false)));
+            },
+            ArrayNewElem(type_idx, segment) => {
+                self.elem_segment_into(segment, self.context.array_type(type_idx)?)?;
+                self.pop_operands(&[I32, I32])?;
+                self.push_operand(Some(concrete(type_idx, false)));
+            },
+            ArrayGet(type_idx) | ArrayGetS(type_idx) | ArrayGetU(type_idx) => {
+                let extend = !matches!(*instruction, ArrayGet(_));
+                let t = Self::read_field(self.context.array_type(type_idx)?, extend)?;
+                self.pop_operands(&[concrete(type_idx, true), I32])?;
+                self.push_operand(Some(t));
+            },
+            ArraySet(type_idx) => {
+                let t = Self::mutable_field(self.context.array_type(type_idx)?, type_idx)?;
+                self.pop_operands(&[concrete(type_idx, true), I32, t])?;
+            },
+            ArrayLen => {
+                self.pop_expected(RefType::nullable(HeapType::Array).into())?;
+                self.push_operand(Some(I32));
+            },
+            ArrayFill(type_idx) => {
+                let t = Self::mutable_field(self.context.array_type(type_idx)?, type_idx)?;
+                self.pop_operands(&[concrete(type_idx, true), I32, t, I32])?;
+            },
+            ArrayCopy(dst, src) => {
+                let dst_elem = self.context.array_type(dst)?;
+                Self::mutable_field(dst_elem, dst)?;
+                let src_elem = self.context.array_type(src)?;
+                if !self.context.storage_matches(src_elem.storage, dst_elem.storage) {
+                    return Err(ValidationError::Other(format!("Elements of array type {} can't be copied into type {}", src, dst)));
+                }
+                self.pop_operands(&[concrete(dst, true), I32, concrete(src, true), I32, I32])?;
+            },
+            ArrayInitData(type_idx, segment) => {
+                let elem = self.context.array_type(type_idx)?;
+                Self::mutable_field(elem, type_idx)?;
+                self.data_segment_into(segment, elem)?;
+                self.pop_operands(&[concrete(type_idx, true), I32, I32, I32])?;
+            },
+            ArrayInitElem(type_idx, segment) => {
+                let elem = self.context.array_type(type_idx)?;
+                Self::mutable_field(elem, type_idx)?;
+                self.elem_segment_into(segment, elem)?;
+                self.pop_operands(&[concrete(type_idx, true), I32, I32, I32])?;
+            },
+            RefTest(target) => {
+                self.pop_cast_operand(target)?;
+                self.push_operand(Some(I32));
+            },
+            RefCast(target) => {
+                self.pop_cast_operand(target)?;
+                self.push_operand(Some(target.into()));
+            },
+            BrOnCast(ref data) => self.br_on_cast(data, false)?,
+            BrOnCastFail(ref data) => self.br_on_cast(data, true)?,
+            AnyConvertExtern | ExternConvertAny => {
+                let (from, to) = match *instruction {
+                    AnyConvertExtern => (HeapType::Extern, HeapType::Any),
+                    _ => (HeapType::Any, HeapType::Extern),
+                };
+                let operand = self.pop_expected(RefType::nullable(from).into())?;
+                let nullable = operand.and_then(|t| t.ref_type()).map(|r| r.nullable).unwrap_or(true);
+                self.push_operand(Some(RefType { nullable, heap: to }.into()));
+            },
+            RefI31 => {
+                self.pop_expected(I32)?;
+                self.push_operand(Some(RefType::non_nullable(HeapType::I31).into()));
+            },
+            I31GetS | I31GetU => {
+                self.pop_expected(RefType::nullable(HeapType::I31).into())?;
+                self.push_operand(Some(I32));
+            },
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, instruction: &Instruction) -> Result<(), ValidationError> {
+        use crate::elements::ops::Instruction::*;
+        use crate::elements::types::ValueType::{I32, I64, F32, F64};
+
+        match *instruction {
+            Unreachable => self.set_unreachable(),
+            Nop => {},
+            Block(ref block_type) => {
+                let (params, results) = self.block_type(block_type)?;
+                self.pop_operands(&params)?;
+                self.push_frame(FrameKind::Block, params, results);
+            },
+            Loop(ref block_type) => {
+                let (params, results) = self.block_type(block_type)?;
+                self.pop_operands(&params)?;
+                self.push_frame(FrameKind::Loop, params, results);
+            },
+            If(ref block_type) => {
+                let (params, results) = self.block_type(block_type)?;
+                self.pop_expected(I32)?;
+                self.pop_operands(&params)?;
+                self.push_frame(FrameKind::If, params, results);
+            },
+            #[cfg(feature = "exceptions")]
+            TryTable(ref data) => {
+                let (params, results) = self.block_type(&data.block_type)?;
+                // 目标 label 相对于 try_table 之外
+                for catch in data.catches.iter() {
+                    let mut carried = match catch.tag() {
+                        Some(tag) => self.context.tag(tag)?.params.clone(),
+                        None => Vec::new(),
+                    };
+                    if catch.is_ref() {
+                        carried.push(ValueType::ExnRef);
+                    }
+                    let expected = self.label_types(catch.label())?;
+                    if !self.context.all_match(&carried, &expected) {
+                        return Err(ValidationError::Other(format!(
+                            "catch clause carries {:?} but label {} expects {:?}", carried, catch.label(), expected
+                        )));
+                    }
+                }
+                self.pop_operands(&params)?;
+                self.push_frame(FrameKind::Block, params, results);
+            },
+            #[cfg(feature = "exceptions")]
+            Throw(tag) => {
+                let ty = self.context.tag(tag)?.clone();
+                self.pop_operands(&ty.params)?;
+                self.set_unreachable();
+            },
+            #[cfg(feature = "exceptions")]
+            ThrowRef => {
+                self.pop_expected(ValueType::ExnRef)?;
+                self.set_unreachable();
+            },
+            Else => {
+                match self.frames.last() {
+                    Some(frame) if frame.kind == FrameKind::If => {},
+                    _ => return Err(ValidationError::UnmatchedControl("else")),
+                }
+                let frame = self.pop_frame()?;
+                self.push_frame(FrameKind::Else, frame.start_types, frame.end_types);
+            },
+            End => {
+                let frame = self.pop_frame()?;
+                // 没有 else 分支的 if 必须保持栈上的类型不变
+                if frame.kind == FrameKind::If && !self.context.all_match(&frame.start_types, &frame.end_types) {
+                    return Err(ValidationError::TypeMismatch {
+                        expected: frame.end_types.first().cloned(),
+                        actual: frame.start_types.first().cloned(),
+                    });
+                }
+                if !self.frames.is_empty() {
+                    self.push_operands(&frame.end_types);
+                }
+            },
+            Br(depth) => {
+                let types = self.label_types(depth)?;
+                self.pop_operands(&types)?;
+                self.set_unreachable();
+            },
+            BrIf(depth) => {
+                self.pop_expected(I32)?;
+                let types = self.label_types(depth)?;
+                self.pop_operands(&types)?;
+                self.push_operands(&types);
+            },
+            BrTable(ref data) => {
+                self.pop_expected(I32)?;
+                let default_types = self.label_types(data.default)?;
+                for &depth in data.table.iter() {
+                    let types = self.label_types(depth)?;
+                    if types.len() != default_types.len() {
+                        return Err(ValidationError::Other(format!(
+                            "br_table targets have different arities: {} and {}", types.len(), default_types.len()
+                        )));
+                    }
+                    // 每个目标都要检查类型，但不消耗栈上的值
+                    let mut popped = Vec::with_capacity(types.len());
+                    for &t in types.iter().rev() {
+                        popped.push(self.pop_expected(t)?);
+                    }
+                    for operand in popped.into_iter().rev() {
+                        self.push_operand(operand);
+                    }
+                }
+                self.pop_operands(&default_types)?;
+                self.set_unreachable();
+            },
+            Return => {
+                let types = self.return_types.clone();
+                self.pop_operands(&types)?;
+                self.set_unreachable();
+            },
+
+            Call(func_idx) => {
+                let ty = self.context.function_type(func_idx)?.clone();
+                self.pop_operands(&ty.params)?;
+                self.push_operands(&ty.results);
+            },
+            CallIndirect(type_idx, table_ref) => {
+                self.table_type(table_ref, ValueType::FuncRef)?;
+                let ty = self.context.func_type(type_idx)?.clone();
+                self.pop_expected(I32)?;
+                self.pop_operands(&ty.params)?;
+                self.push_operands(&ty.results);
+            },
+            #[cfg(feature = "tail_call")]
+            ReturnCall(func_idx) => {
+                let ty = self.context.function_type(func_idx)?.clone();
+                self.return_call(&ty)?;
+            },
+            #[cfg(feature = "tail_call")]
+            ReturnCallIndirect(type_idx, table_ref) => {
+                self.table_type(table_ref, ValueType::FuncRef)?;
+                let ty = self.context.func_type(type_idx)?.clone();
+                self.pop_expected(I32)?;
+                self.return_call(&ty)?;
+            },
+
+            Drop => {
+                self.pop_operand()?;
+            },
+            Select => {
+                self.pop_expected(I32)?;
+                let t1 = self.pop_operand()?;
+                let t2 = self.pop_operand()?;
+                // 不带类型的 select 只能用于数值类型
+                if let Some(t) = t1.or(t2).filter(|t| t.is_ref()) {
+                    return Err(ValidationError::TypeMismatch { expected: None, actual: Some(t) });
+                }
+                let t = match (t1, t2) {
+                    (None, t) | (t, None) => t,
+                    (Some(a), Some(b)) if a == b => Some(a),
+                    (Some(a), Some(b)) => {
+                        return Err(ValidationError::TypeMismatch { expected: Some(a), actual: Some(b) });
+                    },
+                };
+                self.push_operand(t);
+            },
+            TypedSelect(t) => {
+                self.context.check_value_type(t)?;
+                self.pop_expected(I32)?;
+                self.pop_operands(&[t, t])?;
+                self.push_operand(Some(t));
+            },
+
+            GetLocal(idx) => {
+                let t = self.local_type(idx)?;
+                if !self.is_initialized(idx, t) {
+                    return Err(ValidationError::UninitializedLocal(idx));
+                }
+                self.push_operand(Some(t));
+            },
+            SetLocal(idx) => {
+                self.set_local(idx)?;
+            },
+            TeeLocal(idx) => {
+                let t = self.set_local(idx)?;
+                self.push_operand(Some(t));
+            },
+            GetGlobal(idx) => {
+                let t = self.context.global(idx)?.content_type;
+                self.push_operand(Some(t));
+            },
+            SetGlobal(idx) => {
+                let global = self.context.global(idx)?;
+                if !global.is_mutable {
+                    return Err(ValidationError::ImmutableGlobal(idx));
+                }
+                let t = global.content_type;
+                self.pop_expected(t)?;
+            },
+
+            I32Load(align, offset, memory) => self.load(align, offset, memory, 2, I32)?,
+            I64Load(align, offset, memory) => self.load(align, offset, memory, 3, I64)?,
+            F32Load(align, offset, memory) => self.load(align, offset, memory, 2, F32)?,
+            F64Load(align, offset, memory) => self.load(align, offset, memory, 3, F64)?,
+            I32Load8S(align, offset, memory) | I32Load8U(align, offset, memory) => self.load(align, offset, memory, 0, I32)?,
+            I32Load16S(align, offset, memory) | I32Load16U(align, offset, memory) => self.load(align, offset, memory, 1, I32)?,
+            I64Load8S(align, offset, memory) | I64Load8U(align, offset, memory) => self.load(align, offset, memory, 0, I64)?,
+            I64Load16S(align, offset, memory) | I64Load16U(align, offset, memory) => self.load(align, offset, memory, 1, I64)?,
+            I64Load32S(align, offset, memory) | I64Load32U(align, offset, memory) => self.load(align, offset, memory, 2, I64)?,
+            I32Store(align, offset, memory) => self.store(align, offset, memory, 2, I32)?,
+            I64Store(align, offset, memory) => self.store(align, offset, memory, 3, I64)?,
+            F32Store(align, offset, memory) => self.store(align, offset, memory, 2, F32)?,
+            F64Store(align, offset, memory) => self.store(align, offset, memory, 3, F64)?,
+            I32Store8(align, offset, memory) => self.store(align, offset, memory, 0, I32)?,
+            I32Store16(align, offset, memory) => self.store(align, offset, memory, 1, I32)?,
+            I64Store8(align, offset, memory) => self.store(align, offset, memory, 0, I64)?,
+            I64Store16(align, offset, memory) => self.store(align, offset, memory, 1, I64)?,
+            I64Store32(align, offset, memory) => self.store(align, offset, memory, 2, I64)?,
+
+            CurrentMemory(mem_ref) => {
+                let a = self.address_type(mem_ref)?;
+                self.push_operand(Some(a));
+            },
+            GrowMemory(mem_ref) => {
+                let a = self.address_type(mem_ref)?;
+                self.unop(a)?;
+            },
+
+            I32Const(_) => self.push_operand(Some(I32)),
+            I64Const(_) => self.push_operand(Some(I64)),
+            F32Const(_) => self.push_operand(Some(F32)),
+            F64Const(_) => self.push_operand(Some(F64)),
+
+            I32Eqz => self.testop(I32)?,
+            I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU => self.relop(I32)?,
+            I64Eqz => self.testop(I64)?,
+            I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU => self.relop(I64)?,
+            F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge => self.relop(F32)?,
+            F64Eq | F64Ne | F64Lt | F64Gt | F64Le | F64Ge => self.relop(F64)?,
+
+            I32Clz | I32Ctz | I32Popcnt => self.unop(I32)?,
+            I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS | I32RemU | I32And | I32Or | I32Xor
+                | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr => self.binop(I32)?,
+            I64Clz | I64Ctz | I64Popcnt => self.unop(I64)?,
+            I64Add | I64Sub | I64Mul | I64DivS | I64DivU | I64RemS | I64RemU | I64And | I64Or | I64Xor
+                | I64Shl | I64ShrS | I64ShrU | I64Rotl | I64Rotr => self.binop(I64)?,
+            F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt => self.unop(F32)?,
+            F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Copysign => self.binop(F32)?,
+            F64Abs | F64Neg | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt => self.unop(F64)?,
+            F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign => self.binop(F64)?,
+
+            I32WrapI64 => self.cvtop(I64, I32)?,
+            I32TruncSF32 | I32TruncUF32 => self.cvtop(F32, I32)?,
+            I32TruncSF64 | I32TruncUF64 => self.cvtop(F64, I32)?,
+            I64ExtendSI32 | I64ExtendUI32 => self.cvtop(I32, I64)?,
+            I64TruncSF32 | I64TruncUF32 => self.cvtop(F32, I64)?,
+            I64TruncSF64 | I64TruncUF64 => self.cvtop(F64, I64)?,
+            F32ConvertSI32 | F32ConvertUI32 => self.cvtop(I32, F32)?,
+            F32ConvertSI64 | F32ConvertUI64 => self.cvtop(I64, F32)?,
+            F32DemoteF64 => self.cvtop(F64, F32)?,
+            F64ConvertSI32 | F64ConvertUI32 => self.cvtop(I32, F64)?,
+            F64ConvertSI64 | F64ConvertUI64 => self.cvtop(I64, F64)?,
+            F64PromoteF32 => self.cvtop(F32, F64)?,
+
+            I32ReinterpretF32 => self.cvtop(F32, I32)?,
+            I64ReinterpretF64 => self.cvtop(F64, I64)?,
+            F32ReinterpretI32 => self.cvtop(I32, F32)?,
+            F64ReinterpretI64 => self.cvtop(I64, F64)?,
+
+            I32Extend8S | I32Extend16S => self.unop(I32)?,
+            I64Extend8S | I64Extend16S | I64Extend32S => self.unop(I64)?,
+
+            I32TruncSatSF32 | I32TruncSatUF32 => self.cvtop(F32, I32)?,
+            I32TruncSatSF64 | I32TruncSatUF64 => self.cvtop(F64, I32)?,
+            I64TruncSatSF32 | I64TruncSatUF32 => self.cvtop(F32, I64)?,
+            I64TruncSatSF64 | I64TruncSatUF64 => self.cvtop(F64, I64)?,
+
+            RefNull(elem_type) => {
+                self.context.check_value_type(elem_type.value_type())?;
+                self.push_operand(Some(elem_type.value_type()));
+            },
+            RefIsNull => {
+                if let Some(t) = self.pop_operand()?.filter(|t| !t.is_ref()) {
+                    return Err(ValidationError::TypeMismatch { expected: None, actual: Some(t) });
+                }
+                self.push_operand(Some(I32));
+            },
+            RefFunc(idx) => {
+                self.context.function_type(idx)?;
+                if !self.context.refs.contains(&idx) {
+                    return Err(ValidationError::UndeclaredFuncRef(idx));
+                }
+                // 有类型的函数引用指向函数自身的类型
+                #[cfg(feature = "gc")]
+                let t = ValueType::Ref(RefType::non_nullable(HeapType::Concrete(self.context.functions[idx as usize])));
+                #[cfg(not(feature = "gc"))]
+                let t = ValueType::FuncRef;
+                self.push_operand(Some(t));
+            },
+
+            TableGet(table) => {
+                let t = self.context.table(table)?.elem_type.value_type();
+                self.cvtop(I32, t)?;
+            },
+            TableSet(table) => {
+                let t = self.context.table(table)?.elem_type.value_type();
+                self.pop_operands(&[I32, t])?;
+            },
+            TableSize(table) => {
+                self.context.table(table)?;
+                self.push_operand(Some(I32));
+            },
+            TableGrow(table) => {
+                let t = self.context.table(table)?.elem_type.value_type();
+                self.pop_operands(&[t, I32])?;
+                self.push_operand(Some(I32));
+            },
+            TableFill(table) => {
+                let t = self.context.table(table)?.elem_type.value_type();
+                self.pop_operands(&[I32, t, I32])?;
+            },
+
+            MemoryInit(segment, mem_ref) => {
+                let a = self.address_type(mem_ref)?;
+                self.context.data_segment(segment)?;
+                self.pop_operands(&[a, I32, I32])?;
+            },
+            DataDrop(segment) => self.context.data_segment(segment)?,
+            MemoryCopy(dst, src) => {
+                let dst = self.address_type(dst)?;
+                let src = self.address_type(src)?;
+                // 长度取两者中较窄的地址类型
+                let len = if src == I64 { dst } else { src };
+                self.pop_operands(&[dst, src, len])?;
+            },
+            MemoryFill(mem_ref) => {
+                let a = self.address_type(mem_ref)?;
+                self.pop_operands(&[a, I32, a])?;
+            },
+            TableInit(segment, table) => {
+                let t = self.context.elem_segment(segment)?.value_type();
+                let dst = self.context.table(table)?.elem_type.value_type();
+                self.check_matches(t, dst)?;
+                self.pop_operands(&[I32, I32, I32])?;
+            },
+            ElemDrop(segment) => { self.context.elem_segment(segment)?; },
+            TableCopy(dst, src) => {
+                let t = self.context.table(src)?.elem_type.value_type();
+                let dst = self.context.table(dst)?.elem_type.value_type();
+                self.check_matches(t, dst)?;
+                self.pop_operands(&[I32, I32, I32])?;
+            },
+
+            #[cfg(feature = "simd")]
+            Simd(ref simd) => self.simd(simd)?,
+            #[cfg(feature = "threads")]
+            Atomic(ref atomic) => self.atomic(atomic)?,
+
+            #[cfg(feature = "gc")]
+            CallRef(type_idx) => {
+                let ty = self.context.func_type(type_idx)?.clone();
+                self.pop_expected(concrete(type_idx, true))?;
+                self.pop_operands(&ty.params)?;
+                self.push_operands(&ty.results);
+            },
+            #[cfg(all(feature = "gc", feature = "tail_call"))]
+            ReturnCallRef(type_idx) => {
+                let ty = self.context.func_type(type_idx)?.clone();
+                self.pop_expected(concrete(type_idx, true))?;
+                self.return_call(&ty)?;
+            },
+            #[cfg(feature = "gc")]
+            RefEq => {
+                let eq = RefType::nullable(HeapType::Eq).into();
+                self.pop_operands(&[eq, eq])?;
+                self.push_operand(Some(I32));
+            },
+            #[cfg(feature = "gc")]
+            RefAsNonNull => {
+                let operand = self.pop_ref()?;
+                self.push_operand(operand.map(|r| RefType::non_nullable(r.heap).into()));
+            },
+            #[cfg(feature = "gc")]
+            BrOnNull(depth) => {
+                let operand = self.pop_ref()?;
+                let types = self.label_types(depth)?;
+                self.pop_operands(&types)?;
+                self.push_operands(&types);
+                self.push_operand(operand.map(|r| RefType::non_nullable(r.heap).into()));
+            },
+            #[cfg(feature = "gc")]
+            BrOnNonNull(depth) => {
+                let operand = self.pop_ref()?;
+                let mut types = self.label_types(depth)?;
+                let target = types.pop().ok_or_else(|| ValidationError::Other(format!(
+                    "br_on_non_null target {} must take a reference", depth
+                )))?;
+                if let Some(r) = operand {
+                    self.check_matches(RefType::non_nullable(r.heap).into(), target)?;
+                }
+                self.pop_operands(&types)?;
+                self.push_operands(&types);
+            },
+            #[cfg(feature = "gc")]
+            Gc(ref gc) => self.gc(gc)?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Reference to the defined type `type_idx`.
+#[cfg(feature = "gc")]
+fn concrete(type_idx: u32, nullable: bool) -> ValueType {
+    RefType { nullable, heap: HeapType::Concrete(type_idx) }.into()
+}
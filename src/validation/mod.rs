@@ -0,0 +1,817 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::elements::module::Module;
+use crate::elements::sections::Section;
+use crate::elements::types::{ValueType, FunctionType};
+use crate::elements::import_entry::{External, ResizableLimits};
+use crate::elements::export_entry::Internal;
+use crate::elements::ops::InitExpr;
+use crate::elements::const_expr::{ConstEnv, ConstExprError, ConstValue};
+use crate::elements::types::TableElementType;
+#[cfg(feature = "gc")]
+use crate::elements::types::{FieldType, HeapType, RefType};
+#[cfg(feature = "gc")]
+use crate::elements::gc::GcInstruction;
+#[cfg(feature = "gc")]
+use crate::elements::ops::Instruction;
+
+mod context;
+mod func;
+mod subtype;
+
+pub use self::context::ModuleContext;
+
+/// Maximum number of pages of a linear memory (4 GiB).
+pub const LINEAR_MEMORY_MAX_PAGES: u64 = 65536;
+
+/// Maximum number of pages of a memory addressed with `i64` (2^64 bytes).
+pub const LINEAR_MEMORY64_MAX_PAGES: u64 = 1 << 48;
+
+/// Validation error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// Sections are out of order, the section id is reported.
+    SectionsOutOfOrder(u8),
+    /// A non-custom section appears more than once.
+    DuplicatedSection(u8),
+    /// Type index is out of bounds.
+    UnknownType(u32),
+    /// Type is not of the kind the instruction or declaration requires.
+    UnexpectedType {
+        /// Index of the type.
+        index: u32,
+        /// Expected kind: `func`, `struct` or `array`.
+        expected: &'static str,
+    },
+    /// Function index is out of bounds.
+    UnknownFunction(u32),
+    /// Table index is out of bounds.
+    UnknownTable(u32),
+    /// Memory index is out of bounds.
+    UnknownMemory(u32),
+    /// Global index is out of bounds.
+    UnknownGlobal(u32),
+    /// Local index is out of bounds.
+    UnknownLocal(u32),
+    /// Local without a default value is read before it is set.
+    UninitializedLocal(u32),
+    /// Branch depth is out of bounds.
+    UnknownLabel(u32),
+    /// Tag index is out of bounds.
+    UnknownTag(u32),
+    /// Element segment index is out of bounds.
+    UnknownElemSegment(u32),
+    /// Data segment index is out of bounds.
+    UnknownDataSegment(u32),
+    /// `memory.init` or `data.drop` is used without a data count section.
+    MissingDataCount,
+    /// Data count section does not match the number of data segments.
+    InconsistentDataCount {
+        /// Count given by the data count section.
+        declared: u32,
+        /// Entries in the data section.
+        segments: usize,
+    },
+    /// Number of function signatures and bodies does not match.
+    InconsistentCode {
+        /// Entries in the function section.
+        functions: usize,
+        /// Entries in the code section.
+        bodies: usize,
+    },
+    /// Invalid limits of a table or memory.
+    InvalidLimits(&'static str),
+    /// More than one memory is defined or imported without the `multi_memory` feature.
+    MultipleMemories,
+    /// Type of a tag has results, the index of the type is reported.
+    InvalidTagType(u32),
+    /// Export name is used more than once.
+    DuplicatedExport(String),
+    /// Start function must have type `[] -> []`.
+    InvalidStartFunction(u32),
+    /// Invalid constant expression.
+    ConstExpr(ConstExprError),
+    /// `ref.func` names a function which is not declared outside of function bodies.
+    UndeclaredFuncRef(u32),
+    /// Global is immutable and can't be written.
+    ImmutableGlobal(u32),
+    /// Alignment is larger than the natural alignment.
+    InvalidAlignment {
+        /// Alignment exponent of the instruction.
+        align: u32,
+        /// Largest alignment exponent allowed.
+        max: u32,
+    },
+    /// Offset of a memory access does not fit the address type of the memory.
+    InvalidOffset(u64),
+    /// Alignment of an atomic instruction differs from the natural alignment.
+    InvalidAtomicAlignment {
+        /// Alignment exponent of the instruction.
+        align: u32,
+        /// The only alignment exponent allowed.
+        natural: u32,
+    },
+    /// Lane index of a SIMD instruction is out of bounds.
+    InvalidLaneIndex {
+        /// Lane index of the instruction.
+        lane: u8,
+        /// Number of lanes of the shape.
+        lanes: u8,
+    },
+    /// Operand type does not match.
+    TypeMismatch {
+        /// Expected type, `None` when any type is accepted.
+        expected: Option<ValueType>,
+        /// Actual type, `None` when the operand is polymorphic.
+        actual: Option<ValueType>,
+    },
+    /// Results of a tail-called function differ from the results of the caller.
+    TailCallMismatch {
+        /// Results of the calling function.
+        expected: Vec<ValueType>,
+        /// Results of the callee.
+        actual: Vec<ValueType>,
+    },
+    /// Operand stack is empty.
+    StackUnderflow,
+    /// Values are left on the operand stack at the end of a block.
+    UnbalancedStack {
+        /// Expected stack height.
+        expected: usize,
+        /// Actual stack height.
+        actual: usize,
+    },
+    /// `else` or `end` without a matching block.
+    UnmatchedControl(&'static str),
+    /// Instructions after the final `end` of a body.
+    TrailingInstructions,
+    /// Other validation error.
+    Other(String),
+    /// Error inside a function body.
+    Function {
+        /// Index of the function in the function index space.
+        index: u32,
+        /// Position of the offending instruction in the body.
+        position: usize,
+        /// The actual error.
+        error: Box<ValidationError>,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::SectionsOutOfOrder(id) => write!(f, "Section {} is out of order", id),
+            ValidationError::DuplicatedSection(id) => write!(f, "Duplicated section {}", id),
+            ValidationError::UnknownType(idx) => write!(f, "Unknown type {}", idx),
+            ValidationError::UnexpectedType { index, expected } => write!(f, "Type {} is not a {} type", index, expected),
+            ValidationError::UnknownFunction(idx) => write!(f, "Unknown function {}", idx),
+            ValidationError::UnknownTable(idx) => write!(f, "Unknown table {}", idx),
+            ValidationError::UnknownMemory(idx) => write!(f, "Unknown memory {}", idx),
+            ValidationError::UnknownGlobal(idx) => write!(f, "Unknown global {}", idx),
+            ValidationError::UnknownLocal(idx) => write!(f, "Unknown local {}", idx),
+            ValidationError::UninitializedLocal(idx) => write!(f, "Local {} is read before it is set", idx),
+            ValidationError::UnknownLabel(depth) => write!(f, "Unknown label {}", depth),
+            ValidationError::UnknownTag(idx) => write!(f, "Unknown tag {}", idx),
+            ValidationError::UnknownElemSegment(idx) => write!(f, "Unknown element segment {}", idx),
+            ValidationError::UnknownDataSegment(idx) => write!(f, "Unknown data segment {}", idx),
+            ValidationError::MissingDataCount => write!(f, "Data count section required"),
+            ValidationError::InconsistentDataCount { declared, segments } => {
+                write!(f, "Data count section declares {} segments but data section has {}", declared, segments)
+            },
+            ValidationError::InconsistentCode { functions, bodies } => {
+                write!(f, "Function section declares {} functions but code section has {} bodies", functions, bodies)
+            },
+            ValidationError::InvalidLimits(msg) => write!(f, "Invalid limits: {}", msg),
+            ValidationError::MultipleMemories => write!(f, "Multiple memories"),
+            ValidationError::InvalidTagType(idx) => write!(f, "Tag type {} must have no results", idx),
+            ValidationError::DuplicatedExport(ref name) => write!(f, "Duplicated export name {:?}", name),
+            ValidationError::InvalidStartFunction(idx) => write!(f, "Start function {} must have type [] -> []", idx),
+            ValidationError::ConstExpr(ref e) => write!(f, "{}", e),
+            ValidationError::UndeclaredFuncRef(idx) => write!(f, "Undeclared function reference {}", idx),
+            ValidationError::ImmutableGlobal(idx) => write!(f, "Global {} is immutable", idx),
+            ValidationError::InvalidAlignment { align, max } => {
+                write!(f, "Alignment 2^{} is larger than natural alignment 2^{}", align, max)
+            },
+            ValidationError::InvalidOffset(offset) => write!(f, "Offset {} is out of range for a 32-bit memory", offset),
+            ValidationError::InvalidAtomicAlignment { align, natural } => {
+                write!(f, "Atomic alignment 2^{} must equal natural alignment 2^{}", align, natural)
+            },
+            ValidationError::InvalidLaneIndex { lane, lanes } => {
+                write!(f, "Lane index {} out of range for {} lanes", lane, lanes)
+            },
+            ValidationError::TypeMismatch { expected, actual } => {
+                let show = |t: Option<ValueType>| match t {
+                    Some(t) => format!("{:?}", t),
+                    None => "any".to_string(),
+                };
+                write!(f, "Type mismatch: expected {}, found {}", show(expected), show(actual))
+            },
+            ValidationError::TailCallMismatch { ref expected, ref actual } => {
+                write!(f, "Tail call returns {:?} but the caller returns {:?}", actual, expected)
+            },
+            ValidationError::StackUnderflow => write!(f, "Operand stack underflow"),
+            ValidationError::UnbalancedStack { expected, actual } => {
+                write!(f, "Expected {} values on the stack, found {}", expected, actual)
+            },
+            ValidationError::UnmatchedControl(ins) => write!(f, "Unmatched {}", ins),
+            ValidationError::TrailingInstructions => write!(f, "Instructions after the end of the function"),
+            ValidationError::Other(ref msg) => write!(f, "{}", msg),
+            ValidationError::Function { index, position, ref error } => {
+                write!(f, "Function #{}, instruction #{}: {}", index, position, error)
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for ValidationError {}
+
+/// Module which passed validation.
+#[derive(Debug, Clone)]
+pub struct ValidatedModule {
+    module: Module,
+    context: ModuleContext,
+}
+
+impl ValidatedModule {
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// Index spaces of the module.
+    pub fn context(&self) -> &ModuleContext {
+        &self.context
+    }
+
+    pub fn into_module(self) -> Module {
+        self.module
+    }
+}
+
+/// Validate a module according to the WebAssembly specification.
+pub fn validate(module: &Module) -> Result<ValidatedModule, ValidationError> {
+    validate_section_order(module)?;
+
+    let context = ModuleContext::new(module)?;
+
+    for ty in context.types.iter() {
+        if let Some(func) = ty.func_type() {
+            validate_function_type(func)?;
+        }
+    }
+
+    // 类型可以引用同一递归组内位于其后的类型
+    #[cfg(feature = "gc")]
+    if let Some(types) = module.type_section() {
+        let mut group_end = 0u32;
+        for group in types.0.iter() {
+            let start = group_end;
+            group_end += group.0.len() as u32;
+            for idx in start..group_end {
+                context.validate_sub_type(idx, group_end)?;
+            }
+        }
+    }
+
+    for table in context.tables.iter() {
+        context.check_value_type(table.elem_type.value_type())?;
+        if !table.elem_type.value_type().is_defaultable() {
+            return Err(ValidationError::Other("Tables of non-nullable references are not supported".to_string()));
+        }
+        validate_limits(&table.limits, u32::MAX as u64)?;
+        if table.limits.shared {
+            return Err(ValidationError::InvalidLimits("tables can't be shared"));
+        }
+        if table.limits.memory64 {
+            return Err(ValidationError::InvalidLimits("tables can't be 64-bit"));
+        }
+    }
+
+    #[cfg(not(feature = "multi_memory"))]
+    if context.memories.len() > 1 {
+        return Err(ValidationError::MultipleMemories);
+    }
+    for memory in context.memories.iter() {
+        let bound = if memory.memory64 { LINEAR_MEMORY64_MAX_PAGES } else { LINEAR_MEMORY_MAX_PAGES };
+        validate_limits(memory, bound)?;
+        if memory.shared && memory.maximum.is_none() {
+            return Err(ValidationError::InvalidLimits("shared memory must have a maximum"));
+        }
+    }
+
+    if let Some(imports) = module.import_section() {
+        for entry in imports.0.iter() {
+            if let External::Function(type_idx) = entry.external {
+                context.func_type(type_idx)?;
+            }
+        }
+    }
+
+    #[cfg(feature = "exceptions")]
+    for &type_idx in context.tags.iter() {
+        if !context.func_type(type_idx)?.results.is_empty() {
+            return Err(ValidationError::InvalidTagType(type_idx));
+        }
+    }
+
+    for global in context.globals.iter() {
+        context.check_value_type(global.content_type)?;
+    }
+
+    if let Some(globals) = module.global_section() {
+        for (i, entry) in globals.0.iter().enumerate() {
+            // 全局变量的初始化表达式只能引用导入的全局变量，GC 提案放宽到之前定义的全局变量
+            let visible = if cfg!(feature = "gc") { context.imported_globals + i } else { context.imported_globals };
+            validate_init_expr(&context, &entry.init_expr, entry.global_type.content_type, visible)?;
+        }
+    }
+
+    if let Some(exports) = module.export_section() {
+        let mut names: HashSet<&str> = HashSet::new();
+        for entry in exports.0.iter() {
+            if !names.insert(&entry.field_str) {
+                return Err(ValidationError::DuplicatedExport(entry.field_str.clone()));
+            }
+            match entry.internal {
+                Internal::Function(idx) => { context.function_type(idx)?; },
+                Internal::Table(idx) => { context.table(idx)?; },
+                Internal::Memory(idx) => { context.memory(idx)?; },
+                Internal::Global(idx) => { context.global(idx)?; },
+                #[cfg(feature = "exceptions")]
+                Internal::Tag(idx) => { context.tag(idx)?; },
+            }
+        }
+    }
+
+    if let Some(start) = module.start_section() {
+        let ty = context.function_type(start)?;
+        if !ty.params.is_empty() || !ty.results.is_empty() {
+            return Err(ValidationError::InvalidStartFunction(start));
+        }
+    }
+
+    if let Some(elements) = module.elements_section() {
+        for segment in elements.0.iter() {
+            let elem_type = segment.elem_type.value_type();
+            context.check_value_type(elem_type)?;
+            if let Some(ref offset) = segment.offset {
+                let table_type = context.table(segment.index)?.elem_type.value_type();
+                if !context.matches(elem_type, table_type) {
+                    return Err(ValidationError::TypeMismatch { expected: Some(table_type), actual: Some(elem_type) });
+                }
+                validate_init_expr(&context, offset, ValueType::I32, context.globals.len())?;
+            }
+            for &member in segment.members.iter() {
+                context.function_type(member)?;
+            }
+            for expr in segment.exprs.iter().flatten() {
+                validate_init_expr(&context, expr, elem_type, context.globals.len())?;
+            }
+        }
+    }
+
+    if let Some(data) = module.data_section() {
+        for segment in data.0.iter() {
+            if let Some(ref offset) = segment.offset {
+                let memory = context.memory(segment.index)?;
+                let t = if memory.memory64 { ValueType::I64 } else { ValueType::I32 };
+                validate_init_expr(&context, offset, t, context.globals.len())?;
+            }
+        }
+    }
+
+    if let Some(declared) = context.data_count {
+        let segments = module.data_section().map(|s| s.0.len()).unwrap_or(0);
+        if declared as usize != segments {
+            return Err(ValidationError::InconsistentDataCount { declared, segments });
+        }
+    }
+
+    let functions = module.function_section().map(|s| s.0.len()).unwrap_or(0);
+    let bodies = module.code_section().map(|s| s.0.len()).unwrap_or(0);
+    if functions != bodies {
+        return Err(ValidationError::InconsistentCode { functions, bodies });
+    }
+
+    if let Some(code) = module.code_section() {
+        for (i, body) in code.0.iter().enumerate() {
+            let index = (context.imported_functions + i) as u32;
+            func::validate_function(&context, index, body)?;
+        }
+    }
+
+    Ok(ValidatedModule {
+        module: module.clone(),
+        context,
+    })
+}
+
+fn validate_section_order(module: &Module) -> Result<(), ValidationError> {
+    // DataCount section 位于 Element 和 Code 之间，Tag section 位于 Memory 和 Global 之间
+    fn rank(id: u8) -> u8 {
+        const ORDER: [u8; 13] = [1, 2, 3, 4, 5, 13, 6, 7, 8, 9, 12, 10, 11];
+        ORDER.iter().position(|&i| i == id).map(|p| p as u8 + 1).unwrap_or(id)
+    }
+
+    let mut last = 0u8;
+    let mut seen: HashSet<u8> = HashSet::new();
+    for section in module.sections.iter() {
+        let id = section.id();
+        match *section {
+            Section::Custom(_) => continue,
+            Section::Unparsed { id, .. } => return Err(ValidationError::Other(format!("Unknown section {}", id))),
+            _ => {},
+        }
+        if !seen.insert(id) {
+            return Err(ValidationError::DuplicatedSection(id));
+        }
+        if rank(id) < last {
+            return Err(ValidationError::SectionsOutOfOrder(id));
+        }
+        last = rank(id);
+    }
+    Ok(())
+}
+
+fn validate_function_type(ty: &FunctionType) -> Result<(), ValidationError> {
+    let multi_value = cfg!(feature = "multi_value");
+    if !multi_value && ty.results.len() > 1 {
+        return Err(ValidationError::Other("Function types may have at most one result".to_string()));
+    }
+    Ok(())
+}
+
+fn validate_limits(limits: &ResizableLimits, bound: u64) -> Result<(), ValidationError> {
+    if limits.initial > bound {
+        return Err(ValidationError::InvalidLimits("initial size out of range"));
+    }
+    if let Some(maximum) = limits.maximum {
+        if maximum > bound {
+            return Err(ValidationError::InvalidLimits("maximum size out of range"));
+        }
+        if limits.initial > maximum {
+            return Err(ValidationError::InvalidLimits("initial size is larger than maximum"));
+        }
+    }
+    Ok(())
+}
+
+impl From<ConstExprError> for ValidationError {
+    fn from(e: ConstExprError) -> ValidationError {
+        match e {
+            ConstExprError::UnknownGlobal(idx) => ValidationError::UnknownGlobal(idx),
+            ConstExprError::UnknownFunction(idx) => ValidationError::UnknownFunction(idx),
+            #[cfg(feature = "gc")]
+            ConstExprError::UnknownType(idx) => ValidationError::UnknownType(idx),
+            e => ValidationError::ConstExpr(e),
+        }
+    }
+}
+
+/// Constant expressions evaluated with the types of the module.
+///
+/// Values of globals are unknown before instantiation, they read as zero and
+/// only the type of the result matters.
+struct ConstTypes<'a> {
+    context: &'a ModuleContext,
+    visible_globals: usize,
+}
+
+impl ConstEnv for ConstTypes<'_> {
+    type Ref = ValueType;
+
+    fn global(&self, idx: u32) -> Result<ConstValue<ValueType>, ConstExprError> {
+        if idx as usize >= self.visible_globals {
+            return Err(ConstExprError::UnknownGlobal(idx));
+        }
+        let global = self.context.global(idx).map_err(|_| ConstExprError::UnknownGlobal(idx))?;
+        if global.is_mutable {
+            return Err(ConstExprError::MutableGlobal(idx));
+        }
+        Ok(match global.content_type {
+            ValueType::I32 => ConstValue::I32(0),
+            ValueType::I64 => ConstValue::I64(0),
+            ValueType::F32 => ConstValue::F32(0),
+            ValueType::F64 => ConstValue::F64(0),
+            #[cfg(feature = "simd")]
+            ValueType::V128 => ConstValue::V128(0),
+            t => ConstValue::Ref(t),
+        })
+    }
+
+    fn null(&self, elem_type: TableElementType) -> ValueType {
+        elem_type.value_type()
+    }
+
+    fn func(&self, idx: u32) -> Result<ValueType, ConstExprError> {
+        self.context.function_type(idx).map_err(|_| ConstExprError::UnknownFunction(idx))?;
+        #[cfg(feature = "gc")]
+        let func_ref = ValueType::Ref(RefType::non_nullable(HeapType::Concrete(self.context.functions[idx as usize])));
+        #[cfg(not(feature = "gc"))]
+        let func_ref = ValueType::FuncRef;
+        Ok(func_ref)
+    }
+
+    #[cfg(feature = "gc")]
+    fn gc(&self, instruction: &GcInstruction, stack: &mut Vec<ConstValue<ValueType>>) -> Result<ConstValue<ValueType>, ConstExprError> {
+        use crate::elements::gc::GcInstruction::*;
+
+        let context = self.context;
+        let mut pop = |expected: ValueType| -> Result<ValueType, ConstExprError> {
+            let actual = const_type(stack.pop().ok_or(ConstExprError::StackUnderflow)?);
+            if !context.matches(actual, expected) {
+                return Err(ConstExprError::TypeMismatch(expected));
+            }
+            Ok(actual)
+        };
+        let fields = |type_idx: u32| context.struct_type(type_idx).map_err(|_| ConstExprError::UnknownType(type_idx));
+        let elem = |type_idx: u32| context.array_type(type_idx).map_err(|_| ConstExprError::UnknownType(type_idx));
+        let defaultable = |field: &FieldType, type_idx: u32| match field.storage.unpacked().is_defaultable() {
+            true => Ok(()),
+            false => Err(ConstExprError::UnknownType(type_idx)),
+        };
+
+        let result = match *instruction {
+            StructNew(type_idx) => {
+                for field in fields(type_idx)?.iter().rev() {
+                    pop(field.storage.unpacked())?;
+                }
+                HeapType::Concrete(type_idx)
+            },
+            StructNewDefault(type_idx) => {
+                for field in fields(type_idx)?.iter() {
+                    defaultable(field, type_idx)?;
+                }
+                HeapType::Concrete(type_idx)
+            },
+            ArrayNew(type_idx) => {
+                let elem = elem(type_idx)?;
+                pop(ValueType::I32)?;
+                pop(elem.storage.unpacked())?;
+                HeapType::Concrete(type_idx)
+            },
+            ArrayNewDefault(type_idx) => {
+                defaultable(&elem(type_idx)?, type_idx)?;
+                pop(ValueType::I32)?;
+                HeapType::Concrete(type_idx)
+            },
+            ArrayNewFixed(type_idx, len) => {
+                let elem = elem(type_idx)?;
+                for _ in 0..len {
+                    pop(elem.storage.unpacked())?;
+                }
+                HeapType::Concrete(type_idx)
+            },
+            RefI31 => {
+                pop(ValueType::I32)?;
+                HeapType::I31
+            },
+            AnyConvertExtern | ExternConvertAny => {
+                let (from, to) = match *instruction {
+                    AnyConvertExtern => (HeapType::Extern, HeapType::Any),
+                    _ => (HeapType::Any, HeapType::Extern),
+                };
+                let actual = pop(RefType::nullable(from).into())?;
+                let nullable = actual.ref_type().map(|r| r.nullable).unwrap_or(true);
+                return Ok(ConstValue::Ref(RefType { nullable, heap: to }.into()));
+            },
+            ref other => return Err(ConstExprError::NonConstant(Instruction::Gc(other.clone()))),
+        };
+        Ok(ConstValue::Ref(ValueType::Ref(RefType::non_nullable(result))))
+    }
+}
+
+/// Type of a value computed by `ConstTypes`.
+fn const_type(value: ConstValue<ValueType>) -> ValueType {
+    match value {
+        ConstValue::I32(_) => ValueType::I32,
+        ConstValue::I64(_) => ValueType::I64,
+        ConstValue::F32(_) => ValueType::F32,
+        ConstValue::F64(_) => ValueType::F64,
+        #[cfg(feature = "simd")]
+        ConstValue::V128(_) => ValueType::V128,
+        ConstValue::Ref(t) => t,
+    }
+}
+
+/// Validate a constant expression producing a value of `expected` type.
+///
+/// Only the first `visible_globals` globals may be referenced by `global.get`,
+/// and they must be immutable.
+pub(crate) fn validate_init_expr(
+    context: &ModuleContext,
+    expr: &InitExpr,
+    expected: ValueType,
+    visible_globals: usize,
+) -> Result<(), ValidationError> {
+    let actual = const_type(expr.evaluate(&ConstTypes { context, visible_globals })?);
+
+    if !context.matches(actual, expected) {
+        return Err(ValidationError::TypeMismatch { expected: Some(expected), actual: Some(actual) });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elements::deserialize_buffer;
+    use crate::elements::func::FuncBody;
+    use crate::elements::ops::{Instruction, Instructions};
+    use crate::elements::sections::{TypeSection, FunctionSection, CodeSection};
+    use crate::elements::func::Func;
+    use crate::elements::types::BlockType;
+    use crate::tests::SAMPLE_MODULE;
+
+    fn module_with_body(params: Vec<ValueType>, results: Vec<ValueType>, code: Vec<Instruction>) -> Module {
+        let mut m = Module::default();
+        m.sections.push(Section::Type(TypeSection::from(vec![FunctionType { form: 0x60, params, results }])));
+        m.sections.push(Section::Function(FunctionSection(vec![Func(0)])));
+        m.sections.push(Section::Code(CodeSection(vec![FuncBody {
+            locals: Vec::new(),
+            instructions: Instructions(code),
+        }])));
+        m
+    }
+
+    #[test]
+    fn test_sample_module() {
+        let m: Module = deserialize_buffer(SAMPLE_MODULE).unwrap();
+        validate(&m).unwrap();
+    }
+
+    #[test]
+    fn test_function_bodies() {
+        use crate::elements::ops::Instruction::*;
+
+        let ok = module_with_body(vec![ValueType::I32], vec![ValueType::I32], vec![
+            Block(BlockType::Value(ValueType::I32)),
+            GetLocal(0),
+            GetLocal(0),
+            BrIf(0),
+            Drop,
+            I32Const(1),
+            End,
+            End,
+        ]);
+        validate(&ok).unwrap();
+
+        let unreachable = module_with_body(vec![], vec![ValueType::I64], vec![Unreachable, I64Add, End]);
+        validate(&unreachable).unwrap();
+
+        let mismatch = module_with_body(vec![], vec![ValueType::I32], vec![I64Const(1), End]);
+        match validate(&mismatch) {
+            Err(ValidationError::Function { ref error, .. }) => {
+                assert_eq!(**error, ValidationError::TypeMismatch {
+                    expected: Some(ValueType::I32),
+                    actual: Some(ValueType::I64),
+                });
+            },
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let leftover = module_with_body(vec![], vec![], vec![I32Const(1), End]);
+        assert!(validate(&leftover).is_err());
+
+        let bad_call = module_with_body(vec![], vec![], vec![Call(7), End]);
+        assert!(validate(&bad_call).is_err());
+
+        let bad_label = module_with_body(vec![], vec![], vec![Br(1), End]);
+        assert!(validate(&bad_label).is_err());
+    }
+
+    #[test]
+    fn test_br_table_after_unreachable() {
+        use crate::elements::ops::BrTableData;
+        use crate::elements::ops::Instruction::*;
+
+        // unreachable 之后的操作数类型未知，可以同时匹配 i32 和 f32 两个目标
+        let body = module_with_body(vec![], vec![], vec![
+            Block(BlockType::Value(ValueType::F32)),
+            Block(BlockType::Value(ValueType::I32)),
+            Unreachable,
+            BrTable(Box::new(BrTableData { table: Box::new([0]), default: 1 })),
+            End,
+            Drop,
+            F32Const(0),
+            End,
+            Drop,
+            End,
+        ]);
+        validate(&body).unwrap();
+    }
+
+    #[test]
+    fn test_inconsistent_code() {
+        let mut m = module_with_body(vec![], vec![], vec![Instruction::End]);
+        m.sections.retain(|s| s.id() != 10);
+        assert_eq!(
+            validate(&m).unwrap_err(),
+            ValidationError::InconsistentCode { functions: 1, bodies: 0 }
+        );
+    }
+
+    #[test]
+    fn test_const_exprs() {
+        let globals = |init: &str| crate::wat::parse(&format!(r#"
+            (module
+              (import "env" "base" (global $base i32))
+              (import "env" "counter" (global $counter (mut i32)))
+              (global i32 {}))
+        "#, init)).unwrap();
+
+        validate(&globals("(i32.add (global.get $base) (i32.mul (i32.const 4) (i32.const 8)))")).unwrap();
+        validate(&globals("(global.get $base) (i32.const 1) (i32.sub)")).unwrap();
+        assert_eq!(
+            validate(&globals("(i32.div_s (global.get $base) (i32.const 2))")).unwrap_err(),
+            ValidationError::ConstExpr(ConstExprError::NonConstant(Instruction::I32DivS))
+        );
+        assert_eq!(
+            validate(&globals("(global.get $counter)")).unwrap_err(),
+            ValidationError::ConstExpr(ConstExprError::MutableGlobal(1))
+        );
+        assert_eq!(
+            validate(&globals("(i32.add (i32.const 1) (i64.const 2))")).unwrap_err(),
+            ValidationError::ConstExpr(ConstExprError::TypeMismatch(ValueType::I32))
+        );
+        assert_eq!(
+            validate(&globals("(i64.add (i64.const 1) (i64.const 2))")).unwrap_err(),
+            ValidationError::TypeMismatch { expected: Some(ValueType::I32), actual: Some(ValueType::I64) }
+        );
+        assert_eq!(validate(&globals("(global.get 2)")).unwrap_err(), ValidationError::UnknownGlobal(2));
+    }
+
+    #[cfg(feature = "gc")]
+    #[test]
+    fn test_subtyping() {
+        use crate::elements::types::{HeapType, RefType};
+
+        let types = r#"
+            (rec (type $a (struct (field (ref null $b)))) (type $b (struct (field (ref null $a)))))
+            (rec (type $c (struct (field (ref null $d)))) (type $d (struct (field (ref null $c)))))
+            (type $base (sub (struct (field i32))))
+            (type $derived (sub $base (struct (field i32) (field i64))))
+            (type $bytes (array i8))
+        "#;
+        let module = |fields: &str| crate::wat::parse(&format!("(module {} {})", types, fields)).unwrap();
+        let function_error = |fields: &str| match validate(&module(fields)) {
+            Err(ValidationError::Function { error, .. }) => *error,
+            other => panic!("unexpected result {:?}", other),
+        };
+
+        // 结构相同的递归组中的类型等价
+        validate(&module(r#"
+            (func (param (ref $a)) (result (ref null $c)) (local.get 0))
+            (func (param (ref $derived)) (result (ref $base)) (local.get 0))
+            (func (param (ref $derived)) (result eqref) (local.get 0))
+            (func (result (ref null $derived)) (ref.null none))
+            (func (param i31ref) (result anyref) (local.get 0))
+        "#)).unwrap();
+
+        assert_eq!(
+            function_error("(func (param (ref $base)) (result (ref $derived)) (local.get 0))"),
+            ValidationError::TypeMismatch {
+                expected: Some(ValueType::Ref(RefType::non_nullable(HeapType::Concrete(5)))),
+                actual: Some(ValueType::Ref(RefType::non_nullable(HeapType::Concrete(4)))),
+            }
+        );
+        assert_eq!(
+            function_error("(func (param anyref) (result (ref any)) (local.get 0))"),
+            ValidationError::TypeMismatch {
+                expected: Some(ValueType::Ref(RefType::non_nullable(HeapType::Any))),
+                actual: Some(ValueType::Ref(RefType::nullable(HeapType::Any))),
+            }
+        );
+        assert_eq!(
+            function_error("(func (local (ref any)) (drop (local.get 0)))"),
+            ValidationError::UninitializedLocal(0)
+        );
+        assert_eq!(
+            function_error("(func (param (ref $bytes)) (result i32) (struct.get $base 0 (local.get 0)))"),
+            ValidationError::TypeMismatch {
+                expected: Some(ValueType::Ref(RefType::nullable(HeapType::Concrete(4)))),
+                actual: Some(ValueType::Ref(RefType::non_nullable(HeapType::Concrete(6)))),
+            }
+        );
+        assert_eq!(
+            function_error("(func (result i32) (struct.get $bytes 0 (ref.null none)))"),
+            ValidationError::UnexpectedType { index: 6, expected: "struct" }
+        );
+
+        // 扩展 final 类型、字段不匹配、可变字段的协变都不允许
+        let invalid_types = [
+            "(type $f (struct)) (type (sub $f (struct)))",
+            "(type $p (sub (struct (field (mut i32))))) (type (sub $p (struct (field (mut i64)))))",
+            "(type $p (sub (struct (field (mut anyref))))) (type (sub $p (struct (field (mut eqref)))))",
+            "(type (sub 1 (struct))) (type (sub (struct)))",
+        ];
+        for fields in invalid_types.iter() {
+            assert!(validate(&crate::wat::parse(&format!("(module {})", fields)).unwrap()).is_err(), "{}", fields);
+        }
+        validate(&crate::wat::parse(
+            "(module (type $p (sub (struct (field anyref)))) (type (sub $p (struct (field eqref)))))"
+        ).unwrap()).unwrap();
+    }
+}
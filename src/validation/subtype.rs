@@ -0,0 +1,238 @@
+//! Subtyping of value types.
+//!
+//! Without the `gc` feature a type only matches itself. With it, references
+//! match along the `func`, `extern` and `any` hierarchies, and defined types
+//! match their declared supertypes.
+
+#[cfg(feature = "gc")]
+use std::collections::HashMap;
+
+use crate::elements::types::ValueType;
+#[cfg(feature = "gc")]
+use crate::elements::types::{
+    CompositeType, FieldType, FunctionType, HeapType, RecGroup, RefType, StorageType, SubType,
+};
+use super::{ModuleContext, ValidationError};
+
+impl ModuleContext {
+    /// Whether a value of type `actual` can be used where `expected` is required.
+    pub fn matches(&self, actual: ValueType, expected: ValueType) -> bool {
+        if actual == expected {
+            return true;
+        }
+        #[cfg(feature = "gc")]
+        if let (Some(a), Some(b)) = (actual.ref_type(), expected.ref_type()) {
+            return self.ref_matches(a, b);
+        }
+        false
+    }
+
+    /// Whether each of `actual` matches the type at the same position of `expected`.
+    pub fn all_match(&self, actual: &[ValueType], expected: &[ValueType]) -> bool {
+        actual.len() == expected.len() && actual.iter().zip(expected.iter()).all(|(&a, &e)| self.matches(a, e))
+    }
+
+    /// Check that the type indices referenced by `value_type` are defined.
+    pub fn check_value_type(&self, value_type: ValueType) -> Result<(), ValidationError> {
+        check_bound(value_type, self.types.len() as u32)
+    }
+}
+
+#[cfg_attr(not(feature = "gc"), allow(unused_variables))]
+fn check_bound(value_type: ValueType, bound: u32) -> Result<(), ValidationError> {
+    #[cfg(feature = "gc")]
+    if let Some(RefType { heap: HeapType::Concrete(idx), .. }) = value_type.ref_type() {
+        if idx >= bound {
+            return Err(ValidationError::UnknownType(idx));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "gc")]
+impl ModuleContext {
+    pub fn ref_matches(&self, actual: RefType, expected: RefType) -> bool {
+        (!actual.nullable || expected.nullable) && self.heap_matches(actual.heap, expected.heap)
+    }
+
+    pub fn heap_matches(&self, actual: HeapType, expected: HeapType) -> bool {
+        use crate::elements::types::HeapType::*;
+
+        match (actual, expected) {
+            (Concrete(a), Concrete(b)) => self.type_matches(a, b),
+            (Concrete(a), _) => self.heap_matches(self.abstract_heap_type(a), expected),
+            (None, Concrete(b)) => matches!(self.abstract_heap_type(b), Struct | Array),
+            (NoFunc, Concrete(b)) => self.abstract_heap_type(b) == Func,
+            (_, Concrete(_)) => false,
+            _ if actual == expected => true,
+            (None, Any) | (None, Eq) | (None, I31) | (None, Struct) | (None, Array) => true,
+            (I31, Eq) | (Struct, Eq) | (Array, Eq) => true,
+            (Eq, Any) | (I31, Any) | (Struct, Any) | (Array, Any) => true,
+            (NoFunc, Func) | (NoExtern, Extern) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether defined type `actual` is equivalent to `expected` or declares it as a transitive supertype.
+    pub fn type_matches(&self, actual: u32, expected: u32) -> bool {
+        let canonical = |idx: u32| self.canonical.get(idx as usize).cloned();
+        let mut current = actual;
+        loop {
+            if canonical(current).is_some() && canonical(current) == canonical(expected) {
+                return true;
+            }
+            // 声明顺序保证父类型的索引更小，未验证的循环在这里截断
+            match self.types.get(current as usize).and_then(|t| t.supertypes.first()) {
+                Some(&parent) if parent < current => current = parent,
+                _ => return false,
+            }
+        }
+    }
+
+    /// `func`, `struct` or `array` depending on the defined type `idx`.
+    fn abstract_heap_type(&self, idx: u32) -> HeapType {
+        match self.types.get(idx as usize).map(|t| &t.composite) {
+            Some(CompositeType::Func(_)) => HeapType::Func,
+            Some(CompositeType::Struct(_)) => HeapType::Struct,
+            Some(CompositeType::Array(_)) => HeapType::Array,
+            None => HeapType::None,
+        }
+    }
+
+    /// Top of the hierarchy of `heap`: `func`, `extern` or `any`.
+    pub fn top_heap_type(&self, heap: HeapType) -> HeapType {
+        match heap {
+            HeapType::Func | HeapType::NoFunc => HeapType::Func,
+            HeapType::Extern | HeapType::NoExtern => HeapType::Extern,
+            HeapType::Concrete(idx) if self.abstract_heap_type(idx) == HeapType::Func => HeapType::Func,
+            _ => HeapType::Any,
+        }
+    }
+
+    pub fn storage_matches(&self, actual: StorageType, expected: StorageType) -> bool {
+        match (actual, expected) {
+            (StorageType::Val(a), StorageType::Val(b)) => self.matches(a, b),
+            _ => actual == expected,
+        }
+    }
+
+    /// Mutable fields are invariant, immutable ones covariant.
+    pub fn field_matches(&self, actual: FieldType, expected: FieldType) -> bool {
+        actual.mutable == expected.mutable
+            && self.storage_matches(actual.storage, expected.storage)
+            && (!actual.mutable || self.storage_matches(expected.storage, actual.storage))
+    }
+
+    fn composite_matches(&self, actual: &CompositeType, expected: &CompositeType) -> bool {
+        match (actual, expected) {
+            (CompositeType::Func(a), CompositeType::Func(b)) => {
+                // 参数逆变，结果协变
+                self.all_match(&b.params, &a.params) && self.all_match(&a.results, &b.results)
+            },
+            (CompositeType::Struct(a), CompositeType::Struct(b)) => {
+                a.len() >= b.len() && a.iter().zip(b.iter()).all(|(&x, &y)| self.field_matches(x, y))
+            },
+            (CompositeType::Array(a), CompositeType::Array(b)) => self.field_matches(*a, *b),
+            _ => false,
+        }
+    }
+
+    /// Validate the declaration of type `idx`, which may refer to the types before `group_end`.
+    pub(super) fn validate_sub_type(&self, idx: u32, group_end: u32) -> Result<(), ValidationError> {
+        let sub = self.sub_type(idx)?;
+        for value_type in value_types(&sub.composite) {
+            check_bound(value_type, group_end)?;
+        }
+
+        if sub.supertypes.len() > 1 {
+            return Err(ValidationError::Other(format!("Type {} declares more than one supertype", idx)));
+        }
+        if let Some(&parent_idx) = sub.supertypes.first() {
+            if parent_idx >= idx {
+                return Err(ValidationError::Other(format!(
+                    "Supertype {} of type {} must be defined before it", parent_idx, idx
+                )));
+            }
+            let parent = self.sub_type(parent_idx)?;
+            if parent.is_final {
+                return Err(ValidationError::Other(format!("Type {} extends final type {}", idx, parent_idx)));
+            }
+            if !self.composite_matches(&sub.composite, &parent.composite) {
+                return Err(ValidationError::Other(format!("Type {} does not match its supertype {}", idx, parent_idx)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "gc")]
+fn value_types(composite: &CompositeType) -> Vec<ValueType> {
+    match *composite {
+        CompositeType::Func(ref func) => func.params.iter().chain(func.results.iter()).cloned().collect(),
+        CompositeType::Struct(ref fields) => fields.iter().map(|f| f.storage.unpacked()).collect(),
+        CompositeType::Array(ref field) => vec![field.storage.unpacked()],
+    }
+}
+
+#[cfg(feature = "gc")]
+fn map_value_type(value_type: ValueType, f: &dyn Fn(u32) -> u32) -> ValueType {
+    match value_type {
+        ValueType::Ref(RefType { nullable, heap: HeapType::Concrete(idx) }) => {
+            ValueType::Ref(RefType { nullable, heap: HeapType::Concrete(f(idx)) })
+        },
+        other => other,
+    }
+}
+
+/// Copy of `sub` with every type index replaced by `f`.
+#[cfg(feature = "gc")]
+fn map_sub_type(sub: &SubType, f: &dyn Fn(u32) -> u32) -> SubType {
+    let field = |field: &FieldType| FieldType {
+        storage: match field.storage {
+            StorageType::Val(t) => StorageType::Val(map_value_type(t, f)),
+            packed => packed,
+        },
+        mutable: field.mutable,
+    };
+    let composite = match sub.composite {
+        CompositeType::Func(ref func) => CompositeType::Func(FunctionType {
+            form: func.form,
+            params: func.params.iter().map(|&t| map_value_type(t, f)).collect(),
+            results: func.results.iter().map(|&t| map_value_type(t, f)).collect(),
+        }),
+        CompositeType::Struct(ref fields) => CompositeType::Struct(fields.iter().map(field).collect()),
+        CompositeType::Array(ref elem) => CompositeType::Array(field(elem)),
+    };
+    SubType {
+        is_final: sub.is_final,
+        supertypes: sub.supertypes.iter().map(|&idx| f(idx)).collect(),
+        composite,
+    }
+}
+
+/// Index of the first type equivalent to each type of `groups`.
+///
+/// Types are equivalent when their recursion groups are identical once
+/// references inside the group are made relative and references to earlier
+/// groups are replaced by their canonical index.
+#[cfg(feature = "gc")]
+pub(super) fn canonicalize(groups: &[RecGroup]) -> Vec<u32> {
+    let mut canonical: Vec<u32> = Vec::new();
+    let mut seen: HashMap<Vec<SubType>, u32> = HashMap::new();
+    for group in groups.iter() {
+        let start = canonical.len() as u32;
+        let len = group.0.len() as u32;
+        // 组内引用从 u32::MAX 倒数编号，非法的前向引用原样保留，验证时会报错
+        let key: Vec<SubType> = {
+            let relative = |idx: u32| match idx.checked_sub(start) {
+                Some(offset) if offset < len => u32::MAX - offset,
+                Some(_) => idx,
+                None => canonical[idx as usize],
+            };
+            group.0.iter().map(|sub| map_sub_type(sub, &relative)).collect()
+        };
+        let first = *seen.entry(key).or_insert(start);
+        canonical.extend((0..len).map(|i| first + i));
+    }
+    canonical
+}
@@ -0,0 +1,115 @@
+//! Text names of the atomic instructions.
+
+use crate::elements::atomics::AtomicInstruction;
+
+// 带 memarg 的原子指令：文本名称和自然对齐（log2）
+macro_rules! atomic_instructions {
+    ($($variant: ident => ($name: expr, $natural: expr),)*) => {
+        /// Text name, natural alignment, alignment, offset and memory index of an atomic memory access.
+        pub(crate) fn atomic_mnemonic(instruction: &AtomicInstruction) -> Option<(&'static str, u32, u32, u64, u32)> {
+            match *instruction {
+                $(AtomicInstruction::$variant(align, offset, memory) => Some(($name, $natural, align, offset, memory)),)*
+                _ => None,
+            }
+        }
+
+        /// Constructor and natural alignment of the atomic memory access named `name`.
+        pub(crate) fn atomic_instruction(name: &str) -> Option<(fn(u32, u64, u32) -> AtomicInstruction, u32)> {
+            match name {
+                $($name => Some((AtomicInstruction::$variant as fn(u32, u64, u32) -> AtomicInstruction, $natural)),)*
+                _ => None,
+            }
+        }
+    }
+}
+
+atomic_instructions! {
+    MemoryAtomicNotify => ("memory.atomic.notify", 2),
+
+    MemoryAtomicWait32 => ("memory.atomic.wait32", 2),
+    MemoryAtomicWait64 => ("memory.atomic.wait64", 3),
+
+    I32AtomicLoad => ("i32.atomic.load", 2),
+    I64AtomicLoad => ("i64.atomic.load", 3),
+    I32AtomicLoad8U => ("i32.atomic.load8_u", 0),
+    I32AtomicLoad16U => ("i32.atomic.load16_u", 1),
+    I64AtomicLoad8U => ("i64.atomic.load8_u", 0),
+    I64AtomicLoad16U => ("i64.atomic.load16_u", 1),
+    I64AtomicLoad32U => ("i64.atomic.load32_u", 2),
+
+    I32AtomicStore => ("i32.atomic.store", 2),
+    I64AtomicStore => ("i64.atomic.store", 3),
+    I32AtomicStore8 => ("i32.atomic.store8", 0),
+    I32AtomicStore16 => ("i32.atomic.store16", 1),
+    I64AtomicStore8 => ("i64.atomic.store8", 0),
+    I64AtomicStore16 => ("i64.atomic.store16", 1),
+    I64AtomicStore32 => ("i64.atomic.store32", 2),
+
+    I32AtomicRmwAdd => ("i32.atomic.rmw.add", 2),
+    I64AtomicRmwAdd => ("i64.atomic.rmw.add", 3),
+    I32AtomicRmw8AddU => ("i32.atomic.rmw8.add_u", 0),
+    I32AtomicRmw16AddU => ("i32.atomic.rmw16.add_u", 1),
+    I64AtomicRmw8AddU => ("i64.atomic.rmw8.add_u", 0),
+    I64AtomicRmw16AddU => ("i64.atomic.rmw16.add_u", 1),
+    I64AtomicRmw32AddU => ("i64.atomic.rmw32.add_u", 2),
+
+    I32AtomicRmwSub => ("i32.atomic.rmw.sub", 2),
+    I64AtomicRmwSub => ("i64.atomic.rmw.sub", 3),
+    I32AtomicRmw8SubU => ("i32.atomic.rmw8.sub_u", 0),
+    I32AtomicRmw16SubU => ("i32.atomic.rmw16.sub_u", 1),
+    I64AtomicRmw8SubU => ("i64.atomic.rmw8.sub_u", 0),
+    I64AtomicRmw16SubU => ("i64.atomic.rmw16.sub_u", 1),
+    I64AtomicRmw32SubU => ("i64.atomic.rmw32.sub_u", 2),
+
+    I32AtomicRmwAnd => ("i32.atomic.rmw.and", 2),
+    I64AtomicRmwAnd => ("i64.atomic.rmw.and", 3),
+    I32AtomicRmw8AndU => ("i32.atomic.rmw8.and_u", 0),
+    I32AtomicRmw16AndU => ("i32.atomic.rmw16.and_u", 1),
+    I64AtomicRmw8AndU => ("i64.atomic.rmw8.and_u", 0),
+    I64AtomicRmw16AndU => ("i64.atomic.rmw16.and_u", 1),
+    I64AtomicRmw32AndU => ("i64.atomic.rmw32.and_u", 2),
+
+    I32AtomicRmwOr => ("i32.atomic.rmw.or", 2),
+    I64AtomicRmwOr => ("i64.atomic.rmw.or", 3),
+    I32AtomicRmw8OrU => ("i32.atomic.rmw8.or_u", 0),
+    I32AtomicRmw16OrU => ("i32.atomic.rmw16.or_u", 1),
+    I64AtomicRmw8OrU => ("i64.atomic.rmw8.or_u", 0),
+    I64AtomicRmw16OrU => ("i64.atomic.rmw16.or_u", 1),
+    I64AtomicRmw32OrU => ("i64.atomic.rmw32.or_u", 2),
+
+    I32AtomicRmwXor => ("i32.atomic.rmw.xor", 2),
+    I64AtomicRmwXor => ("i64.atomic.rmw.xor", 3),
+    I32AtomicRmw8XorU => ("i32.atomic.rmw8.xor_u", 0),
+    I32AtomicRmw16XorU => ("i32.atomic.rmw16.xor_u", 1),
+    I64AtomicRmw8XorU => ("i64.atomic.rmw8.xor_u", 0),
+    I64AtomicRmw16XorU => ("i64.atomic.rmw16.xor_u", 1),
+    I64AtomicRmw32XorU => ("i64.atomic.rmw32.xor_u", 2),
+
+    I32AtomicRmwXchg => ("i32.atomic.rmw.xchg", 2),
+    I64AtomicRmwXchg => ("i64.atomic.rmw.xchg", 3),
+    I32AtomicRmw8XchgU => ("i32.atomic.rmw8.xchg_u", 0),
+    I32AtomicRmw16XchgU => ("i32.atomic.rmw16.xchg_u", 1),
+    I64AtomicRmw8XchgU => ("i64.atomic.rmw8.xchg_u", 0),
+    I64AtomicRmw16XchgU => ("i64.atomic.rmw16.xchg_u", 1),
+    I64AtomicRmw32XchgU => ("i64.atomic.rmw32.xchg_u", 2),
+
+    I32AtomicRmwCmpxchg => ("i32.atomic.rmw.cmpxchg", 2),
+    I64AtomicRmwCmpxchg => ("i64.atomic.rmw.cmpxchg", 3),
+    I32AtomicRmw8CmpxchgU => ("i32.atomic.rmw8.cmpxchg_u", 0),
+    I32AtomicRmw16CmpxchgU => ("i32.atomic.rmw16.cmpxchg_u", 1),
+    I64AtomicRmw8CmpxchgU => ("i64.atomic.rmw8.cmpxchg_u", 0),
+    I64AtomicRmw16CmpxchgU => ("i64.atomic.rmw16.cmpxchg_u", 1),
+    I64AtomicRmw32CmpxchgU => ("i64.atomic.rmw32.cmpxchg_u", 2),
+}
+
+/// Number of operands and results of an atomic instruction.
+pub(crate) fn atomic_arity(instruction: &AtomicInstruction) -> (usize, usize) {
+    match atomic_mnemonic(instruction) {
+        None => (0, 0),
+        Some((name, ..)) if name.contains("wait") || name.contains("cmpxchg") => (3, 1),
+        Some((name, ..)) if name.contains(".store") => (2, 0),
+        Some((name, ..)) if name.contains(".load") => (1, 1),
+        // notify 和 read-modify-write
+        Some(_) => (2, 1),
+    }
+}
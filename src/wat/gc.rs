@@ -0,0 +1,142 @@
+//! Text names of the GC types and instructions.
+
+use crate::elements::gc::{BrOnCastData, GcInstruction};
+use crate::elements::types::{HeapType, RefType};
+
+// 抽象堆类型：关键字和可空引用的缩写
+const ABSTRACT_HEAP_TYPES: [(HeapType, &str, &str); 10] = [
+    (HeapType::Func, "func", "funcref"),
+    (HeapType::Extern, "extern", "externref"),
+    (HeapType::Any, "any", "anyref"),
+    (HeapType::Eq, "eq", "eqref"),
+    (HeapType::I31, "i31", "i31ref"),
+    (HeapType::Struct, "struct", "structref"),
+    (HeapType::Array, "array", "arrayref"),
+    (HeapType::None, "none", "nullref"),
+    (HeapType::NoFunc, "nofunc", "nullfuncref"),
+    (HeapType::NoExtern, "noextern", "nullexternref"),
+];
+
+/// Abstract heap type named `keyword`.
+pub(crate) fn abstract_heap_type(keyword: &str) -> Option<HeapType> {
+    ABSTRACT_HEAP_TYPES.iter().find(|t| t.1 == keyword).map(|t| t.0)
+}
+
+/// Nullable reference type abbreviated as `keyword`, e.g. `anyref`.
+pub(crate) fn ref_type_shorthand(keyword: &str) -> Option<RefType> {
+    ABSTRACT_HEAP_TYPES.iter().find(|t| t.2 == keyword).map(|t| RefType::nullable(t.0))
+}
+
+pub(crate) fn heap_type_text(heap: HeapType) -> String {
+    match ABSTRACT_HEAP_TYPES.iter().find(|t| t.0 == heap) {
+        Some(t) => t.1.to_string(),
+        None => match heap {
+            HeapType::Concrete(idx) => idx.to_string(),
+            _ => unreachable!("abstract heap types are listed"),
+        },
+    }
+}
+
+/// Shorthand of nullable abstract references, `(ref null? ht)` otherwise.
+pub(crate) fn ref_type_text(ref_type: RefType) -> String {
+    match ABSTRACT_HEAP_TYPES.iter().find(|t| t.0 == ref_type.heap) {
+        Some(t) if ref_type.nullable => t.2.to_string(),
+        _ if ref_type.nullable => format!("(ref null {})", heap_type_text(ref_type.heap)),
+        _ => format!("(ref {})", heap_type_text(ref_type.heap)),
+    }
+}
+
+/// Kind of the index following the type index of a GC instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Operand {
+    Field,
+    /// Number of elements, not an index.
+    Count,
+    Data,
+    Elem,
+    Type,
+}
+
+/// Immediates of a GC instruction and how to build it from them.
+pub(crate) enum GcForm {
+    Plain(GcInstruction),
+    Type(fn(u32) -> GcInstruction),
+    TypeAnd(fn(u32, u32) -> GcInstruction, Operand),
+    Ref(fn(RefType) -> GcInstruction),
+    BrOnCast(fn(Box<BrOnCastData>) -> GcInstruction),
+}
+
+/// Form of the GC instruction named `name`.
+pub(crate) fn gc_instruction(name: &str) -> Option<GcForm> {
+    use self::GcInstruction::*;
+
+    Some(match name {
+        "struct.new" => GcForm::Type(StructNew),
+        "struct.new_default" => GcForm::Type(StructNewDefault),
+        "struct.get" => GcForm::TypeAnd(StructGet, Operand::Field),
+        "struct.get_s" => GcForm::TypeAnd(StructGetS, Operand::Field),
+        "struct.get_u" => GcForm::TypeAnd(StructGetU, Operand::Field),
+        "struct.set" => GcForm::TypeAnd(StructSet, Operand::Field),
+        "array.new" => GcForm::Type(ArrayNew),
+        "array.new_default" => GcForm::Type(ArrayNewDefault),
+        "array.new_fixed" => GcForm::TypeAnd(ArrayNewFixed, Operand::Count),
+        "array.new_data" => GcForm::TypeAnd(ArrayNewData, Operand::Data),
+        "array.new_elem" => GcForm::TypeAnd(ArrayNewElem, Operand::Elem),
+        "array.get" => GcForm::Type(ArrayGet),
+        "array.get_s" => GcForm::Type(ArrayGetS),
+        "array.get_u" => GcForm::Type(ArrayGetU),
+        "array.set" => GcForm::Type(ArraySet),
+        "array.len" => GcForm::Plain(ArrayLen),
+        "array.fill" => GcForm::Type(ArrayFill),
+        "array.copy" => GcForm::TypeAnd(ArrayCopy, Operand::Type),
+        "array.init_data" => GcForm::TypeAnd(ArrayInitData, Operand::Data),
+        "array.init_elem" => GcForm::TypeAnd(ArrayInitElem, Operand::Elem),
+        "ref.test" => GcForm::Ref(RefTest),
+        "ref.cast" => GcForm::Ref(RefCast),
+        "br_on_cast" => GcForm::BrOnCast(BrOnCast),
+        "br_on_cast_fail" => GcForm::BrOnCast(BrOnCastFail),
+        "any.convert_extern" => GcForm::Plain(AnyConvertExtern),
+        "extern.convert_any" => GcForm::Plain(ExternConvertAny),
+        "ref.i31" => GcForm::Plain(RefI31),
+        "i31.get_s" => GcForm::Plain(I31GetS),
+        "i31.get_u" => GcForm::Plain(I31GetU),
+        _ => return None,
+    })
+}
+
+/// Text name of a GC instruction.
+pub(crate) fn gc_mnemonic(instruction: &GcInstruction) -> &'static str {
+    use self::GcInstruction::*;
+
+    match *instruction {
+        StructNew(_) => "struct.new",
+        StructNewDefault(_) => "struct.new_default",
+        StructGet(..) => "struct.get",
+        StructGetS(..) => "struct.get_s",
+        StructGetU(..) => "struct.get_u",
+        StructSet(..) => "struct.set",
+        ArrayNew(_) => "array.new",
+        ArrayNewDefault(_) => "array.new_default",
+        ArrayNewFixed(..) => "array.new_fixed",
+        ArrayNewData(..) => "array.new_data",
+        ArrayNewElem(..) => "array.new_elem",
+        ArrayGet(_) => "array.get",
+        ArrayGetS(_) => "array.get_s",
+        ArrayGetU(_) => "array.get_u",
+        ArraySet(_) => "array.set",
+        ArrayLen => "array.len",
+        ArrayFill(_) => "array.fill",
+        ArrayCopy(..) => "array.copy",
+        ArrayInitData(..) => "array.init_data",
+        ArrayInitElem(..) => "array.init_elem",
+        RefTest(_) => "ref.test",
+        RefCast(_) => "ref.cast",
+        BrOnCast(_) => "br_on_cast",
+        BrOnCastFail(_) => "br_on_cast_fail",
+        AnyConvertExtern => "any.convert_extern",
+        ExternConvertAny => "extern.convert_any",
+        RefI31 => "ref.i31",
+        I31GetS => "i31.get_s",
+        I31GetU => "i31.get_u",
+    }
+}
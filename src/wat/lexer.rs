@@ -0,0 +1,319 @@
+use std::fmt;
+
+/// Position in the source text, both starting at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Pos {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Error in the text of a module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub pos: Pos,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new<M: Into<String>>(pos: Pos, message: M) -> ParseError {
+        ParseError { pos, message: message.into() }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.pos.line, self.pos.column, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    Keyword(String),
+    Id(String),
+    Number(String),
+    String(Vec<u8>),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Keyword(ref s) | Token::Number(ref s) => write!(f, "{}", s),
+            Token::Id(ref s) => write!(f, "${}", s),
+            Token::String(ref bytes) => write!(f, "{:?}", String::from_utf8_lossy(bytes)),
+        }
+    }
+}
+
+/// Atom or parenthesized list of the text format.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SExpr {
+    Atom(Token, Pos),
+    List(Vec<SExpr>, Pos),
+}
+
+impl SExpr {
+    pub fn pos(&self) -> Pos {
+        match *self {
+            SExpr::Atom(_, pos) | SExpr::List(_, pos) => pos,
+        }
+    }
+
+    /// Keyword at the head of a list.
+    pub fn head(&self) -> Option<&str> {
+        match *self {
+            SExpr::List(ref items, _) => match items.first() {
+                Some(SExpr::Atom(Token::Keyword(ref kw), _)) => Some(kw),
+                _ => None,
+            },
+            SExpr::Atom(..) => None,
+        }
+    }
+
+    pub fn keyword(&self) -> Option<&str> {
+        match *self {
+            SExpr::Atom(Token::Keyword(ref kw), _) => Some(kw),
+            _ => None,
+        }
+    }
+}
+
+fn is_idchar(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'*+-./:<=>?@\\^_`|~".contains(&c)
+}
+
+struct Lexer<'a> {
+    src: &'a [u8],
+    offset: usize,
+    pos: Pos,
+}
+
+impl<'a> Lexer<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.offset).cloned()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<u8> {
+        self.src.get(self.offset + n).cloned()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.offset += 1;
+        if c == b'\n' {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else if c & 0xc0 != 0x80 {
+            // utf-8 后续字节不算列
+            self.pos.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_trivia(&mut self) -> Result<(), ParseError> {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(b' '), _) | (Some(b'\t'), _) | (Some(b'\n'), _) | (Some(b'\r'), _) => {
+                    self.bump();
+                },
+                (Some(b';'), Some(b';')) => {
+                    while let Some(c) = self.peek() {
+                        if c == b'\n' {
+                            break;
+                        }
+                        self.bump();
+                    }
+                },
+                (Some(b'('), Some(b';')) => {
+                    let start = self.pos;
+                    self.bump();
+                    self.bump();
+                    let mut depth = 1;
+                    while depth > 0 {
+                        match (self.peek(), self.peek_at(1)) {
+                            (Some(b'('), Some(b';')) => {
+                                self.bump();
+                                self.bump();
+                                depth += 1;
+                            },
+                            (Some(b';'), Some(b')')) => {
+                                self.bump();
+                                self.bump();
+                                depth -= 1;
+                            },
+                            (Some(_), _) => {
+                                self.bump();
+                            },
+                            (None, _) => return Err(ParseError::new(start, "unterminated block comment")),
+                        }
+                    }
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>, ParseError> {
+        let start = self.pos;
+        self.bump();
+        let mut bytes = Vec::new();
+        loop {
+            let c = self.bump().ok_or_else(|| ParseError::new(start, "unterminated string"))?;
+            match c {
+                b'"' => return Ok(bytes),
+                b'\\' => {
+                    let escape_pos = self.pos;
+                    let e = self.bump().ok_or_else(|| ParseError::new(start, "unterminated string"))?;
+                    match e {
+                        b't' => bytes.push(b'\t'),
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b'"' => bytes.push(b'"'),
+                        b'\'' => bytes.push(b'\''),
+                        b'\\' => bytes.push(b'\\'),
+                        b'u' => {
+                            if self.bump() != Some(b'{') {
+                                return Err(ParseError::new(escape_pos, "malformed unicode escape"));
+                            }
+                            let mut code: u32 = 0;
+                            loop {
+                                match self.bump() {
+                                    Some(b'}') => break,
+                                    Some(h) if h.is_ascii_hexdigit() => {
+                                        code = code
+                                            .checked_mul(16)
+                                            .and_then(|v| v.checked_add((h as char).to_digit(16).unwrap()))
+                                            .ok_or_else(|| ParseError::new(escape_pos, "unicode escape out of range"))?;
+                                    },
+                                    _ => return Err(ParseError::new(escape_pos, "malformed unicode escape")),
+                                }
+                            }
+                            let ch = std::char::from_u32(code)
+                                .ok_or_else(|| ParseError::new(escape_pos, "invalid unicode scalar value"))?;
+                            let mut buf = [0u8; 4];
+                            bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                        },
+                        h if h.is_ascii_hexdigit() => {
+                            let l = self.bump().filter(|l| l.is_ascii_hexdigit())
+                                .ok_or_else(|| ParseError::new(escape_pos, "malformed hex escape"))?;
+                            let hi = (h as char).to_digit(16).unwrap();
+                            let lo = (l as char).to_digit(16).unwrap();
+                            bytes.push((hi * 16 + lo) as u8);
+                        },
+                        _ => return Err(ParseError::new(escape_pos, "unknown escape sequence")),
+                    }
+                },
+                b'\n' => return Err(ParseError::new(start, "newline in string")),
+                _ => bytes.push(c),
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<Token, ParseError> {
+        let start = self.pos;
+        let begin = self.offset;
+        while self.peek().is_some_and(is_idchar) {
+            self.bump();
+        }
+        if begin == self.offset {
+            return Err(ParseError::new(start, format!("unexpected character {:?}", self.peek().map(|c| c as char))));
+        }
+        // 只包含 ascii 字符，可以直接转换
+        let text = std::str::from_utf8(&self.src[begin..self.offset]).unwrap().to_string();
+        let bytes = text.as_bytes();
+        let is_number = match bytes[0] {
+            b'0'..=b'9' => true,
+            b'+' | b'-' => bytes.get(1).is_some_and(|c| c.is_ascii_digit())
+                || text[1..].starts_with("inf")
+                || text[1..].starts_with("nan"),
+            _ => text == "inf" || text == "nan" || text.starts_with("nan:"),
+        };
+        if let Some(id) = text.strip_prefix('$') {
+            if id.is_empty() {
+                return Err(ParseError::new(start, "empty identifier"));
+            }
+            Ok(Token::Id(id.to_string()))
+        } else if is_number {
+            Ok(Token::Number(text))
+        } else {
+            Ok(Token::Keyword(text))
+        }
+    }
+}
+
+/// Read the source text into a list of s-expressions.
+pub(crate) fn read(src: &str) -> Result<Vec<SExpr>, ParseError> {
+    let mut lexer = Lexer {
+        src: src.as_bytes(),
+        offset: 0,
+        pos: Pos { line: 1, column: 1 },
+    };
+    // 栈中保存尚未闭合的列表
+    let mut stack: Vec<(Vec<SExpr>, Pos)> = vec![(Vec::new(), lexer.pos)];
+
+    loop {
+        lexer.skip_trivia()?;
+        let pos = lexer.pos;
+        match lexer.peek() {
+            None => break,
+            Some(b'(') => {
+                lexer.bump();
+                stack.push((Vec::new(), pos));
+            },
+            Some(b')') => {
+                lexer.bump();
+                if stack.len() == 1 {
+                    return Err(ParseError::new(pos, "unbalanced `)`"));
+                }
+                let (items, start) = stack.pop().unwrap();
+                stack.last_mut().unwrap().0.push(SExpr::List(items, start));
+            },
+            Some(b'"') => {
+                let bytes = lexer.string()?;
+                stack.last_mut().unwrap().0.push(SExpr::Atom(Token::String(bytes), pos));
+            },
+            Some(_) => {
+                let token = lexer.token()?;
+                stack.last_mut().unwrap().0.push(SExpr::Atom(token, pos));
+            },
+        }
+    }
+
+    if stack.len() > 1 {
+        return Err(ParseError::new(stack.last().unwrap().1, "unclosed `(`"));
+    }
+    Ok(stack.pop().unwrap().0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read() {
+        let exprs = read("(module (; a (; nested ;) comment ;)\n  (func $f ;; line\n (i32.const -1) \"a\\41\\u{3b1}\"))").unwrap();
+        assert_eq!(exprs.len(), 1);
+        let module = match exprs[0] {
+            SExpr::List(ref items, _) => items,
+            _ => panic!("expected a list"),
+        };
+        assert_eq!(module[0].keyword(), Some("module"));
+        let func = match module[1] {
+            SExpr::List(ref items, pos) => {
+                assert_eq!(pos, Pos { line: 2, column: 3 });
+                items
+            },
+            _ => panic!("expected a list"),
+        };
+        assert_eq!(func[1], SExpr::Atom(Token::Id("f".to_string()), Pos { line: 2, column: 9 }));
+        assert_eq!(func[2].head(), Some("i32.const"));
+        match func[3] {
+            SExpr::Atom(Token::String(ref bytes), _) => assert_eq!(bytes, &"aA\u{3b1}".as_bytes().to_vec()),
+            ref other => panic!("unexpected {:?}", other),
+        }
+
+        assert_eq!(read("(module").unwrap_err().message, "unclosed `(`");
+        assert_eq!(read(")").unwrap_err().pos, Pos { line: 1, column: 1 });
+        assert!(read("\"abc").is_err());
+        assert!(read("(; abc").is_err());
+    }
+}
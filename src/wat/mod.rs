@@ -0,0 +1,258 @@
+//! WebAssembly text format.
+//!
+//! ```ignore
+//! let text = wat::print(&module);
+//! let folded = wat::Printer::new().folded(true).print(&module);
+//! let module = wat::parse("(module (func (export \"f\") (result i32) i32.const 1))")?;
+//! ```
+
+use crate::elements::ops::Instruction;
+
+mod lexer;
+mod parse;
+mod print;
+#[cfg(feature = "simd")]
+mod simd;
+#[cfg(feature = "threads")]
+mod atomics;
+#[cfg(feature = "gc")]
+mod gc;
+
+pub use self::lexer::{ParseError, Pos};
+pub use self::parse::parse;
+pub use self::print::{print, Printer};
+
+// 没有立即数的指令和它们的文本名称
+macro_rules! simple_instructions {
+    ($($variant: ident => $name: expr,)*) => {
+        /// Text name of an instruction without immediates.
+        pub(crate) fn simple_mnemonic(instruction: &Instruction) -> Option<&'static str> {
+            match *instruction {
+                $(Instruction::$variant => Some($name),)*
+                _ => None,
+            }
+        }
+
+        /// Instruction without immediates named `name`.
+        pub(crate) fn simple_instruction(name: &str) -> Option<Instruction> {
+            match name {
+                $($name => Some(Instruction::$variant),)*
+                _ => None,
+            }
+        }
+    }
+}
+
+simple_instructions! {
+    Unreachable => "unreachable",
+    Nop => "nop",
+    Else => "else",
+    End => "end",
+    Return => "return",
+    Drop => "drop",
+    Select => "select",
+
+    I32Eqz => "i32.eqz",
+    I32Eq => "i32.eq",
+    I32Ne => "i32.ne",
+    I32LtS => "i32.lt_s",
+    I32LtU => "i32.lt_u",
+    I32GtS => "i32.gt_s",
+    I32GtU => "i32.gt_u",
+    I32LeS => "i32.le_s",
+    I32LeU => "i32.le_u",
+    I32GeS => "i32.ge_s",
+    I32GeU => "i32.ge_u",
+
+    I64Eqz => "i64.eqz",
+    I64Eq => "i64.eq",
+    I64Ne => "i64.ne",
+    I64LtS => "i64.lt_s",
+    I64LtU => "i64.lt_u",
+    I64GtS => "i64.gt_s",
+    I64GtU => "i64.gt_u",
+    I64LeS => "i64.le_s",
+    I64LeU => "i64.le_u",
+    I64GeS => "i64.ge_s",
+    I64GeU => "i64.ge_u",
+
+    F32Eq => "f32.eq",
+    F32Ne => "f32.ne",
+    F32Lt => "f32.lt",
+    F32Gt => "f32.gt",
+    F32Le => "f32.le",
+    F32Ge => "f32.ge",
+
+    F64Eq => "f64.eq",
+    F64Ne => "f64.ne",
+    F64Lt => "f64.lt",
+    F64Gt => "f64.gt",
+    F64Le => "f64.le",
+    F64Ge => "f64.ge",
+
+    I32Clz => "i32.clz",
+    I32Ctz => "i32.ctz",
+    I32Popcnt => "i32.popcnt",
+    I32Add => "i32.add",
+    I32Sub => "i32.sub",
+    I32Mul => "i32.mul",
+    I32DivS => "i32.div_s",
+    I32DivU => "i32.div_u",
+    I32RemS => "i32.rem_s",
+    I32RemU => "i32.rem_u",
+    I32And => "i32.and",
+    I32Or => "i32.or",
+    I32Xor => "i32.xor",
+    I32Shl => "i32.shl",
+    I32ShrS => "i32.shr_s",
+    I32ShrU => "i32.shr_u",
+    I32Rotl => "i32.rotl",
+    I32Rotr => "i32.rotr",
+
+    I64Clz => "i64.clz",
+    I64Ctz => "i64.ctz",
+    I64Popcnt => "i64.popcnt",
+    I64Add => "i64.add",
+    I64Sub => "i64.sub",
+    I64Mul => "i64.mul",
+    I64DivS => "i64.div_s",
+    I64DivU => "i64.div_u",
+    I64RemS => "i64.rem_s",
+    I64RemU => "i64.rem_u",
+    I64And => "i64.and",
+    I64Or => "i64.or",
+    I64Xor => "i64.xor",
+    I64Shl => "i64.shl",
+    I64ShrS => "i64.shr_s",
+    I64ShrU => "i64.shr_u",
+    I64Rotl => "i64.rotl",
+    I64Rotr => "i64.rotr",
+
+    F32Abs => "f32.abs",
+    F32Neg => "f32.neg",
+    F32Ceil => "f32.ceil",
+    F32Floor => "f32.floor",
+    F32Trunc => "f32.trunc",
+    F32Nearest => "f32.nearest",
+    F32Sqrt => "f32.sqrt",
+    F32Add => "f32.add",
+    F32Sub => "f32.sub",
+    F32Mul => "f32.mul",
+    F32Div => "f32.div",
+    F32Min => "f32.min",
+    F32Max => "f32.max",
+    F32Copysign => "f32.copysign",
+
+    F64Abs => "f64.abs",
+    F64Neg => "f64.neg",
+    F64Ceil => "f64.ceil",
+    F64Floor => "f64.floor",
+    F64Trunc => "f64.trunc",
+    F64Nearest => "f64.nearest",
+    F64Sqrt => "f64.sqrt",
+    F64Add => "f64.add",
+    F64Sub => "f64.sub",
+    F64Mul => "f64.mul",
+    F64Div => "f64.div",
+    F64Min => "f64.min",
+    F64Max => "f64.max",
+    F64Copysign => "f64.copysign",
+
+    I32WrapI64 => "i32.wrap_i64",
+    I32TruncSF32 => "i32.trunc_f32_s",
+    I32TruncUF32 => "i32.trunc_f32_u",
+    I32TruncSF64 => "i32.trunc_f64_s",
+    I32TruncUF64 => "i32.trunc_f64_u",
+    I64ExtendSI32 => "i64.extend_i32_s",
+    I64ExtendUI32 => "i64.extend_i32_u",
+    I64TruncSF32 => "i64.trunc_f32_s",
+    I64TruncUF32 => "i64.trunc_f32_u",
+    I64TruncSF64 => "i64.trunc_f64_s",
+    I64TruncUF64 => "i64.trunc_f64_u",
+    F32ConvertSI32 => "f32.convert_i32_s",
+    F32ConvertUI32 => "f32.convert_i32_u",
+    F32ConvertSI64 => "f32.convert_i64_s",
+    F32ConvertUI64 => "f32.convert_i64_u",
+    F32DemoteF64 => "f32.demote_f64",
+    F64ConvertSI32 => "f64.convert_i32_s",
+    F64ConvertUI32 => "f64.convert_i32_u",
+    F64ConvertSI64 => "f64.convert_i64_s",
+    F64ConvertUI64 => "f64.convert_i64_u",
+    F64PromoteF32 => "f64.promote_f32",
+
+    I32ReinterpretF32 => "i32.reinterpret_f32",
+    I64ReinterpretF64 => "i64.reinterpret_f64",
+    F32ReinterpretI32 => "f32.reinterpret_i32",
+    F64ReinterpretI64 => "f64.reinterpret_i64",
+
+    I32Extend8S => "i32.extend8_s",
+    I32Extend16S => "i32.extend16_s",
+    I64Extend8S => "i64.extend8_s",
+    I64Extend16S => "i64.extend16_s",
+    I64Extend32S => "i64.extend32_s",
+
+    RefIsNull => "ref.is_null",
+
+    I32TruncSatSF32 => "i32.trunc_sat_f32_s",
+    I32TruncSatUF32 => "i32.trunc_sat_f32_u",
+    I32TruncSatSF64 => "i32.trunc_sat_f64_s",
+    I32TruncSatUF64 => "i32.trunc_sat_f64_u",
+    I64TruncSatSF32 => "i64.trunc_sat_f32_s",
+    I64TruncSatUF32 => "i64.trunc_sat_f32_u",
+    I64TruncSatSF64 => "i64.trunc_sat_f64_s",
+    I64TruncSatUF64 => "i64.trunc_sat_f64_u",
+}
+
+// 访存指令：文本名称和自然对齐（log2）
+macro_rules! memory_instructions {
+    ($($variant: ident => ($name: expr, $natural: expr),)*) => {
+        /// Text name, natural alignment, alignment, offset and memory index of a load or store.
+        pub(crate) fn memory_mnemonic(instruction: &Instruction) -> Option<(&'static str, u32, u32, u64, u32)> {
+            match *instruction {
+                $(Instruction::$variant(align, offset, memory) => Some(($name, $natural, align, offset, memory)),)*
+                _ => None,
+            }
+        }
+
+        /// Constructor and natural alignment of the load or store named `name`.
+        pub(crate) fn memory_instruction(name: &str) -> Option<(fn(u32, u64, u32) -> Instruction, u32)> {
+            match name {
+                $($name => Some((Instruction::$variant as fn(u32, u64, u32) -> Instruction, $natural)),)*
+                _ => None,
+            }
+        }
+    }
+}
+
+memory_instructions! {
+    I32Load => ("i32.load", 2),
+    I64Load => ("i64.load", 3),
+    F32Load => ("f32.load", 2),
+    F64Load => ("f64.load", 3),
+    I32Load8S => ("i32.load8_s", 0),
+    I32Load8U => ("i32.load8_u", 0),
+    I32Load16S => ("i32.load16_s", 1),
+    I32Load16U => ("i32.load16_u", 1),
+    I64Load8S => ("i64.load8_s", 0),
+    I64Load8U => ("i64.load8_u", 0),
+    I64Load16S => ("i64.load16_s", 1),
+    I64Load16U => ("i64.load16_u", 1),
+    I64Load32S => ("i64.load32_s", 2),
+    I64Load32U => ("i64.load32_u", 2),
+    I32Store => ("i32.store", 2),
+    I64Store => ("i64.store", 3),
+    F32Store => ("f32.store", 2),
+    F64Store => ("f64.store", 3),
+    I32Store8 => ("i32.store8", 0),
+    I32Store16 => ("i32.store16", 1),
+    I64Store8 => ("i64.store8", 0),
+    I64Store16 => ("i64.store16", 1),
+    I64Store32 => ("i64.store32", 2),
+}
+
+/// Whether `name` only contains characters allowed in a `$id`.
+pub(crate) fn is_id(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| {
+        b.is_ascii_alphanumeric() || b"!#$%&'*+-./:<=>?@\\^_`|~".contains(&b)
+    })
+}
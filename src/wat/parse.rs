@@ -0,0 +1,2375 @@
+use std::collections::HashMap;
+
+use crate::elements::module::Module;
+use crate::elements::func::{Func, FuncBody, Local};
+use crate::elements::ops::{BrTableData, Instruction, Instructions, InitExpr};
+use crate::elements::types::{BlockType, CompositeType, FunctionType, RecGroup, SubType, TableElementType, ValueType};
+use crate::elements::import_entry::{External, GlobalType, ImportEntry, ResizableLimits, TableType};
+use crate::elements::export_entry::{ExportEntry, Internal};
+use crate::elements::global_entry::GlobalEntry;
+use crate::elements::segment::{DataSegment, ElementSegment};
+use crate::elements::sections::{
+    Section, TypeSection, ImportSection, FunctionSection, TableSection, MemorySection, GlobalSection,
+    ExportSection, ElementSection, CodeSection, DataSection,
+};
+use super::lexer::{read, ParseError, Pos, SExpr, Token};
+use super::{memory_instruction, simple_instruction};
+#[cfg(feature = "exceptions")]
+use crate::elements::import_entry::TagType;
+#[cfg(feature = "exceptions")]
+use crate::elements::ops::{Catch, TryTableData};
+#[cfg(feature = "exceptions")]
+use crate::elements::sections::TagSection;
+#[cfg(feature = "simd")]
+use crate::elements::simd::SimdInstruction;
+#[cfg(feature = "threads")]
+use crate::elements::atomics::AtomicInstruction;
+#[cfg(feature = "threads")]
+use super::atomics::atomic_instruction;
+#[cfg(feature = "gc")]
+use crate::elements::gc::{BrOnCastData, GcInstruction};
+#[cfg(feature = "gc")]
+use crate::elements::types::{FieldType, HeapType, RefType, StorageType};
+#[cfg(feature = "gc")]
+use super::gc::{abstract_heap_type, gc_instruction, ref_type_shorthand, GcForm, Operand};
+#[cfg(feature = "simd")]
+use super::simd::{simd_lane_instruction, simd_lane_memory_instruction, simd_memory_instruction, simple_simd_instruction};
+
+const PAGE_SIZE: usize = 65536;
+
+/// Parse a module in the text format.
+///
+/// The text may be a `(module ...)` form or just the module fields.
+pub fn parse(src: &str) -> Result<Module, ParseError> {
+    let exprs = read(src)?;
+    let fields: &[SExpr] = match exprs.first() {
+        Some(expr @ SExpr::List(items, _)) if expr.head() == Some("module") => {
+            if let Some(extra) = exprs.get(1) {
+                return Err(ParseError::new(extra.pos(), "unexpected text after module"));
+            }
+            let mut items = Items::new(&items[1..], expr.pos());
+            // 模块名只出现在 name section 中，这里忽略
+            items.id();
+            &items.items[items.index..]
+        },
+        _ => &exprs,
+    };
+
+    let mut parser = ModuleParser::default();
+    parser.declare(fields)?;
+    for field in fields.iter() {
+        parser.field(field)?;
+    }
+    Ok(parser.finish())
+}
+
+/// Cursor over the items of a list.
+#[derive(Clone)]
+struct Items<'a> {
+    items: &'a [SExpr],
+    index: usize,
+    pos: Pos,
+}
+
+impl<'a> Items<'a> {
+    fn new(items: &'a [SExpr], pos: Pos) -> Items<'a> {
+        Items { items, index: 0, pos }
+    }
+
+    fn of(expr: &'a SExpr) -> Items<'a> {
+        match *expr {
+            SExpr::List(ref items, pos) => Items::new(&items[1.min(items.len())..], pos),
+            SExpr::Atom(_, pos) => Items::new(&[], pos),
+        }
+    }
+
+    fn peek(&self) -> Option<&'a SExpr> {
+        self.items.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<&'a SExpr> {
+        let item = self.items.get(self.index);
+        if item.is_some() {
+            self.index += 1;
+        }
+        item
+    }
+
+    fn is_empty(&self) -> bool {
+        self.index >= self.items.len()
+    }
+
+    /// Position of the next item, or of the list if there is none.
+    fn pos(&self) -> Pos {
+        self.peek().map(SExpr::pos).unwrap_or(self.pos)
+    }
+
+    fn error<M: Into<String>>(&self, message: M) -> ParseError {
+        ParseError::new(self.pos(), message)
+    }
+
+    fn peek_head(&self) -> Option<&'a str> {
+        self.peek().and_then(SExpr::head)
+    }
+
+    fn peek_keyword(&self) -> Option<&'a str> {
+        self.peek().and_then(SExpr::keyword)
+    }
+
+    fn id(&mut self) -> Option<(String, Pos)> {
+        match self.peek() {
+            Some(&SExpr::Atom(Token::Id(ref id), pos)) => {
+                self.index += 1;
+                Some((id.clone(), pos))
+            },
+            _ => None,
+        }
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>, ParseError> {
+        match self.peek() {
+            Some(SExpr::Atom(Token::String(bytes), _)) => {
+                self.index += 1;
+                Ok(bytes.clone())
+            },
+            _ => Err(self.error("expected a string")),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, ParseError> {
+        let pos = self.pos();
+        String::from_utf8(self.string()?).map_err(|_| ParseError::new(pos, "malformed UTF-8 encoding"))
+    }
+
+    fn number(&mut self) -> Result<(&'a str, Pos), ParseError> {
+        match self.peek() {
+            Some(&SExpr::Atom(Token::Number(ref text), pos)) => {
+                self.index += 1;
+                Ok((text, pos))
+            },
+            _ => Err(self.error("expected a number")),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, ParseError> {
+        let (text, pos) = self.number()?;
+        parse_u32(text).ok_or_else(|| ParseError::new(pos, format!("invalid u32 `{}`", text)))
+    }
+
+    fn u64(&mut self) -> Result<u64, ParseError> {
+        let (text, pos) = self.number()?;
+        parse_u64(text).ok_or_else(|| ParseError::new(pos, format!("invalid u64 `{}`", text)))
+    }
+
+    /// Index given either as a number or as an `$id`.
+    fn index(&mut self) -> Result<Index, ParseError> {
+        match self.peek() {
+            Some(&SExpr::Atom(Token::Id(ref id), pos)) => {
+                self.index += 1;
+                Ok(Index::Id(id.clone(), pos))
+            },
+            Some(&SExpr::Atom(Token::Number(_), _)) => Ok(Index::Num(self.u32()?)),
+            _ => Err(self.error("expected an index")),
+        }
+    }
+
+    fn peek_index(&self) -> bool {
+        matches!(self.peek(), Some(&SExpr::Atom(Token::Id(_), _)) | Some(&SExpr::Atom(Token::Number(_), _)))
+    }
+
+    /// Whether a lane load or store names its memory: an `$id`, or a number
+    /// followed by the other immediates and the lane index.
+    #[cfg(feature = "simd")]
+    fn peek_lane_memory(&self) -> bool {
+        match self.peek() {
+            Some(&SExpr::Atom(Token::Id(_), _)) => true,
+            Some(&SExpr::Atom(Token::Number(_), _)) => self.items[self.index + 1..]
+                .iter()
+                .find(|item| !item.keyword().is_some_and(|k| k.starts_with("offset=") || k.starts_with("align=")))
+                .is_some_and(|item| matches!(item, SExpr::Atom(Token::Number(_), _))),
+            _ => false,
+        }
+    }
+
+    fn finish(&self) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(item) => Err(ParseError::new(item.pos(), "unexpected token")),
+            None => Ok(()),
+        }
+    }
+}
+
+enum Index {
+    Num(u32),
+    Id(String, Pos),
+}
+
+fn strip_underscores(text: &str) -> Option<String> {
+    if text.starts_with('_') || text.ends_with('_') || text.contains("__") {
+        return None;
+    }
+    Some(text.replace('_', ""))
+}
+
+/// Unsigned integer literal, decimal or hexadecimal.
+fn parse_u64(text: &str) -> Option<u64> {
+    let text = strip_underscores(text)?;
+    if text.is_empty() {
+        return None;
+    }
+    match text.strip_prefix("0x") {
+        Some(hex) if !hex.is_empty() => u64::from_str_radix(hex, 16).ok(),
+        Some(_) => None,
+        None if text.bytes().all(|b| b.is_ascii_digit()) => text.parse().ok(),
+        None => None,
+    }
+}
+
+fn parse_u32(text: &str) -> Option<u32> {
+    parse_u64(text).and_then(|v| if v <= u32::MAX as u64 { Some(v as u32) } else { None })
+}
+
+/// Integer literal with an optional sign, as magnitude and sign.
+fn parse_signed(text: &str) -> Option<(bool, u64)> {
+    match text.as_bytes().first() {
+        Some(b'-') => parse_u64(&text[1..]).map(|v| (true, v)),
+        Some(b'+') => parse_u64(&text[1..]).map(|v| (false, v)),
+        _ => parse_u64(text).map(|v| (false, v)),
+    }
+}
+
+fn parse_i32(text: &str) -> Option<i32> {
+    match parse_signed(text)? {
+        (true, v) if v <= 1 << 31 => Some((v as u32).wrapping_neg() as i32),
+        (false, v) if v <= u32::MAX as u64 => Some(v as u32 as i32),
+        _ => None,
+    }
+}
+
+fn parse_i64(text: &str) -> Option<i64> {
+    match parse_signed(text)? {
+        (true, v) if v <= 1 << 63 => Some(v.wrapping_neg() as i64),
+        (false, v) => Some(v as i64),
+        _ => None,
+    }
+}
+
+/// Bits of an integer lane of `bits` bits, given either signed or unsigned.
+#[cfg(feature = "simd")]
+fn parse_lane(text: &str, bits: u32) -> Option<u64> {
+    let mask = u64::MAX >> (64 - bits);
+    match parse_signed(text)? {
+        (true, v) if v <= 1 << (bits - 1) => Some(v.wrapping_neg() & mask),
+        (false, v) if v <= mask => Some(v),
+        _ => None,
+    }
+}
+
+/// Bits of a float literal with `mantissa_bits` explicit mantissa bits and `exponent_bits` exponent bits.
+fn parse_float(text: &str, mantissa_bits: u32, exponent_bits: u32) -> Option<u64> {
+    let (negative, body) = match text.as_bytes().first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let sign = (negative as u64) << (mantissa_bits + exponent_bits);
+    let exponent_mask = (1u64 << exponent_bits) - 1;
+
+    if body == "inf" {
+        return Some(sign | exponent_mask << mantissa_bits);
+    }
+    if body == "nan" {
+        return Some(sign | exponent_mask << mantissa_bits | 1 << (mantissa_bits - 1));
+    }
+    if let Some(payload) = body.strip_prefix("nan:0x") {
+        let payload = parse_u64(&format!("0x{}", payload))?;
+        if payload == 0 || payload >= 1 << mantissa_bits {
+            return None;
+        }
+        return Some(sign | exponent_mask << mantissa_bits | payload);
+    }
+
+    let body = strip_underscores(body)?;
+    let magnitude = if let Some(hex) = body.strip_prefix("0x") {
+        parse_hex_float(hex, mantissa_bits, exponent_bits)?
+    } else {
+        if !body.bytes().next()?.is_ascii_digit() || body.contains("inf") || body.contains("nan") {
+            return None;
+        }
+        // 标准库的解析是正确舍入的
+        if mantissa_bits == 23 {
+            let v: f32 = body.parse().ok()?;
+            if v.is_infinite() {
+                return None;
+            }
+            v.to_bits() as u64
+        } else {
+            let v: f64 = body.parse().ok()?;
+            if v.is_infinite() {
+                return None;
+            }
+            v.to_bits()
+        }
+    };
+    Some(sign | magnitude)
+}
+
+/// Bits of a positive hexadecimal float `hex` (without `0x`), rounded to nearest even.
+fn parse_hex_float(hex: &str, mantissa_bits: u32, exponent_bits: u32) -> Option<u64> {
+    let (digits, exponent) = match hex.find(['p', 'P']) {
+        Some(i) => {
+            let exp = &hex[i + 1..];
+            let (negative, exp) = match exp.as_bytes().first() {
+                Some(b'-') => (true, &exp[1..]),
+                Some(b'+') => (false, &exp[1..]),
+                _ => (false, exp),
+            };
+            if exp.is_empty() || !exp.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let exp: i64 = exp.parse::<i64>().unwrap_or(i64::MAX / 4).min(i64::MAX / 4);
+            (&hex[..i], if negative { -exp } else { exp })
+        },
+        None => (hex, 0),
+    };
+    let (int_part, frac_part) = match digits.find('.') {
+        Some(i) => (&digits[..i], &digits[i + 1..]),
+        None => (digits, ""),
+    };
+    if int_part.is_empty() || !int_part.bytes().chain(frac_part.bytes()).all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    // 尾数保留 60 位，多余的位记为 sticky
+    let mut mantissa: u64 = 0;
+    let mut exp2: i64 = exponent;
+    let mut sticky = false;
+    for (i, c) in int_part.chars().chain(frac_part.chars()).enumerate() {
+        let d = c.to_digit(16).unwrap() as u64;
+        if mantissa >> 56 == 0 {
+            mantissa = mantissa << 4 | d;
+            if i >= int_part.len() {
+                exp2 -= 4;
+            }
+        } else {
+            sticky |= d != 0;
+            if i < int_part.len() {
+                exp2 += 4;
+            }
+        }
+    }
+    if mantissa == 0 {
+        return Some(0);
+    }
+
+    let bias = (1i64 << (exponent_bits - 1)) - 1;
+    let emin = 1 - bias;
+    let msb = 63 - mantissa.leading_zeros() as i64;
+    // 结果的量子指数：正规数由最高位决定，次正规数固定
+    let q = (msb + exp2).max(emin) - mantissa_bits as i64;
+    let shift = q - exp2;
+    let mut rounded = if shift > 0 {
+        if shift > 63 {
+            // 全部被舍去，结果为 0
+            0
+        } else {
+            let kept = mantissa >> shift;
+            let rest = mantissa & ((1u64 << shift) - 1);
+            let half = 1u64 << (shift - 1);
+            let round_up = rest > half || (rest == half && (sticky || kept & 1 == 1));
+            kept + round_up as u64
+        }
+    } else {
+        mantissa << (-shift)
+    };
+    let mut q = q;
+    if rounded >> (mantissa_bits + 1) != 0 {
+        rounded >>= 1;
+        q += 1;
+    }
+    if rounded >> mantissa_bits != 0 {
+        let biased = q + mantissa_bits as i64 + bias;
+        if biased >= (1 << exponent_bits) - 1 {
+            return None;
+        }
+        Some((biased as u64) << mantissa_bits | (rounded & ((1u64 << mantissa_bits) - 1)))
+    } else {
+        Some(rounded)
+    }
+}
+
+/// Whether `expr` is a reference type, a shorthand like `anyref` or `(ref null? heaptype)`.
+#[cfg(feature = "gc")]
+fn is_ref_type(expr: &SExpr) -> bool {
+    expr.head() == Some("ref") || expr.keyword().and_then(ref_type_shorthand).is_some()
+}
+
+/// Kinds of the index spaces.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Space {
+    Type,
+    Func,
+    Table,
+    Memory,
+    Global,
+    #[cfg(feature = "exceptions")]
+    Tag,
+    Elem,
+    Data,
+}
+
+impl Space {
+    fn of_field(head: &str) -> Option<Space> {
+        match head {
+            "type" => Some(Space::Type),
+            "func" => Some(Space::Func),
+            "table" => Some(Space::Table),
+            "memory" => Some(Space::Memory),
+            "global" => Some(Space::Global),
+            #[cfg(feature = "exceptions")]
+            "tag" => Some(Space::Tag),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Space::Type => "type",
+            Space::Func => "function",
+            Space::Table => "table",
+            Space::Memory => "memory",
+            Space::Global => "global",
+            #[cfg(feature = "exceptions")]
+            Space::Tag => "tag",
+            Space::Elem => "elem segment",
+            Space::Data => "data segment",
+        }
+    }
+}
+
+/// Names of locals and labels inside a function.
+#[derive(Default)]
+struct FuncContext {
+    locals: HashMap<String, u32>,
+    labels: Vec<Option<String>>,
+}
+
+#[derive(Default)]
+struct ModuleParser {
+    ids: HashMap<Space, HashMap<String, u32>>,
+    /// Index of the next imported and defined entity of each space, in `field` order.
+    next_import: HashMap<Space, u32>,
+    next_defined: HashMap<Space, u32>,
+
+    types: Vec<SubType>,
+    /// Number of types in each recursion group, a type outside of `(rec ...)` is a group of its own.
+    rec_groups: Vec<usize>,
+    /// Names of the struct fields of each type.
+    #[cfg(feature = "gc")]
+    fields: HashMap<u32, HashMap<String, u32>>,
+    imports: Vec<ImportEntry>,
+    functions: Vec<Func>,
+    bodies: Vec<FuncBody>,
+    tables: Vec<TableType>,
+    memories: Vec<ResizableLimits>,
+    #[cfg(feature = "exceptions")]
+    tags: Vec<TagType>,
+    globals: Vec<GlobalEntry>,
+    exports: Vec<ExportEntry>,
+    start: Option<u32>,
+    elements: Vec<ElementSegment>,
+    data: Vec<DataSegment>,
+}
+
+impl ModuleParser {
+    /// Whether a field is an import, either `(import ...)` or with an inline `(import ...)`.
+    fn imported_space(field: &SExpr) -> Option<Space> {
+        let head = field.head()?;
+        if head == "import" {
+            let mut items = Items::of(field);
+            items.next();
+            items.next();
+            return items.peek_head().and_then(Space::of_field);
+        }
+        let space = Space::of_field(head)?;
+        let mut items = Items::of(field);
+        items.id();
+        while items.peek_head() == Some("export") {
+            items.next();
+        }
+        if items.peek_head() == Some("import") { Some(space) } else { None }
+    }
+
+    /// Assign indices to all `$id`s, and collect explicit types.
+    fn declare(&mut self, fields: &[SExpr]) -> Result<(), ParseError> {
+        let mut import_counts: HashMap<Space, u32> = HashMap::new();
+        for field in fields.iter() {
+            if field.head().is_none() {
+                return Err(ParseError::new(field.pos(), "expected a module field"));
+            }
+            if let Some(space) = Self::imported_space(field) {
+                *import_counts.entry(space).or_insert(0) += 1;
+            }
+        }
+
+        let mut next_import: HashMap<Space, u32> = HashMap::new();
+        let mut next_defined = import_counts.clone();
+        for field in fields.iter() {
+            let head = field.head().unwrap();
+            #[cfg(feature = "gc")]
+            if head == "rec" {
+                let mut group = Items::of(field);
+                while let Some(item) = group.next() {
+                    let counter = next_defined.entry(Space::Type).or_insert(0);
+                    let index = *counter;
+                    *counter += 1;
+                    if let Some(id) = Items::of(item).id() {
+                        self.bind(Space::Type, id, index)?;
+                    }
+                }
+                continue;
+            }
+            let (space, mut items) = match Self::imported_space(field) {
+                Some(space) if head == "import" => {
+                    let mut items = Items::of(field);
+                    items.next();
+                    items.next();
+                    (space, Items::of(items.peek().unwrap()))
+                },
+                Some(space) => (space, Items::of(field)),
+                None => match Space::of_field(head) {
+                    Some(space) => (space, Items::of(field)),
+                    None => continue,
+                },
+            };
+            let counter = if head == "import" || Self::imported_space(field).is_some() {
+                next_import.entry(space).or_insert(0)
+            } else {
+                next_defined.entry(space).or_insert(0)
+            };
+            let index = *counter;
+            *counter += 1;
+
+            if let Some(id) = items.id() {
+                self.bind(space, id, index)?;
+            }
+        }
+
+        // 所有类型的名字都已知，类型定义可以相互引用
+        for field in fields.iter() {
+            match field.head() {
+                Some("type") => {
+                    let sub = self.type_def(field)?;
+                    self.types.push(sub);
+                    self.rec_groups.push(1);
+                },
+                #[cfg(feature = "gc")]
+                Some("rec") => {
+                    let mut group = Items::of(field);
+                    let mut len = 0;
+                    while let Some(item) = group.next() {
+                        if item.head() != Some("type") {
+                            return Err(ParseError::new(item.pos(), "expected `(type ...)`"));
+                        }
+                        let sub = self.type_def(item)?;
+                        self.types.push(sub);
+                        len += 1;
+                    }
+                    self.rec_groups.push(len);
+                },
+                _ => {},
+            }
+        }
+
+        // 段的序号按出现顺序，table 和 memory 中内联的段也占一个序号
+        let mut segments: HashMap<Space, u32> = HashMap::new();
+        for field in fields.iter() {
+            let space = match field.head() {
+                Some("elem") => Space::Elem,
+                Some("data") => Space::Data,
+                Some(head @ "table") | Some(head @ "memory") => {
+                    let (inline, space) = if head == "table" { ("elem", Space::Elem) } else { ("data", Space::Data) };
+                    let mut items = Items::of(field);
+                    while let Some(item) = items.next() {
+                        if item.head() == Some(inline) {
+                            *segments.entry(space).or_insert(0) += 1;
+                        }
+                    }
+                    continue;
+                },
+                _ => continue,
+            };
+            let counter = segments.entry(space).or_insert(0);
+            let index = *counter;
+            *counter += 1;
+            if let Some(id) = Items::of(field).id() {
+                self.bind(space, id, index)?;
+            }
+        }
+
+        self.next_defined = import_counts;
+        Ok(())
+    }
+
+    /// Give the entity `index` of `space` the name `id`.
+    fn bind(&mut self, space: Space, (id, pos): (String, Pos), index: u32) -> Result<(), ParseError> {
+        let ids = self.ids.entry(space).or_default();
+        if ids.insert(id.clone(), index).is_some() {
+            return Err(ParseError::new(pos, format!("duplicate {} ${}", space.name(), id)));
+        }
+        Ok(())
+    }
+
+    /// `(type $id? comptype)`, with the gc feature also `(type $id? (sub final? typeidx* comptype))`.
+    fn type_def(&mut self, field: &SExpr) -> Result<SubType, ParseError> {
+        let mut items = Items::of(field);
+        items.id();
+        let def = items.next().ok_or_else(|| ParseError::new(field.pos(), "expected `(func ...)`"))?;
+        items.finish()?;
+        #[cfg(feature = "gc")]
+        if def.head() == Some("sub") {
+            let mut sub = Items::of(def);
+            let is_final = sub.peek_keyword() == Some("final");
+            if is_final {
+                sub.next();
+            }
+            let mut supertypes = Vec::new();
+            while sub.peek_index() {
+                let index = sub.index()?;
+                supertypes.push(self.resolve(Space::Type, index)?);
+            }
+            let composite = sub.next().ok_or_else(|| sub.error("expected a composite type"))?;
+            sub.finish()?;
+            return Ok(SubType { is_final, supertypes, composite: self.composite_type(composite)? });
+        }
+        Ok(self.composite_type(def)?.into())
+    }
+
+    /// `(func ...)`, `(struct (field ...)*)` or `(array fieldtype)` of the type being defined.
+    fn composite_type(&mut self, def: &SExpr) -> Result<CompositeType, ParseError> {
+        let mut items = Items::of(def);
+        let composite = match def.head() {
+            Some("func") => CompositeType::Func(self.signature(&mut items)?.0),
+            #[cfg(feature = "gc")]
+            Some("struct") => {
+                let type_idx = self.types.len() as u32;
+                let mut fields = Vec::new();
+                while items.peek_head() == Some("field") {
+                    let mut field = Items::of(items.next().unwrap());
+                    if let Some((id, pos)) = field.id() {
+                        let names = self.fields.entry(type_idx).or_default();
+                        if names.insert(id.clone(), fields.len() as u32).is_some() {
+                            return Err(ParseError::new(pos, format!("duplicate field ${}", id)));
+                        }
+                        fields.push(self.field_type(&mut field)?);
+                        field.finish()?;
+                    } else {
+                        while !field.is_empty() {
+                            fields.push(self.field_type(&mut field)?);
+                        }
+                    }
+                }
+                CompositeType::Struct(fields)
+            },
+            #[cfg(feature = "gc")]
+            Some("array") => CompositeType::Array(self.field_type(&mut items)?),
+            _ => return Err(ParseError::new(def.pos(), "expected `(func ...)`")),
+        };
+        items.finish()?;
+        Ok(composite)
+    }
+
+    /// `storagetype` or `(mut storagetype)`, packed types are `i8` and `i16`.
+    #[cfg(feature = "gc")]
+    fn field_type(&self, items: &mut Items) -> Result<FieldType, ParseError> {
+        let item = items.next().ok_or_else(|| items.error("expected a field type"))?;
+        let (storage, mutable) = if item.head() == Some("mut") {
+            let mut inner = Items::of(item);
+            let t = inner.next().ok_or_else(|| inner.error("expected a field type"))?;
+            inner.finish()?;
+            (t, true)
+        } else {
+            (item, false)
+        };
+        let storage = match storage.keyword() {
+            Some("i8") => StorageType::I8,
+            Some("i16") => StorageType::I16,
+            _ => StorageType::Val(self.value_type(storage)?),
+        };
+        Ok(FieldType { storage, mutable })
+    }
+
+    fn value_type(&self, expr: &SExpr) -> Result<ValueType, ParseError> {
+        match expr.keyword() {
+            Some("i32") => Ok(ValueType::I32),
+            Some("i64") => Ok(ValueType::I64),
+            Some("f32") => Ok(ValueType::F32),
+            Some("f64") => Ok(ValueType::F64),
+            Some("funcref") => Ok(ValueType::FuncRef),
+            Some("externref") => Ok(ValueType::ExternRef),
+            #[cfg(feature = "exceptions")]
+            Some("exnref") => Ok(ValueType::ExnRef),
+            #[cfg(feature = "simd")]
+            Some("v128") => Ok(ValueType::V128),
+            #[cfg(feature = "gc")]
+            _ if is_ref_type(expr) => Ok(self.ref_type(expr)?.into()),
+            _ => Err(ParseError::new(expr.pos(), "expected a value type")),
+        }
+    }
+
+    /// `(ref null? heaptype)` or a shorthand like `anyref`.
+    #[cfg(feature = "gc")]
+    fn ref_type(&self, expr: &SExpr) -> Result<RefType, ParseError> {
+        if let Some(ref_type) = expr.keyword().and_then(ref_type_shorthand) {
+            return Ok(ref_type);
+        }
+        if expr.head() != Some("ref") {
+            return Err(ParseError::new(expr.pos(), "expected a reference type"));
+        }
+        let mut items = Items::of(expr);
+        let nullable = items.peek_keyword() == Some("null");
+        if nullable {
+            items.next();
+        }
+        let heap = self.heap_type(&mut items)?;
+        items.finish()?;
+        Ok(RefType { nullable, heap })
+    }
+
+    /// Abstract heap type like `any`, or a type index.
+    #[cfg(feature = "gc")]
+    fn heap_type(&self, items: &mut Items) -> Result<HeapType, ParseError> {
+        if let Some(heap) = items.peek_keyword().and_then(abstract_heap_type) {
+            items.next();
+            return Ok(heap);
+        }
+        if !items.peek_index() {
+            return Err(items.error("expected a heap type"));
+        }
+        let index = items.index()?;
+        Ok(HeapType::Concrete(self.resolve(Space::Type, index)?))
+    }
+
+    fn resolve(&self, space: Space, index: Index) -> Result<u32, ParseError> {
+        match index {
+            Index::Num(n) => Ok(n),
+            Index::Id(id, pos) => self.ids
+                .get(&space)
+                .and_then(|ids| ids.get(&id))
+                .cloned()
+                .ok_or_else(|| ParseError::new(pos, format!("unknown {} ${}", space.name(), id))),
+        }
+    }
+
+    /// Index of the entity defined by the current field of `space`.
+    fn next_index(&mut self, space: Space, imported: bool) -> u32 {
+        let counter = if imported {
+            self.next_import.entry(space).or_insert(0)
+        } else {
+            self.next_defined.entry(space).or_insert(0)
+        };
+        *counter += 1;
+        *counter - 1
+    }
+
+    /// `(param ...)*` and `(result ...)*`, returns the type and names of the parameters.
+    fn signature(&self, items: &mut Items) -> Result<(FunctionType, Vec<Option<String>>), ParseError> {
+        let mut ty = FunctionType { form: 0x60, params: Vec::new(), results: Vec::new() };
+        let mut names = Vec::new();
+        while items.peek_head() == Some("param") {
+            let mut param = Items::of(items.next().unwrap());
+            if let Some((id, _)) = param.id() {
+                let t = param.next().ok_or_else(|| param.error("expected a value type"))?;
+                ty.params.push(self.value_type(t)?);
+                names.push(Some(id));
+                param.finish()?;
+            } else {
+                while let Some(t) = param.next() {
+                    ty.params.push(self.value_type(t)?);
+                    names.push(None);
+                }
+            }
+        }
+        while items.peek_head() == Some("result") {
+            let mut result = Items::of(items.next().unwrap());
+            while let Some(t) = result.next() {
+                ty.results.push(self.value_type(t)?);
+            }
+        }
+        Ok((ty, names))
+    }
+
+    /// Index of a type equal to `ty`, appending it if there is none.
+    fn type_index(&mut self, ty: FunctionType) -> u32 {
+        // 只有单独成组、没有父类型的 final 函数类型和内联类型等价
+        let mut start = 0;
+        for &len in self.rec_groups.iter() {
+            if len == 1 {
+                let sub = &self.types[start];
+                let same = sub.func_type().is_some_and(|f| f.params == ty.params && f.results == ty.results);
+                if same && sub.is_final && sub.supertypes.is_empty() {
+                    return start as u32;
+                }
+            }
+            start += len;
+        }
+        self.types.push(ty.into());
+        self.rec_groups.push(1);
+        (self.types.len() - 1) as u32
+    }
+
+    /// `(type idx)? (param ...)* (result ...)*`, returns the type index and names of the parameters.
+    fn type_use(&mut self, items: &mut Items) -> Result<(u32, Vec<Option<String>>), ParseError> {
+        let pos = items.pos();
+        let explicit = if items.peek_head() == Some("type") {
+            let mut type_items = Items::of(items.next().unwrap());
+            let index = type_items.index()?;
+            type_items.finish()?;
+            Some(self.resolve(Space::Type, index)?)
+        } else {
+            None
+        };
+        let has_signature = matches!(items.peek_head(), Some("param") | Some("result"));
+        let (ty, names) = self.signature(items)?;
+
+        match explicit {
+            Some(idx) => {
+                let declared = self.types
+                    .get(idx as usize)
+                    .ok_or_else(|| ParseError::new(pos, format!("unknown type {}", idx)))?
+                    .func_type()
+                    .ok_or_else(|| ParseError::new(pos, format!("type {} is not a function type", idx)))?;
+                if has_signature && (declared.params != ty.params || declared.results != ty.results) {
+                    return Err(ParseError::new(pos, "inline function type does not match the type definition"));
+                }
+                let names = if has_signature { names } else { vec![None; declared.params.len()] };
+                Ok((idx, names))
+            },
+            None => Ok((self.type_index(ty), names)),
+        }
+    }
+
+    fn limits(items: &mut Items) -> Result<ResizableLimits, ParseError> {
+        // (memory i64 ...) 的大小可以超过 u32
+        let memory64 = cfg!(feature = "memory64") && items.peek_keyword() == Some("i64");
+        if memory64 {
+            items.next();
+        }
+        let size = |items: &mut Items| if memory64 { items.u64() } else { items.u32().map(u64::from) };
+        let initial = size(items)?;
+        let maximum = if matches!(items.peek(), Some(&SExpr::Atom(Token::Number(_), _))) {
+            Some(size(items)?)
+        } else {
+            None
+        };
+        let shared = cfg!(feature = "threads") && items.peek_keyword() == Some("shared");
+        if shared {
+            items.next();
+        }
+        Ok(ResizableLimits { initial, maximum, shared, memory64 })
+    }
+
+    fn elem_type(&self, items: &mut Items) -> Result<TableElementType, ParseError> {
+        match items.peek_keyword() {
+            Some("funcref") | Some("anyfunc") => {
+                items.next();
+                Ok(TableElementType::AnyFunc)
+            },
+            Some("externref") => {
+                items.next();
+                Ok(TableElementType::ExternRef)
+            },
+            #[cfg(feature = "exceptions")]
+            Some("exnref") => {
+                items.next();
+                Ok(TableElementType::ExnRef)
+            },
+            #[cfg(feature = "gc")]
+            _ if items.peek().is_some_and(is_ref_type) => Ok(self.ref_type(items.next().unwrap())?.into()),
+            _ => Err(items.error("expected `funcref` or `externref`")),
+        }
+    }
+
+    fn global_type(&self, items: &mut Items) -> Result<GlobalType, ParseError> {
+        let item = items.next().ok_or_else(|| items.error("expected a global type"))?;
+        if item.head() == Some("mut") {
+            let mut inner = Items::of(item);
+            let t = inner.next().ok_or_else(|| inner.error("expected a value type"))?;
+            inner.finish()?;
+            Ok(GlobalType { content_type: self.value_type(t)?, is_mutable: true })
+        } else {
+            Ok(GlobalType { content_type: self.value_type(item)?, is_mutable: false })
+        }
+    }
+
+    /// Inline `(export "name")*`, recorded for the entity `internal`.
+    fn inline_exports(&mut self, items: &mut Items, internal: Internal) -> Result<(), ParseError> {
+        while items.peek_head() == Some("export") {
+            let mut export = Items::of(items.next().unwrap());
+            let field_str = export.name()?;
+            export.finish()?;
+            self.exports.push(ExportEntry { field_str, internal });
+        }
+        Ok(())
+    }
+
+    /// Inline `(import "module" "field")`.
+    fn inline_import(items: &mut Items) -> Result<Option<(String, String)>, ParseError> {
+        if items.peek_head() != Some("import") {
+            return Ok(None);
+        }
+        let mut import = Items::of(items.next().unwrap());
+        let module = import.name()?;
+        let field = import.name()?;
+        import.finish()?;
+        Ok(Some((module, field)))
+    }
+
+    fn field(&mut self, field: &SExpr) -> Result<(), ParseError> {
+        let mut items = Items::of(field);
+        match field.head().unwrap() {
+            "type" => Ok(()),
+            #[cfg(feature = "gc")]
+            "rec" => Ok(()),
+            "import" => {
+                let module_str = items.name()?;
+                let field_str = items.name()?;
+                let desc = items.next().ok_or_else(|| items.error("expected an import description"))?;
+                items.finish()?;
+                let mut desc_items = Items::of(desc);
+                desc_items.id();
+                let external = match desc.head() {
+                    Some("func") => {
+                        self.next_index(Space::Func, true);
+                        External::Function(self.type_use(&mut desc_items)?.0)
+                    },
+                    Some("table") => {
+                        self.next_index(Space::Table, true);
+                        let limits = Self::limits(&mut desc_items)?;
+                        External::Table(TableType { elem_type: self.elem_type(&mut desc_items)?, limits })
+                    },
+                    Some("memory") => {
+                        self.next_index(Space::Memory, true);
+                        External::Memory(Self::limits(&mut desc_items)?)
+                    },
+                    Some("global") => {
+                        self.next_index(Space::Global, true);
+                        External::Global(self.global_type(&mut desc_items)?)
+                    },
+                    #[cfg(feature = "exceptions")]
+                    Some("tag") => {
+                        self.next_index(Space::Tag, true);
+                        External::Tag(TagType { type_index: self.type_use(&mut desc_items)?.0 })
+                    },
+                    _ => return Err(ParseError::new(desc.pos(), "expected an import description")),
+                };
+                desc_items.finish()?;
+                self.imports.push(ImportEntry { module_str, field_str, external });
+                Ok(())
+            },
+            "func" => self.func(&mut items),
+            "table" => self.table(&mut items),
+            "memory" => self.memory(&mut items),
+            "global" => self.global(&mut items),
+            #[cfg(feature = "exceptions")]
+            "tag" => self.tag(&mut items),
+            "export" => {
+                let field_str = items.name()?;
+                let desc = items.next().ok_or_else(|| items.error("expected an export description"))?;
+                items.finish()?;
+                let mut desc_items = Items::of(desc);
+                let space = desc.head().and_then(Space::of_field).filter(|&s| s != Space::Type)
+                    .ok_or_else(|| ParseError::new(desc.pos(), "expected an export description"))?;
+                let index = desc_items.index()?;
+                desc_items.finish()?;
+                let index = self.resolve(space, index)?;
+                let internal = match space {
+                    Space::Func => Internal::Function(index),
+                    Space::Table => Internal::Table(index),
+                    Space::Memory => Internal::Memory(index),
+                    #[cfg(feature = "exceptions")]
+                    Space::Tag => Internal::Tag(index),
+                    _ => Internal::Global(index),
+                };
+                self.exports.push(ExportEntry { field_str, internal });
+                Ok(())
+            },
+            "start" => {
+                let index = items.index()?;
+                items.finish()?;
+                if self.start.is_some() {
+                    return Err(ParseError::new(field.pos(), "multiple start functions"));
+                }
+                self.start = Some(self.resolve(Space::Func, index)?);
+                Ok(())
+            },
+            "elem" => {
+                items.id();
+                let declarative = items.peek_keyword() == Some("declare");
+                if declarative {
+                    items.next();
+                }
+                let (table, explicit) = if items.peek_head() == Some("table") {
+                    let mut table_items = Items::of(items.next().unwrap());
+                    let index = table_items.index()?;
+                    table_items.finish()?;
+                    (self.resolve(Space::Table, index)?, true)
+                } else if items.peek_index() {
+                    let index = items.index()?;
+                    (self.resolve(Space::Table, index)?, true)
+                } else {
+                    (0, false)
+                };
+                // 没有偏移表达式的是 passive 段
+                let offset = if !declarative && (explicit || matches!(items.peek(), Some(SExpr::List(..)))) {
+                    Some(self.const_expr(&mut items, "offset")?)
+                } else {
+                    None
+                };
+                let mut segment = ElementSegment::new(table, offset, Vec::new());
+                segment.declarative = declarative;
+                let typed = match items.peek_keyword() {
+                    Some("funcref") | Some("externref") => true,
+                    #[cfg(feature = "gc")]
+                    _ if items.peek().is_some_and(is_ref_type) => true,
+                    _ => false,
+                };
+                match items.peek_keyword() {
+                    _ if typed => {
+                        segment.elem_type = self.elem_type(&mut items)?;
+                        let mut exprs = Vec::new();
+                        while !items.is_empty() {
+                            exprs.push(self.const_expr(&mut items, "item")?);
+                        }
+                        segment.exprs = Some(exprs);
+                    },
+                    keyword => {
+                        if keyword == Some("func") {
+                            items.next();
+                        } else if segment.offset.is_none() {
+                            return Err(items.error("expected `func`, `funcref` or `externref`"));
+                        }
+                        while !items.is_empty() {
+                            let index = items.index()?;
+                            segment.members.push(self.resolve(Space::Func, index)?);
+                        }
+                    },
+                }
+                self.elements.push(segment);
+                Ok(())
+            },
+            "data" => {
+                items.id();
+                let (memory, explicit) = if items.peek_head() == Some("memory") {
+                    let mut memory_items = Items::of(items.next().unwrap());
+                    let index = memory_items.index()?;
+                    memory_items.finish()?;
+                    (self.resolve(Space::Memory, index)?, true)
+                } else if items.peek_index() {
+                    let index = items.index()?;
+                    (self.resolve(Space::Memory, index)?, true)
+                } else {
+                    (0, false)
+                };
+                let offset = if explicit || matches!(items.peek(), Some(SExpr::List(..))) {
+                    Some(self.const_expr(&mut items, "offset")?)
+                } else {
+                    None
+                };
+                let mut value = Vec::new();
+                while !items.is_empty() {
+                    value.extend(items.string()?);
+                }
+                self.data.push(DataSegment { index: memory, offset, value });
+                Ok(())
+            },
+            other => Err(ParseError::new(field.pos(), format!("unknown module field `{}`", other))),
+        }
+    }
+
+    /// `(offset instr*)`, `(item instr*)` or a single folded instruction.
+    fn const_expr(&mut self, items: &mut Items, keyword: &str) -> Result<InitExpr, ParseError> {
+        let message = format!("expected an {} expression", keyword);
+        let item = items.next().ok_or_else(|| items.error(message.clone()))?;
+        let mut code = Vec::new();
+        let mut ctx = FuncContext::default();
+        if item.head() == Some(keyword) {
+            let mut expr_items = Items::of(item);
+            self.instructions(&mut expr_items, &mut ctx, &mut code, &[])?;
+        } else if let SExpr::List(..) = *item {
+            self.folded(item, &mut ctx, &mut code)?;
+        } else {
+            return Err(ParseError::new(item.pos(), message));
+        }
+        code.push(Instruction::End);
+        Ok(InitExpr(code))
+    }
+
+    fn func(&mut self, items: &mut Items) -> Result<(), ParseError> {
+        items.id();
+        let imported = Self::is_inline_import(items);
+        let index = self.next_index(Space::Func, imported);
+        self.inline_exports(items, Internal::Function(index))?;
+
+        if let Some((module_str, field_str)) = Self::inline_import(items)? {
+            let (type_idx, _) = self.type_use(items)?;
+            items.finish()?;
+            self.imports.push(ImportEntry { module_str, field_str, external: External::Function(type_idx) });
+            return Ok(());
+        }
+
+        let (type_idx, param_names) = self.type_use(items)?;
+        let mut ctx = FuncContext::default();
+        for (i, name) in param_names.into_iter().enumerate() {
+            if let Some(name) = name {
+                ctx.locals.insert(name, i as u32);
+            }
+        }
+        let mut next_local = self.types[type_idx as usize].func_type().map_or(0, |f| f.params.len()) as u32;
+
+        let mut locals: Vec<Local> = Vec::new();
+        while items.peek_head() == Some("local") {
+            let mut local = Items::of(items.next().unwrap());
+            let mut push = |t: ValueType| match locals.last_mut() {
+                Some(last) if last.value_type == t => last.count += 1,
+                _ => locals.push(Local { count: 1, value_type: t }),
+            };
+            if let Some((id, pos)) = local.id() {
+                let t = local.next().ok_or_else(|| local.error("expected a value type"))?;
+                push(self.value_type(t)?);
+                if ctx.locals.insert(id.clone(), next_local).is_some() {
+                    return Err(ParseError::new(pos, format!("duplicate local ${}", id)));
+                }
+                next_local += 1;
+                local.finish()?;
+            } else {
+                while let Some(t) = local.next() {
+                    push(self.value_type(t)?);
+                    next_local += 1;
+                }
+            }
+        }
+
+        let mut code = Vec::new();
+        self.instructions(items, &mut ctx, &mut code, &[])?;
+        code.push(Instruction::End);
+
+        self.functions.push(Func(type_idx));
+        self.bodies.push(FuncBody { locals, instructions: Instructions(code) });
+        Ok(())
+    }
+
+    fn is_inline_import(items: &Items) -> bool {
+        let mut probe = items.clone();
+        while probe.peek_head() == Some("export") {
+            probe.next();
+        }
+        probe.peek_head() == Some("import")
+    }
+
+    fn table(&mut self, items: &mut Items) -> Result<(), ParseError> {
+        items.id();
+        let imported = Self::is_inline_import(items);
+        let index = self.next_index(Space::Table, imported);
+        self.inline_exports(items, Internal::Table(index))?;
+
+        if let Some((module_str, field_str)) = Self::inline_import(items)? {
+            let limits = Self::limits(items)?;
+            let elem_type = self.elem_type(items)?;
+            items.finish()?;
+            self.imports.push(ImportEntry { module_str, field_str, external: External::Table(TableType { elem_type, limits }) });
+            return Ok(());
+        }
+
+        if items.peek_keyword().is_some() {
+            // (table funcref (elem ...)) 的缩写形式
+            let elem_type = self.elem_type(items)?;
+            let elem = items.next().filter(|e| e.head() == Some("elem"))
+                .ok_or_else(|| items.error("expected `(elem ...)`"))?;
+            items.finish()?;
+            let mut elem_items = Items::of(elem);
+            let mut members = Vec::new();
+            while !elem_items.is_empty() {
+                let idx = elem_items.index()?;
+                members.push(self.resolve(Space::Func, idx)?);
+            }
+            let size = members.len() as u64;
+            let limits = ResizableLimits { initial: size, maximum: Some(size), shared: false, memory64: false };
+            self.tables.push(TableType { elem_type, limits });
+            self.elements.push(ElementSegment::new(
+                index,
+                Some(InitExpr(vec![Instruction::I32Const(0), Instruction::End])),
+                members,
+            ));
+            return Ok(());
+        }
+
+        let limits = Self::limits(items)?;
+        let elem_type = self.elem_type(items)?;
+        items.finish()?;
+        self.tables.push(TableType { elem_type, limits });
+        Ok(())
+    }
+
+    fn memory(&mut self, items: &mut Items) -> Result<(), ParseError> {
+        items.id();
+        let imported = Self::is_inline_import(items);
+        let index = self.next_index(Space::Memory, imported);
+        self.inline_exports(items, Internal::Memory(index))?;
+
+        if let Some((module_str, field_str)) = Self::inline_import(items)? {
+            let limits = Self::limits(items)?;
+            items.finish()?;
+            self.imports.push(ImportEntry { module_str, field_str, external: External::Memory(limits) });
+            return Ok(());
+        }
+
+        if items.peek_head() == Some("data") {
+            // (memory (data "...")) 的缩写形式
+            let mut data_items = Items::of(items.next().unwrap());
+            items.finish()?;
+            let mut value = Vec::new();
+            while !data_items.is_empty() {
+                value.extend(data_items.string()?);
+            }
+            let pages = value.len().div_ceil(PAGE_SIZE) as u64;
+            self.memories.push(ResizableLimits { initial: pages, maximum: Some(pages), shared: false, memory64: false });
+            self.data.push(DataSegment {
+                index,
+                offset: Some(InitExpr(vec![Instruction::I32Const(0), Instruction::End])),
+                value,
+            });
+            return Ok(());
+        }
+
+        let limits = Self::limits(items)?;
+        items.finish()?;
+        self.memories.push(limits);
+        Ok(())
+    }
+
+    #[cfg(feature = "exceptions")]
+    fn tag(&mut self, items: &mut Items) -> Result<(), ParseError> {
+        items.id();
+        let imported = Self::is_inline_import(items);
+        let index = self.next_index(Space::Tag, imported);
+        self.inline_exports(items, Internal::Tag(index))?;
+
+        let import = Self::inline_import(items)?;
+        let tag = TagType { type_index: self.type_use(items)?.0 };
+        items.finish()?;
+        match import {
+            Some((module_str, field_str)) => self.imports.push(ImportEntry { module_str, field_str, external: External::Tag(tag) }),
+            None => self.tags.push(tag),
+        }
+        Ok(())
+    }
+
+    fn global(&mut self, items: &mut Items) -> Result<(), ParseError> {
+        items.id();
+        let imported = Self::is_inline_import(items);
+        let index = self.next_index(Space::Global, imported);
+        self.inline_exports(items, Internal::Global(index))?;
+
+        if let Some((module_str, field_str)) = Self::inline_import(items)? {
+            let global_type = self.global_type(items)?;
+            items.finish()?;
+            self.imports.push(ImportEntry { module_str, field_str, external: External::Global(global_type) });
+            return Ok(());
+        }
+
+        let global_type = self.global_type(items)?;
+        let mut code = Vec::new();
+        self.instructions(items, &mut FuncContext::default(), &mut code, &[])?;
+        code.push(Instruction::End);
+        self.globals.push(GlobalEntry { global_type, init_expr: InitExpr(code) });
+        Ok(())
+    }
+
+    /// Parse instructions until one of `terminators`, which is left in `items`.
+    fn instructions(
+        &mut self,
+        items: &mut Items,
+        ctx: &mut FuncContext,
+        out: &mut Vec<Instruction>,
+        terminators: &[&str],
+    ) -> Result<(), ParseError> {
+        while let Some(item) = items.peek() {
+            match *item {
+                SExpr::List(..) => {
+                    items.next();
+                    self.folded(item, ctx, out)?;
+                },
+                SExpr::Atom(Token::Keyword(ref kw), _) => {
+                    if terminators.contains(&&kw[..]) {
+                        return Ok(());
+                    }
+                    self.plain(items, ctx, out)?;
+                },
+                _ => return Err(ParseError::new(item.pos(), "expected an instruction")),
+            }
+        }
+        Ok(())
+    }
+
+    /// `$label? blocktype`
+    fn block_header(&mut self, items: &mut Items, ctx: &mut FuncContext) -> Result<BlockType, ParseError> {
+        let label = items.id().map(|(id, _)| id);
+        let mut probe = items.clone();
+        let (ty, _) = self.signature(&mut probe)?;
+        let block_type = if items.peek_head() != Some("type") && ty.params.is_empty() && ty.results.len() <= 1 {
+            *items = probe;
+            match ty.results.first() {
+                Some(&t) => BlockType::Value(t),
+                None => BlockType::NoResult,
+            }
+        } else {
+            self.block_type_use(items)?
+        };
+        ctx.labels.push(label);
+        Ok(block_type)
+    }
+
+    /// `$label? blocktype catch*` of a `try_table`.
+    #[cfg(feature = "exceptions")]
+    fn try_table_header(&mut self, items: &mut Items, ctx: &mut FuncContext) -> Result<Instruction, ParseError> {
+        let block_type = self.block_header(items, ctx)?;
+        // catch 的标签在 try_table 之外解析
+        let label = ctx.labels.pop().unwrap();
+        let mut catches = Vec::new();
+        while let Some(head) = items.peek_head() {
+            let mut catch_items = match head {
+                "catch" | "catch_ref" | "catch_all" | "catch_all_ref" => Items::of(items.next().unwrap()),
+                _ => break,
+            };
+            let tag = if head == "catch" || head == "catch_ref" {
+                let index = catch_items.index()?;
+                self.resolve(Space::Tag, index)?
+            } else {
+                0
+            };
+            let label = Self::label(&mut catch_items, ctx)?;
+            catch_items.finish()?;
+            catches.push(match head {
+                "catch" => Catch::One { tag, label },
+                "catch_ref" => Catch::OneRef { tag, label },
+                "catch_all" => Catch::All { label },
+                _ => Catch::AllRef { label },
+            });
+        }
+        ctx.labels.push(label);
+        Ok(Instruction::TryTable(Box::new(TryTableData { block_type, catches: catches.into_boxed_slice() })))
+    }
+
+    /// Block type given by a type use, with parameters or several results.
+    #[cfg(feature = "multi_value")]
+    fn block_type_use(&mut self, items: &mut Items) -> Result<BlockType, ParseError> {
+        Ok(BlockType::TypeIndex(self.type_use(items)?.0))
+    }
+
+    #[cfg(not(feature = "multi_value"))]
+    fn block_type_use(&mut self, items: &mut Items) -> Result<BlockType, ParseError> {
+        Err(items.error("block parameters, multiple results and type uses require the multi_value feature"))
+    }
+
+    /// Optional label after `end` or `else`, which must match the block label.
+    fn end_label(items: &mut Items, ctx: &FuncContext) -> Result<(), ParseError> {
+        if let Some((id, pos)) = items.id() {
+            if ctx.labels.last().and_then(|l| l.as_ref()) != Some(&id) {
+                return Err(ParseError::new(pos, format!("mismatching label ${}", id)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Flat instruction starting at the current item, blocks include everything up to `end`.
+    fn plain(&mut self, items: &mut Items, ctx: &mut FuncContext, out: &mut Vec<Instruction>) -> Result<(), ParseError> {
+        let pos = items.pos();
+        let keyword = items.peek_keyword().unwrap();
+        match keyword {
+            "block" | "loop" | "if" => {
+                items.next();
+                let block_type = self.block_header(items, ctx)?;
+                out.push(match keyword {
+                    "block" => Instruction::Block(block_type),
+                    "loop" => Instruction::Loop(block_type),
+                    _ => Instruction::If(block_type),
+                });
+                let terminators: &[&str] = if keyword == "if" { &["else", "end"] } else { &["end"] };
+                self.instructions(items, ctx, out, terminators)?;
+                if keyword == "if" && items.peek_keyword() == Some("else") {
+                    items.next();
+                    Self::end_label(items, ctx)?;
+                    out.push(Instruction::Else);
+                    self.instructions(items, ctx, out, &["end"])?;
+                }
+                if items.peek_keyword() != Some("end") {
+                    return Err(ParseError::new(pos, format!("missing `end` of `{}`", keyword)));
+                }
+                items.next();
+                Self::end_label(items, ctx)?;
+                ctx.labels.pop();
+                out.push(Instruction::End);
+                Ok(())
+            },
+            #[cfg(feature = "exceptions")]
+            "try_table" => {
+                items.next();
+                let instruction = self.try_table_header(items, ctx)?;
+                out.push(instruction);
+                self.instructions(items, ctx, out, &["end"])?;
+                if items.peek_keyword() != Some("end") {
+                    return Err(ParseError::new(pos, "missing `end` of `try_table`"));
+                }
+                items.next();
+                Self::end_label(items, ctx)?;
+                ctx.labels.pop();
+                out.push(Instruction::End);
+                Ok(())
+            },
+            "end" | "else" | "then" => Err(ParseError::new(pos, format!("unexpected `{}`", keyword))),
+            _ => {
+                items.next();
+                let instruction = self.operator(keyword, pos, items, ctx)?;
+                out.push(instruction);
+                Ok(())
+            },
+        }
+    }
+
+    /// Folded instruction `(op immediates* operands*)`.
+    fn folded(&mut self, expr: &SExpr, ctx: &mut FuncContext, out: &mut Vec<Instruction>) -> Result<(), ParseError> {
+        let pos = expr.pos();
+        let keyword = expr.head().ok_or_else(|| ParseError::new(pos, "expected an instruction"))?;
+        let mut items = Items::of(expr);
+        match keyword {
+            "block" | "loop" => {
+                let block_type = self.block_header(&mut items, ctx)?;
+                out.push(if keyword == "block" { Instruction::Block(block_type) } else { Instruction::Loop(block_type) });
+                self.instructions(&mut items, ctx, out, &[])?;
+                ctx.labels.pop();
+                out.push(Instruction::End);
+            },
+            #[cfg(feature = "exceptions")]
+            "try_table" => {
+                let instruction = self.try_table_header(&mut items, ctx)?;
+                out.push(instruction);
+                self.instructions(&mut items, ctx, out, &[])?;
+                ctx.labels.pop();
+                out.push(Instruction::End);
+            },
+            "if" => {
+                let block_type = self.block_header(&mut items, ctx)?;
+                // 条件在 if 的标签之外求值
+                let label = ctx.labels.pop().unwrap();
+                while let Some(item) = items.peek() {
+                    if matches!(item.head(), Some("then") | Some("else")) {
+                        break;
+                    }
+                    items.next();
+                    self.folded(item, ctx, out)?;
+                }
+                ctx.labels.push(label);
+                out.push(Instruction::If(block_type));
+
+                let then = items.next().filter(|t| t.head() == Some("then"))
+                    .ok_or_else(|| ParseError::new(pos, "expected `(then ...)`"))?;
+                self.instructions(&mut Items::of(then), ctx, out, &[])?;
+                if let Some(else_expr) = items.next() {
+                    if else_expr.head() != Some("else") {
+                        return Err(ParseError::new(else_expr.pos(), "expected `(else ...)`"));
+                    }
+                    out.push(Instruction::Else);
+                    self.instructions(&mut Items::of(else_expr), ctx, out, &[])?;
+                }
+                items.finish()?;
+                ctx.labels.pop();
+                out.push(Instruction::End);
+            },
+            _ => {
+                let instruction = self.operator(keyword, pos, &mut items, ctx)?;
+                self.instructions(&mut items, ctx, out, &[])?;
+                out.push(instruction);
+            },
+        }
+        Ok(())
+    }
+
+    fn label(items: &mut Items, ctx: &FuncContext) -> Result<u32, ParseError> {
+        match items.index()? {
+            Index::Num(depth) => Ok(depth),
+            Index::Id(id, pos) => ctx.labels
+                .iter()
+                .rev()
+                .position(|l| l.as_ref() == Some(&id))
+                .map(|depth| depth as u32)
+                .ok_or_else(|| ParseError::new(pos, format!("unknown label ${}", id))),
+        }
+    }
+
+    fn local(items: &mut Items, ctx: &FuncContext) -> Result<u32, ParseError> {
+        match items.index()? {
+            Index::Num(idx) => Ok(idx),
+            Index::Id(id, pos) => ctx.locals
+                .get(&id)
+                .cloned()
+                .ok_or_else(|| ParseError::new(pos, format!("unknown local ${}", id))),
+        }
+    }
+
+    /// Optional table index of a table instruction, 0 if omitted.
+    fn table_index(&self, items: &mut Items) -> Result<u32, ParseError> {
+        if items.peek_index() {
+            let index = items.index()?;
+            self.resolve(Space::Table, index)
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Optional memory index of a memory instruction, 0 if omitted.
+    fn memory_index(&self, items: &mut Items) -> Result<u32, ParseError> {
+        if items.peek_index() {
+            let index = items.index()?;
+            self.resolve(Space::Memory, index)
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Optional memory index, `offset=` and `align=` of a load or store, the alignment is returned as log2.
+    fn memarg(&self, items: &mut Items, natural: u32) -> Result<(u32, u64, u32), ParseError> {
+        let memory = self.memory_index(items)?;
+        let (align, offset) = Self::offset_align(items, natural)?;
+        Ok((align, offset, memory))
+    }
+
+    /// Optional `offset=` and `align=` of a load or store, the alignment is returned as log2.
+    fn offset_align(items: &mut Items, natural: u32) -> Result<(u32, u64), ParseError> {
+        let mut offset = 0;
+        let mut align = natural;
+        if let Some(value) = items.peek_keyword().and_then(|k| k.strip_prefix("offset=")) {
+            offset = parse_u64(value).ok_or_else(|| items.error("invalid offset"))?;
+            items.next();
+        }
+        if let Some(value) = items.peek_keyword().and_then(|k| k.strip_prefix("align=")) {
+            let bytes = parse_u32(value).filter(|v| v.is_power_of_two())
+                .ok_or_else(|| items.error("alignment must be a power of two"))?;
+            align = bytes.trailing_zeros();
+            items.next();
+        }
+        Ok((align, offset))
+    }
+
+    /// SIMD instruction named `keyword`, `None` if it is not one.
+    #[cfg(feature = "simd")]
+    fn simd_operator(&self, keyword: &str, items: &mut Items) -> Result<Option<SimdInstruction>, ParseError> {
+        let lane = |items: &mut Items| -> Result<u8, ParseError> {
+            let (text, pos) = items.number()?;
+            parse_u32(text).filter(|&v| v <= u8::MAX as u32).map(|v| v as u8)
+                .ok_or_else(|| ParseError::new(pos, format!("invalid lane index `{}`", text)))
+        };
+
+        if let Some(instruction) = simple_simd_instruction(keyword) {
+            return Ok(Some(instruction));
+        }
+        if let Some((constructor, natural)) = simd_memory_instruction(keyword) {
+            let (align, offset, memory) = self.memarg(items, natural)?;
+            return Ok(Some(constructor(align, offset, memory)));
+        }
+        if let Some((constructor, natural)) = simd_lane_memory_instruction(keyword) {
+            // 只有一个数字时它是 lane，内存索引省略
+            let memory = if items.peek_lane_memory() { self.memory_index(items)? } else { 0 };
+            let (align, offset) = Self::offset_align(items, natural)?;
+            return Ok(Some(constructor(align, offset, memory, lane(items)?)));
+        }
+        if let Some(constructor) = simd_lane_instruction(keyword) {
+            return Ok(Some(constructor(lane(items)?)));
+        }
+
+        let instruction = match keyword {
+            "v128.const" => {
+                let shape = items.next().and_then(SExpr::keyword);
+                let (lanes, bits) = match shape {
+                    Some("i8x16") => (16, 8),
+                    Some("i16x8") => (8, 16),
+                    Some("i32x4") | Some("f32x4") => (4, 32),
+                    Some("i64x2") | Some("f64x2") => (2, 64),
+                    _ => return Err(items.error("expected a vector shape")),
+                };
+                let mut bytes = [0u8; 16];
+                for chunk in bytes.chunks_mut(16 / lanes) {
+                    let (text, pos) = items.number()?;
+                    let value = match shape {
+                        Some("f32x4") => parse_float(text, 23, 8),
+                        Some("f64x2") => parse_float(text, 52, 11),
+                        _ => parse_lane(text, bits),
+                    };
+                    let value = value.ok_or_else(|| ParseError::new(pos, format!("invalid lane `{}`", text)))?;
+                    chunk.copy_from_slice(&value.to_le_bytes()[..16 / lanes]);
+                }
+                SimdInstruction::V128Const(Box::new(bytes))
+            },
+            "i8x16.shuffle" => {
+                let mut lanes = [0u8; 16];
+                for slot in lanes.iter_mut() {
+                    *slot = lane(items)?;
+                }
+                SimdInstruction::I8x16Shuffle(Box::new(lanes))
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(instruction))
+    }
+
+    /// Non-block instruction and its immediates.
+    fn operator(&mut self, keyword: &str, pos: Pos, items: &mut Items, ctx: &mut FuncContext) -> Result<Instruction, ParseError> {
+        if keyword == "select" && items.peek_head() == Some("result") {
+            let mut result = Items::of(items.next().unwrap());
+            let t = result.next().ok_or_else(|| result.error("expected a value type"))?;
+            let t = self.value_type(t)?;
+            result.finish()?;
+            return Ok(Instruction::TypedSelect(t));
+        }
+        if let Some(instruction) = simple_instruction(keyword) {
+            return Ok(instruction);
+        }
+        if let Some((constructor, natural)) = memory_instruction(keyword) {
+            let (align, offset, memory) = self.memarg(items, natural)?;
+            return Ok(constructor(align, offset, memory));
+        }
+        #[cfg(feature = "simd")]
+        if let Some(instruction) = self.simd_operator(keyword, items)? {
+            return Ok(Instruction::Simd(instruction));
+        }
+        #[cfg(feature = "threads")]
+        if keyword == "atomic.fence" {
+            return Ok(Instruction::Atomic(AtomicInstruction::AtomicFence));
+        }
+        #[cfg(feature = "threads")]
+        if let Some((constructor, natural)) = atomic_instruction(keyword) {
+            let (align, offset, memory) = self.memarg(items, natural)?;
+            return Ok(Instruction::Atomic(constructor(align, offset, memory)));
+        }
+        #[cfg(feature = "gc")]
+        if let Some(form) = gc_instruction(keyword) {
+            return Ok(Instruction::Gc(self.gc_operator(form, items, ctx)?));
+        }
+
+        let int = |items: &mut Items| -> Result<(String, Pos), ParseError> {
+            let (text, pos) = items.number()?;
+            Ok((text.to_string(), pos))
+        };
+        let instruction = match keyword {
+            "br" => Instruction::Br(Self::label(items, ctx)?),
+            "br_if" => Instruction::BrIf(Self::label(items, ctx)?),
+            "br_table" => {
+                let mut table = vec![Self::label(items, ctx)?];
+                while items.peek_index() {
+                    table.push(Self::label(items, ctx)?);
+                }
+                let default = table.pop().unwrap();
+                Instruction::BrTable(Box::new(BrTableData { table: table.into_boxed_slice(), default }))
+            },
+            "call" => {
+                let index = items.index()?;
+                Instruction::Call(self.resolve(Space::Func, index)?)
+            },
+            "call_indirect" => {
+                let table = self.table_index(items)?;
+                let (type_idx, _) = self.type_use(items)?;
+                Instruction::CallIndirect(type_idx, table)
+            },
+            #[cfg(feature = "exceptions")]
+            "throw" => {
+                let index = items.index()?;
+                Instruction::Throw(self.resolve(Space::Tag, index)?)
+            },
+            #[cfg(feature = "exceptions")]
+            "throw_ref" => Instruction::ThrowRef,
+            #[cfg(feature = "tail_call")]
+            "return_call" => {
+                let index = items.index()?;
+                Instruction::ReturnCall(self.resolve(Space::Func, index)?)
+            },
+            #[cfg(feature = "tail_call")]
+            "return_call_indirect" => {
+                let table = self.table_index(items)?;
+                let (type_idx, _) = self.type_use(items)?;
+                Instruction::ReturnCallIndirect(type_idx, table)
+            },
+            #[cfg(feature = "gc")]
+            "call_ref" => {
+                let index = items.index()?;
+                Instruction::CallRef(self.resolve(Space::Type, index)?)
+            },
+            #[cfg(all(feature = "gc", feature = "tail_call"))]
+            "return_call_ref" => {
+                let index = items.index()?;
+                Instruction::ReturnCallRef(self.resolve(Space::Type, index)?)
+            },
+            #[cfg(feature = "gc")]
+            "ref.eq" => Instruction::RefEq,
+            #[cfg(feature = "gc")]
+            "ref.as_non_null" => Instruction::RefAsNonNull,
+            #[cfg(feature = "gc")]
+            "br_on_null" => Instruction::BrOnNull(Self::label(items, ctx)?),
+            #[cfg(feature = "gc")]
+            "br_on_non_null" => Instruction::BrOnNonNull(Self::label(items, ctx)?),
+            "local.get" | "get_local" => Instruction::GetLocal(Self::local(items, ctx)?),
+            "local.set" | "set_local" => Instruction::SetLocal(Self::local(items, ctx)?),
+            "local.tee" | "tee_local" => Instruction::TeeLocal(Self::local(items, ctx)?),
+            "global.get" | "get_global" => {
+                let index = items.index()?;
+                Instruction::GetGlobal(self.resolve(Space::Global, index)?)
+            },
+            "global.set" | "set_global" => {
+                let index = items.index()?;
+                Instruction::SetGlobal(self.resolve(Space::Global, index)?)
+            },
+            "memory.size" | "current_memory" => Instruction::CurrentMemory(self.memory_index(items)?),
+            "memory.grow" | "grow_memory" => Instruction::GrowMemory(self.memory_index(items)?),
+            "memory.init" => {
+                // 给出两个索引时第一个是内存
+                let first = items.index()?;
+                if items.peek_index() {
+                    let memory = self.resolve(Space::Memory, first)?;
+                    let index = items.index()?;
+                    Instruction::MemoryInit(self.resolve(Space::Data, index)?, memory)
+                } else {
+                    Instruction::MemoryInit(self.resolve(Space::Data, first)?, 0)
+                }
+            },
+            "data.drop" => {
+                let index = items.index()?;
+                Instruction::DataDrop(self.resolve(Space::Data, index)?)
+            },
+            "memory.copy" => {
+                if items.peek_index() {
+                    let dst = self.memory_index(items)?;
+                    Instruction::MemoryCopy(dst, self.memory_index(items)?)
+                } else {
+                    Instruction::MemoryCopy(0, 0)
+                }
+            },
+            "memory.fill" => Instruction::MemoryFill(self.memory_index(items)?),
+            "table.init" => {
+                // 给出两个索引时第一个是表
+                let first = items.index()?;
+                if items.peek_index() {
+                    let table = self.resolve(Space::Table, first)?;
+                    let index = items.index()?;
+                    Instruction::TableInit(self.resolve(Space::Elem, index)?, table)
+                } else {
+                    Instruction::TableInit(self.resolve(Space::Elem, first)?, 0)
+                }
+            },
+            "elem.drop" => {
+                let index = items.index()?;
+                Instruction::ElemDrop(self.resolve(Space::Elem, index)?)
+            },
+            "table.copy" => {
+                if items.peek_index() {
+                    let dst = items.index()?;
+                    let src = items.index()?;
+                    Instruction::TableCopy(self.resolve(Space::Table, dst)?, self.resolve(Space::Table, src)?)
+                } else {
+                    Instruction::TableCopy(0, 0)
+                }
+            },
+            #[cfg(feature = "gc")]
+            "ref.null" if items.peek_keyword() != Some("exn") => {
+                Instruction::RefNull(TableElementType::null_of(self.heap_type(items)?))
+            },
+            "ref.null" => match items.next().and_then(SExpr::keyword) {
+                Some("func") => Instruction::RefNull(TableElementType::AnyFunc),
+                Some("extern") => Instruction::RefNull(TableElementType::ExternRef),
+                #[cfg(feature = "exceptions")]
+                Some("exn") => Instruction::RefNull(TableElementType::ExnRef),
+                _ => return Err(ParseError::new(pos, "expected a heap type")),
+            },
+            "ref.func" => {
+                let index = items.index()?;
+                Instruction::RefFunc(self.resolve(Space::Func, index)?)
+            },
+            "table.get" => Instruction::TableGet(self.table_index(items)?),
+            "table.set" => Instruction::TableSet(self.table_index(items)?),
+            "table.size" => Instruction::TableSize(self.table_index(items)?),
+            "table.grow" => Instruction::TableGrow(self.table_index(items)?),
+            "table.fill" => Instruction::TableFill(self.table_index(items)?),
+            "i32.const" => {
+                let (text, pos) = int(items)?;
+                Instruction::I32Const(parse_i32(&text).ok_or_else(|| ParseError::new(pos, format!("invalid i32 `{}`", text)))?)
+            },
+            "i64.const" => {
+                let (text, pos) = int(items)?;
+                Instruction::I64Const(parse_i64(&text).ok_or_else(|| ParseError::new(pos, format!("invalid i64 `{}`", text)))?)
+            },
+            "f32.const" => {
+                let (text, pos) = int(items)?;
+                let bits = parse_float(&text, 23, 8).ok_or_else(|| ParseError::new(pos, format!("invalid f32 `{}`", text)))?;
+                Instruction::F32Const(bits as u32)
+            },
+            "f64.const" => {
+                let (text, pos) = int(items)?;
+                let bits = parse_float(&text, 52, 11).ok_or_else(|| ParseError::new(pos, format!("invalid f64 `{}`", text)))?;
+                Instruction::F64Const(bits)
+            },
+            _ => return Err(ParseError::new(pos, format!("unknown instruction `{}`", keyword))),
+        };
+        Ok(instruction)
+    }
+
+    /// GC instruction of the given form and its immediates.
+    #[cfg(feature = "gc")]
+    fn gc_operator(&self, form: GcForm, items: &mut Items, ctx: &FuncContext) -> Result<GcInstruction, ParseError> {
+        let index = |items: &mut Items, space: Space| -> Result<u32, ParseError> {
+            let index = items.index()?;
+            self.resolve(space, index)
+        };
+        let ref_type = |items: &mut Items| -> Result<RefType, ParseError> {
+            let item = items.next().ok_or_else(|| items.error("expected a reference type"))?;
+            self.ref_type(item)
+        };
+
+        let instruction = match form {
+            GcForm::Plain(instruction) => instruction,
+            GcForm::Type(constructor) => constructor(index(items, Space::Type)?),
+            GcForm::TypeAnd(constructor, operand) => {
+                let type_idx = index(items, Space::Type)?;
+                let second = match operand {
+                    Operand::Field => self.field_index(type_idx, items)?,
+                    Operand::Count => items.u32()?,
+                    Operand::Data => index(items, Space::Data)?,
+                    Operand::Elem => index(items, Space::Elem)?,
+                    Operand::Type => index(items, Space::Type)?,
+                };
+                constructor(type_idx, second)
+            },
+            GcForm::Ref(constructor) => constructor(ref_type(items)?),
+            GcForm::BrOnCast(constructor) => {
+                let label = Self::label(items, ctx)?;
+                let from = ref_type(items)?;
+                let to = ref_type(items)?;
+                constructor(Box::new(BrOnCastData { label, from, to }))
+            },
+        };
+        Ok(instruction)
+    }
+
+    /// Field of the struct type `type_idx`, given either as a number or as an `$id`.
+    #[cfg(feature = "gc")]
+    fn field_index(&self, type_idx: u32, items: &mut Items) -> Result<u32, ParseError> {
+        match items.index()? {
+            Index::Num(idx) => Ok(idx),
+            Index::Id(id, pos) => self.fields
+                .get(&type_idx)
+                .and_then(|fields| fields.get(&id))
+                .cloned()
+                .ok_or_else(|| ParseError::new(pos, format!("unknown field ${}", id))),
+        }
+    }
+
+    fn finish(self) -> Module {
+        let mut sections = Vec::new();
+        if !self.rec_groups.is_empty() {
+            let mut types = self.types.into_iter();
+            let groups = self.rec_groups.iter().map(|&len| RecGroup(types.by_ref().take(len).collect())).collect();
+            sections.push(Section::Type(TypeSection(groups)));
+        }
+        if !self.imports.is_empty() {
+            sections.push(Section::Import(ImportSection(self.imports)));
+        }
+        if !self.functions.is_empty() {
+            sections.push(Section::Function(FunctionSection(self.functions)));
+        }
+        if !self.tables.is_empty() {
+            sections.push(Section::Table(TableSection(self.tables)));
+        }
+        if !self.memories.is_empty() {
+            sections.push(Section::Memory(MemorySection(self.memories)));
+        }
+        #[cfg(feature = "exceptions")]
+        if !self.tags.is_empty() {
+            sections.push(Section::Tag(TagSection(self.tags)));
+        }
+        if !self.globals.is_empty() {
+            sections.push(Section::Global(GlobalSection(self.globals)));
+        }
+        if !self.exports.is_empty() {
+            sections.push(Section::Export(ExportSection(self.exports)));
+        }
+        if let Some(start) = self.start {
+            sections.push(Section::Start(start));
+        }
+        if !self.elements.is_empty() {
+            sections.push(Section::Element(ElementSection(self.elements)));
+        }
+        // memory.init、data.drop 和读取数据段的数组指令要求有 DataCount section
+        let uses_data = self.bodies.iter().flat_map(|b| b.instructions.elements()).any(|i| match *i {
+            Instruction::MemoryInit(..) | Instruction::DataDrop(_) => true,
+            #[cfg(feature = "gc")]
+            Instruction::Gc(GcInstruction::ArrayNewData(..)) | Instruction::Gc(GcInstruction::ArrayInitData(..)) => true,
+            _ => false,
+        });
+        if uses_data {
+            sections.push(Section::DataCount(self.data.len() as u32));
+        }
+        if !self.bodies.is_empty() {
+            sections.push(Section::Code(CodeSection(self.bodies)));
+        }
+        if !self.data.is_empty() {
+            sections.push(Section::Data(DataSection(self.data)));
+        }
+        Module { sections, ..Module::default() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elements::{deserialize_buffer, serialize};
+    use crate::tests::SAMPLE_MODULE;
+    use crate::wat::{print, Printer};
+
+    /// Parse `text` and check the module survives the binary encoding and both print styles.
+    fn round_trip(text: &str) -> Module {
+        let module = parse(text).unwrap();
+        assert_eq!(deserialize_buffer::<Module>(&serialize(&module).unwrap()).unwrap(), module);
+        assert_eq!(parse(&print(&module)).unwrap(), module);
+        assert_eq!(parse(&Printer::new().folded(true).print(&module)).unwrap(), module);
+        module
+    }
+
+    #[test]
+    fn test_parse_sample() {
+        let expected: Module = deserialize_buffer(SAMPLE_MODULE).unwrap();
+        let text = print(&expected);
+        let mut parsed = parse(&text).unwrap();
+        // 文本格式中没有自定义 section
+        parsed.sections.push(expected.sections.last().unwrap().clone());
+        assert_eq!(serialize(&parsed).unwrap(), SAMPLE_MODULE.to_vec());
+    }
+
+    #[test]
+    fn test_abbreviations() {
+        use crate::elements::ops::Instruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (func $log (import "env" "log") (param i32))
+              (memory (export "mem") (data "ab" "c"))
+              (table funcref (elem $fac))
+              (global $g (mut i32) (i32.const -1))
+              (func $fac (export "fac") (param $n i64) (result i64)
+                (if (result i64) (i64.eqz (local.get $n))
+                  (then (i64.const 1))
+                  (else
+                    (i64.mul
+                      (local.get $n)
+                      (call $fac (i64.sub (local.get $n) (i64.const 1)))))))
+              (func (local $i i32)
+                block $done
+                  loop $again
+                    local.get $i
+                    i32.const 0xff
+                    i32.ge_u
+                    br_if $done
+                    local.get $i
+                    i32.load8_u offset=4 align=1
+                    call $log
+                    local.get $i
+                    i32.const 1
+                    i32.add
+                    local.set $i
+                    br $again
+                  end $again
+                end))
+        "#);
+
+        assert_eq!(module.import_section().unwrap().0[0].external, External::Function(0));
+        assert_eq!(module.memory_section().unwrap().0[0], ResizableLimits { initial: 1, maximum: Some(1), shared: false, memory64: false });
+        assert_eq!(module.data_section().unwrap().0[0].value, b"abc".to_vec());
+        assert_eq!(module.elements_section().unwrap().0[0].members, vec![1]);
+        let exports: Vec<_> = module.export_section().unwrap().0.iter().map(|e| (e.field_str.clone(), e.internal)).collect();
+        assert_eq!(exports, vec![("mem".to_string(), Internal::Memory(0)), ("fac".to_string(), Internal::Function(1))]);
+        assert_eq!(module.global_section().unwrap().0[0].init_expr, InitExpr(vec![I32Const(-1), End]));
+
+        let bodies = &module.code_section().unwrap().0;
+        assert_eq!(bodies[0].instructions.elements(), &[
+            GetLocal(0), I64Eqz, If(BlockType::Value(ValueType::I64)),
+            I64Const(1),
+            Else,
+            GetLocal(0), GetLocal(0), I64Const(1), I64Sub, Call(1), I64Mul,
+            End,
+            End,
+        ][..]);
+        assert_eq!(bodies[1].locals, vec![Local { count: 1, value_type: ValueType::I32 }]);
+        assert_eq!(&bodies[1].instructions.elements()[..8], &[
+            Block(BlockType::NoResult), Loop(BlockType::NoResult), GetLocal(0), I32Const(255), I32GeU, BrIf(1),
+            GetLocal(0), I32Load8U(0, 4, 0),
+        ][..]);
+        assert_eq!(bodies[1].instructions.elements()[13], Br(0));
+
+        crate::validation::validate(&module).unwrap();
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(parse_i32("-2147483648"), Some(i32::MIN));
+        assert_eq!(parse_i32("0xffff_ffff"), Some(-1));
+        assert_eq!(parse_i32("4294967296"), None);
+        assert_eq!(parse_i64("-0x8000000000000000"), Some(i64::MIN));
+        assert_eq!(parse_i64("1__0"), None);
+
+        let f32_bits = |text: &str| parse_float(text, 23, 8).map(|b| b as u32);
+        assert_eq!(f32_bits("1.5"), Some(1.5f32.to_bits()));
+        assert_eq!(f32_bits("-0x1.8p1"), Some((-3.0f32).to_bits()));
+        assert_eq!(f32_bits("0x1p-149"), Some(1));
+        assert_eq!(f32_bits("0x1.fffffep127"), Some(f32::MAX.to_bits()));
+        assert_eq!(f32_bits("0x1.ffffffp127"), None);
+        assert_eq!(f32_bits("0x1.000001p0"), Some(1.0f32.to_bits()));
+        assert_eq!(f32_bits("0x1.000003p0"), Some(1.0f32.to_bits() + 2));
+        assert_eq!(f32_bits("1e39"), None);
+        assert_eq!(f32_bits("-nan:0x1"), Some(0xff80_0001));
+        assert_eq!(f32_bits("nan"), Some(0x7fc0_0000));
+        assert_eq!(f32_bits("-inf"), Some(f32::NEG_INFINITY.to_bits()));
+        assert_eq!(parse_float("0x1.0000000000001p0", 52, 11), Some(1.0f64.to_bits() + 1));
+        assert_eq!(parse_float("1e-320", 52, 11), Some(1e-320f64.to_bits()));
+
+        // 打印出的浮点数能解析回相同的位模式
+        for &bits in &[0x7f80_0001u32, 0xffc0_0000, 1, 0x3dcc_cccd, 0x8000_0000] {
+            let text = crate::wat::print::f32_literal(bits);
+            assert_eq!(f32_bits(&text), Some(bits), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = parse("(module\n  (func (call $missing)))").unwrap_err();
+        assert_eq!(err.pos, Pos { line: 2, column: 15 });
+        assert_eq!(err.message, "unknown function $missing");
+
+        assert!(parse("(module (func i32.frob))").is_err());
+        assert!(parse("(module (func block))").is_err());
+        assert!(parse("(module (func $f) (func $f))").is_err());
+        assert!(parse("(module (func (br $nowhere)))").is_err());
+        assert!(parse("(module (memory 1) (data (i32.const 0) 1))").is_err());
+        #[cfg(not(feature = "multi_value"))]
+        assert!(parse("(module (func (block (result i32 i32))))").is_err());
+    }
+
+    #[test]
+    fn test_saturating_truncation() {
+        use crate::elements::ops::Instruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (func (param f64) (result i32) (i32.trunc_sat_f64_s (local.get 0)))
+              (func (param f32) (result i64) (i64.trunc_sat_f32_u (local.get 0))))
+        "#);
+        let bodies = &module.code_section().unwrap().0;
+        assert_eq!(bodies[0].instructions.elements(), &[GetLocal(0), I32TruncSatSF64, End][..]);
+        assert_eq!(bodies[1].instructions.elements(), &[GetLocal(0), I64TruncSatUF32, End][..]);
+    }
+
+    #[test]
+    fn test_sign_extension() {
+        use crate::elements::ops::Instruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (func (param i32) (result i32) (i32.extend16_s (local.get 0)))
+              (func (param i64) (result i64) (i64.extend32_s (local.get 0))))
+        "#);
+        let bodies = &module.code_section().unwrap().0;
+        assert_eq!(bodies[0].instructions.elements(), &[GetLocal(0), I32Extend16S, End][..]);
+        assert_eq!(bodies[1].instructions.elements(), &[GetLocal(0), I64Extend32S, End][..]);
+    }
+
+    #[test]
+    fn test_bulk_memory() {
+        use crate::elements::ops::Instruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (memory 1)
+              (table 2 funcref)
+              (data $hello "hello")
+              (data (i32.const 0) "abc")
+              (elem $fs func $f)
+              (elem declare func $f)
+              (func $f
+                (memory.init $hello (i32.const 0) (i32.const 0) (i32.const 5))
+                (data.drop $hello)
+                (memory.copy (i32.const 1) (i32.const 0) (i32.const 3))
+                (memory.fill (i32.const 0) (i32.const 0) (i32.const 3))
+                (table.init $fs (i32.const 0) (i32.const 0) (i32.const 1))
+                (elem.drop $fs)
+                (table.copy (i32.const 1) (i32.const 0) (i32.const 1))))
+        "#);
+        // memory.init 和 data.drop 需要 DataCount section
+        assert_eq!(module.data_count_section(), Some(2));
+        let code = module.code_section().unwrap().0[0].instructions.elements();
+        assert_eq!(code[3], MemoryInit(0, 0));
+        assert_eq!(code[4], DataDrop(0));
+        assert_eq!(code[16], TableInit(0, 0));
+    }
+
+    #[test]
+    fn test_reference_types() {
+        use crate::elements::ops::Instruction::*;
+        use crate::elements::types::TableElementType;
+
+        let module = round_trip(r#"
+            (module
+              (type $t (func (result i32)))
+              (table $ext 2 externref)
+              (table $fs 2 10 funcref)
+              (elem (table $fs) (i32.const 0) func $seven)
+              (elem (table $ext) (i32.const 0) externref (ref.null extern))
+              (func $seven (result i32) i32.const 7)
+              (func (param i32 externref) (result externref)
+                (table.set $ext (local.get 0) (local.get 1))
+                (drop (table.grow $fs (ref.func $seven) (table.size $fs)))
+                (drop (call_indirect $fs (type $t) (local.get 0)))
+                (select (result externref) (table.get $ext (local.get 0)) (ref.null extern) (ref.is_null (local.get 1)))))
+        "#);
+        let elements = &module.elements_section().unwrap().0;
+        assert_eq!((elements[0].index, elements[1].index), (1, 0));
+        assert_eq!(elements[1].elem_type, TableElementType::ExternRef);
+        let code = module.code_section().unwrap().0[1].instructions.elements();
+        assert_eq!(code[8], CallIndirect(0, 1));
+        assert_eq!(code[12], RefNull(TableElementType::ExternRef));
+        assert_eq!(code[15], TypedSelect(ValueType::ExternRef));
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn test_simd() {
+        use crate::elements::ops::Instruction::Simd;
+        use crate::elements::simd::SimdInstruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (memory 1)
+              (func (param v128) (result v128)
+                (i8x16.shuffle 31 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14
+                  (v128.load offset=16 (i32.const 0)) (local.get 0))
+                (drop (i8x16.extract_lane_u 15))
+                (v128.load16_lane 2 (i32.const 4) (v128.const i16x8 -1 0 1 2 3 4 5 32767))
+                (f32x4.min (v128.const f32x4 nan 0 -0 1))))
+        "#);
+        let code = module.code_section().unwrap().0[0].instructions.elements();
+        let mut lanes = [0u8; 16];
+        lanes[0] = 31;
+        for (i, lane) in lanes.iter_mut().enumerate().skip(1) {
+            *lane = i as u8 - 1;
+        }
+        assert_eq!(code[3], Simd(I8x16Shuffle(Box::new(lanes))));
+        assert_eq!(code[4], Simd(I8x16ExtractLaneU(15)));
+        assert_eq!(code[8], Simd(V128Load16Lane(1, 0, 0, 2)));
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_atomics() {
+        use crate::elements::ops::Instruction::Atomic;
+        use crate::elements::atomics::AtomicInstruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (memory 1 2 shared)
+              (func (param i32 i64) (result i64)
+                (drop (i32.atomic.rmw8.cmpxchg_u (local.get 0) (i32.const 1) (i32.const 2)))
+                (drop (memory.atomic.wait32 offset=4 (local.get 0) (i32.const 0) (i64.const -1)))
+                atomic.fence
+                (i64.atomic.rmw.xchg offset=8 (local.get 0) (local.get 1))))
+        "#);
+        assert_eq!(module.memory_section().unwrap().0[0], ResizableLimits { initial: 1, maximum: Some(2), shared: true, memory64: false });
+        let code = module.code_section().unwrap().0[0].instructions.elements();
+        assert_eq!(code[3], Atomic(I32AtomicRmw8CmpxchgU(0, 0, 0)));
+        assert_eq!(code[8], Atomic(MemoryAtomicWait32(2, 4, 0)));
+        assert_eq!(code[10], Atomic(AtomicFence));
+        assert_eq!(code[13], Atomic(I64AtomicRmwXchg(3, 8, 0)));
+    }
+
+    #[cfg(feature = "memory64")]
+    #[test]
+    fn test_memory64() {
+        use crate::elements::ops::Instruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (memory i64 1 4)
+              (data (i64.const 8) "ab")
+              (func (param i64 i64)
+                (i64.store offset=0x1_0000_0000 (local.get 0) (local.get 1))
+                (drop (memory.grow (memory.size)))))
+        "#);
+        assert_eq!(module.memory_section().unwrap().0[0], ResizableLimits { initial: 1, maximum: Some(4), shared: false, memory64: true });
+        let data = &module.data_section().unwrap().0[0];
+        assert_eq!(data.offset, Some(InitExpr(vec![I64Const(8), End])));
+        let code = module.code_section().unwrap().0[0].instructions.elements();
+        assert_eq!(code[2], I64Store(3, 1 << 32, 0));
+    }
+
+    #[cfg(feature = "multi_memory")]
+    #[test]
+    fn test_multi_memory() {
+        use crate::elements::ops::Instruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (memory $a 1)
+              (memory $b 1 2)
+              (data (memory $b) (i32.const 0) "xy")
+              (func (param i32) (result i32)
+                (i32.store8 1 offset=1 (local.get 0) (i32.load8_u $b (local.get 0)))
+                (memory.copy $a $b (i32.const 10) (i32.const 0) (i32.const 2))
+                (memory.grow $b (memory.size $b))))
+        "#);
+        assert_eq!(module.memory_section().unwrap().0.len(), 2);
+        assert_eq!(module.data_section().unwrap().0[0].index, 1);
+        let code = module.code_section().unwrap().0[0].instructions.elements();
+        assert_eq!(code[2], I32Load8U(0, 0, 1));
+        assert_eq!(code[3], I32Store8(0, 1, 1));
+        assert_eq!(code[7], MemoryCopy(0, 1));
+        assert_eq!(code[8], CurrentMemory(1));
+        assert_eq!(code[9], GrowMemory(1));
+    }
+
+    #[cfg(feature = "tail_call")]
+    #[test]
+    fn test_tail_call() {
+        use crate::elements::ops::Instruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (type $pred (func (param i32) (result i32)))
+              (table 2 funcref)
+              (func $even (type $pred)
+                (if (result i32) (i32.eqz (local.get 0))
+                  (then (i32.const 1))
+                  (else (return_call $odd (i32.sub (local.get 0) (i32.const 1))))))
+              (func $odd (type $pred)
+                (return_call_indirect (type $pred) (local.get 0) (i32.const 0))))
+        "#);
+        let bodies = &module.code_section().unwrap().0;
+        assert_eq!(bodies[0].instructions.elements()[8], ReturnCall(1));
+        assert_eq!(bodies[1].instructions.elements()[2], ReturnCallIndirect(0, 0));
+    }
+
+    #[cfg(feature = "exceptions")]
+    #[test]
+    fn test_exceptions() {
+        use crate::elements::ops::Instruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (import "host" "err" (tag $err (param i32)))
+              (tag $e (export "e") (param i32))
+              (func (param i32) (result i32)
+                (block $all (result exnref)
+                  (block $caught (result i32)
+                    (try_table (catch $e $caught) (catch_all_ref $all)
+                      (throw $err (local.get 0)))
+                    (return (i32.const 0)))
+                  (return))
+                (throw_ref)))
+        "#);
+        assert_eq!(module.import_section().unwrap().0[0].external, External::Tag(TagType { type_index: 0 }));
+        assert_eq!(module.tag_section().unwrap().0, vec![TagType { type_index: 0 }]);
+        let code = module.code_section().unwrap().0[0].instructions.elements();
+        match code[2] {
+            TryTable(ref data) => assert_eq!(
+                &data.catches[..],
+                &[Catch::One { tag: 1, label: 0 }, Catch::AllRef { label: 1 }][..]
+            ),
+            ref other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(code[4], Throw(0));
+        assert_eq!(code[11], ThrowRef);
+    }
+
+    #[cfg(feature = "gc")]
+    #[test]
+    fn test_gc() {
+        use crate::elements::ops::Instruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (rec
+                (type $node (sub (struct (field $value (mut i32)) (field $next (ref null $node)))))
+                (type $point (sub final $node (struct (field (mut i32)) (field (ref null $node)) (field $y i8)))))
+              (type $bytes (array (mut i8)))
+              (type $binop (func (param i32 i32) (result i32)))
+              (global $origin (ref $node) (struct.new $node (i32.const 40) (ref.null none)))
+              (elem declare func $add)
+              (func $add (type $binop) (i32.add (local.get 0) (local.get 1)))
+              (func (param $x anyref) (result i32)
+                (local $a (ref null $bytes))
+                (block $is_i31 (result (ref i31))
+                  (br_on_cast $is_i31 anyref (ref i31) (local.get $x))
+                  (drop)
+                  (local.set $a (array.new_default $bytes (i32.const 2)))
+                  (return (struct.get_u $point $y (ref.cast (ref $point) (global.get $origin)))))
+                (call_ref $binop (i31.get_s) (array.len (local.get $a)) (ref.func $add))))
+        "#);
+        let groups = &module.type_section().unwrap().0;
+        // 最后一组是第二个函数的内联类型
+        assert_eq!(groups.len(), 4);
+        assert_eq!(groups[0].0.len(), 2);
+        assert_eq!(groups[0].0[1].supertypes, vec![0]);
+        assert!(groups[0].0[1].is_final);
+        let code = module.code_section().unwrap().0[1].instructions.elements();
+        assert_eq!(code[code.len() - 2], CallRef(3));
+    }
+
+    #[test]
+    fn test_extended_const() {
+        use crate::elements::ops::Instruction::*;
+
+        let module = round_trip(r#"
+            (module
+              (import "env" "base" (global $base i32))
+              (memory 1)
+              (global i64 (i64.mul (i64.const 1000) (i64.sub (i64.const 0) (i64.const 7))))
+              (data (i32.sub (i32.mul (global.get $base) (i32.const 2)) (i32.const 1)) "xy"))
+        "#);
+        assert_eq!(
+            module.global_section().unwrap().0[0].init_expr,
+            InitExpr(vec![I64Const(1000), I64Const(0), I64Const(7), I64Sub, I64Mul, End])
+        );
+        assert_eq!(
+            module.data_section().unwrap().0[0].offset,
+            Some(InitExpr(vec![GetGlobal(0), I32Const(2), I32Mul, I32Const(1), I32Sub, End]))
+        );
+    }
+
+    #[cfg(feature = "multi_value")]
+    #[test]
+    fn test_multi_value() {
+        let module = round_trip(r#"
+            (module
+              (func $swap (param i32 i32) (result i32 i32)
+                local.get 1
+                local.get 0)
+              (func (param i32) (result i32 i32)
+                local.get 0
+                (block (param i32) (result i32 i32)
+                  i32.const 10)
+                call $swap)
+              (func (param $n i32) (result i32)
+                i32.const 0
+                (loop $again (param i32) (result i32)
+                  local.get $n
+                  i32.add
+                  (local.tee $n (i32.sub (local.get $n) (i32.const 1)))
+                  br_if $again)))
+        "#);
+        // 块类型引用一个新增的函数类型
+        let bodies = &module.code_section().unwrap().0;
+        assert!(matches!(bodies[1].instructions.elements()[1], crate::elements::ops::Instruction::Block(BlockType::TypeIndex(_))));
+    }
+}
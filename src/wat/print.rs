@@ -0,0 +1,1253 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::elements::module::Module;
+use crate::elements::name_section::NameMap;
+use crate::elements::ops::{Instruction, InitExpr};
+use crate::elements::types::{BlockType, CompositeType, FunctionType, SubType, TableElementType, ValueType};
+use crate::elements::import_entry::{External, GlobalType, ResizableLimits};
+use crate::elements::export_entry::Internal;
+use super::{is_id, memory_mnemonic, simple_mnemonic};
+#[cfg(feature = "exceptions")]
+use crate::elements::ops::Catch;
+#[cfg(feature = "simd")]
+use crate::elements::simd::SimdInstruction;
+#[cfg(feature = "threads")]
+use crate::elements::atomics::AtomicInstruction;
+#[cfg(feature = "threads")]
+use super::atomics::{atomic_arity, atomic_mnemonic};
+#[cfg(feature = "gc")]
+use crate::elements::gc::GcInstruction;
+#[cfg(feature = "gc")]
+use crate::elements::types::{FieldType, StorageType};
+#[cfg(feature = "gc")]
+use super::gc::{gc_mnemonic, heap_type_text, ref_type_text};
+#[cfg(feature = "simd")]
+use super::simd::{simd_arity, simd_lane_memory_mnemonic, simd_lane_mnemonic, simd_memory_mnemonic, simple_simd_mnemonic};
+
+/// Prints modules in the text format.
+#[derive(Debug, Clone, Default)]
+pub struct Printer {
+    folded: bool,
+}
+
+/// Print `module` in the text format with flat instructions.
+pub fn print(module: &Module) -> String {
+    Printer::new().print(module)
+}
+
+impl Printer {
+    pub fn new() -> Printer {
+        Printer::default()
+    }
+
+    /// Print instructions as nested s-expressions instead of a flat sequence.
+    pub fn folded(mut self, folded: bool) -> Printer {
+        self.folded = folded;
+        self
+    }
+
+    pub fn print(&self, module: &Module) -> String {
+        let mut printer = ModulePrinter::new(module, self.folded);
+        printer.module();
+        printer.out
+    }
+
+    /// Print the function `func_idx` alone, `None` if it is imported or out of range.
+    pub fn print_func(&self, module: &Module, func_idx: u32) -> Option<String> {
+        let mut printer = ModulePrinter::new(module, self.folded);
+        if printer.function(func_idx, 0) {
+            Some(printer.out)
+        } else {
+            None
+        }
+    }
+}
+
+/// Names from the `name` custom section, restricted to those usable as `$id`s.
+#[derive(Debug, Default)]
+struct Names {
+    module: Option<String>,
+    functions: HashMap<u32, String>,
+    locals: HashMap<u32, HashMap<u32, String>>,
+}
+
+impl Names {
+    fn from_module(module: &Module) -> Names {
+        // 格式错误的 name section 直接忽略，只是少了名字
+        let section = module.name_section().ok().flatten().unwrap_or_default();
+        Names {
+            module: section.module.filter(|name| is_id(name)),
+            functions: unique_ids(section.functions),
+            locals: section.locals.into_iter().map(|(func, locals)| (func, unique_ids(locals))).collect(),
+        }
+    }
+}
+
+/// Drop names which are not valid ids, and duplicates except the one with the lowest index.
+fn unique_ids(names: NameMap) -> HashMap<u32, String> {
+    let mut seen = HashSet::new();
+    // NameMap 按序号排序，先出现的序号最小
+    names.into_iter().filter(|(_, name)| is_id(name) && seen.insert(name.clone())).collect()
+}
+
+fn value_type(t: ValueType) -> String {
+    let name = match t {
+        ValueType::I32 => "i32",
+        ValueType::I64 => "i64",
+        ValueType::F32 => "f32",
+        ValueType::F64 => "f64",
+        ValueType::FuncRef => "funcref",
+        ValueType::ExternRef => "externref",
+        #[cfg(feature = "exceptions")]
+        ValueType::ExnRef => "exnref",
+        #[cfg(feature = "simd")]
+        ValueType::V128 => "v128",
+        #[cfg(feature = "gc")]
+        ValueType::Ref(ref_type) => return ref_type_text(ref_type),
+    };
+    name.to_string()
+}
+
+fn limits(limits: &ResizableLimits) -> String {
+    let mut s = String::from(if limits.memory64 { "i64 " } else { "" });
+    match limits.maximum {
+        Some(maximum) => write!(s, "{} {}", limits.initial, maximum).unwrap(),
+        None => write!(s, "{}", limits.initial).unwrap(),
+    }
+    if limits.shared {
+        s.push_str(" shared");
+    }
+    s
+}
+
+fn global_type(global_type: &GlobalType) -> String {
+    if global_type.is_mutable {
+        format!("(mut {})", value_type(global_type.content_type))
+    } else {
+        value_type(global_type.content_type)
+    }
+}
+
+fn table_element_type(elem_type: TableElementType) -> String {
+    value_type(elem_type.value_type())
+}
+
+/// Heap type immediate of `ref.null`.
+fn heap_type(elem_type: TableElementType) -> String {
+    let name = match elem_type {
+        TableElementType::AnyFunc => "func",
+        TableElementType::ExternRef => "extern",
+        #[cfg(feature = "exceptions")]
+        TableElementType::ExnRef => "exn",
+        #[cfg(feature = "gc")]
+        TableElementType::Ref(ref_type) => return heap_type_text(ref_type.heap),
+    };
+    name.to_string()
+}
+
+#[cfg(feature = "gc")]
+fn field_type(field: FieldType) -> String {
+    let storage = match field.storage {
+        StorageType::Val(t) => value_type(t),
+        StorageType::I8 => "i8".to_string(),
+        StorageType::I16 => "i16".to_string(),
+    };
+    if field.mutable { format!("(mut {})", storage) } else { storage }
+}
+
+/// Definition of a type, wrapped in `(sub ...)` unless it is final without supertypes.
+fn sub_type(sub: &SubType) -> String {
+    let composite = match sub.composite {
+        CompositeType::Func(ref func) => format!("(func{})", ModulePrinter::signature(func)),
+        #[cfg(feature = "gc")]
+        CompositeType::Struct(ref fields) => {
+            let mut s = "(struct".to_string();
+            for &field in fields.iter() {
+                write!(s, " (field {})", field_type(field)).unwrap();
+            }
+            s.push(')');
+            s
+        },
+        #[cfg(feature = "gc")]
+        CompositeType::Array(field) => format!("(array {})", field_type(field)),
+    };
+    if sub.is_final && sub.supertypes.is_empty() {
+        return composite;
+    }
+    let mut s = "(sub".to_string();
+    if sub.is_final {
+        s.push_str(" final");
+    }
+    for idx in sub.supertypes.iter() {
+        write!(s, " {}", idx).unwrap();
+    }
+    write!(s, " {})", composite).unwrap();
+    s
+}
+
+/// Immediates of a load or store, omitting the defaults.
+fn memarg(natural: u32, align: u32, offset: u64, memory: u32) -> String {
+    let mut s = String::new();
+    if memory != 0 {
+        write!(s, " {}", memory).unwrap();
+    }
+    if offset != 0 {
+        write!(s, " offset={}", offset).unwrap();
+    }
+    if align != natural {
+        write!(s, " align={}", 1u64 << align.min(63)).unwrap();
+    }
+    s
+}
+
+#[cfg(feature = "threads")]
+fn atomic_instruction(instruction: &AtomicInstruction) -> String {
+    match atomic_mnemonic(instruction) {
+        Some((name, natural, align, offset, memory)) => format!("{}{}", name, memarg(natural, align, offset, memory)),
+        None => "atomic.fence".to_string(),
+    }
+}
+
+#[cfg(feature = "simd")]
+fn simd_instruction(instruction: &SimdInstruction) -> String {
+    if let Some(name) = simple_simd_mnemonic(instruction) {
+        return name.to_string();
+    }
+    if let Some((name, natural, align, offset, memory)) = simd_memory_mnemonic(instruction) {
+        return format!("{}{}", name, memarg(natural, align, offset, memory));
+    }
+    if let Some((name, natural, align, offset, memory, lane)) = simd_lane_memory_mnemonic(instruction) {
+        return format!("{}{} {}", name, memarg(natural, align, offset, memory), lane);
+    }
+    if let Some((name, lane)) = simd_lane_mnemonic(instruction) {
+        return format!("{} {}", name, lane);
+    }
+    match *instruction {
+        SimdInstruction::V128Const(ref bytes) => {
+            let mut s = "v128.const i32x4".to_string();
+            for lane in bytes.chunks(4) {
+                write!(s, " 0x{:08x}", u32::from_le_bytes([lane[0], lane[1], lane[2], lane[3]])).unwrap();
+            }
+            s
+        },
+        SimdInstruction::I8x16Shuffle(ref lanes) => {
+            let mut s = "i8x16.shuffle".to_string();
+            for lane in lanes.iter() {
+                write!(s, " {}", lane).unwrap();
+            }
+            s
+        },
+        ref other => format!(";; {:?}", other),
+    }
+}
+
+#[cfg(feature = "gc")]
+fn gc_instruction(instruction: &GcInstruction) -> String {
+    use crate::elements::gc::GcInstruction::*;
+
+    let name = gc_mnemonic(instruction);
+    match *instruction {
+        StructNew(idx) | StructNewDefault(idx) | ArrayNew(idx) | ArrayNewDefault(idx) | ArrayGet(idx)
+            | ArrayGetS(idx) | ArrayGetU(idx) | ArraySet(idx) | ArrayFill(idx) => format!("{} {}", name, idx),
+        StructGet(a, b) | StructGetS(a, b) | StructGetU(a, b) | StructSet(a, b) | ArrayNewFixed(a, b)
+            | ArrayNewData(a, b) | ArrayNewElem(a, b) | ArrayCopy(a, b) | ArrayInitData(a, b)
+            | ArrayInitElem(a, b) => format!("{} {} {}", name, a, b),
+        RefTest(ref_type) | RefCast(ref_type) => format!("{} {}", name, ref_type_text(ref_type)),
+        BrOnCast(ref data) | BrOnCastFail(ref data) => {
+            format!("{} {} {} {}", name, data.label, ref_type_text(data.from), ref_type_text(data.to))
+        },
+        ArrayLen | AnyConvertExtern | ExternConvertAny | RefI31 | I31GetS | I31GetU => name.to_string(),
+    }
+}
+
+fn block_type(block_type: &BlockType) -> String {
+    match *block_type {
+        BlockType::NoResult => String::new(),
+        BlockType::Value(t) => format!(" (result {})", value_type(t)),
+        #[cfg(feature = "multi_value")]
+        BlockType::TypeIndex(idx) => format!(" (type {})", idx),
+    }
+}
+
+/// `f32` literal which parses back to the same bit pattern.
+pub(crate) fn f32_literal(bits: u32) -> String {
+    let value = f32::from_bits(bits);
+    let sign = if bits >> 31 != 0 { "-" } else { "" };
+    if value.is_nan() {
+        let payload = bits & 0x7f_ffff;
+        if payload == 0x40_0000 {
+            format!("{}nan", sign)
+        } else {
+            format!("{}nan:0x{:x}", sign, payload)
+        }
+    } else if value.is_infinite() {
+        format!("{}inf", sign)
+    } else {
+        // Debug 输出是能精确还原的最短十进制表示
+        format!("{:?}", value)
+    }
+}
+
+/// `f64` literal which parses back to the same bit pattern.
+pub(crate) fn f64_literal(bits: u64) -> String {
+    let value = f64::from_bits(bits);
+    let sign = if bits >> 63 != 0 { "-" } else { "" };
+    if value.is_nan() {
+        let payload = bits & 0xf_ffff_ffff_ffff;
+        if payload == 0x8_0000_0000_0000 {
+            format!("{}nan", sign)
+        } else {
+            format!("{}nan:0x{:x}", sign, payload)
+        }
+    } else if value.is_infinite() {
+        format!("{}inf", sign)
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+pub(crate) fn string_literal(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() + 2);
+    s.push('"');
+    for &b in bytes {
+        match b {
+            b'"' => s.push_str("\\\""),
+            b'\\' => s.push_str("\\\\"),
+            0x20..=0x7e => s.push(b as char),
+            _ => write!(s, "\\{:02x}", b).unwrap(),
+        }
+    }
+    s.push('"');
+    s
+}
+
+/// Instruction with the instructions producing its operands.
+enum Node<'a> {
+    Plain {
+        instruction: &'a Instruction,
+        operands: Vec<Node<'a>>,
+        results: usize,
+    },
+    Block {
+        instruction: &'a Instruction,
+        operands: Vec<Node<'a>>,
+        body: Vec<Node<'a>>,
+        else_body: Option<Vec<Node<'a>>>,
+        results: usize,
+    },
+}
+
+impl Node<'_> {
+    fn results(&self) -> usize {
+        match *self {
+            Node::Plain { results, .. } | Node::Block { results, .. } => results,
+        }
+    }
+}
+
+struct ModulePrinter<'a> {
+    module: &'a Module,
+    names: Names,
+    folded: bool,
+    /// Every type in index order, flattening the recursion groups.
+    types: Vec<&'a SubType>,
+    /// Type index of every function, imported ones first.
+    func_types: Vec<u32>,
+    /// Type index of every tag, imported ones first.
+    #[cfg(feature = "exceptions")]
+    tag_types: Vec<u32>,
+    out: String,
+}
+
+impl<'a> ModulePrinter<'a> {
+    fn new(module: &'a Module, folded: bool) -> ModulePrinter<'a> {
+        let mut func_types = Vec::new();
+        if let Some(section) = module.import_section() {
+            for entry in section.0.iter() {
+                if let External::Function(type_idx) = entry.external {
+                    func_types.push(type_idx);
+                }
+            }
+        }
+        if let Some(section) = module.function_section() {
+            func_types.extend(section.0.iter().map(|f| f.0));
+        }
+        #[cfg(feature = "exceptions")]
+        let mut tag_types = Vec::new();
+        #[cfg(feature = "exceptions")]
+        {
+            if let Some(section) = module.import_section() {
+                for entry in section.0.iter() {
+                    if let External::Tag(ref tag) = entry.external {
+                        tag_types.push(tag.type_index);
+                    }
+                }
+            }
+            if let Some(section) = module.tag_section() {
+                tag_types.extend(section.0.iter().map(|t| t.type_index));
+            }
+        }
+
+        ModulePrinter {
+            module,
+            names: Names::from_module(module),
+            folded,
+            types: module.type_section().map(|s| s.types().collect()).unwrap_or_default(),
+            func_types,
+            #[cfg(feature = "exceptions")]
+            tag_types,
+            out: String::new(),
+        }
+    }
+
+    fn line(&mut self, level: usize, text: &str) {
+        for _ in 0..level {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Close the s-expression opened on a previous line.
+    fn close(&mut self) {
+        if self.out.ends_with('\n') {
+            self.out.pop();
+        }
+        self.out.push_str(")\n");
+    }
+
+    fn func_name(&self, idx: u32) -> String {
+        match self.names.functions.get(&idx) {
+            Some(name) => format!("${}", name),
+            None => idx.to_string(),
+        }
+    }
+
+    /// `$name (;idx;)` or `(;idx;)` of a function definition.
+    fn func_id(&self, idx: u32) -> String {
+        match self.names.functions.get(&idx) {
+            Some(name) => format!("${} (;{};)", name, idx),
+            None => format!("(;{};)", idx),
+        }
+    }
+
+    fn local_name(&self, func: Option<u32>, idx: u32) -> String {
+        match func.and_then(|f| self.names.locals.get(&f)).and_then(|locals| locals.get(&idx)) {
+            Some(name) => format!("${}", name),
+            None => idx.to_string(),
+        }
+    }
+
+    fn func_type(&self, type_idx: u32) -> Option<&'a FunctionType> {
+        self.types.get(type_idx as usize).and_then(|t| t.func_type())
+    }
+
+    fn signature(ty: &FunctionType) -> String {
+        let mut s = String::new();
+        if !ty.params.is_empty() {
+            s.push_str(" (param");
+            for &t in ty.params.iter() {
+                write!(s, " {}", value_type(t)).unwrap();
+            }
+            s.push(')');
+        }
+        if !ty.results.is_empty() {
+            s.push_str(" (result");
+            for &t in ty.results.iter() {
+                write!(s, " {}", value_type(t)).unwrap();
+            }
+            s.push(')');
+        }
+        s
+    }
+
+    fn module(&mut self) {
+        match self.names.module {
+            Some(ref name) => self.out.push_str(&format!("(module ${}\n", name)),
+            None => self.out.push_str("(module\n"),
+        }
+
+        self.types();
+        self.imports();
+        self.functions();
+        self.tables();
+        self.memories();
+        #[cfg(feature = "exceptions")]
+        self.tags();
+        self.globals();
+        self.exports();
+        if let Some(start) = self.module.start_section() {
+            let text = format!("(start {})", self.func_name(start));
+            self.line(1, &text);
+        }
+        self.elements();
+        self.data();
+        let mut commented = false;
+        for section in self.module.custom_sections() {
+            let text = format!(";; custom section {}, size {}", string_literal(section.name.as_bytes()), section.payload.len());
+            self.line(1, &text);
+            commented = true;
+        }
+
+        if commented {
+            // 行注释之后不能直接接右括号
+            self.out.push_str(")\n");
+        } else {
+            self.close();
+        }
+    }
+
+    fn types(&mut self) {
+        let groups = match self.module.type_section() {
+            Some(section) => &section.0,
+            None => return,
+        };
+        let mut idx = 0;
+        for group in groups.iter() {
+            // 单个类型的组不写 rec
+            let rec = group.0.len() != 1;
+            if rec {
+                self.line(1, "(rec");
+            }
+            for sub in group.0.iter() {
+                let text = format!("(type (;{};) {})", idx, sub_type(sub));
+                self.line(if rec { 2 } else { 1 }, &text);
+                idx += 1;
+            }
+            if rec {
+                self.close();
+            }
+        }
+    }
+
+    fn imports(&mut self) {
+        let entries = match self.module.import_section() {
+            Some(section) => &section.0,
+            None => return,
+        };
+        let (mut funcs, mut tables, mut memories, mut globals) = (0, 0, 0, 0);
+        #[cfg(feature = "exceptions")]
+        let mut tags = 0;
+        for entry in entries.iter() {
+            let desc = match entry.external {
+                External::Function(type_idx) => {
+                    funcs += 1;
+                    format!("(func {} (type {}))", self.func_id(funcs - 1), type_idx)
+                },
+                External::Table(ref table_type) => {
+                    tables += 1;
+                    format!("(table (;{};) {} {})", tables - 1, limits(&table_type.limits), table_element_type(table_type.elem_type))
+                },
+                External::Memory(ref mem_limits) => {
+                    memories += 1;
+                    format!("(memory (;{};) {})", memories - 1, limits(mem_limits))
+                },
+                External::Global(ref ty) => {
+                    globals += 1;
+                    format!("(global (;{};) {})", globals - 1, global_type(ty))
+                },
+                #[cfg(feature = "exceptions")]
+                External::Tag(ref tag) => {
+                    tags += 1;
+                    format!("(tag (;{};) (type {}))", tags - 1, tag.type_index)
+                },
+            };
+            let text = format!(
+                "(import {} {} {})",
+                string_literal(entry.module_str.as_bytes()),
+                string_literal(entry.field_str.as_bytes()),
+                desc
+            );
+            self.line(1, &text);
+        }
+    }
+
+    fn imported_count(&self, f: fn(&External) -> bool) -> usize {
+        self.module.import_section().map(|s| s.0.iter().filter(|e| f(&e.external)).count()).unwrap_or(0)
+    }
+
+    fn functions(&mut self) {
+        let count = self.module.code_section().map(|s| s.0.len()).unwrap_or(0);
+        let imported = self.imported_count(|e| matches!(*e, External::Function(_)));
+        for i in 0..count {
+            self.function((imported + i) as u32, 1);
+        }
+    }
+
+    /// Definition of the function `func_idx` indented by `level`, false if it is not defined in the module.
+    fn function(&mut self, func_idx: u32, level: usize) -> bool {
+        let imported = self.imported_count(|e| matches!(*e, External::Function(_)));
+        let module = self.module;
+        let body = match (func_idx as usize).checked_sub(imported).and_then(|i| module.code_section()?.0.get(i)) {
+            Some(body) => body,
+            None => return false,
+        };
+        let type_idx = self.func_types.get(func_idx as usize).cloned().unwrap_or(0);
+        let ty = self.func_type(type_idx).cloned().unwrap_or_default();
+        let has_local_names = self.names.locals.contains_key(&func_idx);
+
+        let mut header = format!("(func {} (type {})", self.func_id(func_idx), type_idx);
+        if has_local_names {
+            for (p, &t) in ty.params.iter().enumerate() {
+                let name = self.local_name(Some(func_idx), p as u32);
+                if name.starts_with('$') {
+                    write!(header, " (param {} {})", name, value_type(t)).unwrap();
+                } else {
+                    write!(header, " (param {})", value_type(t)).unwrap();
+                }
+            }
+            header.push_str(&Self::signature(&FunctionType { params: Vec::new(), ..ty.clone() }));
+        } else {
+            header.push_str(&Self::signature(&ty));
+        }
+        self.line(level, &header);
+
+        let locals: Vec<ValueType> = body.locals
+            .iter()
+            .flat_map(|l| std::iter::repeat_n(l.value_type, l.count as usize))
+            .collect();
+        if !locals.is_empty() {
+            if has_local_names {
+                for (l, &t) in locals.iter().enumerate() {
+                    let name = self.local_name(Some(func_idx), (ty.params.len() + l) as u32);
+                    let text = if name.starts_with('$') {
+                        format!("(local {} {})", name, value_type(t))
+                    } else {
+                        format!("(local {})", value_type(t))
+                    };
+                    self.line(level + 1, &text);
+                }
+            } else {
+                let types: Vec<_> = locals.iter().map(|&t| value_type(t)).collect();
+                let text = format!("(local {})", types.join(" "));
+                self.line(level + 1, &text);
+            }
+        }
+
+        let code = body.instructions.elements();
+        if self.folded {
+            let mut pos = 0;
+            let mut labels = vec![ty.results.len()];
+            let (nodes, _) = self.fold(code, &mut pos, &mut labels);
+            for node in nodes.iter() {
+                self.node(func_idx, node, level + 1);
+            }
+        } else {
+            self.flat(func_idx, code, level + 1);
+        }
+        self.close();
+        true
+    }
+
+    fn flat(&mut self, func_idx: u32, code: &[Instruction], base: usize) {
+        let mut level = base;
+        // 最后的 end 由右括号代替
+        let code = match code.split_last() {
+            Some((&Instruction::End, rest)) => rest,
+            _ => code,
+        };
+        for instruction in code.iter() {
+            match *instruction {
+                Instruction::End => {
+                    level = level.max(base + 1) - 1;
+                    self.line(level, "end");
+                },
+                Instruction::Else => {
+                    self.line(level.max(base + 1) - 1, "else");
+                },
+                _ => {
+                    let text = self.instruction(Some(func_idx), instruction);
+                    self.line(level, &text);
+                    if instruction.is_block() {
+                        level += 1;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Group instructions up to the next `end` or `else` into trees, returns the terminator.
+    fn fold<'c>(&self, code: &'c [Instruction], pos: &mut usize, labels: &mut Vec<usize>) -> (Vec<Node<'c>>, Option<&'c Instruction>) {
+        let mut nodes: Vec<Node<'c>> = Vec::new();
+        while let Some(instruction) = code.get(*pos) {
+            *pos += 1;
+            match *instruction {
+                Instruction::End | Instruction::Else => return (nodes, Some(instruction)),
+                _ if instruction.is_block() => {
+                    let results = match *block_type_of(instruction) {
+                        BlockType::NoResult => 0,
+                        BlockType::Value(_) => 1,
+                        #[cfg(feature = "multi_value")]
+                        BlockType::TypeIndex(idx) => self.func_type(idx).map(|ty| ty.results.len()).unwrap_or(0),
+                    };
+                    let is_if = matches!(*instruction, Instruction::If(_));
+                    let is_loop = matches!(*instruction, Instruction::Loop(_));
+                    let operands = if is_if { take_operands(&mut nodes, 1) } else { Vec::new() };
+                    labels.push(if is_loop { 0 } else { results });
+                    let (body, terminator) = self.fold(code, pos, labels);
+                    let else_body = match terminator {
+                        Some(&Instruction::Else) => Some(self.fold(code, pos, labels).0),
+                        _ => None,
+                    };
+                    labels.pop();
+                    nodes.push(Node::Block { instruction, operands, body, else_body, results });
+                },
+                _ => {
+                    let (pops, results) = self.arity(instruction, labels);
+                    let operands = take_operands(&mut nodes, pops);
+                    nodes.push(Node::Plain { instruction, operands, results });
+                },
+            }
+        }
+        (nodes, None)
+    }
+
+    /// Number of operands and results of a non-block instruction.
+    fn arity(&self, instruction: &Instruction, labels: &[usize]) -> (usize, usize) {
+        use crate::elements::ops::Instruction::*;
+
+        let label = |depth: u32| {
+            labels.len().checked_sub(depth as usize + 1).map(|i| labels[i]).unwrap_or(0)
+        };
+        let call = |type_idx: u32| {
+            self.func_type(type_idx).map(|ty| (ty.params.len(), ty.results.len())).unwrap_or((0, 0))
+        };
+
+        match *instruction {
+            Unreachable | Nop => (0, 0),
+            Br(depth) => (label(depth), 0),
+            BrIf(depth) => (label(depth) + 1, label(depth)),
+            BrTable(ref data) => (label(data.default) + 1, 0),
+            Return => (labels.first().cloned().unwrap_or(0), 0),
+            Call(func_idx) => {
+                self.func_types.get(func_idx as usize).map(|&t| call(t)).unwrap_or((0, 0))
+            },
+            CallIndirect(type_idx, _) => {
+                let (params, results) = call(type_idx);
+                (params + 1, results)
+            },
+            #[cfg(feature = "tail_call")]
+            ReturnCall(func_idx) => {
+                self.func_types.get(func_idx as usize).map(|&t| (call(t).0, 0)).unwrap_or((0, 0))
+            },
+            #[cfg(feature = "tail_call")]
+            ReturnCallIndirect(type_idx, _) => (call(type_idx).0 + 1, 0),
+            #[cfg(feature = "gc")]
+            CallRef(type_idx) => {
+                let (params, results) = call(type_idx);
+                (params + 1, results)
+            },
+            #[cfg(all(feature = "gc", feature = "tail_call"))]
+            ReturnCallRef(type_idx) => (call(type_idx).0 + 1, 0),
+            #[cfg(feature = "gc")]
+            BrOnNull(depth) => (label(depth) + 1, label(depth) + 1),
+            #[cfg(feature = "gc")]
+            BrOnNonNull(depth) => (label(depth), label(depth).saturating_sub(1)),
+            #[cfg(feature = "gc")]
+            RefEq => (2, 1),
+            #[cfg(feature = "gc")]
+            RefAsNonNull => (1, 1),
+            #[cfg(feature = "gc")]
+            Gc(GcInstruction::BrOnCast(ref data)) | Gc(GcInstruction::BrOnCastFail(ref data)) => {
+                (label(data.label), label(data.label))
+            },
+            #[cfg(feature = "gc")]
+            Gc(ref gc) => self.gc_arity(gc),
+            Drop => (1, 0),
+            Select | TypedSelect(_) => (3, 1),
+            #[cfg(feature = "exceptions")]
+            Throw(tag) => {
+                let params = self.tag_types.get(tag as usize).map(|&t| call(t).0).unwrap_or(0);
+                (params, 0)
+            },
+            #[cfg(feature = "exceptions")]
+            ThrowRef => (1, 0),
+            RefIsNull => (1, 1),
+            TableGet(_) => (1, 1),
+            TableSet(_) => (2, 0),
+            TableSize(_) => (0, 1),
+            TableGrow(_) => (2, 1),
+            TableFill(_) => (3, 0),
+            GetLocal(_) | GetGlobal(_) => (0, 1),
+            SetLocal(_) | SetGlobal(_) => (1, 0),
+            TeeLocal(_) => (1, 1),
+            CurrentMemory(_) => (0, 1),
+            GrowMemory(_) => (1, 1),
+            MemoryInit(..) | MemoryCopy(..) | MemoryFill(_) | TableInit(..) | TableCopy(..) => (3, 0),
+            DataDrop(_) | ElemDrop(_) => (0, 0),
+            RefNull(_) | RefFunc(_) => (0, 1),
+            I32Const(_) | I64Const(_) | F32Const(_) | F64Const(_) => (0, 1),
+
+            I32Eqz | I64Eqz | I32Clz | I32Ctz | I32Popcnt | I64Clz | I64Ctz | I64Popcnt
+            | F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt
+            | F64Abs | F64Neg | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt
+            | I32WrapI64 | I32TruncSF32 | I32TruncUF32 | I32TruncSF64 | I32TruncUF64
+            | I64ExtendSI32 | I64ExtendUI32 | I64TruncSF32 | I64TruncUF32 | I64TruncSF64 | I64TruncUF64
+            | F32ConvertSI32 | F32ConvertUI32 | F32ConvertSI64 | F32ConvertUI64 | F32DemoteF64
+            | F64ConvertSI32 | F64ConvertUI32 | F64ConvertSI64 | F64ConvertUI64 | F64PromoteF32
+            | I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64
+            | I32Extend8S | I32Extend16S | I64Extend8S | I64Extend16S | I64Extend32S
+            | I32TruncSatSF32 | I32TruncSatUF32 | I32TruncSatSF64 | I32TruncSatUF64
+            | I64TruncSatSF32 | I64TruncSatUF32 | I64TruncSatSF64 | I64TruncSatUF64 => (1, 1),
+
+            #[cfg(feature = "simd")]
+            Simd(ref simd) => simd_arity(simd),
+            #[cfg(feature = "threads")]
+            Atomic(ref atomic) => atomic_arity(atomic),
+
+            _ => match memory_mnemonic(instruction) {
+                Some((name, ..)) if name.contains(".store") => (2, 0),
+                Some(_) => (1, 1),
+                None => (2, 1),
+            },
+        }
+    }
+
+    /// Number of operands and results of a GC instruction other than `br_on_cast`.
+    #[cfg(feature = "gc")]
+    fn gc_arity(&self, instruction: &GcInstruction) -> (usize, usize) {
+        use crate::elements::gc::GcInstruction::*;
+
+        match *instruction {
+            StructNew(type_idx) => {
+                let fields = match self.types.get(type_idx as usize).map(|t| &t.composite) {
+                    Some(CompositeType::Struct(fields)) => fields.len(),
+                    _ => 0,
+                };
+                (fields, 1)
+            },
+            StructNewDefault(_) => (0, 1),
+            ArrayNewFixed(_, len) => (len as usize, 1),
+            StructSet(..) => (2, 0),
+            ArrayNew(_) | ArrayNewData(..) | ArrayNewElem(..) => (2, 1),
+            ArrayGet(_) | ArrayGetS(_) | ArrayGetU(_) => (2, 1),
+            ArraySet(_) => (3, 0),
+            ArrayFill(_) | ArrayInitData(..) | ArrayInitElem(..) => (4, 0),
+            ArrayCopy(..) => (5, 0),
+            _ => (1, 1),
+        }
+    }
+
+    fn node(&mut self, func_idx: u32, node: &Node, level: usize) {
+        match *node {
+            Node::Plain { instruction, ref operands, .. } => {
+                let text = self.instruction(Some(func_idx), instruction);
+                let inline: Option<Vec<String>> = operands.iter().map(|op| self.inline(func_idx, op)).collect();
+                match inline {
+                    Some(ref parts) if text.len() + parts.iter().map(|p| p.len() + 1).sum::<usize>() < 80 => {
+                        let mut line = format!("({}", text);
+                        for part in parts.iter() {
+                            line.push(' ');
+                            line.push_str(part);
+                        }
+                        line.push(')');
+                        self.line(level, &line);
+                    },
+                    _ => {
+                        self.line(level, &format!("({}", text));
+                        for op in operands.iter() {
+                            self.node(func_idx, op, level + 1);
+                        }
+                        self.close();
+                    },
+                }
+            },
+            Node::Block { instruction, ref operands, ref body, ref else_body, .. } => {
+                let text = self.instruction(Some(func_idx), instruction);
+                self.line(level, &format!("({}", text));
+                for op in operands.iter() {
+                    self.node(func_idx, op, level + 1);
+                }
+                if let Instruction::If(_) = *instruction {
+                    self.line(level + 1, "(then");
+                    for child in body.iter() {
+                        self.node(func_idx, child, level + 2);
+                    }
+                    self.close();
+                    if let Some(ref else_body) = *else_body {
+                        self.line(level + 1, "(else");
+                        for child in else_body.iter() {
+                            self.node(func_idx, child, level + 2);
+                        }
+                        self.close();
+                    }
+                } else {
+                    for child in body.iter() {
+                        self.node(func_idx, child, level + 1);
+                    }
+                }
+                self.close();
+            },
+        }
+    }
+
+    /// Single line text of a node, if it has no blocks.
+    fn inline(&self, func_idx: u32, node: &Node) -> Option<String> {
+        match *node {
+            Node::Plain { instruction, ref operands, .. } => {
+                let mut s = format!("({}", self.instruction(Some(func_idx), instruction));
+                for op in operands.iter() {
+                    s.push(' ');
+                    s.push_str(&self.inline(func_idx, op)?);
+                }
+                s.push(')');
+                Some(s)
+            },
+            Node::Block { .. } => None,
+        }
+    }
+
+    /// Text of an instruction with its immediates, for blocks without the body.
+    fn instruction(&self, func: Option<u32>, instruction: &Instruction) -> String {
+        use crate::elements::ops::Instruction::*;
+
+        if let Some(name) = simple_mnemonic(instruction) {
+            return name.to_string();
+        }
+        if let Some((name, natural, align, offset, memory)) = memory_mnemonic(instruction) {
+            return format!("{}{}", name, memarg(natural, align, offset, memory));
+        }
+
+        match *instruction {
+            Block(ref bt) => format!("block{}", block_type(bt)),
+            Loop(ref bt) => format!("loop{}", block_type(bt)),
+            If(ref bt) => format!("if{}", block_type(bt)),
+            #[cfg(feature = "exceptions")]
+            TryTable(ref data) => {
+                let mut s = format!("try_table{}", block_type(&data.block_type));
+                for catch in data.catches.iter() {
+                    match *catch {
+                        Catch::One { tag, label } => write!(s, " (catch {} {})", tag, label).unwrap(),
+                        Catch::OneRef { tag, label } => write!(s, " (catch_ref {} {})", tag, label).unwrap(),
+                        Catch::All { label } => write!(s, " (catch_all {})", label).unwrap(),
+                        Catch::AllRef { label } => write!(s, " (catch_all_ref {})", label).unwrap(),
+                    }
+                }
+                s
+            },
+            #[cfg(feature = "exceptions")]
+            Throw(tag) => format!("throw {}", tag),
+            #[cfg(feature = "exceptions")]
+            ThrowRef => "throw_ref".to_string(),
+            Br(depth) => format!("br {}", depth),
+            BrIf(depth) => format!("br_if {}", depth),
+            BrTable(ref data) => {
+                let mut s = "br_table".to_string();
+                for depth in data.table.iter().chain(std::iter::once(&data.default)) {
+                    write!(s, " {}", depth).unwrap();
+                }
+                s
+            },
+            Call(idx) => format!("call {}", self.func_name(idx)),
+            CallIndirect(type_idx, 0) => format!("call_indirect (type {})", type_idx),
+            CallIndirect(type_idx, table_idx) => format!("call_indirect {} (type {})", table_idx, type_idx),
+            #[cfg(feature = "tail_call")]
+            ReturnCall(idx) => format!("return_call {}", self.func_name(idx)),
+            #[cfg(feature = "tail_call")]
+            ReturnCallIndirect(type_idx, 0) => format!("return_call_indirect (type {})", type_idx),
+            #[cfg(feature = "tail_call")]
+            ReturnCallIndirect(type_idx, table_idx) => format!("return_call_indirect {} (type {})", table_idx, type_idx),
+            GetLocal(idx) => format!("local.get {}", self.local_name(func, idx)),
+            SetLocal(idx) => format!("local.set {}", self.local_name(func, idx)),
+            TeeLocal(idx) => format!("local.tee {}", self.local_name(func, idx)),
+            GetGlobal(idx) => format!("global.get {}", idx),
+            SetGlobal(idx) => format!("global.set {}", idx),
+            CurrentMemory(0) => "memory.size".to_string(),
+            CurrentMemory(memory) => format!("memory.size {}", memory),
+            GrowMemory(0) => "memory.grow".to_string(),
+            GrowMemory(memory) => format!("memory.grow {}", memory),
+            MemoryInit(segment, 0) => format!("memory.init {}", segment),
+            MemoryInit(segment, memory) => format!("memory.init {} {}", memory, segment),
+            DataDrop(segment) => format!("data.drop {}", segment),
+            MemoryCopy(0, 0) => "memory.copy".to_string(),
+            MemoryCopy(dst, src) => format!("memory.copy {} {}", dst, src),
+            MemoryFill(0) => "memory.fill".to_string(),
+            MemoryFill(memory) => format!("memory.fill {}", memory),
+            TableInit(segment, 0) => format!("table.init {}", segment),
+            TableInit(segment, table) => format!("table.init {} {}", table, segment),
+            ElemDrop(segment) => format!("elem.drop {}", segment),
+            TableCopy(0, 0) => "table.copy".to_string(),
+            TableCopy(dst, src) => format!("table.copy {} {}", dst, src),
+            TypedSelect(t) => format!("select (result {})", value_type(t)),
+            RefNull(elem_type) => format!("ref.null {}", heap_type(elem_type)),
+            RefFunc(idx) => format!("ref.func {}", self.func_name(idx)),
+            TableGet(0) => "table.get".to_string(),
+            TableGet(table) => format!("table.get {}", table),
+            TableSet(0) => "table.set".to_string(),
+            TableSet(table) => format!("table.set {}", table),
+            TableSize(0) => "table.size".to_string(),
+            TableSize(table) => format!("table.size {}", table),
+            TableGrow(0) => "table.grow".to_string(),
+            TableGrow(table) => format!("table.grow {}", table),
+            TableFill(0) => "table.fill".to_string(),
+            TableFill(table) => format!("table.fill {}", table),
+            I32Const(v) => format!("i32.const {}", v),
+            I64Const(v) => format!("i64.const {}", v),
+            F32Const(bits) => format!("f32.const {}", f32_literal(bits)),
+            F64Const(bits) => format!("f64.const {}", f64_literal(bits)),
+            #[cfg(feature = "simd")]
+            Simd(ref simd) => simd_instruction(simd),
+            #[cfg(feature = "threads")]
+            Atomic(ref atomic) => atomic_instruction(atomic),
+            #[cfg(feature = "gc")]
+            CallRef(type_idx) => format!("call_ref {}", type_idx),
+            #[cfg(all(feature = "gc", feature = "tail_call"))]
+            ReturnCallRef(type_idx) => format!("return_call_ref {}", type_idx),
+            #[cfg(feature = "gc")]
+            RefEq => "ref.eq".to_string(),
+            #[cfg(feature = "gc")]
+            RefAsNonNull => "ref.as_non_null".to_string(),
+            #[cfg(feature = "gc")]
+            BrOnNull(depth) => format!("br_on_null {}", depth),
+            #[cfg(feature = "gc")]
+            BrOnNonNull(depth) => format!("br_on_non_null {}", depth),
+            #[cfg(feature = "gc")]
+            Gc(ref gc) => gc_instruction(gc),
+            ref other => format!(";; {:?}", other),
+        }
+    }
+
+    fn const_expr(&self, expr: &InitExpr) -> String {
+        let parts: Vec<String> = expr.0
+            .iter()
+            .filter(|i| **i != Instruction::End)
+            .map(|i| format!("({})", self.instruction(None, i)))
+            .collect();
+        parts.join(" ")
+    }
+
+    fn offset_expr(&self, expr: &InitExpr) -> String {
+        let count = expr.0.iter().filter(|i| **i != Instruction::End).count();
+        if count == 1 {
+            self.const_expr(expr)
+        } else {
+            format!("(offset {})", self.const_expr(expr))
+        }
+    }
+
+    fn tables(&mut self) {
+        let imported = self.imported_count(|e| matches!(*e, External::Table(_)));
+        if let Some(section) = self.module.table_section() {
+            for (i, table_type) in section.0.iter().enumerate() {
+                let text = format!(
+                    "(table (;{};) {} {})",
+                    imported + i, limits(&table_type.limits), table_element_type(table_type.elem_type)
+                );
+                self.line(1, &text);
+            }
+        }
+    }
+
+    fn memories(&mut self) {
+        let imported = self.imported_count(|e| matches!(*e, External::Memory(_)));
+        if let Some(section) = self.module.memory_section() {
+            for (i, mem_limits) in section.0.iter().enumerate() {
+                let text = format!("(memory (;{};) {})", imported + i, limits(mem_limits));
+                self.line(1, &text);
+            }
+        }
+    }
+
+    #[cfg(feature = "exceptions")]
+    fn tags(&mut self) {
+        let imported = self.imported_count(|e| matches!(*e, External::Tag(_)));
+        if let Some(section) = self.module.tag_section() {
+            for (i, tag) in section.0.iter().enumerate() {
+                let text = format!("(tag (;{};) (type {}))", imported + i, tag.type_index);
+                self.line(1, &text);
+            }
+        }
+    }
+
+    fn globals(&mut self) {
+        let imported = self.imported_count(|e| matches!(*e, External::Global(_)));
+        if let Some(section) = self.module.global_section() {
+            for (i, entry) in section.0.iter().enumerate() {
+                let text = format!(
+                    "(global (;{};) {} {})",
+                    imported + i, global_type(&entry.global_type), self.const_expr(&entry.init_expr)
+                );
+                self.line(1, &text);
+            }
+        }
+    }
+
+    fn exports(&mut self) {
+        let entries = match self.module.export_section() {
+            Some(section) => &section.0,
+            None => return,
+        };
+        for entry in entries.iter() {
+            let desc = match entry.internal {
+                Internal::Function(idx) => format!("(func {})", self.func_name(idx)),
+                Internal::Table(idx) => format!("(table {})", idx),
+                Internal::Memory(idx) => format!("(memory {})", idx),
+                Internal::Global(idx) => format!("(global {})", idx),
+                #[cfg(feature = "exceptions")]
+                Internal::Tag(idx) => format!("(tag {})", idx),
+            };
+            let text = format!("(export {} {})", string_literal(entry.field_str.as_bytes()), desc);
+            self.line(1, &text);
+        }
+    }
+
+    fn elements(&mut self) {
+        let segments = match self.module.elements_section() {
+            Some(section) => &section.0,
+            None => return,
+        };
+        for (i, segment) in segments.iter().enumerate() {
+            let mut text = format!("(elem (;{};)", i);
+            if segment.declarative {
+                text.push_str(" declare");
+            }
+            if let Some(ref offset) = segment.offset {
+                if segment.index != 0 {
+                    write!(text, " (table {})", segment.index).unwrap();
+                }
+                write!(text, " {}", self.offset_expr(offset)).unwrap();
+            }
+            match segment.exprs {
+                Some(ref exprs) => {
+                    write!(text, " {}", table_element_type(segment.elem_type)).unwrap();
+                    for expr in exprs.iter() {
+                        write!(text, " (item {})", self.const_expr(expr)).unwrap();
+                    }
+                },
+                None => {
+                    text.push_str(" func");
+                    for &member in segment.members.iter() {
+                        write!(text, " {}", self.func_name(member)).unwrap();
+                    }
+                },
+            }
+            text.push(')');
+            self.line(1, &text);
+        }
+    }
+
+    fn data(&mut self) {
+        let segments = match self.module.data_section() {
+            Some(section) => &section.0,
+            None => return,
+        };
+        for (i, segment) in segments.iter().enumerate() {
+            let mut text = format!("(data (;{};)", i);
+            if segment.index != 0 {
+                write!(text, " (memory {})", segment.index).unwrap();
+            }
+            if let Some(ref offset) = segment.offset {
+                write!(text, " {}", self.offset_expr(offset)).unwrap();
+            }
+            write!(text, " {})", string_literal(&segment.value)).unwrap();
+            self.line(1, &text);
+        }
+    }
+}
+
+/// Block type of a block instruction.
+fn block_type_of(instruction: &Instruction) -> &BlockType {
+    match *instruction {
+        Instruction::Block(ref bt) | Instruction::Loop(ref bt) | Instruction::If(ref bt) => bt,
+        #[cfg(feature = "exceptions")]
+        Instruction::TryTable(ref data) => &data.block_type,
+        _ => unreachable!("not a block instruction"),
+    }
+}
+
+/// Take the last `count` nodes as operands if each of them produces exactly one value.
+fn take_operands<'c>(nodes: &mut Vec<Node<'c>>, count: usize) -> Vec<Node<'c>> {
+    if count == 0 || nodes.len() < count {
+        return Vec::new();
+    }
+    let start = nodes.len() - count;
+    if nodes[start..].iter().all(|n| n.results() == 1) {
+        nodes.split_off(start)
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elements::deserialize_buffer;
+    use crate::tests::SAMPLE_MODULE;
+
+    #[test]
+    fn test_print_sample() {
+        let module: Module = deserialize_buffer(SAMPLE_MODULE).unwrap();
+        let text = print(&module);
+        assert_eq!(text, r#"(module
+  (type (;0;) (func (param i32 i32) (result i32)))
+  (type (;1;) (func))
+  (import "env" "f" (func (;0;) (type 1)))
+  (func (;1;) (type 0) (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.add)
+  (func (;2;) (type 1)
+    (local i32)
+    block
+      nop
+    end
+    call 0)
+  (table (;0;) 1 funcref)
+  (memory (;0;) 1 2)
+  (global (;0;) (mut i32) (i32.const 42))
+  (export "add" (func 1))
+  (export "mem" (memory 0))
+  (start 2)
+  (elem (;0;) (i32.const 0) func 1)
+  (data (;0;) (i32.const 8) "hi")
+  ;; custom section "x", size 2
+)
+"#);
+
+        let folded = Printer::new().folded(true).print(&module);
+        assert!(folded.contains("    (i32.add (local.get 0) (local.get 1)))\n"));
+        assert!(folded.contains("    (block\n      (nop))\n    (call 0))\n"));
+
+        let func = Printer::new().print_func(&module, 2).unwrap();
+        assert_eq!(func, "(func (;2;) (type 1)\n  (local i32)\n  block\n    nop\n  end\n  call 0)\n");
+        assert_eq!(Printer::new().print_func(&module, 0), None);
+        assert_eq!(Printer::new().print_func(&module, 3), None);
+    }
+
+    #[test]
+    fn test_names() {
+        let mut module: Module = deserialize_buffer(SAMPLE_MODULE).unwrap();
+        // module "m"; functions 1 -> "add", 2 -> "bad name"; locals of 1: 0 -> "a"
+        let payload = vec![
+            0x00, 0x02, 0x01, b'm',
+            0x01, 0x10, 0x02, 0x01, 0x03, b'a', b'd', b'd', 0x02, 0x08, b'b', b'a', b'd', b' ', b'n', b'a', b'm', b'e',
+            0x02, 0x06, 0x01, 0x01, 0x01, 0x00, 0x01, b'a',
+        ];
+        module.sections.push(crate::elements::sections::Section::Custom(crate::elements::sections::CustomSection {
+            name: "name".to_string(),
+            payload,
+        }));
+        let text = print(&module);
+        assert!(text.starts_with("(module $m\n"));
+        assert!(text.contains("(func $add (;1;) (type 0) (param $a i32) (param i32) (result i32)\n    local.get $a\n"));
+        assert!(text.contains("(export \"add\" (func $add))"));
+        assert!(text.contains("(func (;2;) (type 1)"));
+    }
+
+    #[test]
+    fn test_float_literals() {
+        assert_eq!(f32_literal(1.5f32.to_bits()), "1.5");
+        assert_eq!(f32_literal((-0.0f32).to_bits()), "-0.0");
+        assert_eq!(f32_literal(f32::INFINITY.to_bits()), "inf");
+        assert_eq!(f32_literal(0x7fc0_0000), "nan");
+        assert_eq!(f32_literal(0xffc0_0000), "-nan");
+        assert_eq!(f32_literal(0x7f80_0001), "nan:0x1");
+        assert_eq!(f32_literal(1), "1e-45");
+        assert_eq!(f64_literal(0.1f64.to_bits()), "0.1");
+        assert_eq!(f64_literal(f64::NEG_INFINITY.to_bits()), "-inf");
+        assert_eq!(f64_literal(0x7ff8_0000_0000_0000), "nan");
+        assert_eq!(f64_literal(0xfff0_0000_0000_0abc), "-nan:0xabc");
+        assert_eq!(string_literal(b"a\"\\\n"), "\"a\\\"\\\\\\0a\"");
+    }
+}